@@ -0,0 +1,31 @@
+
+// Generates the gRPC service from its description in Rust, so building doesn't need protoc. The messages are in
+// src/grpc.rs, and auto_archive.proto describes the same thing for clients.
+
+use tonic_build::manual::Builder;
+use tonic_build::manual::Method;
+use tonic_build::manual::Service;
+
+fn method(name: &str, route_name: &str, input: &str, output: &str) -> tonic_build::manual::MethodBuilder {
+    Method::builder()
+        .name(name)
+        .route_name(route_name)
+        .input_type(format!("crate::grpc::{}", input))
+        .output_type(format!("crate::grpc::{}", output))
+        .codec_path("tonic::codec::ProstCodec")
+}
+
+fn main() {
+    let service = Service::builder()
+        .name("DriveControl")
+        .package("auto_archive")
+        .method(method("list_drives", "ListDrives", "Empty", "DriveList").build())
+        .method(method("name", "Name", "NameRequest", "Reply").build())
+        .method(method("eject", "Eject", "EjectRequest", "Reply").build())
+        .method(method("pause", "Pause", "PauseRequest", "Reply").build())
+        .method(method("rescan", "Rescan", "Empty", "Reply").build())
+        .method(method("watch_progress", "WatchProgress", "Empty", "DriveList").server_streaming().build())
+        .build();
+
+    Builder::new().build_client(false).compile(&[service]);
+}
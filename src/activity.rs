@@ -0,0 +1,84 @@
+
+// What's happened to each drive, newest last. The status line only ever shows what a drive is doing now, so this is
+// where to look for what the last disk was saved as, or what went wrong with the one before it. Only kept while
+// auto_archive is running, and only the last few hundred things, so a long session doesn't eat memory.
+
+use std::collections::VecDeque;
+use std::mem;
+use std::mem::Discriminant;
+use std::sync::Mutex;
+
+use crate::catalog;
+use crate::catalog::CatalogEntry;
+use crate::drive_state::DriveStatus;
+use crate::events::DriveEvent;
+use crate::get_drive_status_message_string;
+
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Clone)]
+pub struct Entry {
+    pub time: u64, // Seconds since the Unix epoch.
+    pub message: String,
+    pub archived: Option<Box<CatalogEntry>>, // What went into the catalog, if that's what happened.
+}
+
+#[derive(Default)]
+pub struct ActivityLog {
+    entries: Mutex<VecDeque<Entry>>,
+
+    // Statuses like ripping track 3 of 12 change all the time without anything really happening, so a status is only
+    // written down when it's a different kind than the last.
+    last_status: Mutex<Option<Discriminant<DriveStatus>>>,
+}
+
+impl ActivityLog {
+    pub fn add(&self, message: String) {
+        self.push(message, None);
+    }
+
+    fn push(&self, message: String, archived: Option<Box<CatalogEntry>>) {
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= MAX_ENTRIES {
+            entries.pop_front();
+        }
+
+        entries.push_back(Entry {
+            time: catalog::now(),
+            message,
+            archived,
+        });
+    }
+
+    pub fn record(&self, event: &DriveEvent) {
+        match event {
+            DriveEvent::Status(status) => {
+                let kind = mem::discriminant(status);
+                if self.last_status.lock().unwrap().replace(kind) == Some(kind) {
+                    return;
+                }
+
+                self.add(get_drive_status_message_string(status));
+            },
+            DriveEvent::DiskInserted => self.add(String::from("Disk inserted.")),
+            DriveEvent::DiskRemoved => self.add(String::from("Disk removed.")),
+            DriveEvent::Archived(entry) => self.push(format!("Saved as {}.", entry.image), Some(entry.clone())),
+            DriveEvent::Plugged => self.add(String::from("Drive plugged in.")),
+            DriveEvent::Unplugged => self.add(String::from("Drive unplugged.")),
+        }
+    }
+
+    // The last disk that went into the catalog, if any have.
+    pub fn last_archived(&self) -> Option<Box<CatalogEntry>> {
+        self.entries.lock().unwrap().iter().rev().find_map(|entry| entry.archived.clone())
+    }
+
+    // One line per entry, oldest first.
+    pub fn describe(&self) -> String {
+        self.entries.lock().unwrap().iter()
+            .map(|entry| format!("{} UTC  {}", catalog::format_date(entry.time), entry.message))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,316 @@
+
+// A small read-only HTTP API, for dashboards of your own. Everything comes back as JSON:
+//
+// GET /drives          Every drive.
+// GET /drives/<id>     One drive, by its device name without the /dev/, like sr0.
+// GET /session/stats   Totals since auto_archive started.
+// GET /catalog         Every disk in the catalog.
+// GET /catalog/fingerprint/<fingerprint>
+//                      The disk with that fingerprint, for other stations to check for duplicates with.
+//
+// Anyone who can reach it can read it, unless api_token is set. See auth.rs.
+
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde_json::json;
+use serde_json::Value;
+
+use crate::auth::Security;
+use crate::catalog::Catalog;
+use crate::drive_list::DriveList;
+use crate::export;
+use crate::get_drive_status_message_string;
+use crate::is_drive_busy;
+use crate::session;
+use crate::DiskDrive;
+use crate::DriveKind;
+
+// Clients that connect and then say nothing, or stop reading what they asked for, get hung up on after this long.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// And everyone gets hung up on after this long, so a client that sends a byte every few seconds can't keep a
+// connection forever.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Nothing here needs a big request, so anything bigger is turned away before it can eat up memory.
+const MAX_REQUEST_SIZE: u64 = 16 * 1024;
+
+// Connections past this are hung up on straight away, so nobody can tie up every thread the machine has.
+const MAX_CONNECTIONS: usize = 32;
+
+pub fn get_drive_id(drive: &DiskDrive) -> &str {
+    drive.file.strip_prefix("/dev/").unwrap_or(&drive.file)
+}
+
+pub fn drive_to_json(drive: &DiskDrive) -> Value {
+    let (done, total) = drive.progress.get();
+
+    json!({
+        "id": get_drive_id(drive),
+        "device": drive.file,
+        "name": *drive.name.lock().unwrap(),
+        "kind": match drive.kind {
+            DriveKind::Optical => "optical",
+            DriveKind::Block => "block",
+            DriveKind::Floppy => "floppy",
+        },
+        "status": get_drive_status_message_string(&drive.state.get()),
+        "busy": is_drive_busy(drive),
+        "has_disk": drive.has_disk.load(Relaxed),
+        "progress": {
+            "done": done,
+            "total": total,
+            "unit": drive.progress.unit().name(),
+            "percent": drive.progress.permille() as f64 / 10.0,
+        },
+        "last_archived": drive.activity.last_archived().map(|entry| entry.image),
+    })
+}
+
+// The status line and what to send back.
+fn route(drives: &DriveList, catalog: &Mutex<Catalog>, method: &str, path: &str) -> (&'static str, Value) {
+    if method != "GET" {
+        return ("405 Method Not Allowed", json!({ "error": "This API is read only." }));
+    }
+
+    // Query strings don't mean anything here.
+    let path = path.split('?').next().unwrap_or_default().trim_end_matches('/');
+
+    match path {
+        "/drives" => ("200 OK", Value::Array(drives.get().iter().map(|drive| drive_to_json(drive)).collect())),
+        "/session/stats" => ("200 OK", session::get_stats(drives)),
+        "/catalog" => ("200 OK", Value::Array(catalog.lock().unwrap().entries().iter().map(export::entry_to_json).collect())),
+        _ => {
+            if let Some(fingerprint) = path.strip_prefix("/catalog/fingerprint/") {
+                return match catalog.lock().unwrap().find_fingerprint(fingerprint) {
+                    Some(entry) => ("200 OK", export::entry_to_json(entry)),
+                    None => ("404 Not Found", json!({ "error": "No disk has that fingerprint." })),
+                };
+            }
+
+            match path.strip_prefix("/drives/").and_then(|id| drives.get().into_iter().find(|drive| get_drive_id(drive) == id)) {
+                Some(drive) => ("200 OK", drive_to_json(&drive)),
+                None => ("404 Not Found", json!({ "error": "There's nothing here." })),
+            }
+        },
+    }
+}
+
+struct Api {
+    drives: Arc<DriveList>,
+    catalog: Arc<Mutex<Catalog>>,
+    security: Security,
+    connections: AtomicUsize, // Being answered right now.
+}
+
+// Counts a connection for as long as it's being answered.
+struct Connection<'a>(&'a AtomicUsize);
+
+impl Drop for Connection<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Relaxed);
+    }
+}
+
+fn handle_connection<S: Read + Write>(api: &Api, mut stream: S) {
+    let mut reader = BufReader::new((&mut stream).take(MAX_REQUEST_SIZE));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+
+    // Only the token matters, but they all have to be read before answering or some clients see a reset.
+    let mut authorization = None;
+    let mut header = String::new();
+    while reader.read_line(&mut header).map(|len| len > 2).unwrap_or(false) {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                authorization = Some(String::from(value.trim()));
+            }
+        }
+        header.clear();
+    }
+
+    // Whatever was cut off might have been the token, so it's not worth looking at.
+    let too_big = reader.get_ref().limit() == 0;
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = if too_big {
+        ("431 Request Header Fields Too Large", json!({ "error": "The request is too big." }))
+    } else if api.security.is_authorized(authorization.as_deref()) {
+        route(&api.drives, &api.catalog, parts.next().unwrap_or_default(), parts.next().unwrap_or_default())
+    } else {
+        ("401 Unauthorized", json!({ "error": "Send the API token as Authorization: Bearer <token>." }))
+    };
+    let body = body.to_string();
+
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body);
+
+    let _ = stream.write_all(response.as_bytes());
+    let _ = stream.flush();
+}
+
+// A connection that has to be done with by a deadline. Every read and write only waits as long as there's left.
+struct Deadline {
+    stream: TcpStream,
+    until: Instant,
+}
+
+impl Deadline {
+    fn start(&self) -> io::Result<()> {
+        let left = self.until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "The connection took too long."));
+        }
+
+        let timeout = Some(left.min(REQUEST_TIMEOUT));
+        self.stream.set_read_timeout(timeout)?;
+        self.stream.set_write_timeout(timeout)
+    }
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.start()?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Deadline {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.start()?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn accept(api: &Api, stream: TcpStream) {
+    let mut stream = Deadline { stream, until: Instant::now() + CONNECTION_TIMEOUT };
+
+    match &api.security.tls {
+        Some(tls) => {
+            // A client that can't finish the handshake just gets hung up on.
+            if let Ok(connection) = rustls::ServerConnection::new(tls.config.clone()) {
+                let mut stream = rustls::StreamOwned::new(connection, stream);
+                handle_connection(api, &mut stream);
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+        },
+        None => handle_connection(api, &mut stream),
+    }
+}
+
+// Starts answering requests on `address`. Fails if something else already has it.
+pub fn spawn_api(address: &str, drives: &Arc<DriveList>, catalog: &Arc<Mutex<Catalog>>, security: &Security) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let api = Arc::new(Api {
+        drives: drives.clone(),
+        catalog: catalog.clone(),
+        security: security.clone(),
+        connections: AtomicUsize::new(0),
+    });
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // Dropping the stream hangs up.
+            if api.connections.fetch_add(1, Relaxed) >= MAX_CONNECTIONS {
+                api.connections.fetch_sub(1, Relaxed);
+                continue;
+            }
+
+            let api = api.clone();
+            thread::spawn(move || {
+                let _connection = Connection(&api.connections);
+                accept(&api, stream);
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // A client that's already sent everything it's going to.
+    struct Client {
+        request: Cursor<Vec<u8>>,
+        response: Vec<u8>,
+    }
+
+    impl Read for Client {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            self.request.read(buffer)
+        }
+    }
+
+    impl Write for Client {
+        fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+            self.response.write(buffer)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn ask(request: &str) -> String {
+        let api = Api {
+            drives: Arc::new(DriveList::default()),
+            catalog: Arc::new(Mutex::new(Catalog::default())),
+            security: Security { token: Some(String::from("secret")), tls: None },
+            connections: AtomicUsize::new(0),
+        };
+
+        let mut client = Client { request: Cursor::new(request.as_bytes().to_vec()), response: Vec::new() };
+        handle_connection(&api, &mut client);
+
+        String::from_utf8(client.response).unwrap()
+    }
+
+    #[test]
+    fn tokens_are_checked() {
+        assert!(ask("GET /drives HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n").starts_with("HTTP/1.1 200 OK"));
+        assert!(ask("GET /drives HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n").starts_with("HTTP/1.1 401"));
+    }
+
+    #[test]
+    fn big_requests_are_turned_away() {
+        let padding = "X".repeat(MAX_REQUEST_SIZE as usize);
+        let request = format!("GET /drives HTTP/1.1\r\nX-Padding: {}\r\nAuthorization: Bearer secret\r\n\r\n", padding);
+
+        assert!(ask(&request).starts_with("HTTP/1.1 431"));
+    }
+
+    #[test]
+    fn connections_run_out_of_time() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let mut stream = Deadline { stream, until: Instant::now() };
+
+        assert!(stream.read(&mut [0; 16]).is_err_and(|error| error.kind() == io::ErrorKind::TimedOut));
+        assert!(stream.write(b"late").is_err_and(|error| error.kind() == io::ErrorKind::TimedOut));
+    }
+}
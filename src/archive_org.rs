@@ -0,0 +1,112 @@
+
+// Uploads finished images to the Internet Archive through its S3-like API, using curl. Catalog fields are mapped to the
+// item's metadata, so published rips carry their volume name, date, and hashes with them.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+use crate::catalog;
+use crate::catalog::CatalogEntry;
+use crate::config::Config;
+
+const S3_ENDPOINT: &str = "https://s3.us.archive.org";
+
+pub enum ArchiveOrgError {
+    NoCredentials,          // ia_access_key and ia_secret_key aren't set.
+    LaunchFail,             // Failed to launch curl.
+    Failed(String, String), // File that failed to upload, and what curl had to say about it.
+}
+
+// Identifiers can only have letters, numbers, dashes, underscores, and dots in them.
+pub fn get_identifier(config: &Config, entry: &CatalogEntry) -> String {
+    let volume: String = entry.volume.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+
+    let hash = entry.sha1.get(..8).unwrap_or(&entry.sha1);
+
+    format!("{}{}_{}", config.ia_identifier_prefix, volume, hash)
+}
+
+fn get_metadata_headers(config: &Config, entry: &CatalogEntry) -> Vec<String> {
+    let mut description = format!("Disk image archived {} UTC. Size {} bytes, CRC32 {}, SHA-1 {}.",
+        catalog::format_date(entry.date), entry.size, entry.crc32, entry.sha1);
+
+    if let Some(redump) = &entry.redump {
+        description += &format!(" Verified against redump.org as a known-good dump of {}.", redump);
+    }
+
+    vec![
+        String::from("x-amz-auto-make-bucket: 1"),
+        format!("x-archive-meta-mediatype: {}", config.ia_mediatype),
+        format!("x-archive-meta-collection: {}", config.ia_collection),
+        format!("x-archive-meta-title: {}", entry.volume),
+        format!("x-archive-meta-date: {}", &catalog::format_date(entry.date)[..10]),
+        format!("x-archive-meta-description: {}", description),
+        String::from("x-archive-meta-subject: disk image"),
+    ]
+}
+
+fn upload_file(config: &Config, identifier: &str, file: &str, headers: &[String]) -> Result<(), ArchiveOrgError> {
+    let name = Path::new(file).file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let url = format!("{}/{}/{}", S3_ENDPOINT, identifier, name);
+
+    // The keys go through curl's config on stdin, so they don't show up in the process list.
+    let mut curl_config = format!("header = \"authorization: LOW {}:{}\"\n",
+        config.ia_access_key.as_deref().unwrap_or_default(), config.ia_secret_key.as_deref().unwrap_or_default());
+
+    for header in headers.iter() {
+        curl_config += &format!("header = \"{}\"\n", header.replace('\\', "\\\\").replace('"', "\\\""));
+    }
+
+    let mut child = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--config", "-", "--upload-file"])
+        .arg(file)
+        .arg(&url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| { ArchiveOrgError::LaunchFail })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(curl_config.as_bytes());
+    }
+
+    let output = child.wait_with_output().map_err(|_| { ArchiveOrgError::LaunchFail })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        Err(ArchiveOrgError::Failed(name, String::from(errors.trim())))
+    }
+}
+
+// Uploads the image and its parity files as a new item, and returns the item's identifier.
+pub fn upload(config: &Config, entry: &CatalogEntry) -> Result<String, ArchiveOrgError> {
+    if config.ia_access_key.is_none() || config.ia_secret_key.is_none() {
+        return Err(ArchiveOrgError::NoCredentials);
+    }
+
+    let identifier = get_identifier(config, entry);
+
+    // The first upload makes the item, so it's the one that needs the metadata.
+    upload_file(config, &identifier, &entry.image, &get_metadata_headers(config, entry))?;
+
+    for parity in entry.parity.iter() {
+        upload_file(config, &identifier, parity, &[])?;
+    }
+
+    Ok(identifier)
+}
+
+pub fn get_archive_org_error_message(error: &ArchiveOrgError) -> String {
+    match error {
+        ArchiveOrgError::NoCredentials => String::from("Set ia_access_key and ia_secret_key in auto_archive.conf first. Get them from https://archive.org/account/s3.php"),
+        ArchiveOrgError::LaunchFail => String::from("Failed to launch curl. Is it installed?"),
+        ArchiveOrgError::Failed(file, message) => format!("Failed to upload {}: {}", file, message),
+    }
+}
@@ -0,0 +1,617 @@
+
+// Audio CD ripping. Every track is read as raw samples and streamed straight into flac, tagged with whatever we could
+// find out about the disk, so what comes out can go straight into a music library. A cue sheet describing the disk's
+// layout goes next to the tracks and into their tags, and the CRC of every track's audio is written down so a later
+// rip, or a rip from another drive, can be checked against this one.
+//
+// The gap before each track (index 0) stays at the end of the track before it, where the cue sheet says it is, and
+// audio hidden before track 1 gets a file of its own. Ripping tracks from index 1 to index 1 without saying so in the
+// cue sheet would lose where the gaps were, and hidden tracks would be lost entirely.
+
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+
+use crate::buffer_pool;
+use crate::cdrom;
+use crate::cdrom::TocEntry;
+use crate::hash::HashingWriter;
+use crate::sense_log;
+
+// Sectors per READ CD.
+const SECTORS_PER_READ: u32 = 16;
+
+// How many times we try a chunk before giving up on the track.
+const READ_RETRIES: usize = 5;
+
+// Cheap drives don't always start reading audio exactly where they're told to, so every read backs up over the end of
+// the one before it, and the end of what we already have is looked for in it to line the two up. This is how far
+// back each read starts, how much of what we have is looked for, and how far off the drive can be, in bytes.
+const OVERLAP_SECTORS: usize = 2;
+const JITTER_ANCHOR_SIZE: usize = 1024;
+const MAX_JITTER: usize = cdrom::RAW_SECTOR_SIZE;
+
+pub const CUE_SHEET_FILE: &str = "disc.cue";
+pub const CHECKSUM_FILE: &str = "checksums.txt";
+pub const SIDECAR_FILE: &str = "disc.json"; // Everything we know about the disk, for other tools.
+
+pub enum AudioError {
+    Read(String),         // Couldn't read the disk.
+    Write(String),        // Couldn't write the output.
+    LaunchFail,           // Failed to launch flac.
+    Encode(String),       // flac ran but failed. Holds what it had to say about it.
+    Cancelled,
+}
+
+#[derive(Clone, Copy)]
+pub struct Track {
+    pub number: u8,
+    pub start: u32,  // LBA of index 1.
+    pub length: u32, // In sectors, up to the start of the next track.
+    pub pregap: u32, // Sectors of index 0 before the start. For track 1, anything between LBA 0 and the start.
+}
+
+#[derive(Clone, Default)]
+pub struct DiscMetadata {
+    pub album: Option<String>,
+    pub artist: Option<String>,
+    pub titles: HashMap<u8, String>,  // By track number.
+    pub artists: HashMap<u8, String>, // Only for tracks that aren't by the album's artist.
+    pub mcn: Option<String>,          // Media catalog number. The UPC/EAN barcode, usually.
+    pub isrcs: HashMap<u8, String>,
+    pub sources: Vec<String>,         // Where all of this came from, for the sidecar.
+}
+
+impl DiscMetadata {
+    // Fills in whatever we don't know from another source.
+    pub fn merge(&mut self, other: DiscMetadata) {
+        if self.album.is_none() {
+            self.album = other.album;
+        }
+
+        if self.artist.is_none() {
+            self.artist = other.artist;
+        }
+
+        for (track, title) in other.titles {
+            self.titles.entry(track).or_insert(title);
+        }
+
+        for (track, artist) in other.artists {
+            self.artists.entry(track).or_insert(artist);
+        }
+
+        if self.mcn.is_none() {
+            self.mcn = other.mcn;
+        }
+
+        for (track, isrc) in other.isrcs {
+            self.isrcs.entry(track).or_insert(isrc);
+        }
+
+        self.sources.extend(other.sources);
+    }
+
+    pub fn get_track_title(&self, track: u8) -> String {
+        match self.titles.get(&track) {
+            Some(title) => title.clone(),
+            None if track == 0 => String::from("Hidden Track One Audio"),
+            None => format!("Track {:02}", track),
+        }
+    }
+}
+
+// Audio CDs have nothing but audio tracks in their first session. Enhanced CDs put their data in a second one.
+pub fn is_audio_disk(toc: &[TocEntry]) -> bool {
+    let tracks: Vec<&TocEntry> = toc.iter()
+        .filter(|entry| entry.session == 1 && entry.adr == 1 && (1..=99).contains(&entry.point))
+        .collect();
+
+    !tracks.is_empty() && tracks.iter().all(|entry| !entry.is_data())
+}
+
+// Where the first session's audio ends.
+pub fn get_lead_out(toc: &[TocEntry]) -> Option<u32> {
+    toc.iter()
+        .find(|entry| entry.session == 1 && entry.point == cdrom::TOC_POINT_LEAD_OUT)
+        .map(|entry| entry.point_lba().max(0) as u32)
+}
+
+pub fn get_audio_tracks(toc: &[TocEntry]) -> Vec<Track> {
+    let lead_out = get_lead_out(toc).unwrap_or(0);
+
+    let mut starts: Vec<(u8, u32)> = toc.iter()
+        .filter(|entry| entry.session == 1 && entry.adr == 1 && (1..=99).contains(&entry.point))
+        .map(|entry| (entry.point, entry.point_lba().max(0) as u32))
+        .collect();
+    starts.sort();
+
+    starts.iter().enumerate().map(|(index, (number, start))| {
+        let end = starts.get(index + 1).map(|(_, start)| *start).unwrap_or(lead_out);
+
+        Track {
+            number: *number,
+            start: *start,
+            length: end.saturating_sub(*start),
+            pregap: if index == 0 { *start } else { 0 }, // The rest need the subchannel to find.
+        }
+    }).collect()
+}
+
+fn from_bcd(byte: u8) -> u8 {
+    (byte >> 4) * 10 + (byte & 0x0F)
+}
+
+// Which track a sector belongs to, going by its Q subchannel.
+fn get_sector_track(device: &fs::File, lba: u32) -> Option<u8> {
+    // Every hundred sectors or so the Q subchannel carries the catalog number or an ISRC instead of the position.
+    // The sector next to it is close enough.
+    for lba in [lba, lba + 1, lba.saturating_sub(1)].iter() {
+        let q = cdrom::read_q_subchannel(device, *lba).ok()?;
+
+        if q[0] & 0x0F == 1 {
+            return Some(from_bcd(q[1]));
+        }
+    }
+
+    None
+}
+
+// Finds the gaps before tracks 2 and on. The TOC only says where index 1 is, so the subchannel is searched for where
+// the track actually starts. Drives that can't read subchannels just get no gaps, which is what we'd have had anyway.
+pub fn detect_pregaps(device: &fs::File, tracks: &mut [Track]) {
+    for index in 1..tracks.len() {
+        let (previous, track) = (tracks[index - 1], tracks[index]);
+
+        if track.start == 0 || get_sector_track(device, track.start - 1) != Some(track.number) {
+            continue;
+        }
+
+        // Everything between here and the start of the previous track belongs to one track or the other.
+        let mut low = previous.start;
+        let mut high = track.start - 1;
+
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+
+            match get_sector_track(device, middle) {
+                Some(number) if number == track.number => high = middle,
+                Some(_) => low = middle,
+                None => break,
+            }
+        }
+
+        tracks[index].pregap = track.start - high;
+    }
+}
+
+// Reads the disk's catalog number and the tracks' ISRCs. Plenty of disks don't have them, and plenty of drives can't
+// read them, so whatever we can't get is just left out.
+pub fn read_codes(device: &fs::File, tracks: &[Track], metadata: &mut DiscMetadata) {
+    if let Ok(Some(mcn)) = cdrom::read_mcn(device) {
+        metadata.mcn = Some(mcn);
+    }
+
+    for track in tracks.iter() {
+        if let Ok(Some(isrc)) = cdrom::read_isrc(device, track.number) {
+            metadata.isrcs.insert(track.number, isrc);
+        }
+    }
+}
+
+// True if a range of the disk is nothing but zeros. Most disks have a little silence before track 1 that isn't
+// worth a file of its own.
+pub fn is_silent(device: &fs::File, start: u32, length: u32) -> Result<bool, AudioError> {
+    let mut buffer = buffer_pool::get(SECTORS_PER_READ as usize * cdrom::RAW_SECTOR_SIZE);
+    let mut offset = 0;
+
+    while offset < length {
+        let count = SECTORS_PER_READ.min(length - offset);
+        let read = cdrom::read_cd_audio(device, start + offset, count, &mut buffer)
+            .map_err(|e| {
+                sense_log::log_error(device, (start + offset) as u64, &e);
+                AudioError::Read(format!("sector {}: {}", start + offset, cdrom::get_scsi_error_message(&e)))
+            })?;
+
+        if buffer[..read].iter().any(|byte| *byte != 0) {
+            return Ok(false);
+        }
+
+        offset += count;
+    }
+
+    Ok(true)
+}
+
+// The audio hidden before track 1, if there's any. It gets ripped like a track of its own, numbered 0.
+pub fn get_hidden_track(device: &fs::File, tracks: &[Track]) -> Result<Option<Track>, AudioError> {
+    let first = match tracks.first() {
+        Some(first) if first.pregap > 0 => first,
+        _ => return Ok(None),
+    };
+
+    if is_silent(device, 0, first.pregap)? {
+        return Ok(None);
+    }
+
+    Ok(Some(Track {
+        number: 0,
+        start: 0,
+        length: first.pregap,
+        pregap: 0,
+    }))
+}
+
+// CD-TEXT packs hold one kind of text each, and the strings for consecutive tracks are packed end to end across them.
+fn parse_cd_text(data: &[u8], pack_type: u8) -> HashMap<u8, String> {
+    let mut strings = HashMap::new();
+    let mut text = Vec::new();
+    let mut track = None;
+
+    // Only the first block. The rest are the same text in other languages.
+    for pack in data.chunks_exact(18).filter(|pack| pack[0] == pack_type && (pack[3] >> 4) & 0x07 == 0) {
+        let number = track.get_or_insert(pack[1] & 0x7F);
+
+        for byte in pack[4..16].iter() {
+            if *byte != 0 {
+                text.push(*byte);
+                continue;
+            }
+
+            // Padding at the end of the last pack.
+            if !text.is_empty() {
+                // A tab means "same as the last one".
+                let value: String = if text == b"\t" {
+                    strings.get(&number.wrapping_sub(1)).cloned().unwrap_or_default()
+                } else {
+                    text.iter().map(|byte| *byte as char).collect() // Latin-1.
+                };
+
+                strings.insert(*number, value);
+            }
+
+            text.clear();
+            *number = number.wrapping_add(1);
+        }
+    }
+
+    strings
+}
+
+// Reads the titles and performers burned onto the disk, if it has any.
+pub fn read_cd_text(drive: &str) -> DiscMetadata {
+    let data = cdrom::open_device(drive).ok().and_then(|device| cdrom::read_cd_text(&device).ok()).unwrap_or_default();
+
+    let mut titles = parse_cd_text(&data, 0x80);
+    let mut artists = parse_cd_text(&data, 0x81);
+
+    // Track 0 is the disk itself.
+    let album = titles.remove(&0);
+    let artist = artists.remove(&0);
+
+    if let Some(artist) = &artist {
+        artists.retain(|_, track_artist| track_artist != artist);
+    }
+
+    // Most disks don't have any, and it shouldn't get credit for nothing.
+    let sources = if album.is_some() || !titles.is_empty() { vec![String::from("CD-TEXT")] } else { Vec::new() };
+
+    DiscMetadata { album, artist, titles, artists, sources, ..DiscMetadata::default() }
+}
+
+// Keeps tags out of file names when they have characters file systems don't like.
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c == '/' || c == '\\' || c == ':' || c == '\0' { '_' } else { c })
+        .collect::<String>()
+        .trim()
+        .trim_start_matches('.')
+        .to_string()
+}
+
+pub fn get_track_file_name(metadata: &DiscMetadata, track: &Track) -> String {
+    format!("{:02} - {}.flac", track.number, sanitize_file_name(&metadata.get_track_title(track.number)))
+}
+
+fn format_msf(sectors: u32) -> String {
+    format!("{:02}:{:02}:{:02}", sectors / 75 / 60, sectors / 75 % 60, sectors % 75)
+}
+
+fn quote(text: &str) -> String {
+    text.replace('"', "'")
+}
+
+fn write_track_info(cue: &mut String, metadata: &DiscMetadata, track: &Track) {
+    let _ = writeln!(cue, "  TRACK {:02} AUDIO", track.number);
+    let _ = writeln!(cue, "    TITLE \"{}\"", quote(&metadata.get_track_title(track.number)));
+
+    if let Some(artist) = metadata.artists.get(&track.number).or(metadata.artist.as_ref()) {
+        let _ = writeln!(cue, "    PERFORMER \"{}\"", quote(artist));
+    }
+
+    if let Some(isrc) = metadata.isrcs.get(&track.number) {
+        let _ = writeln!(cue, "    ISRC {}", isrc);
+    }
+}
+
+// Each track's file runs from its index 1 to the next track's index 1, so a track's gap is at the end of the file
+// before it. Hidden audio before track 1 is in a file of its own.
+pub fn build_cue_sheet(metadata: &DiscMetadata, tracks: &[Track], hidden_track: Option<&Track>) -> String {
+    let mut cue = String::new();
+
+    // Writing to a String can't fail.
+    if let Some(mcn) = &metadata.mcn {
+        let _ = writeln!(cue, "CATALOG {}", mcn);
+    }
+
+    if let Some(artist) = &metadata.artist {
+        let _ = writeln!(cue, "PERFORMER \"{}\"", quote(artist));
+    }
+
+    if let Some(album) = &metadata.album {
+        let _ = writeln!(cue, "TITLE \"{}\"", quote(album));
+    }
+
+    for (index, track) in tracks.iter().enumerate() {
+        let file = format!("FILE \"{}\" WAVE", quote(&get_track_file_name(metadata, track)));
+
+        match (index.checked_sub(1).map(|previous| &tracks[previous]), hidden_track) {
+            (None, Some(hidden_track)) => {
+                let _ = writeln!(cue, "FILE \"{}\" WAVE", quote(&get_track_file_name(metadata, hidden_track)));
+                write_track_info(&mut cue, metadata, track);
+                let _ = writeln!(cue, "    INDEX 00 {}", format_msf(0));
+                let _ = writeln!(cue, "{}", file);
+            },
+            (None, None) => {
+                let _ = writeln!(cue, "{}", file);
+                write_track_info(&mut cue, metadata, track);
+
+                // Silence we didn't keep.
+                if track.pregap > 0 {
+                    let _ = writeln!(cue, "    PREGAP {}", format_msf(track.pregap));
+                }
+            },
+            (Some(previous), _) if track.pregap > 0 => {
+                // Still in the previous track's file.
+                write_track_info(&mut cue, metadata, track);
+                let _ = writeln!(cue, "    INDEX 00 {}", format_msf(track.start - track.pregap - previous.start));
+                let _ = writeln!(cue, "{}", file);
+            },
+            (Some(_), _) => {
+                let _ = writeln!(cue, "{}", file);
+                write_track_info(&mut cue, metadata, track);
+            },
+        }
+
+        let _ = writeln!(cue, "    INDEX 01 {}", format_msf(0));
+    }
+
+    cue
+}
+
+fn build_tags(metadata: &DiscMetadata, track: &Track, track_count: usize) -> Vec<String> {
+    let mut tags = vec![
+        format!("TITLE={}", metadata.get_track_title(track.number)),
+        format!("TRACKNUMBER={}", track.number),
+        format!("TRACKTOTAL={}", track_count),
+    ];
+
+    if let Some(album) = &metadata.album {
+        tags.push(format!("ALBUM={}", album));
+    }
+
+    if let Some(artist) = &metadata.artist {
+        tags.push(format!("ALBUMARTIST={}", artist));
+    }
+
+    if let Some(artist) = metadata.artists.get(&track.number).or(metadata.artist.as_ref()) {
+        tags.push(format!("ARTIST={}", artist));
+    }
+
+    if let Some(isrc) = metadata.isrcs.get(&track.number) {
+        tags.push(format!("ISRC={}", isrc));
+    }
+
+    if let Some(mcn) = &metadata.mcn {
+        tags.push(format!("BARCODE={}", mcn));
+    }
+
+    tags
+}
+
+// Finds where the anchor is in a read that should have it ending at `expected`, and returns where the new samples
+// start. Closest to where it should be wins, since silence and repeating patterns match in more than one place.
+fn find_anchor(buffer: &[u8], anchor: &[u8], expected: usize) -> Option<usize> {
+    let expected = expected.checked_sub(anchor.len())? as isize;
+
+    // One stereo sample is 4 bytes, and the drive can only be off by whole samples.
+    for distance in (0..=MAX_JITTER as isize).step_by(4) {
+        for start in [expected + distance, expected - distance].iter() {
+            let start = *start;
+            if start < 0 || start as usize + anchor.len() > buffer.len() {
+                continue;
+            }
+
+            if &buffer[start as usize..start as usize + anchor.len()] == anchor {
+                return Some(start as usize + anchor.len());
+            }
+        }
+    }
+
+    None
+}
+
+// Reads one track and encodes it to FLAC as it goes. The samples also go into `disc`, for hashing the whole disk.
+// Returns the CRC32 of the track's samples.
+#[allow(clippy::too_many_arguments)]
+pub fn rip_track<CB, ECB>(device: &fs::File, track: &Track, track_count: usize, metadata: &DiscMetadata, directory: &Path, disc: &mut HashingWriter<io::Sink>, cancel: &AtomicBool, mut callback: CB, mut error_callback: ECB)
+    -> Result<String, AudioError> where
+    CB: FnMut(usize),
+    ECB: FnMut(String)
+{
+    let mut command = Command::new("flac");
+    command.args(["--silent", "--force", "--force-raw-format", "--endian=little", "--sign=signed", "--channels=2", "--bps=16", "--sample-rate=44100"]);
+
+    for tag in build_tags(metadata, track, track_count) {
+        command.arg("-T").arg(tag);
+    }
+
+    command.arg(format!("--tag-from-file=CUESHEET={}", directory.join(CUE_SHEET_FILE).to_string_lossy()));
+    command.arg("-o").arg(directory.join(get_track_file_name(metadata, track)));
+    command.arg("-");
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| { AudioError::LaunchFail })?;
+
+    let mut crc32 = crc32fast::Hasher::new();
+    let mut buffer = buffer_pool::get(SECTORS_PER_READ as usize * cdrom::RAW_SECTOR_SIZE);
+
+    let result = (|| {
+        // Shouldn't fail since we asked for it.
+        let stdin = child.stdin.as_mut().ok_or(AudioError::LaunchFail)?;
+
+        let total = track.length as usize * cdrom::RAW_SECTOR_SIZE;
+        let mut position = 0; // Bytes of the track we have so far.
+        let mut anchor: Vec<u8> = Vec::new(); // The last of them.
+
+        while position < total {
+            if cancel.load(Relaxed) {
+                return Err(AudioError::Cancelled);
+            }
+
+            let first_sector = if position == 0 { 0 } else { (position / cdrom::RAW_SECTOR_SIZE).saturating_sub(OVERLAP_SECTORS) };
+            let count = SECTORS_PER_READ.min(track.length - first_sector as u32);
+            let lba = track.start + first_sector as u32;
+
+            // Where the new samples would start if the drive read exactly what it was told to.
+            let expected = position - first_sector * cdrom::RAW_SECTOR_SIZE;
+            let mut attempts = 0;
+
+            let (start, read) = loop {
+                let result = cdrom::read_cd_audio(device, lba, count, &mut buffer)
+                    .map_err(|error| {
+                        sense_log::log_error(device, lba as u64, &error);
+                        format!("track {}, sector {}: {}", track.number, lba, cdrom::get_scsi_error_message(&error))
+                    })
+                    .and_then(|read| {
+                        if anchor.is_empty() {
+                            return Ok((expected, read));
+                        }
+
+                        find_anchor(&buffer[..read], &anchor, expected).map(|start| (start, read))
+                            .ok_or_else(|| { format!("track {}, sector {}: reads don't line up, drive is jittering", track.number, lba) })
+                    });
+
+                match result {
+                    Ok(found) => break found,
+                    Err(message) => {
+                        attempts += 1;
+
+                        if attempts >= READ_RETRIES {
+                            return Err(AudioError::Read(message));
+                        }
+
+                        error_callback(message);
+                    },
+                }
+            };
+
+            let end = read.min(start + (total - position));
+
+            // The drive read ahead of where it was told to, right at the end of the last track, so there's nothing
+            // more to be had. It's at most a sector of samples, and silence is the best guess for them.
+            let samples = if start < end { buffer[start..end].to_vec() } else { vec![0u8; total - position] };
+
+            crc32.update(&samples);
+            let _ = disc.write_all(&samples); // Can't fail, it's a sink.
+
+            // If flac dies, we find out why once it's been waited on.
+            if stdin.write_all(&samples).is_err() {
+                break;
+            }
+
+            anchor.extend_from_slice(&samples);
+            let excess = anchor.len().saturating_sub(JITTER_ANCHOR_SIZE);
+            anchor.drain(..excess);
+
+            position += samples.len();
+            callback(samples.len());
+        }
+
+        Ok(())
+    })();
+
+    // Closing stdin is how flac knows the track is over.
+    drop(child.stdin.take());
+    let output = child.wait_with_output().map_err(|_| { AudioError::LaunchFail })?;
+
+    result?;
+
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no reason given.");
+        return Err(AudioError::Encode(String::from(reason.trim())));
+    }
+
+    Ok(format!("{:08x}", crc32.finalize()))
+}
+
+pub fn write_checksums(directory: &Path, metadata: &DiscMetadata, tracks: &[Track], checksums: &[String]) -> Result<(), AudioError> {
+    let mut text = String::from("# CRC32 of each track's audio, as read from the disk.\n");
+
+    for (track, crc32) in tracks.iter().zip(checksums.iter()) {
+        let _ = writeln!(text, "{}  {}", crc32, get_track_file_name(metadata, track));
+    }
+
+    fs::write(directory.join(CHECKSUM_FILE), text).map_err(|e| { AudioError::Write(format!("{}", e)) })
+}
+
+pub fn write_sidecar(directory: &Path, metadata: &DiscMetadata, tracks: &[Track], checksums: &[String], disc_id: &str) -> Result<(), AudioError> {
+    let tracks: Vec<Value> = tracks.iter().zip(checksums.iter()).map(|(track, crc32)| json!({
+        "number": track.number,
+        "file": get_track_file_name(metadata, track),
+        "title": metadata.get_track_title(track.number),
+        "artist": metadata.artists.get(&track.number).or(metadata.artist.as_ref()),
+        "isrc": metadata.isrcs.get(&track.number),
+        "start": track.start,
+        "length": track.length,
+        "pregap": track.pregap,
+        "crc32": crc32,
+    })).collect();
+
+    let sidecar = json!({
+        "album": metadata.album,
+        "artist": metadata.artist,
+        "mcn": metadata.mcn,
+        "musicbrainz_disc_id": disc_id,
+        "metadata_sources": metadata.sources,
+        "tracks": tracks,
+    });
+
+    // Can't fail, it's all strings and numbers.
+    let text = serde_json::to_string_pretty(&sidecar).unwrap_or_default();
+    fs::write(directory.join(SIDECAR_FILE), text).map_err(|e| { AudioError::Write(format!("{}", e)) })
+}
+
+pub fn get_audio_error_message(error: &AudioError) -> String {
+    match error {
+        AudioError::Read(message) => format!("Error reading disk: {}", message),
+        AudioError::Write(message) => format!("Error writing output: {}", message),
+        AudioError::LaunchFail => String::from("Failed to launch flac. Is it installed?"),
+        AudioError::Encode(message) => format!("flac failed: {}", message),
+        AudioError::Cancelled => String::from("Rip cancelled. Disk was ejected before it finished."),
+    }
+}
@@ -0,0 +1,103 @@
+
+// Keeps strangers on a shared network away from the API and gRPC. With api_token set, clients have to send it as
+// `Authorization: Bearer <token>`, and with a certificate and key they have to talk TLS, so the token can't be
+// read off the wire.
+
+use std::fs;
+use std::sync::Arc;
+
+use rustls::pki_types::CertificateDer;
+
+use crate::config::Config;
+
+pub enum AuthError {
+    Read(String, String), // Couldn't read a PEM file. Holds which one and why.
+    HalfTls,              // Only one of the certificate and key is set.
+    NoKey(String),        // The key file doesn't have a private key in it.
+    Invalid(String),      // The certificate and key didn't work together. Holds what rustls said about it.
+}
+
+pub struct Tls {
+    pub certificate: Vec<u8>, // PEM, as read, for gRPC.
+    pub key: Vec<u8>,
+    pub config: Arc<rustls::ServerConfig>, // For the API.
+}
+
+#[derive(Clone, Default)]
+pub struct Security {
+    pub token: Option<String>,
+    pub tls: Option<Arc<Tls>>,
+}
+
+impl Security {
+    // gRPC can control the drives, so it can have a token of its own, for when the API's is handed out to dashboards.
+    pub fn for_grpc(&self, config: &Config) -> Security {
+        Security {
+            token: config.grpc_token.clone().or_else(|| self.token.clone()),
+            tls: self.tls.clone(),
+        }
+    }
+
+    // Takes what's in an Authorization header, if there was one.
+    pub fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        let token = match &self.token {
+            Some(token) => token,
+            None => return true,
+        };
+
+        let sent = authorization.and_then(|header| header.trim().strip_prefix("Bearer ")).unwrap_or_default();
+
+        // Look at every byte, so how long it takes to say no doesn't give away how much of it was right.
+        sent.len() == token.len() && sent.bytes().zip(token.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+    }
+}
+
+fn read_pem(file: &str) -> Result<Vec<u8>, AuthError> {
+    fs::read(file).map_err(|e| { AuthError::Read(String::from(file), format!("{}", e)) })
+}
+
+fn load_tls(certificate_file: &str, key_file: &str) -> Result<Tls, AuthError> {
+    let certificate = read_pem(certificate_file)?;
+    let key = read_pem(key_file)?;
+
+    let chain: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut certificate.as_slice())
+        .collect::<Result<_, _>>()
+        .map_err(|e| { AuthError::Read(String::from(certificate_file), format!("{}", e)) })?;
+
+    let private_key = rustls_pemfile::private_key(&mut key.as_slice())
+        .map_err(|e| { AuthError::Read(String::from(key_file), format!("{}", e)) })?
+        .ok_or_else(|| { AuthError::NoKey(String::from(key_file)) })?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(chain, private_key)
+        .map_err(|e| { AuthError::Invalid(format!("{}", e)) })?;
+
+    Ok(Tls {
+        certificate,
+        key,
+        config: Arc::new(config),
+    })
+}
+
+pub fn load_security(config: &Config) -> Result<Security, AuthError> {
+    let tls = match (&config.tls_certificate, &config.tls_key) {
+        (Some(certificate), Some(key)) => Some(Arc::new(load_tls(certificate, key)?)),
+        (None, None) => None,
+        _ => return Err(AuthError::HalfTls),
+    };
+
+    Ok(Security {
+        token: config.api_token.clone(),
+        tls,
+    })
+}
+
+pub fn get_auth_error_message(error: &AuthError) -> String {
+    match error {
+        AuthError::Read(file, message) => format!("Failed to read {}: {}", file, message),
+        AuthError::HalfTls => String::from("TLS needs both tls_certificate and tls_key."),
+        AuthError::NoKey(file) => format!("There's no private key in {}.", file),
+        AuthError::Invalid(message) => format!("The TLS certificate and key don't work: {}", message),
+    }
+}
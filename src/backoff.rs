@@ -0,0 +1,79 @@
+
+// Keeps track of a helper tool that keeps failing, like blkid or lsscsi, so it gets tried less and less often instead
+// of every poll, and so there's something to tell the operator about it. Waits twice as long after each failure in a
+// row, up to MAX_DELAY, and goes straight back to normal the first time it works.
+
+use std::time::Duration;
+use std::time::Instant;
+
+const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Default)]
+pub struct Backoff {
+    failures: u32,            // In a row.
+    retry_at: Option<Instant>,
+    reason: Option<String>,   // Why it failed last.
+}
+
+impl Backoff {
+    // Whether it's been long enough to try again.
+    pub fn is_due(&self) -> bool {
+        self.retry_at.is_none_or(|time| Instant::now() >= time)
+    }
+
+    // `interval` is how often it'd be tried if it was working.
+    pub fn failed(&mut self, interval: Duration, reason: String) {
+        self.failures += 1;
+
+        let delay = interval.saturating_mul(2u32.saturating_pow(self.failures - 1)).min(MAX_DELAY);
+        self.retry_at = Some(Instant::now() + delay);
+        self.reason = Some(reason);
+    }
+
+    pub fn succeeded(&mut self) {
+        *self = Backoff::default();
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delay(backoff: &Backoff) -> Duration {
+        backoff.retry_at.unwrap().saturating_duration_since(Instant::now())
+    }
+
+    #[test]
+    fn delays_double_up_to_the_limit() {
+        let interval = Duration::from_secs(2);
+        let mut backoff = Backoff::default();
+        assert!(backoff.is_due());
+
+        backoff.failed(interval, String::from("first"));
+        assert!(delay(&backoff) <= interval && delay(&backoff) > interval / 2);
+        assert!(!backoff.is_due());
+
+        backoff.failed(interval, String::from("second"));
+        assert!(delay(&backoff) <= interval * 2 && delay(&backoff) > interval);
+
+        for _ in 0..40 {
+            backoff.failed(interval, String::from("again"));
+        }
+        assert!(delay(&backoff) <= MAX_DELAY && delay(&backoff) > MAX_DELAY / 2);
+        assert_eq!(backoff.failures(), 42);
+        assert_eq!(backoff.reason(), Some("again"));
+
+        backoff.succeeded();
+        assert!(backoff.is_due());
+        assert_eq!(backoff.failures(), 0);
+        assert_eq!(backoff.reason(), None);
+    }
+}
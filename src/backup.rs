@@ -0,0 +1,124 @@
+
+// Sends finished images into a restic or Borg repository, streamed through their stdin import so nothing extra has
+// to be written to disk. The snapshot or archive they end up in gets recorded in the catalog.
+
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BackupTool {
+    Restic,
+    Borg,
+}
+
+pub enum BackupError {
+    Password(String), // Couldn't read the password file.
+    Read(String),     // Couldn't read the image.
+    LaunchFail,       // Failed to launch the tool.
+    Failed(String),   // The tool ran but failed. Holds what it had to say about it.
+    Parse,            // The tool worked, but we couldn't find the snapshot ID in what it said.
+}
+
+impl BackupTool {
+    pub fn name(self) -> &'static str {
+        match self {
+            BackupTool::Restic => "restic",
+            BackupTool::Borg => "borg",
+        }
+    }
+}
+
+fn build_command(tool: BackupTool, repository: &str, name: &str, password: Option<String>) -> Command {
+    let mut command = Command::new(tool.name());
+
+    match tool {
+        BackupTool::Restic => {
+            command.args(["backup", "--json", "--stdin", "--stdin-filename", name, "--repo", repository]);
+
+            if let Some(password) = password {
+                command.env("RESTIC_PASSWORD", password);
+            }
+        },
+        BackupTool::Borg => {
+            // Borg fills in {now} itself, so archives of the same disk don't collide.
+            command.args(["create", "--json", "--stdin-name", name]);
+            command.arg(format!("{}::{}-{{now}}", repository, name));
+            command.arg("-");
+
+            if let Some(password) = password {
+                command.env("BORG_PASSPHRASE", password);
+            }
+        },
+    }
+
+    command
+}
+
+// restic prints a line of JSON for every status update, and the snapshot ID is in the summary at the end.
+// Borg prints one JSON document describing the archive.
+fn parse_snapshot_id(tool: BackupTool, output: &str) -> Option<String> {
+    match tool {
+        BackupTool::Restic => output.lines().rev()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find_map(|value| value["snapshot_id"].as_str().map(String::from)),
+        BackupTool::Borg => serde_json::from_str::<Value>(output).ok()
+            .and_then(|value| value["archive"]["name"].as_str().map(String::from)),
+    }
+}
+
+// Streams the image into the repository, and returns the ID of the snapshot (restic) or name of the archive (Borg) it's in.
+pub fn backup_image(tool: BackupTool, repository: &str, password_file: Option<&str>, image: &str) -> Result<String, BackupError> {
+    let password = match password_file {
+        Some(file) => Some(fs::read_to_string(file)
+            .map_err(|e| { BackupError::Password(format!("{}", e)) })?
+            .trim_end().to_string()),
+        None => None,
+    };
+
+    let name = Path::new(image).file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let mut source = fs::File::open(image).map_err(|e| { BackupError::Read(format!("{}", e)) })?;
+
+    let mut child = build_command(tool, repository, &name, password)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| { BackupError::LaunchFail })?;
+
+    // The image goes in on its own thread, so the tool can't stall us by filling its output pipes while we write.
+    // Dropping stdin when the copy's done is what tells the tool the image has ended.
+    let mut stdin = child.stdin.take().ok_or(BackupError::LaunchFail)?;
+    let copy = thread::spawn(move || io::copy(&mut source, &mut stdin));
+
+    let output = child.wait_with_output().map_err(|_| { BackupError::LaunchFail })?;
+    let copied = copy.join().unwrap_or_else(|_| Err(io::Error::other("the copy panicked")));
+
+    // If the tool dies partway through, the copy fails too, but what the tool said is the more useful reason.
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no reason given.");
+        return Err(BackupError::Failed(String::from(reason.trim())));
+    }
+
+    // The tool's happy, but it didn't get the whole image, so what it saved can't stand in for it.
+    if let Err(error) = copied {
+        return Err(BackupError::Read(format!("{}", error)));
+    }
+
+    parse_snapshot_id(tool, &String::from_utf8_lossy(&output.stdout)).ok_or(BackupError::Parse)
+}
+
+pub fn get_backup_error_message(tool: BackupTool, error: &BackupError) -> String {
+    match error {
+        BackupError::Password(message) => format!("Failed to read the {} password file: {}", tool.name(), message),
+        BackupError::Read(message) => format!("Failed to read the image: {}", message),
+        BackupError::LaunchFail => format!("Failed to launch {}. Is it installed?", tool.name()),
+        BackupError::Failed(message) => format!("{} failed: {}", tool.name(), message),
+        BackupError::Parse => format!("Couldn't find the snapshot ID in what {} said.", tool.name()),
+    }
+}
@@ -0,0 +1,126 @@
+
+// Measures how fast a drive really reads, on whatever disk is put in it. Drives are much faster at the outside of a
+// disk than the inside, and some take ages to seek, so both ends get timed and a handful of random reads too. Knowing
+// which drives are slow says which ones should get the small disks.
+
+use std::fs;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::time::Instant;
+
+use crate::buffer_pool;
+use crate::catalog;
+
+const SECTOR_SIZE: u64 = 2048;
+
+// How much to read at each end. Enough for the drive to get up to speed, small enough to finish in under a minute.
+const SAMPLE_SIZE: u64 = 64 * 1024 * 1024;
+
+const READ_SIZE: usize = 1024 * 1024;
+
+const SEEK_COUNT: u64 = 32;
+
+#[derive(Clone, Default, PartialEq)]
+pub struct Profile {
+    pub start_speed: u64, // Bytes per second, reading from the start of the disk.
+    pub end_speed: u64,   // Same, at the end of the disk.
+    pub seek_time: u64,   // Average milliseconds to read a sector from somewhere random.
+    pub measured: u64,    // When, in seconds since the epoch.
+}
+
+impl Profile {
+    // What a copy buffer for this drive should be. About a tenth of a second of reading, so every read is big enough
+    // to keep the drive busy but there's still progress to show.
+    pub fn suggested_buffer_size(&self) -> usize {
+        let target = (self.start_speed.max(self.end_speed) / 10).clamp(64 * 1024, 16 * 1024 * 1024);
+
+        // Round down to a power of two, which every block size divides.
+        1 << (63 - target.leading_zeros())
+    }
+
+    pub fn summary(&self) -> String {
+        format!("Reads {:.1} MB/s at the start and {:.1} MB/s at the end, seeks in {} ms. Suggested buffer size {} KB.",
+            self.start_speed as f64 / 1_000_000.0, self.end_speed as f64 / 1_000_000.0, self.seek_time,
+            self.suggested_buffer_size() / 1024)
+    }
+}
+
+// Makes sure a read actually goes to the disk and doesn't come out of memory from an earlier read.
+fn drop_cache(device: &fs::File) {
+    unsafe {
+        libc::posix_fadvise(device.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+}
+
+// Reads a stretch of the disk and says how many bytes per second that went at.
+fn time_read(device: &fs::File, start: u64, length: u64, cancel: &AtomicBool) -> Result<u64, String> {
+    let mut buffer = buffer_pool::get(READ_SIZE);
+    let mut position = start;
+
+    drop_cache(device);
+    let began = Instant::now();
+
+    while position < start + length {
+        if cancel.load(Relaxed) {
+            return Err(String::from("Cancelled."));
+        }
+
+        let len = (start + length - position).min(READ_SIZE as u64) as usize;
+        device.read_exact_at(&mut buffer[..len], position)
+            .map_err(|e| { format!("Read failed at byte {}: {}", position, e) })?;
+
+        position += len as u64;
+    }
+
+    let seconds = began.elapsed().as_secs_f64().max(0.001);
+    Ok((length as f64 / seconds) as u64)
+}
+
+// Spread out over the whole disk, but the same every time, so two drives get the same test.
+fn seek_targets(sectors: u64) -> impl Iterator<Item = u64> {
+    (0..SEEK_COUNT).map(move |index| ((index * 7919) % SEEK_COUNT) * sectors / SEEK_COUNT)
+}
+
+// Benchmarks the disk in the device, which is `length` bytes long. The callback gets progress out of 1000.
+pub fn run_benchmark<CB>(device: &str, length: u64, cancel: &AtomicBool, mut callback: CB) -> Result<Profile, String> where
+    CB: FnMut(usize) {
+
+    let device = fs::File::open(device).map_err(|e| { format!("Couldn't open the drive: {}", e) })?;
+
+    let sample = (length / 4 / SECTOR_SIZE * SECTOR_SIZE).min(SAMPLE_SIZE);
+    if sample == 0 {
+        return Err(String::from("The disk is too small to benchmark."));
+    }
+
+    let start_speed = time_read(&device, 0, sample, cancel)?;
+    callback(400);
+
+    let end_speed = time_read(&device, length - sample, sample, cancel)?;
+    callback(800);
+
+    let mut sector = buffer_pool::get(SECTOR_SIZE as usize);
+    let mut seek_total = 0;
+
+    for (index, target) in seek_targets(length / SECTOR_SIZE).enumerate() {
+        if cancel.load(Relaxed) {
+            return Err(String::from("Cancelled."));
+        }
+
+        drop_cache(&device);
+        let began = Instant::now();
+        device.read_exact_at(&mut sector, target * SECTOR_SIZE)
+            .map_err(|e| { format!("Read failed at sector {}: {}", target, e) })?;
+        seek_total += began.elapsed().as_millis() as u64;
+
+        callback(800 + (index + 1) * 200 / SEEK_COUNT as usize);
+    }
+
+    Ok(Profile {
+        start_speed,
+        end_speed,
+        seek_time: seek_total / SEEK_COUNT,
+        measured: catalog::now(),
+    })
+}
@@ -0,0 +1,102 @@
+
+// Copy buffers shared by every drive, so eight drives copying with big buffers can't take more memory than the
+// machine has, and buffers get reused instead of allocated fresh for every disk.
+
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+// Enough for every drive a big rip station is likely to have, with room to spare.
+const DEFAULT_LIMIT: usize = 256 * 1024 * 1024;
+
+struct PoolState {
+    free: Vec<Vec<u8>>,
+    allocated: usize, // Bytes in every buffer the pool has made, in use or not.
+}
+
+pub struct BufferPool {
+    limit: usize,
+    state: Mutex<PoolState>,
+    returned: Condvar,
+}
+
+static POOL: OnceLock<BufferPool> = OnceLock::new();
+
+// Sets how much memory the pool can use. Only works before anything has taken a buffer.
+pub fn init(limit: usize) {
+    let _ = POOL.set(BufferPool::new(limit));
+}
+
+// A buffer of exactly `len` bytes. Waits for other drives to give some back if the pool is full.
+// Whatever the last user left in it is still there.
+pub fn get(len: usize) -> PooledBuffer {
+    POOL.get_or_init(|| BufferPool::new(DEFAULT_LIMIT)).get(len)
+}
+
+impl BufferPool {
+    fn new(limit: usize) -> BufferPool {
+        BufferPool {
+            limit,
+            state: Mutex::new(PoolState {
+                free: Vec::new(),
+                allocated: 0,
+            }),
+            returned: Condvar::new(),
+        }
+    }
+
+    fn get(&'static self, len: usize) -> PooledBuffer {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(index) = state.free.iter().position(|buffer| buffer.len() == len) {
+                let buffer = state.free.swap_remove(index);
+                return PooledBuffer { pool: self, buffer };
+            }
+
+            // A buffer bigger than the whole pool still has to be allowed, or it would wait forever.
+            if state.allocated + len <= self.limit || state.allocated == 0 {
+                state.allocated += len;
+                return PooledBuffer { pool: self, buffer: vec![0; len] };
+            }
+
+            // Free buffers of the wrong size are only taking up room.
+            if let Some(buffer) = state.free.pop() {
+                state.allocated -= buffer.len();
+                continue;
+            }
+
+            state = self.returned.wait(state).unwrap();
+        }
+    }
+}
+
+pub struct PooledBuffer {
+    pool: &'static BufferPool,
+    buffer: Vec<u8>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let buffer = std::mem::take(&mut self.buffer);
+
+        self.pool.state.lock().unwrap().free.push(buffer);
+        self.pool.returned.notify_all();
+    }
+}
@@ -0,0 +1,179 @@
+
+// Writes images back to blank disks with wodim, so the same tower that rips can also duplicate.
+// Also erases rewritable disks so they can be reused.
+
+use nom::bytes::complete::tag;
+use nom::character::complete::digit1;
+use nom::character::complete::multispace0;
+use nom::sequence::preceded;
+use nom::sequence::terminated;
+use nom::sequence::tuple;
+use std::io::Read;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::ParserResult;
+
+// wodim's megabytes are binary ones.
+const MEGABYTE: u64 = 1024 * 1024;
+
+pub enum BurnError {
+    LaunchFail,     // Failed to launch wodim.
+    Failed(String), // wodim ran but the burn failed. Holds the last thing it complained about.
+}
+
+// Parses wodim's progress lines, which look like "Track 01:   12 of  650 MB written (fifo 100%) [buf  99%]  48.0x."
+fn parse_burn_progress(input: &str) -> ParserResult<'_, (u64, u64)> {
+    let (input, (_, written, total)) = tuple((
+        terminated(preceded(tag("Track "), digit1), tag(":")),
+        preceded(multispace0, digit1),
+        preceded(tuple((tag(" of"), multispace0)), terminated(digit1, tag(" MB written")))
+    ))(input)?;
+
+    // Only way these could panic is if they exceed 64 bits.
+    Ok((input, (written.parse().unwrap(), total.parse().unwrap())))
+}
+
+// Burns the image. The callback gets the bytes written and the total, to the nearest megabyte since that's all
+// wodim says.
+// The disk is left in the drive so it can be verified.
+pub fn burn_image<CB>(drive: &str, image: &str, mut callback: CB) -> Result<(), BurnError> where
+    CB: FnMut(u64, u64)
+{
+    let mut command = Command::new("wodim");
+    command.args(["-v", "-dao", &format!("dev={}", drive), image]);
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+
+    let mut child = command.spawn().map_err(|_| { BurnError::LaunchFail })?;
+
+    // Drain stderr on the side so wodim can't stall on a full pipe while we watch stdout.
+    let mut stderr = child.stderr.take().unwrap();
+    let error_reader = thread::spawn(move || {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors);
+        errors
+    });
+
+    // Progress is redrawn in place with carriage returns, so we can't read it line by line.
+    let mut stdout = child.stdout.take().unwrap();
+    let mut buffer = [0; 256];
+    let mut pending = String::new();
+
+    while let Ok(len) = stdout.read(&mut buffer) {
+        if len == 0 {
+            break;
+        }
+
+        pending.push_str(&String::from_utf8_lossy(&buffer[..len]));
+
+        while let Some(end) = pending.find(['\r', '\n']) {
+            if let Ok((_, (written, total))) = parse_burn_progress(pending[..end].trim()) {
+                callback(written * MEGABYTE, total * MEGABYTE);
+            }
+            pending.drain(..=end);
+        }
+    }
+
+    let status = child.wait().map_err(|_| { BurnError::LaunchFail })?;
+    let errors = error_reader.join().unwrap_or_default();
+
+    if status.success() {
+        Ok(())
+    } else {
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("wodim failed.");
+        Err(BurnError::Failed(String::from(reason.trim())))
+    }
+}
+
+pub fn get_burn_error_message(error: &BurnError) -> String {
+    match error {
+        BurnError::LaunchFail => String::from("Failed to launch wodim. Is it installed?"),
+        BurnError::Failed(message) => format!("wodim failed: {}", message),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlankMode {
+    Quick, // Just wipes the table of contents. Takes a minute or so.
+    Full,  // Overwrites the whole disk. Takes about as long as burning it.
+}
+
+impl BlankMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            BlankMode::Quick => "quick",
+            BlankMode::Full => "full",
+        }
+    }
+
+    // Rough time a typical drive takes, since wodim doesn't report blanking progress.
+    pub fn expected_duration(self) -> Duration {
+        match self {
+            BlankMode::Quick => Duration::from_secs(90),
+            BlankMode::Full => Duration::from_secs(20 * 60),
+        }
+    }
+}
+
+// Erases a rewritable disk. The callback is called periodically with how long it's been going.
+pub fn blank_disk<CB>(drive: &str, mode: BlankMode, mut callback: CB) -> Result<(), BurnError> where
+    CB: FnMut(Duration)
+{
+    let blank = match mode {
+        BlankMode::Quick => "blank=fast",
+        BlankMode::Full => "blank=all",
+    };
+
+    let mut command = Command::new("wodim");
+    command.args([blank, &format!("dev={}", drive)]);
+    command.stdout(Stdio::null());
+    command.stderr(Stdio::piped());
+
+    let mut child = command.spawn().map_err(|_| { BurnError::LaunchFail })?;
+
+    let mut stderr = child.stderr.take().unwrap();
+    let error_reader = thread::spawn(move || {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors);
+        errors
+    });
+
+    let start = Instant::now();
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {
+                callback(start.elapsed());
+                thread::sleep(Duration::from_millis(1000));
+            },
+            Err(_) => return Err(BurnError::LaunchFail),
+        }
+    };
+
+    let errors = error_reader.join().unwrap_or_default();
+
+    if status.success() {
+        Ok(())
+    } else {
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("wodim failed.");
+        Err(BurnError::Failed(String::from(reason.trim())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wodim_progress_parses() {
+        let (_, progress) = parse_burn_progress("Track 01:   12 of  650 MB written (fifo 100%) [buf  99%]  48.0x.").unwrap();
+        assert_eq!(progress, (12, 650));
+
+        assert!(parse_burn_progress("Starting new track at sector: 0").is_err());
+    }
+}
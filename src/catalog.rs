@@ -0,0 +1,420 @@
+
+// The catalog of everything that's been archived, kept in the working directory alongside the images.
+// Each disk gets an `[id]` header followed by `key = value` lines, like the config file. Keys that can
+// have more than one value are just repeated.
+
+use nom::bytes::complete::take_until;
+use nom::character::complete::char as char_tag;
+use nom::character::complete::digit1;
+use nom::combinator::rest;
+use nom::sequence::delimited;
+use nom::sequence::separated_pair;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::ParserResult;
+
+pub const CATALOG_FILE: &str = "auto_archive.catalog";
+
+pub enum CatalogError {
+    Read(String),  // Failed to read the file. Not having one yet is fine.
+    Parse(usize),  // Line number that didn't make sense.
+    Write(String), // Failed to save the catalog.
+}
+
+#[derive(Clone, Default)]
+pub struct CatalogEntry {
+    pub id: u64,
+    pub image: String,       // Path of the saved image, relative to the working directory.
+    pub volume: String,      // Volume name read off the disk.
+    pub hfs_volume: Option<String>, // Name of the Mac volume on Mac/PC hybrid disks.
+    pub publisher: Option<String>,  // From the disk's volume descriptor, when it says.
+    pub preparer: Option<String>,
+    pub application: Option<String>,
+    pub created: Option<String>,    // When the volume was made, as the disk tells it.
+    pub date: u64,           // When it was archived, in seconds since the Unix epoch.
+    pub size: u64,           // Size of the image as read, before any compression, in bytes.
+    pub parity: Vec<String>, // PAR2 recovery files for the image.
+    pub crc32: String,       // Hashes of the image as read, before any compression.
+    pub sha1: String,
+    pub md5: Option<String>,    // Only for rips made with extra_hashes.
+    pub sha256: Option<String>,
+    pub redump: Option<String>, // Name of the redump.org entry this is a known-good dump of.
+    pub fingerprint: String,    // Quick fingerprint taken before ripping, for catching duplicates.
+    pub ia_item: Option<String>, // Internet Archive item it was uploaded to.
+    pub backup: Option<String>, // Backup tool and the snapshot or archive the image went into.
+    pub tape: Option<String>,   // Tape label and file number the image was spooled to, or "pending" if it's still waiting.
+    pub notes: Vec<String>,     // Anything odd about the disk or the rip worth knowing later.
+    pub damage: Option<String>, // What recovery mode had to zero fill. Images with this are incomplete.
+    pub label_photo: Option<String>, // Webcam picture of the disk's label.
+    pub inventory_id: Option<String>, // Barcode scanned off the disk's box, to find it in some other inventory.
+    pub mirror: Option<String>, // Second copy of the image, written while it was ripped, for mirror_directory.
+}
+
+// What to do with an imported disk that's already in the catalog.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DuplicatePolicy {
+    Skip,     // Keep ours.
+    Replace,  // Keep theirs.
+    KeepBoth,
+}
+
+#[derive(Default)]
+pub struct MergeResult {
+    pub added: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+}
+
+#[derive(Default)]
+pub struct Catalog {
+    entries: Vec<CatalogEntry>,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+}
+
+// Formats a date from the catalog as `YYYY-MM-DD HH:MM`, in UTC.
+pub fn format_date(seconds: u64) -> String {
+    // Converting days to a calendar date, from Howard Hinnant's civil_from_days.
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let time = seconds % 86400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, time / 3600, time % 3600 / 60)
+}
+
+fn parse_header(input: &str) -> ParserResult<'_, &str> {
+    delimited(char_tag('['), digit1, char_tag(']'))(input)
+}
+
+fn parse_field(input: &str) -> ParserResult<'_, (&str, &str)> {
+    separated_pair(take_until("="), char_tag('='), rest)(input)
+}
+
+impl CatalogEntry {
+    fn apply(&mut self, key: &str, value: &str) -> Result<(), ()> {
+        match key {
+            "image" => self.image = String::from(value),
+            "volume" => self.volume = String::from(value),
+            "hfs_volume" => self.hfs_volume = Some(String::from(value)),
+            "publisher" => self.publisher = Some(String::from(value)),
+            "preparer" => self.preparer = Some(String::from(value)),
+            "application" => self.application = Some(String::from(value)),
+            "created" => self.created = Some(String::from(value)),
+            "date" => self.date = value.parse().map_err(|_| ())?,
+            "size" => self.size = value.parse().map_err(|_| ())?,
+            "parity" => self.parity.push(String::from(value)),
+            "crc32" => self.crc32 = String::from(value),
+            "sha1" => self.sha1 = String::from(value),
+            "md5" => self.md5 = Some(String::from(value)),
+            "sha256" => self.sha256 = Some(String::from(value)),
+            "redump" => self.redump = Some(String::from(value)),
+            "fingerprint" => self.fingerprint = String::from(value),
+            "ia_item" => self.ia_item = Some(String::from(value)),
+            "backup" => self.backup = Some(String::from(value)),
+            "tape" => self.tape = Some(String::from(value)),
+            "note" => self.notes.push(String::from(value)),
+            "damage" => self.damage = Some(String::from(value)),
+            "label_photo" => self.label_photo = Some(String::from(value)),
+            "inventory_id" => self.inventory_id = Some(String::from(value)),
+            "mirror" => self.mirror = Some(String::from(value)),
+            _ => return Err(()),
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, output: &mut String) {
+        // Writing to a String can't fail.
+        let _ = writeln!(output, "[{}]", self.id);
+        let _ = writeln!(output, "image = {}", self.image);
+        let _ = writeln!(output, "volume = {}", self.volume);
+
+        if let Some(hfs_volume) = &self.hfs_volume {
+            let _ = writeln!(output, "hfs_volume = {}", hfs_volume);
+        }
+
+        if let Some(publisher) = &self.publisher {
+            let _ = writeln!(output, "publisher = {}", publisher);
+        }
+
+        if let Some(preparer) = &self.preparer {
+            let _ = writeln!(output, "preparer = {}", preparer);
+        }
+
+        if let Some(application) = &self.application {
+            let _ = writeln!(output, "application = {}", application);
+        }
+
+        if let Some(created) = &self.created {
+            let _ = writeln!(output, "created = {}", created);
+        }
+
+        let _ = writeln!(output, "date = {}", self.date);
+        let _ = writeln!(output, "size = {}", self.size);
+
+        for parity in self.parity.iter() {
+            let _ = writeln!(output, "parity = {}", parity);
+        }
+
+        let _ = writeln!(output, "crc32 = {}", self.crc32);
+        let _ = writeln!(output, "sha1 = {}", self.sha1);
+
+        if let Some(md5) = &self.md5 {
+            let _ = writeln!(output, "md5 = {}", md5);
+        }
+
+        if let Some(sha256) = &self.sha256 {
+            let _ = writeln!(output, "sha256 = {}", sha256);
+        }
+
+        if let Some(redump) = &self.redump {
+            let _ = writeln!(output, "redump = {}", redump);
+        }
+
+        if !self.fingerprint.is_empty() {
+            let _ = writeln!(output, "fingerprint = {}", self.fingerprint);
+        }
+
+        if let Some(ia_item) = &self.ia_item {
+            let _ = writeln!(output, "ia_item = {}", ia_item);
+        }
+
+        if let Some(backup) = &self.backup {
+            let _ = writeln!(output, "backup = {}", backup);
+        }
+
+        if let Some(tape) = &self.tape {
+            let _ = writeln!(output, "tape = {}", tape);
+        }
+
+        for note in self.notes.iter() {
+            let _ = writeln!(output, "note = {}", note);
+        }
+
+        if let Some(damage) = &self.damage {
+            let _ = writeln!(output, "damage = {}", damage);
+        }
+
+        if let Some(label_photo) = &self.label_photo {
+            let _ = writeln!(output, "label_photo = {}", label_photo);
+        }
+
+        if let Some(inventory_id) = &self.inventory_id {
+            let _ = writeln!(output, "inventory_id = {}", inventory_id);
+        }
+
+        if let Some(mirror) = &self.mirror {
+            let _ = writeln!(output, "mirror = {}", mirror);
+        }
+
+        let _ = writeln!(output);
+    }
+
+    // Same disk, as far as we can tell. Either the fingerprints or the image hashes match.
+    pub fn is_duplicate_of(&self, other: &CatalogEntry) -> bool {
+        (!self.fingerprint.is_empty() && self.fingerprint == other.fingerprint)
+            || (!self.sha1.is_empty() && self.sha1 == other.sha1)
+    }
+
+    // True if the query shows up anywhere an operator might look for it. Case doesn't matter.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+
+        let mut fields = vec![format_date(self.date), self.image.clone(), self.volume.clone(),
+            self.crc32.clone(), self.sha1.clone(), self.fingerprint.clone()];
+        fields.extend(self.hfs_volume.iter().cloned());
+        fields.extend(self.publisher.iter().cloned());
+        fields.extend(self.preparer.iter().cloned());
+        fields.extend(self.md5.iter().cloned());
+        fields.extend(self.sha256.iter().cloned());
+        fields.extend(self.redump.iter().cloned());
+
+        fields.iter().any(|field| field.to_lowercase().contains(&query))
+    }
+}
+
+impl Catalog {
+    pub fn search(&self, query: &str) -> Vec<&CatalogEntry> {
+        self.entries.iter().filter(|entry| entry.matches(query)).collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut CatalogEntry> {
+        self.entries.iter_mut().find(|entry| entry.id == id)
+    }
+
+    pub fn find_fingerprint(&self, fingerprint: &str) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|entry| entry.fingerprint == fingerprint)
+    }
+
+    pub fn entries(&self) -> &[CatalogEntry] {
+        &self.entries
+    }
+
+    // Gives the entry the next free ID and adds it, without saving. Returns the ID.
+    fn insert(&mut self, mut entry: CatalogEntry) -> u64 {
+        entry.id = self.entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+        let id = entry.id;
+
+        self.entries.push(entry);
+
+        id
+    }
+
+    // Adds the entry and saves the catalog. Returns the ID.
+    pub fn add(&mut self, entry: CatalogEntry) -> Result<u64, CatalogError> {
+        let id = self.insert(entry);
+        self.save()?;
+
+        Ok(id)
+    }
+
+    // Merges in entries from another catalog and saves. They get new IDs, since theirs only mean anything over there.
+    pub fn merge(&mut self, entries: Vec<CatalogEntry>, policy: DuplicatePolicy) -> Result<MergeResult, CatalogError> {
+        let mut result = MergeResult::default();
+
+        for entry in entries {
+            let existing = self.entries.iter().position(|ours| ours.is_duplicate_of(&entry));
+
+            match (existing, policy) {
+                (Some(_), DuplicatePolicy::Skip) => result.skipped += 1,
+                (Some(index), DuplicatePolicy::Replace) => {
+                    let id = self.entries[index].id;
+                    self.entries[index] = CatalogEntry { id, ..entry };
+                    result.replaced += 1;
+                },
+                (Some(_), DuplicatePolicy::KeepBoth) | (None, _) => {
+                    self.insert(entry);
+                    result.added += 1;
+                },
+            }
+        }
+
+        self.save()?;
+
+        Ok(result)
+    }
+
+    pub fn save(&self) -> Result<(), CatalogError> {
+        let mut output = String::new();
+        for entry in self.entries.iter() {
+            entry.write(&mut output);
+        }
+
+        // Write it next to the real one and swap it in, so a crash can't leave us with half a catalog.
+        let temp_file = format!("{}.tmp", CATALOG_FILE);
+        fs::write(&temp_file, output).map_err(|e| { CatalogError::Write(format!("{}", e)) })?;
+        fs::rename(&temp_file, CATALOG_FILE).map_err(|e| { CatalogError::Write(format!("{}", e)) })
+    }
+}
+
+pub fn parse_catalog(input: &str) -> Result<Catalog, CatalogError> {
+    let mut catalog = Catalog::default();
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Ok((_, id)) = parse_header(line) {
+            catalog.entries.push(CatalogEntry {
+                id: id.parse().map_err(|_| { CatalogError::Parse(index + 1) })?,
+                ..CatalogEntry::default()
+            });
+            continue;
+        }
+
+        let (_, (key, value)) = parse_field(line).map_err(|_| { CatalogError::Parse(index + 1) })?;
+
+        // Fields have to come after a header.
+        let entry = catalog.entries.last_mut().ok_or(CatalogError::Parse(index + 1))?;
+        entry.apply(key.trim(), value.trim()).map_err(|_| { CatalogError::Parse(index + 1) })?;
+    }
+
+    Ok(catalog)
+}
+
+pub fn load_catalog() -> Result<Catalog, CatalogError> {
+    match fs::read_to_string(CATALOG_FILE) {
+        Ok(text) => parse_catalog(&text),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(Catalog::default()),
+        Err(error) => Err(CatalogError::Read(format!("{}", error))),
+    }
+}
+
+pub fn get_catalog_error_message(error: &CatalogError) -> String {
+    match error {
+        CatalogError::Read(message) => format!("Failed to read {}: {}", CATALOG_FILE, message),
+        CatalogError::Parse(line) => format!("Line {} of {} doesn't make sense.", line, CATALOG_FILE),
+        CatalogError::Write(message) => format!("Failed to save {}: {}", CATALOG_FILE, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_read_back() {
+        let entry = CatalogEntry {
+            id: 7,
+            image: String::from("DATA.iso"),
+            volume: String::from("DATA"),
+            publisher: Some(String::from("Someone")),
+            date: 1600000000,
+            size: 2048,
+            parity: vec![String::from("DATA.iso.par2"), String::from("DATA.iso.vol0+1.par2")],
+            crc32: String::from("1a2b3c4d"),
+            sha1: String::from("da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+            fingerprint: String::from("abc"),
+            notes: vec![String::from("First note."), String::from("Second note.")],
+            mirror: Some(String::from("/mnt/nas/DATA.iso")),
+            ..CatalogEntry::default()
+        };
+
+        let mut text = String::new();
+        entry.write(&mut text);
+
+        let catalog = parse_catalog(&text).ok().unwrap();
+        let read = &catalog.entries()[0];
+
+        assert_eq!(catalog.entries().len(), 1);
+        assert_eq!(read.id, 7);
+        assert_eq!(read.image, entry.image);
+        assert_eq!(read.publisher, entry.publisher);
+        assert_eq!(read.preparer, None);
+        assert_eq!(read.date, entry.date);
+        assert_eq!(read.size, entry.size);
+        assert_eq!(read.parity, entry.parity);
+        assert_eq!(read.sha1, entry.sha1);
+        assert_eq!(read.fingerprint, entry.fingerprint);
+        assert_eq!(read.notes, entry.notes);
+        assert_eq!(read.mirror, entry.mirror);
+    }
+
+    #[test]
+    fn bad_lines_are_caught() {
+        assert!(matches!(parse_catalog("image = x.iso\n"), Err(CatalogError::Parse(1))));
+        assert!(matches!(parse_catalog("[1]\nimage = x.iso\nsize = big\n"), Err(CatalogError::Parse(3))));
+        assert!(matches!(parse_catalog("[1]\nno_such_field = 1\n"), Err(CatalogError::Parse(2))));
+    }
+}
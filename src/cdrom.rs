@@ -0,0 +1,595 @@
+
+// Direct access to the Linux CD-ROM driver through ioctls, for the things the command line tools can't tell us.
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+
+// From linux/cdrom.h
+const CDROM_DRIVE_STATUS: libc::c_ulong = 0x5326;
+const CDSL_CURRENT: libc::c_int = libc::c_int::MAX;
+
+const CDS_NO_DISC: libc::c_int = 1;
+const CDS_TRAY_OPEN: libc::c_int = 2;
+const CDS_DRIVE_NOT_READY: libc::c_int = 3;
+const CDS_DISC_OK: libc::c_int = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TrayStatus {
+    Unknown,  // Drive doesn't report it, or we haven't asked yet.
+    Open,
+    Empty,    // Tray closed with nothing in it.
+    NotReady, // Usually means it's still spinning up a disk it just took in.
+    Loaded,
+}
+
+// Opens the device without waiting for media, so this works with an open or empty tray.
+pub fn open_device(drive: &str) -> io::Result<fs::File> {
+    fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(drive)
+}
+
+pub fn get_tray_status(drive: &str) -> io::Result<TrayStatus> {
+    read_tray_status(&open_device(drive)?)
+}
+
+// For a device that's already open, with O_NONBLOCK like `open_device` does it.
+pub fn read_tray_status(device: &impl AsRawFd) -> io::Result<TrayStatus> {
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), CDROM_DRIVE_STATUS as _, CDSL_CURRENT) };
+
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(match result {
+        CDS_NO_DISC => TrayStatus::Empty,
+        CDS_TRAY_OPEN => TrayStatus::Open,
+        CDS_DRIVE_NOT_READY => TrayStatus::NotReady,
+        CDS_DISC_OK => TrayStatus::Loaded,
+        _ => TrayStatus::Unknown,
+    })
+}
+
+// From scsi/sg.h
+const SG_IO: libc::c_ulong = 0x2285;
+const SG_DXFER_NONE: libc::c_int = -1;
+const SG_DXFER_FROM_DEV: libc::c_int = -3;
+const SG_INFO_OK_MASK: libc::c_uint = 0x1;
+
+// Long enough for a slow drive to spin up a scratched disk and retry a few times internally.
+const SCSI_TIMEOUT_MS: libc::c_uint = 30000;
+
+#[repr(C)]
+struct SgIoHdr {
+    interface_id: libc::c_int,
+    dxfer_direction: libc::c_int,
+    cmd_len: libc::c_uchar,
+    mx_sb_len: libc::c_uchar,
+    iovec_count: libc::c_ushort,
+    dxfer_len: libc::c_uint,
+    dxferp: *mut libc::c_void,
+    cmdp: *const libc::c_uchar,
+    sbp: *mut libc::c_uchar,
+    timeout: libc::c_uint,
+    flags: libc::c_uint,
+    pack_id: libc::c_int,
+    usr_ptr: *mut libc::c_void,
+    status: libc::c_uchar,
+    masked_status: libc::c_uchar,
+    msg_status: libc::c_uchar,
+    sb_len_wr: libc::c_uchar,
+    host_status: libc::c_ushort,
+    driver_status: libc::c_ushort,
+    resid: libc::c_int,
+    duration: libc::c_uint,
+    info: libc::c_uint,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SenseData {
+    pub key: u8,
+    pub asc: u8,  // Additional sense code.
+    pub ascq: u8, // Additional sense code qualifier.
+}
+
+pub enum ScsiError {
+    Io(io::Error),     // The ioctl itself failed.
+    Check(SenseData),  // The drive rejected the command and told us why.
+    Transport,         // Something between us and the drive failed, or the drive failed without saying why.
+}
+
+impl SenseData {
+    fn parse(sense: &[u8]) -> SenseData {
+        match sense.first().map(|code| code & 0x7F) {
+            // Descriptor format.
+            Some(0x72) | Some(0x73) if sense.len() >= 4 => SenseData {
+                key: sense[1] & 0x0F,
+                asc: sense[2],
+                ascq: sense[3],
+            },
+            // Fixed format.
+            Some(0x70) | Some(0x71) if sense.len() >= 14 => SenseData {
+                key: sense[2] & 0x0F,
+                asc: sense[12],
+                ascq: sense[13],
+            },
+            _ => SenseData::default(),
+        }
+    }
+}
+
+// Sends a command to the drive and reads its response into `data`, if there is one. Returns how much was read.
+pub fn scsi_read(device: &fs::File, cdb: &[u8], data: &mut [u8]) -> Result<usize, ScsiError> {
+    let mut sense = [0u8; 32];
+
+    let mut header = SgIoHdr {
+        interface_id: 'S' as libc::c_int,
+        dxfer_direction: if data.is_empty() { SG_DXFER_NONE } else { SG_DXFER_FROM_DEV },
+        cmd_len: cdb.len() as libc::c_uchar,
+        mx_sb_len: sense.len() as libc::c_uchar,
+        iovec_count: 0,
+        dxfer_len: data.len() as libc::c_uint,
+        dxferp: data.as_mut_ptr() as *mut libc::c_void,
+        cmdp: cdb.as_ptr(),
+        sbp: sense.as_mut_ptr(),
+        timeout: SCSI_TIMEOUT_MS,
+        flags: 0,
+        pack_id: 0,
+        usr_ptr: std::ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    };
+
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), SG_IO as _, &mut header as *mut SgIoHdr) };
+
+    if result < 0 {
+        return Err(ScsiError::Io(io::Error::last_os_error()));
+    }
+
+    if header.info & SG_INFO_OK_MASK != 0 {
+        if header.sb_len_wr > 0 {
+            return Err(ScsiError::Check(SenseData::parse(&sense[..header.sb_len_wr as usize])));
+        }
+
+        return Err(ScsiError::Transport);
+    }
+
+    Ok(data.len() - header.resid.max(0) as usize)
+}
+
+// What a failed read most likely means, so the operator knows whether to clean the disk, try another drive, or give up.
+pub fn describe_sense(sense: &SenseData) -> &'static str {
+    match (sense.key, sense.asc) {
+        (_, 0x6F) => "copy protection failure, is libdvdcss installed?",
+        (0x03, 0x11) | (0x03, 0x15) => "the disk is unreadable there, clean it or try another drive",
+        (0x03, 0x02) | (0x03, 0x09) => "the drive couldn't focus or track, a dirty lens does this",
+        (0x03, _) => "the disk is damaged or dirty",
+        (0x04, _) => "the drive itself is failing, try another drive",
+        (0x02, 0x3A) => "there's no disk in the drive",
+        (0x02, _) => "the drive wasn't ready",
+        (0x05, 0x21) => "the drive says that sector doesn't exist",
+        (0x05, 0x64) => "the drive can't read that kind of sector",
+        (0x05, _) => "the drive didn't understand the command",
+        (0x06, _) => "the disk was changed or the drive was reset",
+        (0x0B, _) => "the command was aborted, usually a cable or connection problem",
+        _ => "no idea what this one means",
+    }
+}
+
+// Reads one block straight from the drive, to find out why a normal read of it failed. The kernel keeps the sense data
+// from its own reads to itself. Returns None if it reads fine this time, or the drive doesn't say why not.
+pub fn probe_read_error(device: &fs::File, offset: u64) -> Option<(u64, SenseData)> {
+    let mut capacity = [0u8; 8];
+    let block_size = match scsi_read(device, &[0x25, 0, 0, 0, 0, 0, 0, 0, 0, 0], &mut capacity) {
+        Ok(_) => (u32::from_be_bytes([capacity[4], capacity[5], capacity[6], capacity[7]]) as u64).max(512),
+        Err(_) => 2048,
+    };
+
+    let lba = offset / block_size;
+    let mut data = vec![0u8; block_size as usize];
+
+    // READ(10), one block.
+    let cdb = [0x28, 0, (lba >> 24) as u8, (lba >> 16) as u8, (lba >> 8) as u8, lba as u8, 0, 0, 1, 0];
+
+    match scsi_read(device, &cdb, &mut data) {
+        Err(ScsiError::Check(sense)) => Some((lba, sense)),
+        _ => None,
+    }
+}
+
+pub fn get_scsi_error_message(error: &ScsiError) -> String {
+    match error {
+        ScsiError::Io(error) => format!("{}", error),
+        ScsiError::Check(sense) => format!("drive reported sense key {:X}, ASC {:02X}, ASCQ {:02X}, {}", sense.key, sense.asc, sense.ascq, describe_sense(sense)),
+        ScsiError::Transport => String::from("command failed without sense data"),
+    }
+}
+
+// One entry of the raw table of contents, as returned by READ TOC format 2.
+#[derive(Clone, Copy)]
+pub struct TocEntry {
+    pub session: u8,
+    pub adr: u8,
+    pub control: u8,
+    pub track: u8,
+    pub point: u8,
+    pub min: u8,
+    pub sec: u8,
+    pub frame: u8,
+    pub zero: u8,
+    pub pmin: u8,
+    pub psec: u8,
+    pub pframe: u8,
+}
+
+// Point of the raw TOC entry that says where a session's lead-out starts.
+pub const TOC_POINT_LEAD_OUT: u8 = 0xA2;
+
+pub fn msf_to_lba(min: u8, sec: u8, frame: u8) -> i64 {
+    (min as i64 * 60 + sec as i64) * 75 + frame as i64 - 150
+}
+
+impl TocEntry {
+    pub fn address_lba(&self) -> i64 {
+        msf_to_lba(self.min, self.sec, self.frame)
+    }
+
+    pub fn point_lba(&self) -> i64 {
+        msf_to_lba(self.pmin, self.psec, self.pframe)
+    }
+
+    // True for entries that point at a data track rather than audio.
+    pub fn is_data(&self) -> bool {
+        self.control & 0x04 != 0
+    }
+}
+
+// What READ DISC INFORMATION says about a disk. Pressed disks always say they're complete.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiscState {
+    Blank,
+    Appendable, // Recordable disk that's been written to, but not closed.
+    Complete,
+    Other,      // Rewritable disks that can be written anywhere, like DVD-RAM.
+}
+
+pub fn read_disc_state(device: &fs::File) -> Result<DiscState, ScsiError> {
+    let mut data = [0u8; 34];
+
+    // READ DISC INFORMATION, standard disc information.
+    let cdb = [0x51, 0, 0, 0, 0, 0, 0, 0, data.len() as u8, 0];
+    scsi_read(device, &cdb, &mut data)?;
+
+    Ok(match data[2] & 0x03 {
+        0 => DiscState::Blank,
+        1 => DiscState::Appendable,
+        2 => DiscState::Complete,
+        _ => DiscState::Other,
+    })
+}
+
+// Who made a drive and what it is, from INQUIRY. Works for anything that speaks SCSI, which is most USB storage too.
+#[derive(Clone)]
+pub struct DriveIdentity {
+    pub vendor: String,
+    pub model: String,
+    pub firmware: String,
+    pub serial: Option<String>, // Not every drive has one, and some cheap ones all have the same one.
+}
+
+fn get_inquiry_string(data: &[u8]) -> String {
+    data.iter().filter(|byte| byte.is_ascii_graphic() || **byte == b' ').map(|byte| *byte as char).collect::<String>().trim().to_string()
+}
+
+pub fn inquire(drive: &str) -> Option<DriveIdentity> {
+    let device = open_device(drive).ok()?;
+
+    // INQUIRY, standard data.
+    let mut data = [0u8; 36];
+    scsi_read(&device, &[0x12, 0, 0, 0, data.len() as u8, 0], &mut data).ok()?;
+
+    // INQUIRY, unit serial number page.
+    let mut page = [0u8; 252];
+    let serial = scsi_read(&device, &[0x12, 0x01, 0x80, 0, page.len() as u8, 0], &mut page).ok()
+        .map(|read| get_inquiry_string(&page[4..(4 + page[3] as usize).min(read).max(4)]))
+        .filter(|serial| !serial.is_empty());
+
+    Some(DriveIdentity {
+        vendor: get_inquiry_string(&data[8..16]),
+        model: get_inquiry_string(&data[16..32]),
+        firmware: get_inquiry_string(&data[32..36]),
+        serial,
+    })
+}
+
+// What a drive says it can do. Asked once at startup, so features it doesn't have can be turned away up front instead
+// of failing halfway through.
+#[derive(Clone, Default)]
+pub struct Capabilities {
+    pub profiles: Vec<u16>,      // Every kind of disk the drive knows, as MMC profiles.
+    pub max_read_speed: u16,     // In kB/s.
+    pub buffer_size: u16,        // In kB.
+    pub can_write: bool,
+    pub reads_subchannels: bool, // Can give us raw P-W subchannels, which CloneCD images need.
+    pub changer: bool,           // The drive is a changer itself, rather than sitting in one.
+}
+
+fn read_profile_list(device: &fs::File) -> Result<Vec<u16>, ScsiError> {
+    let mut data = [0u8; 8 + 4 + 4 * 64];
+
+    // GET CONFIGURATION, just the profile list feature.
+    let len = data.len() as u16;
+    let cdb = [0x46, 0x02, 0, 0, 0, 0, 0, (len >> 8) as u8, len as u8, 0];
+    let read = scsi_read(device, &cdb, &mut data)?;
+
+    let end = (12 + data[11] as usize).min(read).max(12);
+    Ok(data[12..end].chunks_exact(4).map(|profile| ((profile[0] as u16) << 8) | profile[1] as u16).collect())
+}
+
+fn read_capabilities_page(device: &fs::File) -> Result<Vec<u8>, ScsiError> {
+    let mut data = [0u8; 8 + 32];
+
+    // MODE SENSE, the CD/DVD capabilities page, with no block descriptors.
+    let cdb = [0x5A, 0x08, 0x2A, 0, 0, 0, 0, 0, data.len() as u8, 0];
+    let read = scsi_read(device, &cdb, &mut data)?;
+
+    let start = 8 + (((data[6] as usize) << 8) | data[7] as usize);
+    Ok(data.get(start..read).unwrap_or_default().to_vec())
+}
+
+// Returns None if the drive won't tell us anything.
+pub fn probe_capabilities(drive: &str) -> Option<Capabilities> {
+    let device = open_device(drive).ok()?;
+
+    let profiles = read_profile_list(&device).unwrap_or_default();
+    let page = read_capabilities_page(&device).unwrap_or_default();
+
+    if profiles.is_empty() && page.len() < 14 {
+        return None;
+    }
+
+    let byte = |index: usize| page.get(index).copied().unwrap_or(0);
+
+    Some(Capabilities {
+        profiles,
+        max_read_speed: ((byte(8) as u16) << 8) | byte(9) as u16,
+        buffer_size: ((byte(12) as u16) << 8) | byte(13) as u16,
+        can_write: byte(3) & 0x33 != 0, // CD-R, CD-RW, DVD-R, or DVD-RAM.
+        reads_subchannels: byte(5) & 0x04 != 0,
+        changer: matches!(byte(6) >> 5, 4 | 5),
+    })
+}
+
+// Families of disks a drive can read, going by its profiles.
+pub fn get_media_families(profiles: &[u16]) -> Vec<&'static str> {
+    let mut families = Vec::new();
+
+    for (family, range) in [("CD", 0x08..=0x0A), ("DVD", 0x10..=0x2B), ("BD", 0x40..=0x43), ("HD DVD", 0x50..=0x5A)].iter() {
+        if profiles.iter().any(|profile| range.contains(profile)) {
+            families.push(*family);
+        }
+    }
+
+    families
+}
+
+// The MMC profile the drive says the disk in it is, from GET CONFIGURATION.
+pub fn read_media_profile(device: &fs::File) -> Result<u16, ScsiError> {
+    let mut data = [0u8; 8];
+
+    // GET CONFIGURATION, just the header. It has the current profile in it.
+    let cdb = [0x46, 0x01, 0, 0, 0, 0, 0, 0, data.len() as u8, 0];
+    scsi_read(device, &cdb, &mut data)?;
+
+    Ok(((data[6] as u16) << 8) | data[7] as u16)
+}
+
+pub fn get_profile_name(profile: u16) -> &'static str {
+    match profile {
+        0x0008 => "CD-ROM",
+        0x0009 => "CD-R",
+        0x000A => "CD-RW",
+        0x0010 => "DVD-ROM",
+        0x0011 => "DVD-R",
+        0x0012 => "DVD-RAM",
+        0x0013 | 0x0014 => "DVD-RW",
+        0x0015 | 0x0016 => "DVD-R DL",
+        0x001A => "DVD+RW",
+        0x001B => "DVD+R",
+        0x002A => "DVD+RW DL",
+        0x002B => "DVD+R DL",
+        0x0040 => "BD-ROM",
+        0x0041 | 0x0042 => "BD-R",
+        0x0043 => "BD-RE",
+        0x0050 => "HD DVD-ROM",
+        0x0051 => "HD DVD-R",
+        0x0052 => "HD DVD-RAM",
+        _ => "Unknown disk",
+    }
+}
+
+// Bytes of data on the disk, going by the last block READ CAPACITY reports.
+pub fn read_capacity(device: &fs::File) -> Result<u64, ScsiError> {
+    let mut data = [0u8; 8];
+
+    let cdb = [0x25, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    scsi_read(device, &cdb, &mut data)?;
+
+    let last_block = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as u64;
+    let block_size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as u64;
+
+    Ok((last_block + 1) * block_size)
+}
+
+// Bytes that can still be written to a recordable disk, from the free blocks in its invisible track.
+pub fn read_free_space(device: &fs::File) -> Result<u64, ScsiError> {
+    let mut data = [0u8; 28];
+
+    // READ TRACK INFORMATION, by track number, 0xFF being the invisible track.
+    let cdb = [0x52, 0x01, 0, 0, 0, 0xFF, 0, 0, data.len() as u8, 0];
+    scsi_read(device, &cdb, &mut data)?;
+
+    Ok(u32::from_be_bytes([data[16], data[17], data[18], data[19]]) as u64 * 2048)
+}
+
+// Read speeds to fall back through when a disk won't read, in kB/s. 1x on a CD is 176kB/s.
+const SPEED_STEPS: [u16; 5] = [24 * 176, 16 * 176, 8 * 176, 4 * 176, 2 * 176];
+
+// As fast as the drive will go.
+pub const MAX_SPEED: u16 = 0xFFFF;
+
+pub fn set_read_speed(device: &fs::File, speed: u16) -> Result<(), ScsiError> {
+    // SET CD SPEED, leaving the write speed alone. DVD and BD drives take it too, and round to a speed they have.
+    let cdb = [0xBB, 0, (speed >> 8) as u8, speed as u8, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0];
+    scsi_read(device, &cdb, &mut []).map(|_| ())
+}
+
+// Slows the drive down a step for each failed attempt, skipping speeds it can't reach anyway. Marginal disks that
+// fail at full speed often read fine slower. Drives that won't change speed just keep trying at the speed they're at.
+pub fn step_down_speed(device: &fs::File, attempt: usize, max_speed: u16) {
+    let steps: Vec<u16> = SPEED_STEPS.iter().copied().filter(|speed| max_speed == 0 || *speed < max_speed).collect();
+
+    if let Some(speed) = steps.get(attempt.saturating_sub(1).min(steps.len().saturating_sub(1))) {
+        let _ = set_read_speed(device, *speed);
+    }
+}
+
+// Reads the full table of contents, including the lead-in entries describing the sessions.
+pub fn read_full_toc(device: &fs::File) -> Result<Vec<TocEntry>, ScsiError> {
+    let mut data = vec![0u8; 4 + 11 * 128];
+
+    // READ TOC/PMA/ATIP, format 2 (full TOC), session 1, MSF addresses.
+    let len = data.len() as u16;
+    let cdb = [0x43, 0x02, 0x02, 0, 0, 0, 1, (len >> 8) as u8, len as u8, 0];
+    let read = scsi_read(device, &cdb, &mut data)?;
+
+    let toc_len = (((data[0] as usize) << 8) | data[1] as usize) + 2;
+    let end = toc_len.min(read);
+
+    Ok(data[4..end.max(4)].chunks_exact(11).map(|entry| TocEntry {
+        session: entry[0],
+        adr: entry[1] >> 4,
+        control: entry[1] & 0x0F,
+        track: entry[2],
+        point: entry[3],
+        min: entry[4],
+        sec: entry[5],
+        frame: entry[6],
+        zero: entry[7],
+        pmin: entry[8],
+        psec: entry[9],
+        pframe: entry[10],
+    }).collect())
+}
+
+// Size of a raw sector and of the raw P-W subchannel data that comes with it.
+pub const RAW_SECTOR_SIZE: usize = 2352;
+pub const SUBCHANNEL_SIZE: usize = 96;
+
+// Reads whole raw sectors, headers and error correction included, each followed by its raw interleaved P-W subchannel.
+pub fn read_cd_raw(device: &fs::File, lba: u32, count: u32, data: &mut [u8]) -> Result<usize, ScsiError> {
+    let cdb = [
+        0xBE, 0x00, // READ CD, any sector type.
+        (lba >> 24) as u8, (lba >> 16) as u8, (lba >> 8) as u8, lba as u8,
+        (count >> 16) as u8, (count >> 8) as u8, count as u8,
+        0xF8, // Sync, all headers, user data, EDC/ECC.
+        0x01, // Raw P-W subchannel.
+        0x00,
+    ];
+
+    scsi_read(device, &cdb, &mut data[..count as usize * (RAW_SECTOR_SIZE + SUBCHANNEL_SIZE)])
+}
+
+// Reads audio sectors, just the samples. Some drives get this wrong by a few samples, but they're consistent about it.
+pub fn read_cd_audio(device: &fs::File, lba: u32, count: u32, data: &mut [u8]) -> Result<usize, ScsiError> {
+    let cdb = [
+        0xBE, 0x04, // READ CD, CD-DA sectors only.
+        (lba >> 24) as u8, (lba >> 16) as u8, (lba >> 8) as u8, lba as u8,
+        (count >> 16) as u8, (count >> 8) as u8, count as u8,
+        0x10, // User data.
+        0x00, // No subchannel.
+        0x00,
+    ];
+
+    scsi_read(device, &cdb, &mut data[..count as usize * RAW_SECTOR_SIZE])
+}
+
+// Reads the raw CD-TEXT packs from the lead-in. Most disks don't have any, and the drive rejects the command.
+pub fn read_cd_text(device: &fs::File) -> Result<Vec<u8>, ScsiError> {
+    let mut data = vec![0u8; 4 + 18 * 255 * 8];
+
+    // READ TOC/PMA/ATIP, format 5 (CD-TEXT).
+    let len = data.len() as u16;
+    let cdb = [0x43, 0x00, 0x05, 0, 0, 0, 0, (len >> 8) as u8, len as u8, 0];
+    let read = scsi_read(device, &cdb, &mut data)?;
+
+    let text_len = (((data[0] as usize) << 8) | data[1] as usize) + 2;
+    let end = text_len.min(read).max(4);
+
+    Ok(data[4..end].to_vec())
+}
+
+// Size of the formatted Q subchannel READ CD can give us.
+pub const Q_SUBCHANNEL_SIZE: usize = 16;
+
+// Reads the Q subchannel of one sector, already decoded by the drive. It says which track and index the sector is in.
+pub fn read_q_subchannel(device: &fs::File, lba: u32) -> Result<[u8; Q_SUBCHANNEL_SIZE], ScsiError> {
+    let cdb = [
+        0xBE, 0x00, // READ CD, any sector type.
+        (lba >> 24) as u8, (lba >> 16) as u8, (lba >> 8) as u8, lba as u8,
+        0, 0, 1,
+        0x10, // User data. Some drives won't give us subchannels without it.
+        0x02, // Formatted Q subchannel.
+        0x00,
+    ];
+
+    let mut data = [0u8; RAW_SECTOR_SIZE + Q_SUBCHANNEL_SIZE];
+    scsi_read(device, &cdb, &mut data)?;
+
+    let mut q = [0u8; Q_SUBCHANNEL_SIZE];
+    q.copy_from_slice(&data[RAW_SECTOR_SIZE..]);
+
+    Ok(q)
+}
+
+// Reads one of the codes the disk carries in its Q subchannel. Format 2 is the disk's media catalog number (its UPC/EAN
+// barcode), and format 3 is a track's ISRC. Returns None if the disk doesn't have one.
+fn read_subchannel_code(device: &fs::File, format: u8, track: u8, length: usize) -> Result<Option<String>, ScsiError> {
+    let mut data = [0u8; 24];
+
+    // READ SUB-CHANNEL, Q subchannel data.
+    let cdb = [0x42, 0x00, 0x40, format, 0, 0, track, 0, data.len() as u8, 0];
+    scsi_read(device, &cdb, &mut data)?;
+
+    // Top bit of byte 8 says whether it's there at all.
+    if data[8] & 0x80 == 0 {
+        return Ok(None);
+    }
+
+    let code: String = data[9..9 + length].iter().map(|byte| *byte as char).collect();
+
+    // Some disks say they have one, then fill it with zeros.
+    if code.chars().all(|c| c == '0' || c == '\0') {
+        return Ok(None);
+    }
+
+    Ok(Some(code))
+}
+
+pub fn read_mcn(device: &fs::File) -> Result<Option<String>, ScsiError> {
+    read_subchannel_code(device, 0x02, 0, 13)
+}
+
+pub fn read_isrc(device: &fs::File, track: u8) -> Result<Option<String>, ScsiError> {
+    read_subchannel_code(device, 0x03, track, 12)
+}
@@ -0,0 +1,269 @@
+
+// Drives an attached medium changer (autoloader, disc jukebox) through mtx, so a whole magazine can be ripped unattended.
+
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::digit1;
+use nom::character::complete::char as char_tag;
+use nom::combinator::opt;
+use nom::sequence::preceded;
+use nom::sequence::terminated;
+use nom::sequence::tuple;
+use std::collections::BTreeSet;
+use std::process::Command;
+use std::str;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::DiskDrive;
+use crate::drive_state::is_drive_finished;
+use crate::drive_state::DriveStatus;
+use crate::ParserResult;
+
+// How long a loaded disk gets to show up before we give up on it. Unreadable disks never get past NoDisk.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+const CHANGER_POLL_INTERVAL: Duration = Duration::from_millis(2000);
+
+pub enum ChangerError {
+    LaunchFail,      // Failed to launch mtx.
+    ConvertToUTF,    // mtx output was not valid UTF8.
+    Parse,           // Failed to parse the output of mtx status.
+    Command(String), // mtx ran but failed. Holds what it had to say about it.
+}
+
+struct ChangerState {
+    drives: Vec<Option<usize>>, // Which storage slot each drive's disk came from, if it has one.
+    full_slots: Vec<usize>,
+}
+
+enum ChangerDriveState {
+    Idle,
+    Ripping { slot: usize, loaded_at: Instant },
+}
+
+pub struct Changer {
+    device: String,
+    drives: Vec<Arc<DiskDrive>>, // Index matches the mtx data transfer element.
+    pub status_message: Mutex<String>,
+}
+
+fn parse_drive_line(input: &str) -> ParserResult<'_, (usize, Option<usize>)> {
+    let (input, (index, _, _, slot)) = tuple((
+        preceded(tag("Data Transfer Element "), digit1),
+        char_tag(':'),
+        alt((tag("Full"), tag("Empty"))),
+        opt(preceded(tag(" (Storage Element "), terminated(digit1, tag(" Loaded)"))))
+    ))(input)?;
+
+    // Only way these could panic is if they exceed the machine's bit width.
+    Ok((input, (index.parse().unwrap(), slot.map(|slot| slot.parse().unwrap()))))
+}
+
+fn parse_slot_line(input: &str) -> ParserResult<'_, (usize, bool)> {
+    let (input, (slot, _, _, state)) = tuple((
+        preceded(tag("Storage Element "), digit1),
+        opt(tag(" IMPORT/EXPORT")),
+        char_tag(':'),
+        alt((tag("Full"), tag("Empty")))
+    ))(input)?;
+
+    Ok((input, (slot.parse().unwrap(), state == "Full")))
+}
+
+fn parse_changer_status(input: &str) -> Result<ChangerState, ChangerError> {
+    let mut state = ChangerState {
+        drives: Vec::new(),
+        full_slots: Vec::new(),
+    };
+
+    for line in input.lines() {
+        let line = line.trim_start();
+
+        if let Ok((_, (index, slot))) = parse_drive_line(line) {
+            if state.drives.len() <= index {
+                state.drives.resize(index + 1, None);
+            }
+            state.drives[index] = slot;
+        } else if let Ok((_, (slot, full))) = parse_slot_line(line) {
+            if full {
+                state.full_slots.push(slot);
+            }
+        }
+        // Ignore the header and anything else we don't understand.
+    }
+
+    if state.drives.is_empty() {
+        Err(ChangerError::Parse)
+    } else {
+        Ok(state)
+    }
+}
+
+fn run_mtx(device: &str, args: &[String]) -> Result<String, ChangerError> {
+    let mut command = Command::new("mtx");
+    command.arg("-f").arg(device).args(args);
+
+    let output = command.output().map_err(|_| { ChangerError::LaunchFail })?;
+
+    if !output.status.success() {
+        return Err(ChangerError::Command(String::from(String::from_utf8_lossy(&output.stderr).trim())));
+    }
+
+    let data = str::from_utf8(&output.stdout).map_err(|_| { ChangerError::ConvertToUTF })?;
+
+    Ok(String::from(data))
+}
+
+fn fetch_changer_status(device: &str) -> Result<ChangerState, ChangerError> {
+    parse_changer_status(&run_mtx(device, &[String::from("status")])?)
+}
+
+fn load_slot(device: &str, slot: usize, drive: usize) -> Result<(), ChangerError> {
+    run_mtx(device, &[String::from("load"), slot.to_string(), drive.to_string()])?;
+    Ok(())
+}
+
+fn unload_slot(device: &str, slot: usize, drive: usize) -> Result<(), ChangerError> {
+    run_mtx(device, &[String::from("unload"), slot.to_string(), drive.to_string()])?;
+    Ok(())
+}
+
+pub fn get_changer_error_message(error: &ChangerError) -> String {
+    match error {
+        ChangerError::LaunchFail => String::from("Failed to launch mtx. Is it installed?"),
+        ChangerError::ConvertToUTF => String::from("Failed to convert mtx output to UTF8 for parsing. Major bug?"),
+        ChangerError::Parse => String::from("Failed to parse mtx status. Has the application changed its formatting?"),
+        ChangerError::Command(message) => format!("mtx failed: {}", message),
+    }
+}
+
+impl Changer {
+    pub fn new(device: &str, drive_files: &[String], drives: &[Arc<DiskDrive>]) -> Result<Changer, String> {
+        let mut changer_drives = Vec::new();
+
+        for file in drive_files.iter() {
+            match drives.iter().find(|drive| &drive.file == file) {
+                Some(drive) => changer_drives.push(drive.clone()),
+                None => return Err(format!("Changer drive {} was not found.", file)),
+            }
+        }
+
+        Ok(Changer {
+            device: String::from(device),
+            drives: changer_drives,
+            status_message: Mutex::new(String::from("Setting up...")),
+        })
+    }
+
+    fn set_status(&self, message: String) {
+        *self.status_message.lock().unwrap() = message;
+    }
+
+    fn run(&self) -> Result<(), ChangerError> {
+        let state = fetch_changer_status(&self.device)?;
+
+        // Everything in the magazine when we start is what we're going to rip, including disks already sitting in drives.
+        let mut pending: BTreeSet<usize> = state.full_slots.iter().cloned().collect();
+        let mut drive_states: Vec<ChangerDriveState> = Vec::new();
+
+        for (index, drive) in self.drives.iter().enumerate() {
+            // We name disks ourselves so nobody has to babysit the changer.
+            drive.auto_name.store(true, Relaxed);
+
+            drive_states.push(match state.drives.get(index) {
+                Some(Some(slot)) => ChangerDriveState::Ripping { slot: *slot, loaded_at: Instant::now() },
+                _ => ChangerDriveState::Idle,
+            });
+        }
+
+        let total = pending.len() + drive_states.iter().filter(|state| matches!(state, ChangerDriveState::Ripping { .. })).count();
+        let mut finished = 0;
+        let mut failed = 0;
+
+        loop {
+            for (index, drive) in self.drives.iter().enumerate() {
+                let status = drive.state.get();
+
+                match drive_states[index] {
+                    ChangerDriveState::Ripping { slot, loaded_at } => {
+                        let gave_up = matches!(status, DriveStatus::NoDisk) && loaded_at.elapsed() > LOAD_TIMEOUT;
+
+                        if is_drive_finished(&status) || gave_up {
+                            // Put it back where it came from.
+                            unload_slot(&self.device, slot, index)?;
+
+                            finished += 1;
+                            if gave_up || !matches!(status, DriveStatus::Done | DriveStatus::Rehearsed(_, 0)) {
+                                failed += 1;
+                            }
+
+                            drive_states[index] = ChangerDriveState::Idle;
+                        }
+                    },
+                    ChangerDriveState::Idle => {
+                        // Wait for the drive to notice the last disk is gone before handing it a new one.
+                        if matches!(status, DriveStatus::NoDisk) {
+                            if let Some(slot) = pending.iter().next().cloned() {
+                                pending.remove(&slot);
+                                load_slot(&self.device, slot, index)?;
+                                drive_states[index] = ChangerDriveState::Ripping { slot, loaded_at: Instant::now() };
+                            }
+                        }
+                    },
+                }
+            }
+
+            let busy = drive_states.iter().any(|state| matches!(state, ChangerDriveState::Ripping { .. }));
+
+            if pending.is_empty() && !busy {
+                self.set_status(format!("Magazine finished. {} disks, {} failed.", total, failed));
+                return Ok(());
+            }
+
+            self.set_status(format!("Ripping magazine: {} of {} disks done, {} failed, {} waiting.", finished, total, failed, pending.len()));
+
+            thread::sleep(CHANGER_POLL_INTERVAL);
+        }
+    }
+}
+
+pub fn spawn_changer_thread(changer: &Arc<Changer>) {
+    let changer = changer.clone();
+
+    thread::spawn(move || {
+        if let Err(error) = changer.run() {
+            changer.set_status(format!("Changer stopped. {}", get_changer_error_message(&error)));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mtx_status_parses() {
+        let status = "  Storage Changer /dev/sg5:2 Drives, 4 Slots ( 1 Import/Export )\n\
+            Data Transfer Element 0:Full (Storage Element 3 Loaded):VolumeTag = DISK03\n\
+            Data Transfer Element 1:Empty\n\
+            \x20     Storage Element 1:Full :VolumeTag=DISK01\n\
+            \x20     Storage Element 2:Empty\n\
+            \x20     Storage Element 3:Empty\n\
+            \x20     Storage Element 4 IMPORT/EXPORT:Full\n";
+
+        let state = parse_changer_status(status).ok().unwrap();
+
+        assert_eq!(state.drives, vec![Some(3), None]);
+        assert_eq!(state.full_slots, vec![1, 4]);
+    }
+
+    #[test]
+    fn no_drives_is_an_error() {
+        assert!(matches!(parse_changer_status("  Storage Element 1:Full\n"), Err(ChangerError::Parse)));
+    }
+}
@@ -0,0 +1,352 @@
+
+// Tells someone in another room what the drives are up to over Telegram or Matrix, and lets them name disks by
+// replying. A drive that needs a name gets a message saying what it'll be called, and a reply of "ok" takes that, or
+// any other reply is the name to use instead. With more than one drive waiting, start the reply with the drive, like
+// "sr1 MY_DISK.iso". Names of files that already exist are turned down, since nobody's there to ask about
+// overwriting them.
+
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::json;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::drive_list::DriveList;
+use crate::drive_state::is_drive_finished;
+use crate::drive_state::DriveStatus;
+use crate::events;
+use crate::events::DriveEvent;
+use crate::get_drive_status_message_string;
+use crate::name_drive;
+use crate::DiskDrive;
+
+const TELEGRAM_ENDPOINT: &str = "https://api.telegram.org";
+
+// How long to wait for a reply before asking again. The services hold the request open until something comes in.
+const POLL_TIMEOUT_SECONDS: u64 = 30;
+
+// How long to give the service to come back after it fails.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+pub trait ChatService: Send + Sync {
+    fn send(&self, text: &str) -> Result<(), String>;
+
+    // Waits a while for messages to the bot, and returns what they said.
+    fn receive(&self) -> Result<Vec<String>, String>;
+}
+
+// Runs curl with its options on stdin, so tokens don't show up in the process list.
+fn curl(options: &str) -> Result<Value, String> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--max-time", &(POLL_TIMEOUT_SECONDS * 2).to_string(), "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| { String::from("Failed to launch curl. Is it installed?") })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(options.as_bytes());
+    }
+
+    let output = child.wait_with_output().map_err(|_| { String::from("Failed to launch curl. Is it installed?") })?;
+
+    if !output.status.success() {
+        return Err(String::from(String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| { format!("The chat service sent back nonsense: {}", e) })
+}
+
+// Quoted for curl's config file.
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+fn encode_url(text: &str) -> String {
+    text.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+pub struct Telegram {
+    token: String,
+    chat_id: String,
+    offset: AtomicU64, // Updates before this one have already been seen.
+    caught_up: AtomicBool,
+}
+
+impl Telegram {
+    fn url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", TELEGRAM_ENDPOINT, self.token, method)
+    }
+}
+
+impl ChatService for Telegram {
+    fn send(&self, text: &str) -> Result<(), String> {
+        curl(&format!("url = {}\ndata-urlencode = {}\ndata-urlencode = {}\n",
+            quote(&self.url("sendMessage")), quote(&format!("chat_id={}", self.chat_id)), quote(&format!("text={}", text))))
+            .map(|_| ())
+    }
+
+    fn receive(&self) -> Result<Vec<String>, String> {
+        // Same as Matrix, the first batch is from before we got here.
+        let first = !self.caught_up.swap(true, Relaxed);
+        let timeout = if first { 0 } else { POLL_TIMEOUT_SECONDS };
+
+        let url = format!("{}?offset={}&timeout={}", self.url("getUpdates"), self.offset.load(Relaxed), timeout);
+        let value = curl(&format!("url = {}\n", quote(&url)))?;
+
+        let updates = match value["result"].as_array() {
+            Some(updates) => updates,
+            None => return Err(String::from(value["description"].as_str().unwrap_or("Telegram didn't say what went wrong."))),
+        };
+
+        let mut messages = Vec::new();
+
+        for update in updates.iter() {
+            if let Some(id) = update["update_id"].as_u64() {
+                self.offset.fetch_max(id + 1, Relaxed);
+            }
+
+            if first {
+                continue;
+            }
+
+            // Anyone can message a bot, so only listen to the chat it was set up for.
+            let message = &update["message"];
+            let chat = match &message["chat"]["id"] {
+                Value::Number(id) => id.to_string(),
+                _ => continue,
+            };
+
+            if chat == self.chat_id {
+                if let Some(text) = message["text"].as_str() {
+                    messages.push(String::from(text));
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+}
+
+pub struct Matrix {
+    homeserver: String,
+    access_token: String,
+    room: String,
+    transaction: AtomicU64,
+    user: Mutex<Option<String>>,  // Who we are, so our own messages can be ignored.
+    since: Mutex<Option<String>>, // Where the last sync left off.
+}
+
+impl Matrix {
+    fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value, String> {
+        let mut options = format!("url = {}\nrequest = {}\nheader = {}\n", quote(&format!("{}/_matrix/client/v3/{}", self.homeserver, path)),
+            method, quote(&format!("Authorization: Bearer {}", self.access_token)));
+
+        if let Some(body) = body {
+            options += &format!("header = \"Content-Type: application/json\"\ndata = {}\n", quote(&body.to_string()));
+        }
+
+        let value = curl(&options)?;
+
+        match value["errcode"].as_str() {
+            Some(code) => Err(format!("{}: {}", code, value["error"].as_str().unwrap_or_default())),
+            None => Ok(value),
+        }
+    }
+}
+
+impl ChatService for Matrix {
+    fn send(&self, text: &str) -> Result<(), String> {
+        // The transaction ID only has to be unique to this login, so it doesn't send twice if it's retried.
+        let transaction = format!("auto_archive-{}-{}", std::process::id(), self.transaction.fetch_add(1, Relaxed));
+        let path = format!("rooms/{}/send/m.room.message/{}", encode_url(&self.room), transaction);
+
+        self.request("PUT", &path, Some(&json!({ "msgtype": "m.text", "body": text }))).map(|_| ())
+    }
+
+    fn receive(&self) -> Result<Vec<String>, String> {
+        let mut user = self.user.lock().unwrap();
+        if user.is_none() {
+            *user = self.request("GET", "account/whoami", None)?["user_id"].as_str().map(String::from);
+        }
+
+        let mut since = self.since.lock().unwrap();
+        let path = match since.as_ref() {
+            Some(since) => format!("sync?timeout={}&since={}", POLL_TIMEOUT_SECONDS * 1000, encode_url(since)),
+            None => String::from("sync?timeout=0"),
+        };
+
+        let value = self.request("GET", &path, None)?;
+
+        // The first sync is everything that was said before we got here, which has already been answered or was
+        // meant for someone else.
+        let first = since.is_none();
+        *since = value["next_batch"].as_str().map(String::from);
+        if first {
+            return Ok(Vec::new());
+        }
+
+        let events = value["rooms"]["join"][&self.room]["timeline"]["events"].as_array().cloned().unwrap_or_default();
+
+        Ok(events.iter()
+            .filter(|event| event["type"] == "m.room.message" && event["sender"].as_str() != user.as_deref())
+            .filter_map(|event| event["content"]["body"].as_str().map(String::from))
+            .collect())
+    }
+}
+
+// Whichever services the config sets up.
+pub fn get_services(config: &Config) -> Vec<Arc<dyn ChatService>> {
+    let mut services: Vec<Arc<dyn ChatService>> = Vec::new();
+
+    if let (Some(token), Some(chat_id)) = (&config.telegram_bot_token, &config.telegram_chat_id) {
+        services.push(Arc::new(Telegram {
+            token: token.clone(),
+            chat_id: chat_id.clone(),
+            offset: AtomicU64::new(0),
+            caught_up: AtomicBool::new(false),
+        }));
+    }
+
+    if let (Some(homeserver), Some(access_token), Some(room)) = (&config.matrix_homeserver, &config.matrix_access_token, &config.matrix_room) {
+        services.push(Arc::new(Matrix {
+            homeserver: String::from(homeserver.trim_end_matches('/')),
+            access_token: access_token.clone(),
+            room: room.clone(),
+            transaction: AtomicU64::new(0),
+            user: Mutex::new(None),
+            since: Mutex::new(None),
+        }));
+    }
+
+    services
+}
+
+// What to tell the chat about an event, if anything.
+fn describe_event(drive: &DiskDrive, event: &DriveEvent) -> Option<String> {
+    match event {
+        DriveEvent::Status(DriveStatus::WaitingForName) => {
+            let name = drive.suggested_name.lock().unwrap().clone().unwrap_or_default();
+            Some(format!("{} needs a name. Reply \"ok\" to save it as {}, or reply with the name to use.", drive.file, name))
+        },
+        // Done goes without saying once it's been saved.
+        DriveEvent::Status(DriveStatus::Done) => None,
+        DriveEvent::Status(status) if is_drive_finished(status) =>
+            Some(format!("{}: {}", drive.file, get_drive_status_message_string(status))),
+        DriveEvent::Archived(entry) => Some(format!("{} saved a disk as {}.", drive.file, entry.image)),
+        _ => None,
+    }
+}
+
+fn matches_drive(drive: &DiskDrive, text: &str) -> bool {
+    text == drive.file
+        || Some(text) == drive.file.strip_prefix("/dev/")
+        || drive.name.lock().unwrap().as_deref() == Some(text)
+}
+
+// Works out which drive a reply is naming, and names it. Returns what to say back.
+fn handle_reply(drives: &[Arc<DiskDrive>], text: &str) -> String {
+    let waiting: Vec<&Arc<DiskDrive>> = drives.iter().filter(|drive| drive.state.get() == DriveStatus::WaitingForName).collect();
+    let text = text.trim();
+
+    let (drive, name) = match text.split_once(' ').and_then(|(first, rest)| {
+            waiting.iter().find(|drive| matches_drive(drive, first)).map(|drive| (*drive, rest.trim()))
+        }) {
+        Some(found) => found,
+        None => match waiting.as_slice() {
+            [drive] => (*drive, text),
+            [] => return String::from("No drives need a name right now."),
+            _ => return format!("More than one drive needs a name. Start the reply with which one: {}",
+                waiting.iter().map(|drive| drive.file.as_str()).collect::<Vec<&str>>().join(", ")),
+        },
+    };
+
+    let name = if name.eq_ignore_ascii_case("ok") {
+        match drive.suggested_name.lock().unwrap().clone() {
+            Some(name) => name,
+            None => return format!("{} doesn't have a name to suggest. Reply with one.", drive.file),
+        }
+    } else {
+        String::from(name)
+    };
+
+    match name_drive(drive, &name) {
+        Ok(()) => format!("Saving {} as {}.", drive.file, name),
+        Err(message) => message,
+    }
+}
+
+// Starts talking to every service the config sets up. Losing a message isn't worth stopping a rip over, so failures
+// to send are dropped.
+pub fn spawn_chat_bots(config: &Config, drives: &Arc<DriveList>) {
+    for service in get_services(config) {
+        let events = events::subscribe();
+        let (service1, drives1) = (service.clone(), drives.clone());
+
+        thread::spawn(move || {
+            for event in events.iter() {
+                let message = drives1.find(&event.drive).and_then(|drive| describe_event(&drive, &event.event));
+
+                if let Some(message) = message {
+                    let _ = service1.send(&message);
+                }
+            }
+        });
+
+        let drives = drives.clone();
+
+        thread::spawn(move || loop {
+            match service.receive() {
+                Ok(messages) => {
+                    for message in messages.iter() {
+                        let _ = service.send(&handle_reply(&drives.get(), message));
+                    }
+                },
+                Err(_) => thread::sleep(RETRY_DELAY),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::new_disk_drive;
+    use crate::DriveKind;
+
+    fn waiting_drive(file: &str) -> Arc<DiskDrive> {
+        let drive = new_disk_drive(String::from(file), DriveKind::Optical);
+
+        for status in [DriveStatus::Fingerprinting, DriveStatus::Copying, DriveStatus::WaitingForName] {
+            assert!(drive.state.transition(status).is_ok());
+        }
+
+        Arc::new(drive)
+    }
+
+    #[test]
+    fn replies_cant_leave_the_working_directory() {
+        let drives = [waiting_drive("/dev/sr8"), waiting_drive("/dev/sr9")];
+
+        for reply in ["sr8 ../escape.iso", "sr9 /tmp/escape.iso", "/dev/sr8 dir/escape.iso", "sr9 .."] {
+            handle_reply(&drives, reply);
+        }
+
+        assert!(drives.iter().all(|drive| drive.state.get() == DriveStatus::WaitingForName));
+
+        assert_eq!(handle_reply(&drives, "sr8 auto_archive-test-reply.iso"), "Saving /dev/sr8 as auto_archive-test-reply.iso.");
+    }
+}
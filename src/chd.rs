@@ -0,0 +1,75 @@
+
+// Converts finished images to MAME's CHD format with chdman. CHDs are compressed, checksummed, and carry the
+// track layout with them, which is what the preservation community generally wants.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Anything bigger than a CD can hold has to be a DVD.
+const MAX_CD_SIZE: u64 = 900 * 1024 * 1024;
+
+pub enum ChdError {
+    LaunchFail,     // Failed to launch chdman.
+    Cue(String),    // Failed to write the cue sheet chdman needs for CDs.
+    Failed(String), // chdman ran but failed. Holds what it had to say about it.
+}
+
+// chdman wants a cue sheet for CDs. A plain ISO is a single mode 1 data track.
+fn write_cue_sheet(image: &Path) -> Result<PathBuf, ChdError> {
+    let cue_path = image.with_extension("cue");
+    let file_name = image.file_name().unwrap_or_default().to_string_lossy();
+
+    let mut cue = fs::File::create(&cue_path).map_err(|e| { ChdError::Cue(format!("{}", e)) })?;
+    write!(cue, "FILE \"{}\" BINARY\n  TRACK 01 MODE1/2048\n    INDEX 01 00:00:00\n", file_name)
+        .map_err(|e| { ChdError::Cue(format!("{}", e)) })?;
+
+    Ok(cue_path)
+}
+
+// Converts the image, and returns the path of the CHD. The original image is left alone.
+pub fn convert_to_chd(image: &str) -> Result<String, ChdError> {
+    let image = Path::new(image);
+    let chd_path = image.with_extension("chd");
+    let size = fs::metadata(image).map(|metadata| metadata.len()).unwrap_or(0);
+
+    let mut command = Command::new("chdman");
+
+    let cue_path = if size > MAX_CD_SIZE {
+        command.args(["createdvd", "-i"]).arg(image);
+        None
+    } else {
+        let cue_path = write_cue_sheet(image)?;
+        command.args(["createcd", "-i"]).arg(&cue_path);
+        Some(cue_path)
+    };
+
+    command.arg("-o").arg(&chd_path);
+    command.arg("-f"); // Overwrite. The operator already agreed to that when naming the image.
+
+    let output = command.output();
+
+    if let Some(cue_path) = cue_path {
+        let _ = fs::remove_file(cue_path);
+    }
+
+    let output = output.map_err(|_| { ChdError::LaunchFail })?;
+
+    if output.status.success() {
+        Ok(chd_path.to_string_lossy().into_owned())
+    } else {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("chdman failed.");
+        Err(ChdError::Failed(String::from(reason.trim())))
+    }
+}
+
+pub fn get_chd_error_message(error: &ChdError) -> String {
+    match error {
+        ChdError::LaunchFail => String::from("Failed to launch chdman. Is it installed?"),
+        ChdError::Cue(message) => format!("Failed to write cue sheet for chdman: {}", message),
+        ChdError::Failed(message) => format!("chdman failed: {}", message),
+    }
+}
@@ -0,0 +1,396 @@
+
+// Command line tools for working with the catalog without starting the disk drive UI.
+
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::archive_org;
+use crate::catalog;
+use crate::catalog::CatalogEntry;
+use crate::catalog::DuplicatePolicy;
+use crate::compare;
+use crate::config;
+use crate::dedup;
+use crate::export;
+use crate::file_index;
+use crate::fingerprint;
+use crate::hash;
+use crate::stations;
+use crate::tape;
+
+const USAGE: &str = "Usage:
+    auto_archive [--operator|--admin]
+                                    Start archiving disks. Operators can only rip, name, and eject. Admins can do
+                                    everything, and need admin_password if the config has one.
+    auto_archive export <file>      Export the catalog as CSV or JSON, depending on the file's extension.
+    auto_archive import <file> [--duplicates=skip|replace|keep]
+                                    Merge a CSV, JSON, or auto_archive.catalog file from another rip station into
+                                    the catalog. Disks already in the catalog are skipped unless told otherwise.
+    auto_archive sync-stations [--duplicates=skip|replace|keep]
+                                    Merge the catalogs of every station in the config's stations into this one.
+    auto_archive scan <directory>   Add ISOs ripped before the catalog existed, so duplicates of them get caught.
+    auto_archive index              Add the files on archived disks to the file index, for disks ripped before it
+                                    existed. Only ISOs can be read.
+    auto_archive find <name>        Find which disks have a file with <name> in its path.
+    auto_archive upload <id>...     Publish disks from the catalog to the Internet Archive.
+    auto_archive compare <image> <image>
+                                    Show where two images differ, and for ISOs, which files. For making sure two
+                                    copies of a disk really match before getting rid of one.
+    auto_archive restore <manifest> <image>
+                                    Put an image back together from the dedup store.
+    auto_archive tape-label <name> [--force]
+                                    Label the tape in tape_device so images can be spooled to it. Won't relabel a
+                                    tape that already has one without --force, since that throws away what's on it.";
+
+fn get_extension(file: &str) -> String {
+    file.rsplit('.').next().unwrap_or_default().to_lowercase()
+}
+
+fn export(args: &[String]) -> Result<(), String> {
+    let file = args.first().ok_or_else(|| { String::from("Export to where?") })?;
+    let catalog = catalog::load_catalog().map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+
+    let output = match get_extension(file).as_str() {
+        "csv" => export::export_csv(catalog.entries()),
+        "json" => export::export_json(catalog.entries()),
+        _ => return Err(format!("Don't know how to export to {}. Use a .csv or .json file.", file)),
+    };
+
+    fs::write(file, output).map_err(|e| { format!("Failed to write {}: {}", file, e) })?;
+    println!("Exported {} disks to {}.", catalog.entries().len(), file);
+
+    Ok(())
+}
+
+fn parse_duplicate_policy(arg: &str) -> Result<DuplicatePolicy, String> {
+    match arg {
+        "--duplicates=skip" => Ok(DuplicatePolicy::Skip),
+        "--duplicates=replace" => Ok(DuplicatePolicy::Replace),
+        "--duplicates=keep" => Ok(DuplicatePolicy::KeepBoth),
+        _ => Err(format!("Unknown option \"{}\".", arg)),
+    }
+}
+
+fn import(args: &[String]) -> Result<(), String> {
+    let mut file = None;
+    let mut policy = DuplicatePolicy::Skip;
+
+    for arg in args.iter() {
+        if arg.starts_with("--") {
+            policy = parse_duplicate_policy(arg)?;
+        } else {
+            file = Some(arg);
+        }
+    }
+
+    let file = file.ok_or_else(|| { String::from("Import from where?") })?;
+    let text = fs::read_to_string(file).map_err(|e| { format!("Failed to read {}: {}", file, e) })?;
+
+    let entries = match get_extension(file).as_str() {
+        "csv" => export::import_csv(&text),
+        "json" => export::import_json(&text),
+        _ => catalog::parse_catalog(&text)
+            .map(|other| other.entries().to_vec())
+            .map_err(|e| { catalog::get_catalog_error_message(&e) }),
+    }.map_err(|message| { format!("Failed to import {}: {}", file, message) })?;
+
+    let mut catalog = catalog::load_catalog().map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+    let result = catalog.merge(entries, policy).map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+
+    println!("Added {} disks, replaced {}, and skipped {} that were already in the catalog.", result.added, result.replaced, result.skipped);
+
+    Ok(())
+}
+
+fn sync_stations(args: &[String]) -> Result<(), String> {
+    let mut policy = DuplicatePolicy::Skip;
+    for arg in args.iter() {
+        policy = parse_duplicate_policy(arg)?;
+    }
+
+    let config = config::load_config().map_err(|e| { config::get_config_error_message(&e) })?;
+    if config.stations.is_empty() {
+        return Err(String::from("There are no stations in the config to sync with."));
+    }
+
+    let mut catalog = catalog::load_catalog().map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+
+    // One station being down shouldn't stop the rest from syncing.
+    for address in config.stations.iter() {
+        let entries = match stations::fetch_catalog(&config, address) {
+            Ok(entries) => entries,
+            Err(message) => {
+                eprintln!("Skipping {}: {}", address, message);
+                continue;
+            },
+        };
+
+        let result = catalog.merge(entries, policy).map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+        println!("{}: added {} disks, replaced {}, and skipped {} that were already in the catalog.", address, result.added, result.replaced, result.skipped);
+    }
+
+    Ok(())
+}
+
+fn find_isos(directory: &Path, isos: &mut Vec<String>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) => {
+            eprintln!("Skipping {}: {}", directory.display(), error);
+            return;
+        },
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+
+        if path.is_dir() {
+            find_isos(&path, isos);
+        } else if get_extension(&path.to_string_lossy()) == "iso" {
+            isos.push(path.to_string_lossy().into_owned());
+        }
+    }
+}
+
+fn scan(args: &[String]) -> Result<(), String> {
+    let directory = args.first().ok_or_else(|| { String::from("Scan where?") })?;
+    let mut catalog = catalog::load_catalog().map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+
+    let mut isos = Vec::new();
+    find_isos(Path::new(directory), &mut isos);
+    isos.sort();
+
+    let mut entries = Vec::new();
+
+    for iso in isos.iter() {
+        if catalog.entries().iter().any(|entry| entry.image == *iso) {
+            continue;
+        }
+
+        // Same as reading it off a disk, so the fingerprint comes out the same as it would for the disk.
+        let info = match crate::fetch_iso_info(iso) {
+            Ok(info) => info,
+            Err(_) => {
+                eprintln!("Skipping {}: isoinfo couldn't read it.", iso);
+                continue;
+            },
+        };
+
+        println!("Hashing {}...", iso);
+
+        let hashes = match hash::hash_file(iso) {
+            Ok(hashes) => hashes,
+            Err(error) => {
+                eprintln!("Skipping {}: {}", iso, error);
+                continue;
+            },
+        };
+
+        let date = fs::metadata(iso).and_then(|metadata| metadata.modified()).ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+
+        entries.push(CatalogEntry {
+            image: iso.clone(),
+            fingerprint: fingerprint::fingerprint_disk(iso, &info.name, info.length as u64, None).unwrap_or_default(),
+            volume: info.name,
+            date,
+            size: hashes.size,
+            crc32: hashes.crc32,
+            sha1: hashes.sha1,
+            ..CatalogEntry::default()
+        });
+    }
+
+    let result = catalog.merge(entries, DuplicatePolicy::Skip).map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+
+    println!("Found {} ISOs. Added {} to the catalog, and skipped {} duplicates of disks already in it.", isos.len(), result.added, result.skipped);
+
+    Ok(())
+}
+
+fn index() -> Result<(), String> {
+    let catalog = catalog::load_catalog().map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+    let indexed = file_index::indexed_ids().map_err(|e| { format!("Failed to read the file index: {}", e) })?;
+
+    let mut added = 0;
+
+    for entry in catalog.entries().iter().filter(|entry| !indexed.contains(&entry.id)) {
+        if get_extension(&entry.image) != "iso" || !Path::new(&entry.image).is_file() {
+            continue;
+        }
+
+        match file_index::list_files(&entry.image) {
+            Ok(files) => {
+                file_index::add_files(entry.id, &files).map_err(|e| { format!("Failed to write the file index: {}", e) })?;
+                println!("Indexed {} files on {}.", files.len(), entry.image);
+                added += 1;
+            },
+            Err(message) => eprintln!("Skipping {}: {}", entry.image, message),
+        }
+    }
+
+    println!("Added {} disks to the file index.", added);
+
+    Ok(())
+}
+
+fn find(args: &[String]) -> Result<(), String> {
+    let query = args.first().ok_or_else(|| { String::from("Find what?") })?;
+    let catalog = catalog::load_catalog().map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+    let found = file_index::search(query, usize::MAX).map_err(|e| { format!("Failed to read the file index: {}", e) })?;
+
+    for (id, file) in found.iter() {
+        if let Some(entry) = catalog.get(*id) {
+            println!("{}: {} on disk {} ({}, {})", file, entry.volume, id, entry.image, catalog::format_date(entry.date));
+        }
+    }
+
+    Ok(())
+}
+
+fn upload(args: &[String]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(String::from("Upload which disks? Give their catalog IDs."));
+    }
+
+    let config = config::load_config().map_err(|e| { config::get_config_error_message(&e) })?;
+    let mut catalog = catalog::load_catalog().map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+
+    for arg in args.iter() {
+        let id: u64 = arg.parse().map_err(|_| { format!("\"{}\" isn't a catalog ID.", arg) })?;
+        let entry = catalog.get(id).cloned().ok_or_else(|| { format!("There's no disk {} in the catalog.", id) })?;
+
+        println!("Uploading {}...", entry.image);
+        let identifier = archive_org::upload(&config, &entry).map_err(|e| { archive_org::get_archive_org_error_message(&e) })?;
+
+        // Shouldn't fail since we just found it.
+        if let Some(entry) = catalog.get_mut(id) {
+            entry.ia_item = Some(identifier.clone());
+        }
+        catalog.save().map_err(|e| { catalog::get_catalog_error_message(&e) })?;
+
+        println!("Uploaded to https://archive.org/details/{}", identifier);
+    }
+
+    Ok(())
+}
+
+fn print_list(heading: &str, items: &[String]) {
+    if !items.is_empty() {
+        println!("{}:", heading);
+        for item in items.iter() {
+            println!("    {}", item);
+        }
+    }
+}
+
+fn compare(args: &[String]) -> Result<(), String> {
+    let (first, second) = match args {
+        [first, second] => (first, second),
+        _ => return Err(String::from("Compare needs two images.")),
+    };
+
+    println!("Comparing {} and {}...", first, second);
+    let diff = compare::compare_bytes(first, second).map_err(|e| { format!("Failed to read the images: {}", e) })?;
+
+    if diff.is_identical() {
+        println!("The images are identical.");
+        return Ok(());
+    }
+
+    if diff.sizes.0 != diff.sizes.1 {
+        println!("{} is {} bytes, {} is {} bytes.", first, diff.sizes.0, second, diff.sizes.1);
+    }
+
+    println!("{} bytes differ, in {}{} ranges:", diff.bytes, if diff.truncated { "more than " } else { "" }, diff.ranges.len());
+    for (start, length) in diff.ranges.iter() {
+        println!("    0x{:08X} - 0x{:08X} ({} bytes)", start, start + length, length);
+    }
+
+    // Not every image is an ISO, so the byte ranges are all some of them get.
+    let files = compare::list_file_extents(first).and_then(|first_files| {
+        compare::list_file_extents(second).map(|second_files| (first_files, second_files))
+    });
+
+    match files {
+        Ok((first_files, second_files)) => {
+            let files = compare::compare_files(first, second, &first_files, &second_files, &diff)
+                .map_err(|e| { format!("Failed to read the images: {}", e) })?;
+
+            print_list(&format!("Only in {}", first), &files.only_first);
+            print_list(&format!("Only in {}", second), &files.only_second);
+            print_list("Different in each", &files.changed);
+
+            if files.only_first.is_empty() && files.only_second.is_empty() && files.changed.is_empty() {
+                println!("Every file is the same in both. Only the file system around them differs.");
+            }
+        },
+        Err(message) => println!("Can't compare their files: {}", message),
+    }
+
+    Ok(())
+}
+
+fn restore(args: &[String]) -> Result<(), String> {
+    let (manifest, output) = match args {
+        [manifest, output] => (manifest, output),
+        _ => return Err(String::from("Restore needs a manifest and where to put the image.")),
+    };
+
+    let written = dedup::restore_image(manifest, output)?;
+    println!("Restored {} bytes to {}.", written, output);
+
+    Ok(())
+}
+
+fn tape_label(args: &[String]) -> Result<(), String> {
+    let force = args.iter().any(|arg| arg == "--force");
+    let name = args.iter().find(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| { String::from("What should the tape be called?") })?;
+
+    let config = config::load_config().map_err(|e| { config::get_config_error_message(&e) })?;
+    let device = config.tape_device.ok_or_else(|| { String::from("Set tape_device in auto_archive.conf first.") })?;
+
+    if !force {
+        if let Ok(label) = tape::read_label(&device) {
+            return Err(format!("This tape is already labeled {}. Use --force to erase it and label it again.", label));
+        }
+    }
+
+    tape::write_label(&device, name).map_err(|e| { tape::get_tape_error_message(&e) })?;
+    println!("Labeled the tape {}.", name);
+
+    Ok(())
+}
+
+// Runs the command given on the command line, and returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let result = match args[0].as_str() {
+        "export" => export(&args[1..]),
+        "import" => import(&args[1..]),
+        "sync-stations" => sync_stations(&args[1..]),
+        "scan" => scan(&args[1..]),
+        "index" => index(),
+        "find" => find(&args[1..]),
+        "upload" => upload(&args[1..]),
+        "compare" => compare(&args[1..]),
+        "restore" => restore(&args[1..]),
+        "tape-label" => tape_label(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        command => Err(format!("Unknown command \"{}\".\n\n{}", command, USAGE)),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        },
+    }
+}
@@ -0,0 +1,174 @@
+
+// Raw CloneCD output. Reads every sector whole, along with its subchannel data, and writes the
+// CCD/IMG/SUB trio CloneCD and most emulators understand. Some copy protection lives entirely in
+// the subchannels, so an ISO of those disks is useless.
+
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+use std::time::Duration;
+
+use crate::buffer_pool;
+use crate::cdrom;
+use crate::cdrom::TocEntry;
+use crate::CopyError;
+use crate::sense_log;
+
+// Sectors per READ CD. Big enough to keep the drive streaming, small enough to retry cheaply.
+const SECTORS_PER_READ: u32 = 16;
+
+// How many times we try a chunk before giving up on the disk.
+const READ_RETRIES: usize = 5;
+
+// Number of sectors on the disk, from the lead-out of its last session.
+pub fn get_sector_count(toc: &[TocEntry]) -> Option<u32> {
+    toc.iter()
+        .filter(|entry| entry.point == cdrom::TOC_POINT_LEAD_OUT)
+        .map(|entry| entry.point_lba())
+        .max()
+        .map(|lba| lba.max(0) as u32)
+}
+
+pub fn read_toc(drive: &str) -> Result<Vec<TocEntry>, String> {
+    let device = cdrom::open_device(drive).map_err(|e| { format!("{}", e) })?;
+    cdrom::read_full_toc(&device).map_err(|e| { cdrom::get_scsi_error_message(&e) })
+}
+
+// The drive hands us subchannels interleaved, one bit of each channel per byte. CloneCD wants them
+// one after the other, P first, 12 bytes each.
+fn deinterleave_subchannel(raw: &[u8]) -> [u8; cdrom::SUBCHANNEL_SIZE] {
+    let mut packed = [0u8; cdrom::SUBCHANNEL_SIZE];
+
+    for (index, byte) in raw.iter().enumerate().take(cdrom::SUBCHANNEL_SIZE) {
+        for channel in 0..8 {
+            let bit = (byte >> (7 - channel)) & 1;
+            packed[channel * 12 + index / 8] |= bit << (7 - index % 8);
+        }
+    }
+
+    packed
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn copy_disk_raw<O, S, CB, ECB>(source: &str, target: &mut O, subchannels: &mut S, sectors: u32, max_speed: u16, cancel: &AtomicBool, mut callback: CB, mut error_callback: ECB)
+    -> Result<(), CopyError> where
+    O: Write,
+    S: Write,
+    CB: FnMut(usize),
+    ECB: FnMut(CopyError)
+{
+    let device = cdrom::open_device(source).map_err(|e| { CopyError::Read(format!("{}", e)) })?;
+
+    let stride = cdrom::RAW_SECTOR_SIZE + cdrom::SUBCHANNEL_SIZE;
+    let mut buffer = buffer_pool::get(SECTORS_PER_READ as usize * stride);
+    let mut lba = 0;
+
+    while lba < sectors {
+        if cancel.load(Relaxed) {
+            return Err(CopyError::Cancelled);
+        }
+
+        let count = SECTORS_PER_READ.min(sectors - lba);
+        let mut attempts = 0;
+
+        loop {
+            match cdrom::read_cd_raw(&device, lba, count, &mut buffer) {
+                Ok(_) => {
+                    // Past the bad spot, so back up to full speed.
+                    if attempts > 0 {
+                        let _ = cdrom::set_read_speed(&device, cdrom::MAX_SPEED);
+                    }
+
+                    error_callback(CopyError::None);
+                    break;
+                },
+                Err(error) => {
+                    sense_log::log_error(&device, lba as u64, &error);
+                    let message = format!("sector {}: {}", lba, cdrom::get_scsi_error_message(&error));
+                    attempts += 1;
+
+                    if attempts >= READ_RETRIES {
+                        return Err(CopyError::Read(message));
+                    }
+
+                    error_callback(CopyError::Read(message));
+
+                    // Marginal disks often read fine slower.
+                    cdrom::step_down_speed(&device, attempts, max_speed);
+                    thread::sleep(Duration::from_millis(250 * attempts as u64));
+                },
+            }
+        }
+
+        for sector in buffer[..count as usize * stride].chunks_exact(stride) {
+            let (data, raw_subchannel) = sector.split_at(cdrom::RAW_SECTOR_SIZE);
+
+            target.write_all(data).map_err(|e| { CopyError::Write(format!("{}", e)) })?;
+            subchannels.write_all(&deinterleave_subchannel(raw_subchannel)).map_err(|e| { CopyError::Write(format!("{}", e)) })?;
+        }
+
+        lba += count;
+        callback(count as usize * cdrom::RAW_SECTOR_SIZE);
+    }
+
+    Ok(())
+}
+
+fn build_ccd(toc: &[TocEntry]) -> String {
+    let mut ccd = String::new();
+    let sessions = toc.iter().map(|entry| entry.session).max().unwrap_or(1);
+
+    // Writing to a String can't fail.
+    let _ = write!(ccd, "[CloneCD]\r\nVersion=3\r\n");
+    let _ = write!(ccd, "[Disc]\r\nTocEntries={}\r\nSessions={}\r\nDataTracksScrambled=0\r\nCDTextLength=0\r\n", toc.len(), sessions);
+
+    for session in 1..=sessions {
+        let _ = write!(ccd, "[Session {}]\r\nPreGapMode=1\r\nPreGapSubC=0\r\n", session);
+    }
+
+    for (index, entry) in toc.iter().enumerate() {
+        let _ = write!(ccd, "[Entry {}]\r\n", index);
+        let _ = write!(ccd, "Session={}\r\nPoint=0x{:02x}\r\nADR=0x{:02x}\r\nControl=0x{:02x}\r\nTrackNo={}\r\n",
+            entry.session, entry.point, entry.adr, entry.control, entry.track);
+        let _ = write!(ccd, "AMin={}\r\nASec={}\r\nAFrame={}\r\nALBA={}\r\nZero={}\r\n",
+            entry.min, entry.sec, entry.frame, entry.address_lba(), entry.zero);
+        let _ = write!(ccd, "PMin={}\r\nPSec={}\r\nPFrame={}\r\nPLBA={}\r\n",
+            entry.pmin, entry.psec, entry.pframe, entry.point_lba());
+    }
+
+    // Regular tracks are the entries that point at a track number rather than something special.
+    for entry in toc.iter().filter(|entry| entry.adr == 1 && (1..=99).contains(&entry.point)) {
+        let mode = if entry.is_data() { 1 } else { 0 };
+        let _ = write!(ccd, "[TRACK {}]\r\nMODE={}\r\nINDEX 1={}\r\n", entry.point, mode, entry.point_lba());
+    }
+
+    ccd
+}
+
+// Writes the CCD control file that goes with an image.
+pub fn write_ccd(image: &Path, toc: &[TocEntry]) -> Result<(), String> {
+    fs::write(image.with_extension("ccd"), build_ccd(toc)).map_err(|e| { format!("{}", e) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subchannels_come_out_one_after_the_other() {
+        // P set everywhere, and W only in the first byte.
+        let mut raw = [0x80u8; cdrom::SUBCHANNEL_SIZE];
+        raw[0] |= 0x01;
+
+        let packed = deinterleave_subchannel(&raw);
+
+        assert!(packed[..12].iter().all(|byte| *byte == 0xFF));
+        assert!(packed[12..84].iter().all(|byte| *byte == 0));
+        assert_eq!(packed[84], 0x80);
+        assert!(packed[85..].iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,241 @@
+
+// Compares two images, for making sure two copies of the "same" disk really are the same before one of them goes.
+// Finds every byte range that differs, and for ISOs, works out which files those bytes belong to.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::os::unix::fs::FileExt;
+use std::process::Command;
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+// Past this many, listing more ranges doesn't tell anyone anything new.
+const MAX_RANGES: usize = 1000;
+
+const SECTOR_SIZE: u64 = 2048;
+
+#[derive(Default)]
+pub struct ByteDiff {
+    pub sizes: (u64, u64),
+    pub ranges: Vec<(u64, u64)>, // Start and length of each range that differs, up to MAX_RANGES of them.
+    pub bytes: u64,              // Every byte that differs, including past the end of the shorter image.
+    pub truncated: bool,         // There were more ranges than MAX_RANGES.
+}
+
+impl ByteDiff {
+    fn add(&mut self, position: u64) {
+        self.bytes += 1;
+
+        if let Some(last) = self.ranges.last_mut() {
+            if last.0 + last.1 == position {
+                last.1 += 1;
+                return;
+            }
+        }
+
+        if self.ranges.len() < MAX_RANGES {
+            self.ranges.push((position, 1));
+        } else {
+            self.truncated = true;
+        }
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.bytes == 0 && self.sizes.0 == self.sizes.1
+    }
+}
+
+// Reads until the buffer is full or the file ends.
+fn fill(file: &mut fs::File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(filled)
+}
+
+pub fn compare_bytes(first: &str, second: &str) -> io::Result<ByteDiff> {
+    let mut first_file = fs::File::open(first)?;
+    let mut second_file = fs::File::open(second)?;
+
+    let mut diff = ByteDiff {
+        sizes: (first_file.metadata()?.len(), second_file.metadata()?.len()),
+        ..ByteDiff::default()
+    };
+
+    let mut first_buffer = vec![0; BUFFER_SIZE];
+    let mut second_buffer = vec![0; BUFFER_SIZE];
+    let mut position: u64 = 0;
+
+    loop {
+        let first_len = fill(&mut first_file, &mut first_buffer)?;
+        let second_len = fill(&mut second_file, &mut second_buffer)?;
+        let len = first_len.min(second_len);
+
+        // Comparing a whole buffer at once is much faster, and most of them will match.
+        if first_buffer[..len] != second_buffer[..len] {
+            for (offset, (a, b)) in first_buffer[..len].iter().zip(second_buffer[..len].iter()).enumerate() {
+                if a != b {
+                    diff.add(position + offset as u64);
+                }
+            }
+        }
+
+        position += len as u64;
+
+        if first_len != second_len || len == 0 {
+            break;
+        }
+    }
+
+    // Whatever's left over in the longer one is a single range on its own.
+    let longest = diff.sizes.0.max(diff.sizes.1);
+    if longest > position {
+        diff.bytes += longest - position;
+
+        if diff.ranges.len() < MAX_RANGES {
+            diff.ranges.push((position, longest - position));
+        } else {
+            diff.truncated = true;
+        }
+    }
+
+    Ok(diff)
+}
+
+pub struct IsoFile {
+    pub path: String,
+    pub start: u64, // Byte offset of the file in the image.
+    pub size: u64,
+}
+
+// Lists every file in an ISO with where it sits in the image. Same name choice as the file index, Joliet, then
+// Rock Ridge, then plain ISO 9660.
+pub fn list_file_extents(image: &str) -> Result<Vec<IsoFile>, String> {
+    for extension in ["-J", "-R"].iter() {
+        let output = Command::new("isoinfo")
+            .args(["-l", extension, &format!("-i{}", image)])
+            .output()
+            .map_err(|_| { String::from("Failed to launch isoinfo. Is it installed?") })?;
+
+        if output.status.success() && !output.stdout.is_empty() {
+            return Ok(parse_listing(&String::from_utf8_lossy(&output.stdout)));
+        }
+    }
+
+    Err(format!("isoinfo couldn't list the files in {}.", image))
+}
+
+// Lines look like this, under a "Directory listing of /PATH/" header:
+// -r--r--r--   1    0    0        12345 Jan 01 2000 [    345 00]  FILE.TXT;1
+fn parse_listing(listing: &str) -> Vec<IsoFile> {
+    let mut files = Vec::new();
+    let mut directory = String::from("/");
+
+    for line in listing.lines() {
+        if let Some(path) = line.strip_prefix("Directory listing of ") {
+            directory = String::from(path.trim());
+            continue;
+        }
+
+        // Directories are listed on their own.
+        if line.starts_with('d') {
+            continue;
+        }
+
+        let (open, close) = match (line.find('['), line.find(']')) {
+            (Some(open), Some(close)) if open < close => (open, close),
+            _ => continue,
+        };
+
+        let size = line[..open].split_whitespace().nth(4).and_then(|size| size.parse::<u64>().ok());
+        let extent = line[open + 1..close].split_whitespace().next().and_then(|extent| extent.parse::<u64>().ok());
+        let name = line[close + 1..].trim().trim_end_matches(";1");
+
+        if let (Some(size), Some(extent), false) = (size, extent, name.is_empty()) {
+            files.push(IsoFile {
+                path: format!("{}{}", directory, name),
+                start: extent * SECTOR_SIZE,
+                size,
+            });
+        }
+    }
+
+    files
+}
+
+#[derive(Default)]
+pub struct FileDiff {
+    pub only_first: Vec<String>,
+    pub only_second: Vec<String>,
+    pub changed: Vec<String>, // In both, but with different contents.
+}
+
+// True if the file has the same contents in both images, wherever each one put it.
+fn is_same_file(first_image: &fs::File, second_image: &fs::File, first: &IsoFile, second: &IsoFile) -> io::Result<bool> {
+    if first.size != second.size {
+        return Ok(false);
+    }
+
+    let mut first_buffer = vec![0; BUFFER_SIZE];
+    let mut second_buffer = vec![0; BUFFER_SIZE];
+    let mut position = 0;
+
+    while position < first.size {
+        let len = (first.size - position).min(BUFFER_SIZE as u64) as usize;
+        first_image.read_exact_at(&mut first_buffer[..len], first.start + position)?;
+        second_image.read_exact_at(&mut second_buffer[..len], second.start + position)?;
+
+        if first_buffer[..len] != second_buffer[..len] {
+            return Ok(false);
+        }
+
+        position += len as u64;
+    }
+
+    Ok(true)
+}
+
+// Works out which files differ between the two images. Files that sit in the same place in both and don't overlap
+// any bytes that differ are the same without having to read them again.
+pub fn compare_files(first_image: &str, second_image: &str, first: &[IsoFile], second: &[IsoFile], diff: &ByteDiff) -> io::Result<FileDiff> {
+    let first_image = fs::File::open(first_image)?;
+    let second_image = fs::File::open(second_image)?;
+
+    let first_files: HashMap<&str, &IsoFile> = first.iter().map(|file| (file.path.as_str(), file)).collect();
+    let second_files: HashMap<&str, &IsoFile> = second.iter().map(|file| (file.path.as_str(), file)).collect();
+
+    let touches_diff = |file: &IsoFile| {
+        diff.truncated || diff.ranges.iter().any(|(start, length)| *start < file.start + file.size && file.start < start + length)
+    };
+
+    let mut result = FileDiff::default();
+
+    for file in first.iter() {
+        match second_files.get(file.path.as_str()) {
+            None => result.only_first.push(file.path.clone()),
+            Some(other) if file.start == other.start && file.size == other.size && !touches_diff(file) => {},
+            Some(other) => {
+                if !is_same_file(&first_image, &second_image, file, other)? {
+                    result.changed.push(file.path.clone());
+                }
+            },
+        }
+    }
+
+    result.only_second = second.iter()
+        .filter(|file| !first_files.contains_key(file.path.as_str()))
+        .map(|file| file.path.clone())
+        .collect();
+
+    Ok(result)
+}
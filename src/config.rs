@@ -0,0 +1,557 @@
+
+// Settings loaded from the config file in the working directory.
+// The file is a list of `key = value` lines. Lines starting with # are comments.
+
+use nom::bytes::complete::take_until;
+use nom::character::complete::char as char_tag;
+use nom::combinator::rest;
+use nom::sequence::separated_pair;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use crate::backup::BackupTool;
+use crate::hash::HashKind;
+use crate::label::LabelFormat;
+use crate::priority::IoPriority;
+use crate::profile::RipProfile;
+use crate::remote;
+use crate::role;
+use crate::role::Role;
+use crate::ParserResult;
+
+pub const CONFIG_FILE: &str = "auto_archive.conf";
+
+pub enum ConfigError {
+    Read(String),       // Failed to read the file. Not having one at all is fine though.
+    Parse(usize),       // Line number that isn't a `key = value` pair.
+    UnknownKey(String),
+    BadValue(String),   // Key whose value didn't make sense.
+    KioskWithoutPassword, // A kiosk anyone could quit.
+    GrpcWithoutToken,     // gRPC anyone could control the drives through.
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub enum BlockDevices {
+    #[default]
+    None,              // Only optical drives.
+    Removable,         // Also anything the kernel flags as removable: USB sticks, card readers, ZIP drives.
+    List(Vec<String>), // Also these specific devices, removable or not.
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Iso, // Raw image, exactly as read.
+    Chd,     // Compressed MAME CHD. Only applies to optical disks.
+    CloneCd, // Raw sectors plus subchannels as CCD/IMG/SUB. Only applies to optical disks.
+    Dedup,   // Chunked into a deduplicating store, shared with every other disk in it.
+}
+
+// How often things get checked on. Faster notices disks sooner, slower wakes the machine up less.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Polling {
+    pub tray: Duration,   // Asking the drives about their trays. This is how fast a disk going in gets noticed.
+    pub media: Duration,  // Running blkid even if no tray has moved, in case a drive can't report its tray.
+    pub config: Duration, // Checking the config file for changes.
+}
+
+impl Polling {
+    pub fn parse(value: &str) -> Option<Polling> {
+        let (tray, media, config) = match value {
+            "fast" => (100, 1000, 1000),
+            "normal" => (500, 5000, 5000),
+            "slow" => (2000, 30000, 30000),
+            _ => return None,
+        };
+
+        Some(Polling {
+            tray: Duration::from_millis(tray),
+            media: Duration::from_millis(media),
+            config: Duration::from_millis(config),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub changer: Option<String>,  // Medium changer device for mtx, such as /dev/sg5.
+    pub changer_drives: Vec<String>, // Disk drives inside the changer, in mtx data transfer element order.
+    pub block_devices: BlockDevices, // Non-optical block devices to image.
+    pub floppy_drives: bool,
+    pub sparse_output: bool,         // Skip over runs of zeros in images instead of writing them out.
+    pub output_format: OutputFormat,
+    pub output_sink: String,         // Registered name of where images get written while they're ripped.
+    pub dedup_store: String,         // Where the dedup store lives, for the dedup output format.
+    pub mirror_directory: Option<String>, // Second place to write every image to as it's ripped, like a NAS.
+    pub recovery_mode: bool,         // Give up on blocks that keep failing and zero fill them, instead of retrying forever.
+    pub read_to_lead_out: bool,      // Rip optical disks to where they end, not where their volume says they do.
+    pub verify_rips: bool,           // Read the disk a second time and make sure it matches the image.
+    pub provenance_xattrs: bool,     // Write the drive, date, hashes, and version into the image's extended attributes.
+    pub nfo_files: bool,             // Save DVD and Blu-ray movies in Kodi and Jellyfin style folders, with .nfo files.
+    pub webcam_device: Option<String>, // Webcam to photograph disk labels with, like /dev/video0. Needs the webcam feature.
+    pub barcode_names: bool,         // Name images after the barcode scanned for the disk, instead of its volume name.
+    pub naming_map: Option<String>,  // CSV of barcodes or sequence numbers, and the titles to name those disks.
+    pub hash_names: bool,            // Put the start of the image's SHA-1 after the volume name, like DATA-1a2b3c4d.iso.
+    pub profile: Option<RipProfile>, // Settings to use on top of these, for drives that don't have a profile of their own.
+    pub par2_redundancy: Option<u32>, // Percent of PAR2 recovery data to make for each image. None to skip it.
+    pub redump_dats: Vec<String>,    // redump.org DAT files to check rips against.
+    pub skip_duplicates: bool,       // Don't rip disks whose fingerprint is already in the catalog.
+    pub dry_run: bool,               // Do everything but the copy, and write down what would have happened.
+    pub labels: LabelFormat,         // Label to make for each disk after it's archived.
+    pub ia_access_key: Option<String>, // Internet Archive S3 keys, for uploading.
+    pub ia_secret_key: Option<String>,
+    pub ia_collection: String,       // Collection uploaded items go into.
+    pub ia_mediatype: String,
+    pub ia_identifier_prefix: String, // Put in front of every uploaded item's identifier, to keep them from colliding with others.
+    pub backup_tool: Option<BackupTool>, // Send every image into a restic or Borg repository.
+    pub backup_repository: String,
+    pub backup_password_file: Option<String>,
+    pub backup_keep_image: bool,     // Keep the image around once it's in the repository.
+    pub tape_device: Option<String>, // Non-rewinding tape device to spool images to, such as /dev/nst0.
+    pub tape_batch_size: u64,        // Bytes of images to wait for before writing them to tape.
+    pub tape_keep_images: bool,      // Keep images around once they're on tape.
+    pub photo_library: String,       // Where photos from photo CDs get filed.
+    pub musicbrainz: bool,           // Look audio CDs up on MusicBrainz for their track names.
+    pub gnudb_email: Option<String>, // Look audio CDs MusicBrainz doesn't know up on gnudb, which wants an email address.
+    pub extra_hashes: Vec<HashKind>, // Hashes to make on top of CRC32 and SHA-1.
+    pub parallel_hashing: bool,      // Give each hash its own thread, instead of making them on the copy thread.
+    pub sequential_rips: bool,       // Work on one drive at a time, in the order disks went in.
+    pub copy_nice: Option<i32>,      // CPU priority of the copy and hash threads, from -20 (greediest) to 19 (politest).
+    pub copy_io_priority: Option<IoPriority>,
+    pub buffer_pool_size: usize,     // Bytes of copy buffers every drive shares.
+    pub hook_script: Option<String>, // Rhai script to call when a disk is detected, needs a name, and is finished.
+    pub export_session_on_exit: bool, // Write down everything the drives did when auto_archive closes.
+    pub telegram_bot_token: Option<String>, // Tell a Telegram chat what the drives are up to, and take names from it.
+    pub telegram_chat_id: Option<String>,
+    pub matrix_homeserver: Option<String>, // The same for a Matrix room.
+    pub matrix_access_token: Option<String>,
+    pub matrix_room: Option<String>,
+    pub mqtt_host: Option<String>,   // MQTT broker to show the drives in Home Assistant through.
+    pub mqtt_port: u16,
+    pub mqtt_username: Option<String>,
+    pub mqtt_password: Option<String>,
+    pub mqtt_discovery_prefix: String, // Where Home Assistant looks for new devices.
+    pub api_address: Option<String>, // Address and port to answer the read-only HTTP API on.
+    pub grpc_address: Option<String>, // Address and port to answer the gRPC control interface on.
+    pub api_token: Option<String>, // What clients of the API and gRPC have to send to get in. Anyone gets in without one.
+    pub grpc_token: Option<String>, // What gRPC clients send instead, if set, so the API's token can't control the drives.
+    pub tls_certificate: Option<String>, // PEM files to serve the API and gRPC over TLS with, instead of in the clear.
+    pub tls_key: Option<String>,
+    pub stations: Vec<String>, // API addresses of other rip stations, to check for duplicates and show their drives.
+    pub remote_drives: Vec<String>, // Drives on other machines shared with nbd-server, like host:port/export.
+    pub role: Role, // Who runs the station when --operator or --admin isn't given.
+    pub admin_password: Option<String>, // Asked for before running as admin, if set.
+    pub kiosk: bool, // For patrons feeding in their own disks. Operator only, no intro, and starts back up after crashing.
+    pub polling: Polling,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            changer: None,
+            changer_drives: Vec::new(),
+            block_devices: BlockDevices::None,
+            floppy_drives: false,
+            sparse_output: true,
+            output_format: OutputFormat::Iso,
+            output_sink: String::from("file"),
+            dedup_store: String::from("dedup_store"),
+            mirror_directory: None,
+            recovery_mode: false,
+            read_to_lead_out: false,
+            verify_rips: false,
+            provenance_xattrs: false,
+            nfo_files: false,
+            webcam_device: None,
+            barcode_names: false,
+            naming_map: None,
+            hash_names: false,
+            profile: None,
+            par2_redundancy: None,
+            redump_dats: Vec::new(),
+            skip_duplicates: false,
+            dry_run: false,
+            labels: LabelFormat::None,
+            ia_access_key: None,
+            ia_secret_key: None,
+            ia_collection: String::from("opensource_media"),
+            ia_mediatype: String::from("software"),
+            ia_identifier_prefix: String::new(),
+            backup_tool: None,
+            backup_repository: String::new(),
+            backup_password_file: None,
+            backup_keep_image: true,
+            tape_device: None,
+            tape_batch_size: 100_000_000_000,
+            tape_keep_images: true,
+            photo_library: String::from("photos"),
+            musicbrainz: true,
+            gnudb_email: None,
+            extra_hashes: Vec::new(),
+            parallel_hashing: false,
+            sequential_rips: false,
+            copy_nice: None,
+            copy_io_priority: None,
+            buffer_pool_size: 256 * 1024 * 1024,
+            hook_script: None,
+            export_session_on_exit: false,
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+            matrix_homeserver: None,
+            matrix_access_token: None,
+            matrix_room: None,
+            mqtt_host: None,
+            mqtt_port: 1883,
+            mqtt_username: None,
+            mqtt_password: None,
+            mqtt_discovery_prefix: String::from("homeassistant"),
+            api_address: None,
+            grpc_address: None,
+            api_token: None,
+            grpc_token: None,
+            tls_certificate: None,
+            tls_key: None,
+            stations: Vec::new(),
+            remote_drives: Vec::new(),
+            role: Role::Admin,
+            admin_password: None,
+            kiosk: false,
+            polling: Polling::parse("normal").unwrap(),
+        }
+    }
+}
+
+fn parse_config_line(input: &str) -> ParserResult<'_, (&str, &str)> {
+    separated_pair(take_until("="), char_tag('='), rest)(input)
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn parse_millis(key: &str, value: &str) -> Result<Duration, ConfigError> {
+    value.parse().ok().filter(|millis| *millis > 0).map(Duration::from_millis).ok_or_else(|| ConfigError::BadValue(String::from(key)))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ConfigError> {
+    match value {
+        "yes" | "true" | "on" => Ok(true),
+        "no" | "false" | "off" => Ok(false),
+        _ => Err(ConfigError::BadValue(String::from(key))),
+    }
+}
+
+impl Config {
+    fn apply(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "changer" => self.changer = Some(String::from(value)),
+            "changer_drives" => self.changer_drives = parse_list(value),
+            "block_devices" => self.block_devices = match value {
+                "none" => BlockDevices::None,
+                "removable" => BlockDevices::Removable,
+                _ => BlockDevices::List(parse_list(value)),
+            },
+            "floppy_drives" => self.floppy_drives = parse_bool(key, value)?,
+            "sparse_output" => self.sparse_output = parse_bool(key, value)?,
+            "output_format" => self.output_format = match value {
+                "iso" => OutputFormat::Iso,
+                "chd" => OutputFormat::Chd,
+                "clonecd" => OutputFormat::CloneCd,
+                "dedup" => OutputFormat::Dedup,
+                _ => return Err(ConfigError::BadValue(String::from(key))),
+            },
+            "dedup_store" => self.dedup_store = String::from(value),
+            "mirror_directory" => self.mirror_directory = Some(String::from(value)),
+            "recovery_mode" => self.recovery_mode = parse_bool(key, value)?,
+            "read_to_lead_out" => self.read_to_lead_out = parse_bool(key, value)?,
+            "par2_redundancy" => self.par2_redundancy = match value {
+                "none" | "0" => None,
+                _ => Some(value.parse().ok().filter(|percent| (1..=100).contains(percent))
+                    .ok_or_else(|| { ConfigError::BadValue(String::from(key)) })?),
+            },
+            "redump_dat" => self.redump_dats = parse_list(value),
+            "skip_duplicates" => self.skip_duplicates = parse_bool(key, value)?,
+            "dry_run" => self.dry_run = parse_bool(key, value)?,
+            "output_sink" => self.output_sink = String::from(value),
+            "hook_script" => self.hook_script = Some(String::from(value)),
+            "export_session_on_exit" => self.export_session_on_exit = parse_bool(key, value)?,
+            "telegram_bot_token" => self.telegram_bot_token = Some(String::from(value)),
+            "telegram_chat_id" => self.telegram_chat_id = Some(String::from(value)),
+            "matrix_homeserver" => self.matrix_homeserver = Some(String::from(value)),
+            "matrix_access_token" => self.matrix_access_token = Some(String::from(value)),
+            "matrix_room" => self.matrix_room = Some(String::from(value)),
+            "mqtt_host" => self.mqtt_host = Some(String::from(value)),
+            "mqtt_port" => self.mqtt_port = value.parse().map_err(|_| ConfigError::BadValue(String::from(key)))?,
+            "mqtt_username" => self.mqtt_username = Some(String::from(value)),
+            "mqtt_password" => self.mqtt_password = Some(String::from(value)),
+            "mqtt_discovery_prefix" => self.mqtt_discovery_prefix = String::from(value),
+            "api_address" => self.api_address = Some(String::from(value)),
+            "grpc_address" => self.grpc_address = Some(String::from(value)),
+            "api_token" => self.api_token = Some(String::from(value)),
+            "grpc_token" => self.grpc_token = Some(String::from(value)),
+            "tls_certificate" => self.tls_certificate = Some(String::from(value)),
+            "tls_key" => self.tls_key = Some(String::from(value)),
+            "stations" => self.stations = parse_list(value),
+            "role" => self.role = role::parse_role(value).ok_or_else(|| { ConfigError::BadValue(String::from(key)) })?,
+            "admin_password" => self.admin_password = Some(String::from(value)),
+            "kiosk" => self.kiosk = parse_bool(key, value)?,
+            "polling" => self.polling = Polling::parse(value).ok_or_else(|| ConfigError::BadValue(String::from(key)))?,
+            "tray_poll_ms" => self.polling.tray = parse_millis(key, value)?,
+            "media_poll_ms" => self.polling.media = parse_millis(key, value)?,
+            "config_poll_ms" => self.polling.config = parse_millis(key, value)?,
+            "remote_drives" => {
+                self.remote_drives = parse_list(value);
+                if self.remote_drives.iter().any(|spec| remote::parse_remote_drive(spec).is_none()) {
+                    return Err(ConfigError::BadValue(String::from(key)));
+                }
+            },
+            "verify_rips" => self.verify_rips = parse_bool(key, value)?,
+            "provenance_xattrs" => self.provenance_xattrs = parse_bool(key, value)?,
+            "nfo_files" => self.nfo_files = parse_bool(key, value)?,
+            "webcam_device" => self.webcam_device = Some(String::from(value)),
+            "barcode_names" => self.barcode_names = parse_bool(key, value)?,
+            "naming_map" => self.naming_map = Some(String::from(value)),
+            "hash_names" => self.hash_names = parse_bool(key, value)?,
+            "profile" => self.profile = match value {
+                "none" => None,
+                _ => Some(RipProfile::parse(value).ok_or_else(|| ConfigError::BadValue(String::from(key)))?),
+            },
+            "labels" => self.labels = match value {
+                "none" => LabelFormat::None,
+                "text" => LabelFormat::Text,
+                "png" => LabelFormat::Png,
+                _ => return Err(ConfigError::BadValue(String::from(key))),
+            },
+            "ia_access_key" => self.ia_access_key = Some(String::from(value)),
+            "ia_secret_key" => self.ia_secret_key = Some(String::from(value)),
+            "ia_collection" => self.ia_collection = String::from(value),
+            "ia_mediatype" => self.ia_mediatype = String::from(value),
+            "ia_identifier_prefix" => self.ia_identifier_prefix = String::from(value),
+            "backup_tool" => self.backup_tool = match value {
+                "none" => None,
+                "restic" => Some(BackupTool::Restic),
+                "borg" => Some(BackupTool::Borg),
+                _ => return Err(ConfigError::BadValue(String::from(key))),
+            },
+            "backup_repository" => self.backup_repository = String::from(value),
+            "backup_password_file" => self.backup_password_file = Some(String::from(value)),
+            "backup_keep_image" => self.backup_keep_image = parse_bool(key, value)?,
+            "tape_device" => self.tape_device = Some(String::from(value)),
+            "tape_batch_gb" => self.tape_batch_size = value.parse::<u64>().ok().filter(|size| *size > 0)
+                .ok_or_else(|| { ConfigError::BadValue(String::from(key)) })? * 1_000_000_000,
+            "tape_keep_images" => self.tape_keep_images = parse_bool(key, value)?,
+            "photo_library" => self.photo_library = String::from(value),
+            "musicbrainz" => self.musicbrainz = parse_bool(key, value)?,
+            "gnudb_email" => self.gnudb_email = Some(String::from(value)),
+            "extra_hashes" => self.extra_hashes = match value {
+                "none" => Vec::new(),
+                _ => parse_list(value).iter().map(|name| match name.as_str() {
+                    "md5" => Ok(HashKind::Md5),
+                    "sha256" => Ok(HashKind::Sha256),
+                    _ => Err(ConfigError::BadValue(String::from(key))),
+                }).collect::<Result<Vec<HashKind>, ConfigError>>()?,
+            },
+            "parallel_hashing" => self.parallel_hashing = parse_bool(key, value)?,
+            "sequential_rips" => self.sequential_rips = parse_bool(key, value)?,
+            "copy_nice" => self.copy_nice = match value {
+                "normal" => None,
+                _ => Some(value.parse().ok().filter(|nice| (-20..=19).contains(nice))
+                    .ok_or_else(|| ConfigError::BadValue(String::from(key)))?),
+            },
+            "buffer_pool_mb" => self.buffer_pool_size = value.parse::<usize>().ok().filter(|size| *size > 0)
+                .ok_or_else(|| ConfigError::BadValue(String::from(key)))? * 1024 * 1024,
+            "copy_io_priority" => self.copy_io_priority = match value {
+                "normal" => None,
+                _ => Some(IoPriority::parse(value).ok_or_else(|| ConfigError::BadValue(String::from(key)))?),
+            },
+            _ => return Err(ConfigError::UnknownKey(String::from(key))),
+        }
+
+        Ok(())
+    }
+}
+
+pub fn parse_config(input: &str) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (_, (key, value)) = parse_config_line(line).map_err(|_| { ConfigError::Parse(index + 1) })?;
+        config.apply(key.trim(), value.trim())?;
+    }
+
+    // The password is all that keeps patrons from quitting a kiosk.
+    if config.kiosk && config.admin_password.is_none() {
+        return Err(ConfigError::KioskWithoutPassword);
+    }
+
+    // gRPC can eject and name disks, which is too much to hand anyone on the network.
+    if config.grpc_address.is_some() && config.grpc_token.is_none() && config.api_token.is_none() {
+        return Err(ConfigError::GrpcWithoutToken);
+    }
+
+    Ok(config)
+}
+
+pub fn load_config() -> Result<Config, ConfigError> {
+    match fs::read_to_string(CONFIG_FILE) {
+        Ok(text) => parse_config(&text),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(error) => Err(ConfigError::Read(format!("{}", error))),
+    }
+}
+
+fn get_modified_time() -> Option<SystemTime> {
+    fs::metadata(CONFIG_FILE).and_then(|metadata| metadata.modified()).ok()
+}
+
+// Settings only looked at when auto_archive starts, which changing in the file won't do anything about until then.
+fn get_restart_changes(old: &Config, new: &Config) -> Vec<&'static str> {
+    let mut changes = Vec::new();
+
+    if old.changer != new.changer || old.changer_drives != new.changer_drives {
+        changes.push("changer");
+    }
+    if old.block_devices != new.block_devices {
+        changes.push("block_devices");
+    }
+    if old.floppy_drives != new.floppy_drives {
+        changes.push("floppy_drives");
+    }
+    if old.tape_device != new.tape_device || old.tape_batch_size != new.tape_batch_size || old.tape_keep_images != new.tape_keep_images {
+        changes.push("tape");
+    }
+    if old.redump_dats != new.redump_dats {
+        changes.push("redump_dat");
+    }
+    if old.naming_map != new.naming_map {
+        changes.push("naming_map");
+    }
+    if old.buffer_pool_size != new.buffer_pool_size {
+        changes.push("buffer_pool_mb");
+    }
+    if old.copy_nice != new.copy_nice || old.copy_io_priority != new.copy_io_priority {
+        changes.push("copy_nice and copy_io_priority");
+    }
+    if old.stations != new.stations {
+        changes.push("stations");
+    }
+    if old.remote_drives != new.remote_drives {
+        changes.push("remote_drives");
+    }
+    if old.role != new.role || old.admin_password != new.admin_password || old.kiosk != new.kiosk {
+        changes.push("role, admin_password, and kiosk");
+    }
+
+    changes
+}
+
+// The config, as it is right now. The file can be changed while auto_archive is running, without stopping any rips.
+// Each disk goes by the config it started with, so nothing changes halfway through one.
+pub struct LiveConfig {
+    current: Mutex<Arc<Config>>,
+    modified: Mutex<Option<SystemTime>>, // Of the file, when it was last loaded.
+}
+
+impl LiveConfig {
+    pub fn new(config: Config) -> LiveConfig {
+        LiveConfig {
+            current: Mutex::new(Arc::new(config)),
+            modified: Mutex::new(get_modified_time()),
+        }
+    }
+
+    pub fn get(&self) -> Arc<Config> {
+        self.current.lock().unwrap().clone()
+    }
+
+    // Loads the file again if it's changed. None if it hasn't. Otherwise the settings that changed but won't do
+    // anything until a restart, or what's wrong with the new file. A broken file leaves the old config in place.
+    pub fn reload(&self) -> Option<Result<Vec<&'static str>, ConfigError>> {
+        let modified = get_modified_time();
+
+        {
+            let mut last = self.modified.lock().unwrap();
+            if *last == modified {
+                return None;
+            }
+            *last = modified;
+        }
+
+        Some(load_config().map(|config| {
+            let mut current = self.current.lock().unwrap();
+            let changes = get_restart_changes(&current, &config);
+            *current = Arc::new(config);
+
+            changes
+        }))
+    }
+}
+
+pub fn get_config_error_message(error: &ConfigError) -> String {
+    match error {
+        ConfigError::Read(message) => format!("Failed to read {}: {}", CONFIG_FILE, message),
+        ConfigError::Parse(line) => format!("Line {} of {} is not a `key = value` pair.", line, CONFIG_FILE),
+        ConfigError::UnknownKey(key) => format!("Unknown setting \"{}\" in {}.", key, CONFIG_FILE),
+        ConfigError::BadValue(key) => format!("Invalid value for \"{}\" in {}.", key, CONFIG_FILE),
+        ConfigError::KioskWithoutPassword => format!("kiosk is on in {}, but there's no admin_password to quit it with.", CONFIG_FILE),
+        ConfigError::GrpcWithoutToken => format!("grpc_address is set in {}, but there's no grpc_token or api_token to keep strangers from controlling the drives.", CONFIG_FILE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_read() {
+        let config = parse_config("# A comment.\n\
+            \n\
+            changer_drives = /dev/sr0, /dev/sr1,\n\
+            block_devices = removable\n\
+            output_format = chd\n\
+            sparse_output = off\n\
+            mirror_directory = /mnt/nas\n\
+            polling = slow\n").ok().unwrap();
+
+        assert_eq!(config.changer_drives, vec!["/dev/sr0", "/dev/sr1"]);
+        assert!(config.block_devices == BlockDevices::Removable);
+        assert!(config.output_format == OutputFormat::Chd);
+        assert!(!config.sparse_output);
+        assert_eq!(config.mirror_directory.as_deref(), Some("/mnt/nas"));
+        assert!(config.polling == Polling::parse("slow").unwrap());
+    }
+
+    #[test]
+    fn mistakes_are_caught() {
+        assert!(matches!(parse_config("sparse_output = yes\nnot a setting\n"), Err(ConfigError::Parse(2))));
+        assert!(matches!(parse_config("no_such_key = 1"), Err(ConfigError::UnknownKey(key)) if key == "no_such_key"));
+        assert!(matches!(parse_config("sparse_output = maybe"), Err(ConfigError::BadValue(key)) if key == "sparse_output"));
+    }
+
+    #[test]
+    fn kiosks_need_a_password() {
+        assert!(matches!(parse_config("kiosk = yes"), Err(ConfigError::KioskWithoutPassword)));
+        assert!(parse_config("kiosk = yes\nadmin_password = hunter3").is_ok());
+        assert!(parse_config("kiosk = no").is_ok());
+    }
+
+    #[test]
+    fn grpc_needs_a_token() {
+        assert!(matches!(parse_config("grpc_address = 0.0.0.0:50051"), Err(ConfigError::GrpcWithoutToken)));
+        assert!(parse_config("grpc_address = 0.0.0.0:50051\napi_token = a").is_ok());
+        assert!(parse_config("grpc_address = 0.0.0.0:50051\ngrpc_token = b").is_ok());
+    }
+}
@@ -0,0 +1,236 @@
+
+// What happens when something panics. Without this a panic in the UI leaves the terminal in curses mode with the
+// message scribbled somewhere on the screen, and nobody knows which disks were in the middle of what.
+// Every panic gets a report in the working directory. A panic in the UI also puts the terminal back and keeps
+// whatever was half ripped, since the process is going down with it. Kiosks start themselves back up after that,
+// since there's nobody around who'd know how.
+
+use std::backtrace::Backtrace;
+use std::ffi::CString;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::panic;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::catalog;
+use crate::jobs;
+use crate::mapfile::DamageMap;
+use crate::resume;
+
+// Leaves curses' alternate screen, shows the cursor again, and resets colors.
+const RESET_TERMINAL: &str = "\x1b[?1049l\x1b[?25h\x1b[0m";
+
+// Audio CDs get ripped into these until they have a name. The next rip on the drive would clear it out.
+const AUDIO_STAGING_PREFIX: &str = ".audio-";
+
+fn restore_terminal() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(RESET_TERMINAL.as_bytes());
+    let _ = stdout.flush();
+
+    // Curses turns off echo and line buffering. Nothing else left to do if stty isn't there.
+    let _ = Command::new("stty").arg("sane").status();
+}
+
+// Images being ripped are temp files with no name, that disappear once nothing has them open. Gives each a name, so
+// they outlive the crash. Ones for disks with a fingerprint are kept as that disk's partial image, along with a map of
+// what was zero filled in it, so putting it back in picks up where it left off. Returns the names they got.
+fn preserve_temp_files(time: u64) -> Vec<PathBuf> {
+    let mut preserved = Vec::new();
+    let jobs = jobs::try_get().unwrap_or_default();
+
+    let descriptors = match fs::read_dir("/proc/self/fd") {
+        Ok(descriptors) => descriptors,
+        Err(_) => return preserved,
+    };
+
+    for descriptor in descriptors.filter_map(|descriptor| descriptor.ok()) {
+        let target = match fs::read_link(descriptor.path()) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+
+        // Unnamed temp files show up as "/some/directory/#1234 (deleted)".
+        let text = target.to_string_lossy();
+        let directory = match text.strip_suffix(" (deleted)").map(Path::new) {
+            Some(path) if path.file_name().map(|name| name.as_bytes().starts_with(b"#")).unwrap_or(false) => {
+                path.parent().unwrap_or_else(|| Path::new(".")).to_path_buf()
+            },
+            _ => continue,
+        };
+
+        let fd = descriptor.file_name().to_string_lossy().parse().ok();
+        let job = jobs.iter().find(|job| job.temp_fd.is_some() && job.temp_fd == fd && !job.fingerprint.is_empty());
+
+        let name = match job {
+            Some(job) => directory.join(resume::get_partial_name(&job.fingerprint)),
+            None => directory.join(format!("crash-{}-{}.partial", time, descriptor.file_name().to_string_lossy())),
+        };
+
+        // The map has to match, or zero filled parts would be taken for what was on the disk. An older map is
+        // replaced even when nothing was zero filled, since the old one's about a different image.
+        if let Some(job) = job {
+            let mut damage = DamageMap::new(job.length);
+            for (start, length) in job.damage.iter() {
+                damage.add_bad(*start, *length);
+            }
+
+            if damage.write_mapfile(&resume::get_map_name(&job.fingerprint)).is_err() {
+                continue;
+            }
+        }
+
+        // It has everything an older partial image of the disk had, since that's where it started from.
+        let _ = fs::remove_file(&name);
+        let (source, destination) = match (CString::new(descriptor.path().as_os_str().as_bytes()), CString::new(name.as_os_str().as_bytes())) {
+            (Ok(source), Ok(destination)) => (source, destination),
+            _ => continue,
+        };
+
+        let result = unsafe { libc::linkat(libc::AT_FDCWD, source.as_ptr(), libc::AT_FDCWD, destination.as_ptr(), libc::AT_SYMLINK_FOLLOW) };
+        if result == 0 {
+            preserved.push(name);
+        }
+    }
+
+    // Half ripped audio CDs already have names, but get cleared out the next time their drive starts a rip.
+    if let Ok(entries) = fs::read_dir(".") {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if let Some(drive) = name.strip_prefix(AUDIO_STAGING_PREFIX) {
+                let new_name = PathBuf::from(format!("crash-{}-audio-{}", time, drive));
+                if fs::rename(entry.path(), &new_name).is_ok() {
+                    preserved.push(new_name);
+                }
+            }
+        }
+    }
+
+    preserved
+}
+
+// Long enough to read what happened before the UI covers it back up.
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+// How many times in a row it's been restarted, handed down to the next one.
+const RESTARTS_VARIABLE: &str = "AUTO_ARCHIVE_RESTARTS";
+
+// Something that crashes on every start would go around forever otherwise.
+const MAX_RESTARTS: u32 = 5;
+
+// Running this long counts as having started fine, so the count starts over.
+const STARTED_FINE: Duration = Duration::from_secs(10 * 60);
+
+// How many restarts in a row this one would be, counting it.
+fn count_restart(inherited: Option<&str>, running: Duration) -> u32 {
+    let before: u32 = if running < STARTED_FINE { inherited.and_then(|count| count.parse().ok()).unwrap_or(0) } else { 0 };
+    before.saturating_add(1)
+}
+
+// Starts over in this process, with the same arguments. Only returns if that didn't work, or it's crashed too many
+// times in a row to bother.
+fn restart(started: Instant) {
+    let program = match std::env::current_exe() {
+        Ok(program) => program,
+        Err(_) => return,
+    };
+
+    let restarts = count_restart(std::env::var(RESTARTS_VARIABLE).ok().as_deref(), started.elapsed());
+    if restarts > MAX_RESTARTS {
+        eprintln!("Not restarting, since it crashed right after starting the last {} times.", MAX_RESTARTS);
+        return;
+    }
+
+    eprintln!("Restarting in {} seconds...", RESTART_DELAY.as_secs());
+    thread::sleep(RESTART_DELAY);
+
+    let error = Command::new(program).args(std::env::args_os().skip(1)).env(RESTARTS_VARIABLE, restarts.to_string()).exec();
+    eprintln!("Couldn't restart: {}", error);
+}
+
+// Installs the panic hook. `describe_drives` says what every drive was doing, and can't wait on anything the
+// panicking thread might be holding. Has to be called from the UI thread.
+pub fn install<F>(describe_drives: F, restart_after: bool) where
+    F: Fn() -> String + Send + Sync + 'static {
+
+    let ui_thread = thread::current().id();
+    let started = Instant::now();
+
+    panic::set_hook(Box::new(move |info| {
+        let time = catalog::now();
+        let fatal = thread::current().id() == ui_thread;
+
+        let preserved = if fatal { preserve_temp_files(time) } else { Vec::new() };
+
+        let mut report = String::new();
+
+        // Writing to a String can't fail.
+        let _ = writeln!(report, "auto_archive {} crashed at {}.", env!("CARGO_PKG_VERSION"), catalog::format_date(time));
+        let _ = writeln!(report, "Thread: {}", thread::current().name().unwrap_or("unnamed"));
+        let _ = writeln!(report, "{}", info);
+        let _ = writeln!(report);
+        let _ = writeln!(report, "Drives:");
+        let _ = writeln!(report, "{}", describe_drives());
+
+        if !preserved.is_empty() {
+            let _ = writeln!(report, "Kept what was being ripped:");
+            for path in preserved.iter() {
+                let _ = writeln!(report, "{}", path.display());
+            }
+            let _ = writeln!(report);
+        }
+
+        let _ = writeln!(report, "{}", Backtrace::force_capture());
+
+        let report_file = format!("auto_archive.crash-{}.txt", time);
+        let saved = fs::write(&report_file, &report).is_ok();
+
+        // Anything but the UI can die on its own without taking the rest down.
+        if !fatal {
+            return;
+        }
+
+        restore_terminal();
+
+        eprintln!("{}", info);
+        if saved {
+            eprintln!("auto_archive crashed. A report was written to {}.", report_file);
+        } else {
+            eprintln!("auto_archive crashed, and couldn't write a report either.\n{}", report);
+        }
+
+        for path in preserved.iter() {
+            eprintln!("Kept {}", path.display());
+        }
+
+        if restart_after {
+            restart(started);
+        }
+
+        // Unwinding would drop cursive, which would try to clean up a terminal that's already been cleaned up.
+        std::process::exit(101);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restarts_are_counted_until_one_starts_fine() {
+        assert_eq!(count_restart(None, Duration::ZERO), 1);
+        assert_eq!(count_restart(Some("4"), Duration::from_secs(30)), 5);
+        assert_eq!(count_restart(Some("4"), STARTED_FINE), 1);
+        assert_eq!(count_restart(Some("junk"), Duration::ZERO), 1);
+    }
+}
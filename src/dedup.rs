@@ -0,0 +1,231 @@
+
+// A deduplicating store for images. Images are cut into chunks wherever their content says to, rather than at fixed
+// offsets, so the same data lines up into the same chunks even when it's shifted around from one disk to the next.
+// Each chunk is stored once, named after its hash, and each disk gets a manifest listing the chunks that make it up.
+
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+// Chunks are cut when the bottom bits of the rolling hash are all zero, which averages out to one every 64KiB.
+const CHUNK_MASK: u64 = (1 << 16) - 1;
+const MIN_CHUNK_SIZE: usize = 16 * 1024;
+const MAX_CHUNK_SIZE: usize = 256 * 1024;
+
+const MANIFEST_HEADER: &str = "# auto_archive dedup manifest";
+
+// Random numbers for the rolling hash, one for each byte value. They have to be the same every run, or chunks
+// won't line up with the ones already stored.
+fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+
+    for entry in table.iter_mut() {
+        // splitmix64
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        *entry = value ^ (value >> 31);
+    }
+
+    table
+}
+
+fn get_chunk_path(store: &str, hash: &str) -> PathBuf {
+    // Split them up by the first byte, so no one directory ends up with millions of files in it.
+    Path::new(store).join("chunks").join(&hash[..2]).join(hash)
+}
+
+// Named after the image's hash as well as its name, since plenty of different disks get the same name.
+fn get_manifest_path(store: &str, image: &str, sha1: &str) -> PathBuf {
+    let name = Path::new(image).file_name().unwrap_or_default().to_string_lossy().into_owned();
+    Path::new(store).join("manifests").join(format!("{}.{}.manifest", name, sha1))
+}
+
+// Never replaces a manifest that's already there, since that would lose whatever disk it was for. The same image
+// stored twice makes the same manifest, so that one's fine.
+fn write_manifest(path: &Path, manifest: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, manifest)?;
+
+    // Linking doesn't replace anything, unlike renaming.
+    let result = match fs::hard_link(&temp_path, path) {
+        Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => match fs::read_to_string(path) {
+            Ok(existing) if existing == manifest => Ok(()),
+            Ok(_) => Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is already there, for a different image", path.display()))),
+            Err(error) => Err(error),
+        },
+        result => result,
+    };
+
+    let _ = fs::remove_file(&temp_path);
+    result
+}
+
+// Writes the chunk unless it's already there.
+fn store_chunk(store: &str, chunk: &[u8], manifest: &mut String) -> io::Result<()> {
+    let hash = sha1_smol::Sha1::from(chunk).digest().to_string();
+    let path = get_chunk_path(store, &hash);
+
+    *manifest += &format!("{} {}\n", hash, chunk.len());
+
+    if path.exists() {
+        return Ok(());
+    }
+
+    // Write it somewhere else first, so a crash can't leave a partial chunk under a good name.
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, chunk)?;
+    fs::rename(&temp_path, &path)
+}
+
+// Chunks the image into the store and writes its manifest, and returns where the manifest went. The image itself is left alone.
+pub fn store_image(image: &str, store: &str) -> Result<String, String> {
+    let gear = gear_table();
+    let file = fs::File::open(image).map_err(|e| { format!("{}", e) })?;
+    let mut reader = BufReader::with_capacity(1024 * 1024, file);
+
+    let mut manifest = format!("{}\n", MANIFEST_HEADER);
+    let mut chunk = Vec::with_capacity(MAX_CHUNK_SIZE);
+    let mut hash: u64 = 0;
+    let mut image_hash = sha1_smol::Sha1::new();
+
+    loop {
+        let buffer = reader.fill_buf().map_err(|e| { format!("{}", e) })?;
+        if buffer.is_empty() {
+            break;
+        }
+
+        let length = buffer.len();
+        image_hash.update(buffer);
+
+        for byte in buffer.iter() {
+            hash = (hash << 1).wrapping_add(gear[*byte as usize]);
+            chunk.push(*byte);
+
+            if chunk.len() >= MAX_CHUNK_SIZE || (chunk.len() >= MIN_CHUNK_SIZE && hash & CHUNK_MASK == 0) {
+                store_chunk(store, &chunk, &mut manifest).map_err(|e| { format!("{}", e) })?;
+
+                chunk.clear();
+                hash = 0;
+            }
+        }
+
+        reader.consume(length);
+    }
+
+    if !chunk.is_empty() {
+        store_chunk(store, &chunk, &mut manifest).map_err(|e| { format!("{}", e) })?;
+    }
+
+    let manifest_path = get_manifest_path(store, image, &image_hash.digest().to_string());
+    write_manifest(&manifest_path, &manifest).map_err(|e| { format!("{}", e) })?;
+
+    Ok(manifest_path.to_string_lossy().into_owned())
+}
+
+// Puts an image back together from its manifest. The store is found from where the manifest is.
+pub fn restore_image(manifest: &str, output: &str) -> Result<u64, String> {
+    let store = Path::new(manifest).parent().and_then(|parent| parent.parent())
+        .ok_or_else(|| { String::from("The manifest isn't in a dedup store.") })?
+        .to_string_lossy().into_owned();
+
+    let text = fs::read_to_string(manifest).map_err(|e| { format!("Failed to read {}: {}", manifest, e) })?;
+    if !text.starts_with(MANIFEST_HEADER) {
+        return Err(format!("{} isn't a dedup manifest.", manifest));
+    }
+
+    let file = fs::File::create(output).map_err(|e| { format!("Failed to create {}: {}", output, e) })?;
+    let mut writer = BufWriter::new(file);
+    let mut written = 0;
+
+    for line in text.lines().skip(1).filter(|line| !line.trim().is_empty()) {
+        let mut parts = line.split(' ');
+        let (hash, length) = match (parts.next(), parts.next().and_then(|length| length.parse::<u64>().ok())) {
+            (Some(hash), Some(length)) if hash.len() == 40 => (hash, length),
+            _ => return Err(format!("Bad line in {}: {}", manifest, line)),
+        };
+
+        let mut chunk = Vec::new();
+        fs::File::open(get_chunk_path(&store, hash))
+            .and_then(|mut file| file.read_to_end(&mut chunk))
+            .map_err(|e| { format!("Chunk {} is missing: {}", hash, e) })?;
+
+        if chunk.len() as u64 != length || sha1_smol::Sha1::from(&chunk).digest().to_string() != hash {
+            return Err(format!("Chunk {} is damaged.", hash));
+        }
+
+        writer.write_all(&chunk).map_err(|e| { format!("Failed to write {}: {}", output, e) })?;
+        written += length;
+    }
+
+    writer.flush().map_err(|e| { format!("Failed to write {}: {}", output, e) })?;
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("auto_archive-dedup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn same_named_images_keep_their_own_manifests() {
+        let dir = temp_store("names");
+        let store = dir.join("store");
+        let store = store.to_str().unwrap();
+
+        let first = dir.join("first").join("DATA.iso");
+        let second = dir.join("second").join("DATA.iso");
+        fs::create_dir_all(first.parent().unwrap()).unwrap();
+        fs::create_dir_all(second.parent().unwrap()).unwrap();
+        fs::write(&first, vec![1u8; 100000]).unwrap();
+        fs::write(&second, vec![2u8; 100000]).unwrap();
+
+        let first_manifest = store_image(first.to_str().unwrap(), store).unwrap();
+        let second_manifest = store_image(second.to_str().unwrap(), store).unwrap();
+        assert_ne!(first_manifest, second_manifest);
+
+        // Storing the same image again is fine.
+        assert_eq!(store_image(first.to_str().unwrap(), store).unwrap(), first_manifest);
+
+        let restored = dir.join("restored.iso");
+        restore_image(&first_manifest, restored.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read(&restored).unwrap(), vec![1u8; 100000]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn manifests_arent_replaced() {
+        let dir = temp_store("replace");
+        let path = dir.join("a.manifest");
+
+        write_manifest(&path, "one").unwrap();
+        assert!(write_manifest(&path, "one").is_ok());
+        assert!(write_manifest(&path, "two").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
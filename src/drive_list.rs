@@ -0,0 +1,84 @@
+
+// Every drive auto_archive knows about. Drives can be plugged in and pulled out while it's running, so anything that
+// keeps hold of the drives keeps this instead, and looks in it each time it wants them. Drives that get pulled out are
+// kept aside, for their history, and for when they're plugged back in.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+use crate::DiskDrive;
+
+#[derive(Default)]
+pub struct DriveList {
+    drives: RwLock<Vec<Arc<DiskDrive>>>,
+    retired: Mutex<Vec<Arc<DiskDrive>>>, // Pulled out this session.
+}
+
+impl DriveList {
+    pub fn new(drives: Vec<Arc<DiskDrive>>) -> DriveList {
+        DriveList {
+            drives: RwLock::new(drives),
+            retired: Mutex::new(Vec::new()),
+        }
+    }
+
+    // The drives that are plugged in right now.
+    pub fn get(&self) -> Vec<Arc<DiskDrive>> {
+        self.drives.read().unwrap().clone()
+    }
+
+    // Same, but gives up instead of waiting if something has the list.
+    pub fn try_get(&self) -> Option<Vec<Arc<DiskDrive>>> {
+        self.drives.try_read().ok().map(|drives| drives.clone())
+    }
+
+    // Including the ones that have been pulled out, for looking back over the session.
+    pub fn everything(&self) -> Vec<Arc<DiskDrive>> {
+        let mut drives = self.get();
+        drives.extend(self.retired.lock().unwrap().iter().cloned());
+        drives
+    }
+
+    pub fn len(&self) -> usize {
+        self.drives.read().unwrap().len()
+    }
+
+    // Looks through the ones that have been pulled out too, since whatever hears a drive was pulled out only gets to
+    // look for it afterwards.
+    pub fn find(&self, file: &str) -> Option<Arc<DiskDrive>> {
+        self.find_plugged(file).or_else(|| self.retired.lock().unwrap().iter().find(|drive| drive.file == file).cloned())
+    }
+
+    pub fn find_plugged(&self, file: &str) -> Option<Arc<DiskDrive>> {
+        self.drives.read().unwrap().iter().find(|drive| drive.file == file).cloned()
+    }
+
+    pub fn add(&self, drive: Arc<DiskDrive>) {
+        self.drives.write().unwrap().push(drive);
+    }
+
+    // Sets the drive aside. Returns it, if it was plugged in.
+    pub fn retire(&self, file: &str) -> Option<Arc<DiskDrive>> {
+        let mut drives = self.drives.write().unwrap();
+        let index = drives.iter().position(|drive| drive.file == file)?;
+
+        let drive = drives.remove(index);
+        self.retired.lock().unwrap().push(drive.clone());
+        Some(drive)
+    }
+
+    // Puts a drive that was pulled out back in the list, if `found` is it plugged back in where it was. Drives are
+    // told apart by their settings key, so drives without one are taken to be whatever was there last.
+    pub fn bring_back(&self, found: &DiskDrive) -> Option<Arc<DiskDrive>> {
+        // Let go of the retired list first, since retire() takes them the other way around.
+        let drive = {
+            let mut retired = self.retired.lock().unwrap();
+            let index = retired.iter().rposition(|drive| drive.file == found.file && drive.settings_key == found.settings_key)?;
+            retired.remove(index)
+        };
+
+        self.add(drive.clone());
+        Some(drive)
+    }
+}
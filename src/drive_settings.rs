@@ -0,0 +1,131 @@
+
+// Settings for individual drives, kept by the drive's serial number so they follow the drive around no matter which
+// /dev/sr* it turns up as. Drives that won't give a serial over SCSI, like a lot of USB ones, are kept by their name in
+// /dev/disk/by-id instead. Same format as the catalog, a `[serial]` header followed by `key = value` lines.
+
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::benchmark::Profile;
+use crate::profile::RipProfile;
+
+const SETTINGS_FILE: &str = "auto_archive.drives";
+
+#[derive(Clone, Default)]
+pub struct DriveSettings {
+    pub name: Option<String>, // What the operator calls it. "Top left", "The one that reads everything".
+    pub auto_name: bool,      // Save under the default name without asking.
+    pub benchmark: Option<Profile>, // From the last time it was benchmarked.
+    pub profile: Option<RipProfile>, // Used instead of the config file's profile.
+}
+
+const BY_ID_DIRECTORY: &str = "/dev/disk/by-id";
+
+// udev names these from the drive's model and serial. A drive can have a few, so the first in order is picked, to
+// pick the same one every time. Partitions get their own, which aren't the drive.
+pub fn find_by_id(file: &str) -> Option<String> {
+    let device = fs::canonicalize(file).ok()?;
+
+    let mut names: Vec<String> = fs::read_dir(BY_ID_DIRECTORY).ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| fs::canonicalize(entry.path()).ok().as_deref() == Some(device.as_path()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.contains("-part"))
+        .collect();
+
+    names.sort();
+    names.into_iter().next()
+}
+
+// What a drive's settings are kept under. None if there's nothing that'll still be the same next time.
+pub fn get_settings_key(file: &str, serial: Option<&str>) -> Option<String> {
+    match serial {
+        Some(serial) => Some(String::from(serial)),
+        None if Path::new(file).exists() => find_by_id(file),
+        None => None,
+    }
+}
+
+fn read_all() -> io::Result<Vec<(String, DriveSettings)>> {
+    let text = match fs::read_to_string(SETTINGS_FILE) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let mut drives: Vec<(String, DriveSettings)> = Vec::new();
+
+    for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        if let Some(serial) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            drives.push((String::from(serial), DriveSettings::default()));
+            continue;
+        }
+
+        // Anything we don't understand was probably written by a newer version, so leave it be.
+        if let (Some((_, settings)), Some((key, value))) = (drives.last_mut(), line.split_once('=')) {
+            let value = value.trim();
+
+            match key.trim() {
+                "name" => settings.name = Some(String::from(value)),
+                "auto_name" => settings.auto_name = value == "yes",
+                "profile" => settings.profile = RipProfile::parse(value),
+                "benchmark_start_speed" => settings.benchmark.get_or_insert_with(Profile::default).start_speed = value.parse().unwrap_or(0),
+                "benchmark_end_speed" => settings.benchmark.get_or_insert_with(Profile::default).end_speed = value.parse().unwrap_or(0),
+                "benchmark_seek_ms" => settings.benchmark.get_or_insert_with(Profile::default).seek_time = value.parse().unwrap_or(0),
+                "benchmark_date" => settings.benchmark.get_or_insert_with(Profile::default).measured = value.parse().unwrap_or(0),
+                _ => {},
+            }
+        }
+    }
+
+    Ok(drives)
+}
+
+pub fn load(serial: &str) -> DriveSettings {
+    // A broken settings file shouldn't keep the drive from working, it just won't remember anything.
+    read_all().unwrap_or_default().into_iter()
+        .find(|(key, _)| key == serial)
+        .map(|(_, settings)| settings)
+        .unwrap_or_default()
+}
+
+pub fn save(serial: &str, settings: &DriveSettings) -> io::Result<()> {
+    let mut drives = read_all()?;
+
+    match drives.iter_mut().find(|(key, _)| key == serial) {
+        Some((_, existing)) => *existing = settings.clone(),
+        None => drives.push((String::from(serial), settings.clone())),
+    }
+
+    let mut output = String::new();
+    for (serial, settings) in drives.iter() {
+        // Writing to a String can't fail.
+        let _ = writeln!(output, "[{}]", serial);
+
+        if let Some(name) = &settings.name {
+            let _ = writeln!(output, "name = {}", name);
+        }
+
+        let _ = writeln!(output, "auto_name = {}", if settings.auto_name { "yes" } else { "no" });
+
+        if let Some(profile) = settings.profile {
+            let _ = writeln!(output, "profile = {}", profile.name());
+        }
+
+        if let Some(profile) = &settings.benchmark {
+            let _ = writeln!(output, "benchmark_start_speed = {}", profile.start_speed);
+            let _ = writeln!(output, "benchmark_end_speed = {}", profile.end_speed);
+            let _ = writeln!(output, "benchmark_seek_ms = {}", profile.seek_time);
+            let _ = writeln!(output, "benchmark_date = {}", profile.measured);
+        }
+
+        let _ = writeln!(output);
+    }
+
+    // Same as the catalog, so a crash can't leave half a file.
+    let temp_file = format!("{}.tmp", SETTINGS_FILE);
+    fs::write(&temp_file, output)?;
+    fs::rename(&temp_file, SETTINGS_FILE)
+}
@@ -0,0 +1,435 @@
+
+// What each drive is doing, and what it's allowed to do next. The drive's task and the operator both move drives from
+// one status to the next, and without rules the operator can answer a question the drive's stopped asking, like saving
+// a disk that was already ejected. Moves that don't make sense from where the drive is are turned away. Every move
+// that's more than progress, turned away or not, goes in the log.
+
+use std::fs;
+use std::io::Write;
+use std::mem;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+
+use crate::burn::BlankMode;
+use crate::catalog;
+use crate::events;
+use crate::events::DriveEvent;
+use crate::get_drive_status_message_string;
+
+const LOG_FILE: &str = "auto_archive.states.log";
+
+#[derive(Clone, PartialEq)]
+pub enum DriveStatus {
+    Setup,
+    NoDisk,
+    Fingerprinting,
+    ReadingMetadata,
+    Copying,
+    RippingTrack(u8, u8), // Track, out of how many.
+    WaitingForName,
+    ConfirmingName,
+    Saving(String),
+    Converting(String),
+    GeneratingParity(String),
+    Storing(String),
+    BackingUp(String),
+    ImportingPhotos(usize, usize), // Photos done, out of how many.
+    Done,
+    DoneVerified(String),
+    DoneWithDamage(u64),
+    DoneShort(u64, u64), // Bytes the disk actually had, and bytes its volume said it had.
+    DoneUnmountable(String), // Saved, but the image has no file system anything could mount. Holds what's wrong with it.
+    DoneMismatch(String),    // Saved, but reading the disk again gave something different. Holds where.
+    DoneWithoutMirror(String), // Saved, but the second copy for mirror_directory wasn't. Holds why.
+    DonePhotos(usize, usize), // Photos added to the library, and duplicates skipped.
+    Cancelled,
+    WaitingForFloppy,
+    AlreadyArchived(String),
+    SkippedByHook(String), // The hook script said not to rip it. Holds why.
+    Discarded,             // Cut short by a crash, and the operator said to forget about it.
+    Rehearsed(String, usize), // Dry run. What it would have been saved as, and how many problems that would have had.
+    WaitingForTurn(usize), // How many drives are ahead of this one.
+    WaitingForSpace(u64),  // How many more bytes it needs than there's room for, with the other rips going.
+    Paused,                // Won't start on another disk until it's resumed.
+    ThreadStopped,         // The drive's task died. Nothing happens on it until it's restarted.
+    NeedsRestart,
+
+    WaitingForBlank(String),
+    Burning(String),
+    Verifying(String),
+    BurnVerified,
+
+    OfferingPatch(String),   // The disk has an earlier rip with bad regions. Holds the image.
+    ConfirmingPatch(String),
+    Patching(String),
+    Patched(u64, u64), // Bytes recovered, and bytes still unreadable.
+
+    WaitingForDiskToVerify(String),
+    VerifyingImage(String),
+    ImageVerified(String),
+
+    WaitingForDiskToBenchmark,
+    Benchmarking,
+    Benchmarked(String), // Summary of the results.
+
+    WaitingForRewritable(BlankMode),
+    Erasing(BlankMode, Duration),
+    Erased,
+
+    CopyWriteError(String),
+    CopyReadError(String),
+    Disconnected(Option<String>), // The drive went away part way through. Holds where what was read got kept, if it was.
+    NonFatalCopyWriteError(String),
+    NonFatalCopyReadError(String),
+    IsoFetchError,
+    TocReadError,
+    BlankDisk,
+    UnrecognizedFileSystem,
+    DamagedDisk(String),
+    Unsupported(&'static str), // The drive can't do what it's been asked to.
+    DeviceSizeError(String),
+    BurnError(String),
+    ChdError(String),
+    ParityError(String),
+    CatalogError(String),
+    LabelError(String),
+    ProvenanceError(String),
+    StoreError(String),
+    BackupError(String),
+    PhotoError(String),
+    AudioError(String),
+    DvdError(String),
+    NfoError(String),
+    NotEnoughSpace(u64, u64), // Bytes the image needs, and bytes free.
+    VerifyFailed(String),
+    EraseError(String),
+    BenchmarkError(String),
+    HookError(String),
+}
+
+// True once the drive has finished with its disk, one way or another.
+pub fn is_drive_finished(status: &DriveStatus) -> bool {
+    matches!(status,
+        DriveStatus::Done
+        | DriveStatus::DoneVerified(_)
+        | DriveStatus::DoneWithDamage(_)
+        | DriveStatus::DoneShort(_, _)
+        | DriveStatus::DoneUnmountable(_)
+        | DriveStatus::DoneMismatch(_)
+        | DriveStatus::DoneWithoutMirror(_)
+        | DriveStatus::Patched(_, _)
+        | DriveStatus::DonePhotos(_, _)
+        | DriveStatus::Cancelled
+        | DriveStatus::AlreadyArchived(_)
+        | DriveStatus::Rehearsed(_, _)
+        | DriveStatus::SkippedByHook(_)
+        | DriveStatus::Discarded
+        | DriveStatus::HookError(_)
+        | DriveStatus::NeedsRestart
+        | DriveStatus::ThreadStopped
+        | DriveStatus::CopyReadError(_)
+        | DriveStatus::CopyWriteError(_)
+        | DriveStatus::Disconnected(_)
+        | DriveStatus::IsoFetchError
+        | DriveStatus::BlankDisk
+        | DriveStatus::UnrecognizedFileSystem
+        | DriveStatus::DamagedDisk(_)
+        | DriveStatus::Unsupported(_)
+        | DriveStatus::ChdError(_)
+        | DriveStatus::ParityError(_)
+        | DriveStatus::CatalogError(_)
+        | DriveStatus::LabelError(_)
+        | DriveStatus::ProvenanceError(_)
+        | DriveStatus::StoreError(_)
+        | DriveStatus::BackupError(_)
+        | DriveStatus::PhotoError(_)
+        | DriveStatus::AudioError(_)
+        | DriveStatus::DvdError(_)
+        | DriveStatus::NfoError(_)
+        | DriveStatus::NotEnoughSpace(_, _))
+}
+
+// Nothing's happening on the drive until a disk goes in. Waiting for a turn isn't one of these, since there's a disk
+// in the drive waiting for it.
+pub fn is_waiting_for_disk(status: &DriveStatus) -> bool {
+    matches!(status,
+        DriveStatus::Setup
+        | DriveStatus::NoDisk
+        | DriveStatus::WaitingForFloppy
+        | DriveStatus::Paused
+        | DriveStatus::ThreadStopped
+        | DriveStatus::NeedsRestart
+        | DriveStatus::WaitingForBlank(_)
+        | DriveStatus::WaitingForDiskToVerify(_)
+        | DriveStatus::WaitingForDiskToBenchmark
+        | DriveStatus::WaitingForRewritable(_))
+}
+
+fn is_legal_transition(from: &DriveStatus, to: &DriveStatus) -> bool {
+    // A disk that's done with, or isn't there yet, can't be named or copied.
+    let settled = is_drive_finished(from) || is_waiting_for_disk(from);
+
+    match (from, to) {
+        // Anything can die, but only a restart brings it back.
+        (_, DriveStatus::ThreadStopped) => true,
+        (DriveStatus::ThreadStopped, to) => *to == DriveStatus::NeedsRestart,
+        (DriveStatus::NeedsRestart, to) => *to == DriveStatus::Setup,
+        (_, DriveStatus::NeedsRestart) => false,
+
+        // The operator's answers only count while the question is still up.
+        (DriveStatus::WaitingForName, DriveStatus::ConfirmingName) => true,
+        (_, DriveStatus::ConfirmingName) => false,
+        (DriveStatus::OfferingPatch(offered), DriveStatus::ConfirmingPatch(image)) => offered == image,
+        (_, DriveStatus::ConfirmingPatch(_)) => false,
+        (DriveStatus::ConfirmingPatch(offered), DriveStatus::Patching(image)) => offered == image,
+        (_, DriveStatus::Patching(_)) => false,
+
+        (_, DriveStatus::WaitingForName) | (_, DriveStatus::Saving(_)) | (_, DriveStatus::Copying) => !settled,
+
+        _ => true,
+    }
+}
+
+pub struct IllegalTransition {
+    pub from: DriveStatus,
+    pub to: DriveStatus,
+}
+
+pub fn get_illegal_transition_message(error: &IllegalTransition) -> String {
+    format!("The drive has moved on, so that can't be done anymore.\nIt was going to be: {}\nIt is: {}",
+        get_drive_status_message_string(&error.to), get_drive_status_message_string(&error.from))
+}
+
+pub struct DriveStateMachine {
+    drive: String, // The drive's device file.
+    status: Mutex<DriveStatus>,
+    changed: Condvar, // Signalled whenever the status changes, or anything else the drive might be waiting on does.
+    task_changed: Notify, // The same, for the drive's task.
+}
+
+impl DriveStateMachine {
+    pub fn new(drive: &str) -> DriveStateMachine {
+        DriveStateMachine {
+            drive: String::from(drive),
+            status: Mutex::new(DriveStatus::Setup),
+            changed: Condvar::new(),
+            task_changed: Notify::new(),
+        }
+    }
+
+    pub fn get(&self) -> DriveStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    // For looking without a copy. Hold on to it and nothing else can move the drive.
+    pub fn lock(&self) -> MutexGuard<'_, DriveStatus> {
+        self.status.lock().unwrap()
+    }
+
+    // None if something has it locked, which might be something that's stuck.
+    pub fn try_get(&self) -> Option<DriveStatus> {
+        self.status.try_lock().ok().map(|status| status.clone())
+    }
+
+    // For after whatever was holding the status died.
+    pub fn clear_poison(&self) {
+        self.status.clear_poison();
+    }
+
+    // Moves the drive on, and tells everyone subscribed. Moving to where it already is does nothing.
+    pub fn transition(&self, to: DriveStatus) -> Result<(), IllegalTransition> {
+        self.transition_if(|_| true, to)
+    }
+
+    // The same, but only if `check` is happy with where the drive is now. Checked under the same lock as the move, so
+    // nothing can move the drive in between.
+    pub fn transition_if<F>(&self, check: F, to: DriveStatus) -> Result<(), IllegalTransition> where
+        F: FnOnce(&DriveStatus) -> bool {
+
+        let (line, result) = self.move_to(check, to);
+
+        // Written once the status is let go, so a slow disk doesn't hold up everything looking at the drive.
+        if let Some(line) = line {
+            write_log(&line);
+        }
+
+        result
+    }
+
+    // The move itself, and the log line for it, if it's worth one.
+    fn move_to<F>(&self, check: F, to: DriveStatus) -> (Option<String>, Result<(), IllegalTransition>) where
+        F: FnOnce(&DriveStatus) -> bool {
+
+        let mut status = self.status.lock().unwrap();
+
+        if !check(&status) {
+            return (None, Err(IllegalTransition { from: status.clone(), to }));
+        }
+
+        if *status == to {
+            return (None, Ok(()));
+        }
+
+        if !is_legal_transition(&status, &to) {
+            let line = self.log_line(&status, &to, true);
+            return (Some(line), Err(IllegalTransition { from: status.clone(), to }));
+        }
+
+        // Updates that only move progress along aren't worth a line.
+        let line = if mem::discriminant(&*status) != mem::discriminant(&to) {
+            Some(self.log_line(&status, &to, false))
+        } else {
+            None
+        };
+
+        *status = to.clone();
+
+        // Still holding the status, so subscribers hear about moves in the order they happened.
+        events::publish(&self.drive, DriveEvent::Status(to));
+
+        self.changed.notify_all();
+        self.task_changed.notify_waiters();
+
+        (line, Ok(()))
+    }
+
+    // Wakes up anything waiting on the drive, after something other than its status changed.
+    pub fn wake(&self) {
+        // Holding the status means nobody's between checking and waiting, so nobody misses it.
+        let _status = self.status.lock().unwrap();
+
+        self.changed.notify_all();
+        self.task_changed.notify_waiters();
+    }
+
+    // Blocks until `check` has an answer, asking again whenever anything changes.
+    pub fn wait_until<T, F>(&self, mut check: F) -> T where
+        F: FnMut(&DriveStatus) -> Option<T> {
+
+        let mut status = self.status.lock().unwrap();
+
+        loop {
+            if let Some(answer) = check(&status) {
+                return answer;
+            }
+
+            status = self.changed.wait(status).unwrap();
+        }
+    }
+
+    // Same, but gives up with None once `timeout` has gone by, for waits on things that don't wake the drive.
+    pub fn wait_until_timeout<T, F>(&self, timeout: Duration, mut check: F) -> Option<T> where
+        F: FnMut(&DriveStatus) -> Option<T> {
+
+        let deadline = Instant::now() + timeout;
+        let mut status = self.status.lock().unwrap();
+
+        loop {
+            if let Some(answer) = check(&status) {
+                return Some(answer);
+            }
+
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return None;
+            }
+
+            status = self.changed.wait_timeout(status, left).unwrap().0;
+        }
+    }
+
+    // Resolves the next time anything changes. Make it before checking, so a change in between isn't missed.
+    pub fn changed(&self) -> Notified<'_> {
+        self.task_changed.notified()
+    }
+
+    // Waits until `done` says so, checking again whenever anything changes.
+    pub async fn wait_for<F>(&self, done: F) where
+        F: Fn() -> bool {
+
+        loop {
+            let changed = self.changed();
+            if done() {
+                return;
+            }
+
+            changed.await;
+        }
+    }
+
+    fn log_line(&self, from: &DriveStatus, to: &DriveStatus, rejected: bool) -> String {
+        format!("{} UTC {}: {}{} -> {}\n", catalog::format_date(catalog::now()), self.drive,
+            if rejected { "turned away " } else { "" }, get_drive_status_message_string(from), get_drive_status_message_string(to))
+    }
+}
+
+// Losing a log line isn't worth stopping a rip over.
+fn write_log(line: &str) {
+    // Tests move drives around too, and shouldn't leave a log behind.
+    if cfg!(test) {
+        return;
+    }
+
+    if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(LOG_FILE) {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settled_drives_cant_be_copied_or_named() {
+        for from in &[DriveStatus::Done, DriveStatus::NoDisk, DriveStatus::Discarded] {
+            assert!(!is_legal_transition(from, &DriveStatus::Copying));
+            assert!(!is_legal_transition(from, &DriveStatus::WaitingForName));
+            assert!(!is_legal_transition(from, &DriveStatus::Saving(String::from("a"))));
+        }
+
+        assert!(is_legal_transition(&DriveStatus::Fingerprinting, &DriveStatus::Copying));
+        assert!(is_legal_transition(&DriveStatus::Copying, &DriveStatus::WaitingForName));
+        assert!(is_legal_transition(&DriveStatus::WaitingForName, &DriveStatus::Saving(String::from("a"))));
+    }
+
+    #[test]
+    fn patches_need_the_offered_image() {
+        let offered = DriveStatus::OfferingPatch(String::from("a"));
+
+        assert!(is_legal_transition(&offered, &DriveStatus::ConfirmingPatch(String::from("a"))));
+        assert!(!is_legal_transition(&offered, &DriveStatus::ConfirmingPatch(String::from("b"))));
+        assert!(!is_legal_transition(&DriveStatus::NoDisk, &DriveStatus::ConfirmingPatch(String::from("a"))));
+
+        let confirming = DriveStatus::ConfirmingPatch(String::from("a"));
+
+        assert!(is_legal_transition(&confirming, &DriveStatus::Patching(String::from("a"))));
+        assert!(!is_legal_transition(&confirming, &DriveStatus::Patching(String::from("b"))));
+    }
+
+    #[test]
+    fn stopped_threads_only_come_back_through_a_restart() {
+        assert!(is_legal_transition(&DriveStatus::Copying, &DriveStatus::ThreadStopped));
+        assert!(!is_legal_transition(&DriveStatus::ThreadStopped, &DriveStatus::Setup));
+        assert!(is_legal_transition(&DriveStatus::ThreadStopped, &DriveStatus::NeedsRestart));
+        assert!(!is_legal_transition(&DriveStatus::NeedsRestart, &DriveStatus::NoDisk));
+        assert!(is_legal_transition(&DriveStatus::NeedsRestart, &DriveStatus::Setup));
+        assert!(!is_legal_transition(&DriveStatus::Copying, &DriveStatus::NeedsRestart));
+    }
+
+    #[test]
+    fn timed_waits_give_up() {
+        let state = DriveStateMachine::new("/dev/sr9");
+
+        let waited = state.wait_until_timeout(Duration::from_millis(10), |_| None::<()>);
+        let answered = state.wait_until_timeout(Duration::from_millis(10), |status| Some(status.clone()));
+
+        assert!(waited.is_none());
+        assert!(answered == Some(DriveStatus::Setup));
+    }
+}
@@ -0,0 +1,67 @@
+
+// Dry runs go through everything a rip does except the copy, so a big job can be rehearsed first. Every disk gets a
+// line in the report saying what it would have been saved as, and anything that would have gone wrong with that.
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::catalog::Catalog;
+
+const REPORT_FILE: &str = "auto_archive.dry_run";
+
+// Names given out so far this run. Nothing gets written, so two disks given the same name wouldn't find out otherwise.
+static NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub struct Rehearsal {
+    pub image: String,
+    pub volume: String,
+    pub size: u64,
+    pub fingerprint: Option<String>,
+    pub duplicate_of: Option<String>, // Image in the catalog with the same fingerprint.
+    pub problems: Vec<String>,
+}
+
+// Everything that would go wrong saving an image under this name. Takes the name, so asking twice is a problem.
+pub fn check_name(catalog: &Catalog, name: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let path = Path::new(name);
+
+    if path.exists() {
+        problems.push(format!("{} already exists and would be overwritten.", name));
+    }
+
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if !parent.is_dir() {
+            problems.push(format!("The directory {} doesn't exist.", parent.display()));
+        }
+    }
+
+    if catalog.entries().iter().any(|entry| entry.image == name) {
+        problems.push(format!("The catalog already has an image called {}.", name));
+    }
+
+    let mut names = NAMES.lock().unwrap();
+    if names.iter().any(|used| used == name) {
+        problems.push(format!("Another disk in this dry run was already given the name {}.", name));
+    } else {
+        names.push(String::from(name));
+    }
+
+    problems
+}
+
+pub fn record(rehearsal: &Rehearsal) -> io::Result<()> {
+    let mut line = format!("{}\t{}\t{}\t{}\t{}",
+        rehearsal.image, rehearsal.volume, rehearsal.size,
+        rehearsal.fingerprint.as_deref().unwrap_or("-"), rehearsal.duplicate_of.as_deref().unwrap_or("-"));
+
+    for problem in rehearsal.problems.iter() {
+        line += &format!("\t{}", problem);
+    }
+
+    let mut file = fs::OpenOptions::new().append(true).create(true).open(REPORT_FILE)?;
+    writeln!(file, "{}", line)
+}
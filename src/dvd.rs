@@ -0,0 +1,232 @@
+
+// Reads the titles, chapters, and languages out of a DVD-Video image's IFO files, and writes them to a JSON sidecar
+// next to the image, so whatever transcodes it later knows what's in there without having to dig through it again.
+// The IFO layout is documented at http://dvd.sourceforge.net/dvdinfo/ifo.html
+
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use std::process::Command;
+
+use crate::sidecar;
+
+pub enum DvdError {
+    LaunchFail,      // Failed to launch isoinfo.
+    Extract(String), // isoinfo couldn't get an IFO out of the image. Holds the IFO's name.
+    Parse(String),   // An IFO didn't make sense. Holds the IFO's name.
+    Write(String),   // Couldn't write the sidecar.
+}
+
+const VMG_IFO: &str = "/VIDEO_TS/VIDEO_TS.IFO";
+const SECTOR_SIZE: usize = 2048;
+
+// Does this list of files, as the file index has them, look like a DVD-Video disk?
+pub fn is_dvd_video(files: &[String]) -> bool {
+    files.iter().any(|file| file.eq_ignore_ascii_case(VMG_IFO))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(((bytes[0] as usize) << 8) | bytes[1] as usize)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(((bytes[0] as usize) << 24) | ((bytes[1] as usize) << 16) | ((bytes[2] as usize) << 8) | bytes[3] as usize)
+}
+
+fn read_ifo(image: &str, name: &str) -> Result<Vec<u8>, DvdError> {
+    // Plain ISO 9660 names, since that's the part of the UDF bridge isoinfo can read.
+    let output = Command::new("isoinfo")
+        .args(["-i", image, "-x", &format!("{};1", name)])
+        .output()
+        .map_err(|_| { DvdError::LaunchFail })?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(DvdError::Extract(String::from(name)));
+    }
+
+    Ok(output.stdout)
+}
+
+// Playback times are BCD hours, minutes, seconds, and frames, with the frame rate in the top two bits of the frames.
+fn get_playback_time(data: &[u8], offset: usize) -> Option<f64> {
+    let bytes = data.get(offset..offset + 4)?;
+    let bcd = |byte: u8| ((byte >> 4) * 10 + (byte & 0x0F)) as f64;
+
+    let fps = if bytes[3] >> 6 == 1 { 25.0 } else { 30.0 };
+    Some(bcd(bytes[0]) * 3600.0 + bcd(bytes[1]) * 60.0 + bcd(bytes[2]) + bcd(bytes[3] & 0x3F) / fps)
+}
+
+// Two letter ISO 639 codes, when the disk bothers to say.
+fn get_language(data: &[u8], offset: usize, has_language: bool) -> Option<String> {
+    let code = data.get(offset..offset + 2)?;
+
+    if has_language && code.iter().all(|byte| byte.is_ascii_alphabetic()) {
+        Some(String::from_utf8_lossy(code).to_lowercase())
+    } else {
+        None
+    }
+}
+
+fn get_audio_streams(vts: &[u8]) -> Vec<Value> {
+    let count = read_u16(vts, 0x200).unwrap_or(0).min(8);
+
+    (0..count).filter_map(|index| {
+        let attributes = vts.get(0x202 + index * 8..0x202 + index * 8 + 8)?;
+
+        let format = match attributes[0] >> 5 {
+            0 => "AC3",
+            2 => "MPEG-1",
+            3 => "MPEG-2",
+            4 => "LPCM",
+            6 => "DTS",
+            _ => "unknown",
+        };
+
+        Some(json!({
+            "format": format,
+            "channels": (attributes[1] & 0x07) + 1,
+            "language": get_language(attributes, 2, (attributes[0] >> 2) & 0x03 == 1),
+        }))
+    }).collect()
+}
+
+fn get_subtitle_streams(vts: &[u8]) -> Vec<Value> {
+    let count = read_u16(vts, 0x254).unwrap_or(0).min(32);
+
+    (0..count).filter_map(|index| {
+        let attributes = vts.get(0x256 + index * 6..0x256 + index * 6 + 6)?;
+        Some(json!({ "language": get_language(attributes, 2, attributes[0] & 0x03 == 1) }))
+    }).collect()
+}
+
+// How long a program in a PGC plays for, which is all of its cells added up. Only the first angle of a multi-angle
+// block counts, since the others play instead of it, not after it.
+fn get_program_duration(pgc: &[u8], program: usize) -> Option<f64> {
+    let program_count = *pgc.get(0x02)? as usize;
+    let cell_count = *pgc.get(0x03)? as usize;
+    let program_map = read_u16(pgc, 0xE6)?;
+    let cell_playback = read_u16(pgc, 0xE8)?;
+
+    let first_cell = *pgc.get(program_map + program.checked_sub(1)?)? as usize;
+    let last_cell = if program < program_count { (*pgc.get(program_map + program)? as usize).checked_sub(1)? } else { cell_count };
+
+    let mut duration = 0.0;
+
+    for cell in first_cell.max(1)..=last_cell {
+        let info = pgc.get(cell_playback + (cell - 1) * 24..cell_playback + cell * 24)?;
+
+        let in_angle_block = (info[0] >> 4) & 0x03 == 1;
+        let first_in_block = info[0] >> 6 == 1;
+
+        if !in_angle_block || first_in_block {
+            duration += get_playback_time(info, 4)?;
+        }
+    }
+
+    Some(duration)
+}
+
+// Reads a title's chapters from its title set. Each chapter points at a program in one of the title set's PGCs.
+fn get_chapters(vts: &[u8], title_in_vts: usize) -> Option<Vec<f64>> {
+    let ptt_table = read_u32(vts, 0xC8)? * SECTOR_SIZE;
+    let pgc_table = read_u32(vts, 0xCC)? * SECTOR_SIZE;
+
+    let title_count = read_u16(vts, ptt_table)?;
+    let table_end = read_u32(vts, ptt_table + 4)? + 1;
+
+    if title_in_vts == 0 || title_in_vts > title_count {
+        return None;
+    }
+
+    let start = read_u32(vts, ptt_table + 8 + (title_in_vts - 1) * 4)?;
+    let end = if title_in_vts < title_count { read_u32(vts, ptt_table + 8 + title_in_vts * 4)? } else { table_end };
+
+    (start..end).step_by(4).map(|offset| {
+        let pgc_number = read_u16(vts, ptt_table + offset)?;
+        let program = read_u16(vts, ptt_table + offset + 2)?;
+
+        let pgc_offset = read_u32(vts, pgc_table + 8 + pgc_number.checked_sub(1)? * 8 + 4)?;
+        get_program_duration(vts.get(pgc_table + pgc_offset..)?, program)
+    }).collect()
+}
+
+pub fn read_dvd_info(image: &str) -> Result<Value, DvdError> {
+    let vmg = read_ifo(image, VMG_IFO)?;
+    let bad_vmg = || DvdError::Parse(String::from(VMG_IFO));
+
+    if !vmg.starts_with(b"DVDVIDEO-VMG") {
+        return Err(bad_vmg());
+    }
+
+    let title_table = read_u32(&vmg, 0xC4).ok_or_else(bad_vmg)? * SECTOR_SIZE;
+    let title_count = read_u16(&vmg, title_table).ok_or_else(bad_vmg)?;
+
+    // Titles share title sets, so each set's IFO only gets pulled out of the image once.
+    let mut title_sets: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut titles = Vec::new();
+
+    for index in 0..title_count {
+        let entry = vmg.get(title_table + 8 + index * 12..title_table + 20 + index * 12).ok_or_else(bad_vmg)?;
+        let angles = entry[1];
+        let vts_number = entry[6] as usize;
+        let title_in_vts = entry[7] as usize;
+
+        let vts_name = format!("/VIDEO_TS/VTS_{:02}_0.IFO", vts_number);
+
+        if !title_sets.iter().any(|(number, _)| *number == vts_number) {
+            let vts = read_ifo(image, &vts_name)?;
+
+            if !vts.starts_with(b"DVDVIDEO-VTS") {
+                return Err(DvdError::Parse(vts_name));
+            }
+
+            title_sets.push((vts_number, vts));
+        }
+
+        let vts = &title_sets.iter().find(|(number, _)| *number == vts_number).unwrap().1;
+        let chapters = get_chapters(vts, title_in_vts).ok_or_else(|| DvdError::Parse(vts_name.clone()))?;
+
+        titles.push(json!({
+            "title": index + 1,
+            "title_set": vts_number,
+            "angles": angles,
+            "duration": chapters.iter().sum::<f64>(),
+            "chapters": chapters.iter().enumerate().map(|(chapter, duration)| json!({
+                "chapter": chapter + 1,
+                "duration": duration,
+            })).collect::<Vec<Value>>(),
+            "audio": get_audio_streams(vts),
+            "subtitles": get_subtitle_streams(vts),
+        }));
+    }
+
+    Ok(json!({ "titles": titles }))
+}
+
+// Hands back what it wrote, for anything else that wants to know.
+pub fn write_sidecar(image: &str) -> Result<Value, DvdError> {
+    let info = read_dvd_info(image)?;
+
+    // The sidecar has other things in it too, like the damage report, so only the titles get replaced.
+    let mut values = Map::new();
+    values.insert(String::from("titles"), info["titles"].clone());
+
+    sidecar::update(image, values).map_err(|e| { DvdError::Write(format!("{}", e)) })?;
+    Ok(info)
+}
+
+// How long the main feature is, in seconds, going by it being the longest title on the disk.
+pub fn get_longest_title(info: &Value) -> Option<f64> {
+    info["titles"].as_array()?.iter().filter_map(|title| title["duration"].as_f64()).reduce(f64::max)
+}
+
+pub fn get_dvd_error_message(error: &DvdError) -> String {
+    match error {
+        DvdError::LaunchFail => String::from("Failed to launch isoinfo. Is it installed?"),
+        DvdError::Extract(name) => format!("Couldn't read {} from the image.", name),
+        DvdError::Parse(name) => format!("{} is damaged or isn't a real IFO.", name),
+        DvdError::Write(message) => format!("Failed to write the title list: {}", message),
+    }
+}
@@ -0,0 +1,45 @@
+
+// What the drives are up to, for anything that wants to know. Drive threads publish events as things happen, and
+// every subscriber gets its own copy of each one on a channel of its own, so a slow subscriber never holds up a drive.
+// The UI, and anything else that reacts to drives, subscribes instead of checking up on them.
+
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use crate::catalog::CatalogEntry;
+use crate::drive_state::DriveStatus;
+
+pub enum DriveEvent {
+    Status(DriveStatus),         // The drive's status changed. Holds the new one.
+    DiskInserted,
+    DiskRemoved,
+    Archived(Box<CatalogEntry>), // A disk was saved and went into the catalog.
+    Plugged,                     // The drive showed up after startup, or came back after being pulled out.
+    Unplugged,
+}
+
+pub struct Event {
+    pub drive: String, // The drive's device file.
+    pub event: DriveEvent,
+}
+
+static SUBSCRIBERS: Mutex<Vec<mpsc::Sender<Arc<Event>>>> = Mutex::new(Vec::new());
+
+// Every event from now on. Dropping the receiver unsubscribes.
+pub fn subscribe() -> mpsc::Receiver<Arc<Event>> {
+    let (sender, receiver) = mpsc::channel();
+    SUBSCRIBERS.lock().unwrap().push(sender);
+
+    receiver
+}
+
+pub fn publish(drive: &str, event: DriveEvent) {
+    let event = Arc::new(Event {
+        drive: String::from(drive),
+        event,
+    });
+
+    // Anyone who's stopped listening gets forgotten.
+    SUBSCRIBERS.lock().unwrap().retain(|subscriber| subscriber.send(event.clone()).is_ok());
+}
@@ -0,0 +1,280 @@
+
+// Converts the catalog to and from CSV and JSON, for spreadsheets, inventory systems, and other rip stations.
+
+use serde_json::json;
+use serde_json::Value;
+
+use crate::catalog;
+use crate::catalog::CatalogEntry;
+
+const CSV_HEADER: &str = "id,image,volume,hfs_volume,archived,date,size,crc32,sha1,md5,sha256,fingerprint,redump,ia_item,backup,tape,parity,notes,damage,publisher,preparer,application,created,label_photo,inventory_id,mirror";
+
+pub fn escape_csv(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+pub fn export_csv(entries: &[CatalogEntry]) -> String {
+    let mut output = format!("{}\n", CSV_HEADER);
+
+    for entry in entries.iter() {
+        let fields = [
+            entry.id.to_string(),
+            entry.image.clone(),
+            entry.volume.clone(),
+            entry.hfs_volume.clone().unwrap_or_default(),
+            catalog::format_date(entry.date),
+            entry.date.to_string(),
+            entry.size.to_string(),
+            entry.crc32.clone(),
+            entry.sha1.clone(),
+            entry.md5.clone().unwrap_or_default(),
+            entry.sha256.clone().unwrap_or_default(),
+            entry.fingerprint.clone(),
+            entry.redump.clone().unwrap_or_default(),
+            entry.ia_item.clone().unwrap_or_default(),
+            entry.backup.clone().unwrap_or_default(),
+            entry.tape.clone().unwrap_or_default(),
+            entry.parity.join(";"), // Spreadsheets don't do lists.
+            entry.notes.join(";"),
+            entry.damage.clone().unwrap_or_default(),
+            entry.publisher.clone().unwrap_or_default(),
+            entry.preparer.clone().unwrap_or_default(),
+            entry.application.clone().unwrap_or_default(),
+            entry.created.clone().unwrap_or_default(),
+            entry.label_photo.clone().unwrap_or_default(),
+            entry.inventory_id.clone().unwrap_or_default(),
+            entry.mirror.clone().unwrap_or_default(),
+        ];
+
+        let line: Vec<String> = fields.iter().map(|field| escape_csv(field)).collect();
+        output += &format!("{}\n", line.join(","));
+    }
+
+    output
+}
+
+// The same fields as the CSV, for anything else that wants an entry as JSON too.
+pub fn entry_to_json(entry: &CatalogEntry) -> Value {
+    json!({
+        "id": entry.id,
+        "image": entry.image,
+        "volume": entry.volume,
+        "hfs_volume": entry.hfs_volume,
+        "date": entry.date,
+        "size": entry.size,
+        "crc32": entry.crc32,
+        "sha1": entry.sha1,
+        "md5": entry.md5,
+        "sha256": entry.sha256,
+        "fingerprint": entry.fingerprint,
+        "redump": entry.redump,
+        "ia_item": entry.ia_item,
+        "backup": entry.backup,
+        "tape": entry.tape,
+        "parity": entry.parity,
+        "notes": entry.notes,
+        "damage": entry.damage,
+        "publisher": entry.publisher,
+        "preparer": entry.preparer,
+        "application": entry.application,
+        "created": entry.created,
+        "label_photo": entry.label_photo,
+        "inventory_id": entry.inventory_id,
+        "mirror": entry.mirror,
+    })
+}
+
+pub fn export_json(entries: &[CatalogEntry]) -> String {
+    let entries: Vec<Value> = entries.iter().map(entry_to_json).collect();
+
+    // Can't fail, it's all strings and numbers.
+    serde_json::to_string_pretty(&entries).unwrap_or_default()
+}
+
+// Splits up a CSV file, handling quoted fields with commas, quotes, and line breaks in them.
+pub fn parse_csv(input: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {},
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            _ => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+pub fn import_csv(input: &str) -> Result<Vec<CatalogEntry>, String> {
+    let mut rows = parse_csv(input).into_iter();
+    let header = rows.next().ok_or_else(|| { String::from("The CSV file is empty.") })?;
+
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let image = column("image").ok_or_else(|| { String::from("The CSV file has no image column.") })?;
+
+    let mut entries = Vec::new();
+
+    for (index, row) in rows.enumerate() {
+        let get = |name: &str| column(name).and_then(|column| row.get(column)).cloned().unwrap_or_default();
+        let number = |name: &str| -> Result<u64, String> {
+            match get(name).as_str() {
+                "" => Ok(0),
+                value => value.parse().map_err(|_| { format!("Row {} has a bad {}.", index + 2, name) }),
+            }
+        };
+
+        let hfs_volume = get("hfs_volume");
+        let md5 = get("md5");
+        let sha256 = get("sha256");
+        let redump = get("redump");
+        let ia_item = get("ia_item");
+        let backup = get("backup");
+        let tape = get("tape");
+        let parity = get("parity");
+        let notes = get("notes");
+        let damage = get("damage");
+        let optional = |name: &str| Some(get(name)).filter(|value| !value.is_empty());
+
+        entries.push(CatalogEntry {
+            image: row.get(image).cloned().unwrap_or_default(),
+            volume: get("volume"),
+            hfs_volume: if hfs_volume.is_empty() { None } else { Some(hfs_volume) },
+            date: number("date")?,
+            size: number("size")?,
+            crc32: get("crc32"),
+            sha1: get("sha1"),
+            md5: if md5.is_empty() { None } else { Some(md5) },
+            sha256: if sha256.is_empty() { None } else { Some(sha256) },
+            fingerprint: get("fingerprint"),
+            redump: if redump.is_empty() { None } else { Some(redump) },
+            ia_item: if ia_item.is_empty() { None } else { Some(ia_item) },
+            backup: if backup.is_empty() { None } else { Some(backup) },
+            tape: if tape.is_empty() { None } else { Some(tape) },
+            parity: parity.split(';').filter(|path| !path.is_empty()).map(String::from).collect(),
+            notes: notes.split(';').filter(|note| !note.is_empty()).map(String::from).collect(),
+            damage: if damage.is_empty() { None } else { Some(damage) },
+            publisher: optional("publisher"),
+            preparer: optional("preparer"),
+            application: optional("application"),
+            created: optional("created"),
+            label_photo: optional("label_photo"),
+            inventory_id: optional("inventory_id"),
+            mirror: optional("mirror"),
+            ..CatalogEntry::default()
+        });
+    }
+
+    Ok(entries)
+}
+
+pub fn import_json(input: &str) -> Result<Vec<CatalogEntry>, String> {
+    let value: Value = serde_json::from_str(input).map_err(|e| { format!("{}", e) })?;
+    let list = value.as_array().ok_or_else(|| { String::from("Expected a list of catalog entries.") })?;
+
+    Ok(list.iter().map(|item| {
+        let text = |name: &str| item[name].as_str().map(String::from).unwrap_or_default();
+        let number = |name: &str| item[name].as_u64().unwrap_or(0);
+
+        CatalogEntry {
+            image: text("image"),
+            volume: text("volume"),
+            hfs_volume: item["hfs_volume"].as_str().map(String::from),
+            date: number("date"),
+            size: number("size"),
+            crc32: text("crc32"),
+            sha1: text("sha1"),
+            md5: item["md5"].as_str().map(String::from),
+            sha256: item["sha256"].as_str().map(String::from),
+            fingerprint: text("fingerprint"),
+            redump: item["redump"].as_str().map(String::from),
+            ia_item: item["ia_item"].as_str().map(String::from),
+            backup: item["backup"].as_str().map(String::from),
+            tape: item["tape"].as_str().map(String::from),
+            parity: item["parity"].as_array()
+                .map(|list| list.iter().filter_map(|path| path.as_str()).map(String::from).collect())
+                .unwrap_or_default(),
+            notes: item["notes"].as_array()
+                .map(|list| list.iter().filter_map(|note| note.as_str()).map(String::from).collect())
+                .unwrap_or_default(),
+            damage: item["damage"].as_str().map(String::from),
+            publisher: item["publisher"].as_str().map(String::from),
+            preparer: item["preparer"].as_str().map(String::from),
+            application: item["application"].as_str().map(String::from),
+            created: item["created"].as_str().map(String::from),
+            label_photo: item["label_photo"].as_str().map(String::from),
+            inventory_id: item["inventory_id"].as_str().map(String::from),
+            mirror: item["mirror"].as_str().map(String::from),
+            ..CatalogEntry::default()
+        }
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_import_back() {
+        let entry = CatalogEntry {
+            id: 3,
+            image: String::from("Disk, with a comma.iso"),
+            volume: String::from("A \"quoted\" volume"),
+            date: 1600000000,
+            size: 4096,
+            sha1: String::from("abc"),
+            parity: vec![String::from("a.par2"), String::from("b.par2")],
+            notes: vec![String::from("Line one\nline two")],
+            mirror: Some(String::from("/mnt/nas/disk.iso")),
+            ..CatalogEntry::default()
+        };
+
+        let entries = import_csv(&export_csv(std::slice::from_ref(&entry))).unwrap();
+        let read = &entries[0];
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(read.image, entry.image);
+        assert_eq!(read.volume, entry.volume);
+        assert_eq!(read.date, entry.date);
+        assert_eq!(read.size, entry.size);
+        assert_eq!(read.parity, entry.parity);
+        assert_eq!(read.notes, entry.notes);
+        assert_eq!(read.mirror, entry.mirror);
+        assert_eq!(read.md5, None);
+    }
+
+    #[test]
+    fn columns_can_be_missing_or_moved() {
+        let entries = import_csv("size,image\n2048,a.iso\n,b.iso\n").unwrap();
+
+        assert_eq!(entries[0].image, "a.iso");
+        assert_eq!(entries[0].size, 2048);
+        assert_eq!(entries[1].size, 0);
+
+        assert!(import_csv("").is_err());
+        assert!(import_csv("volume\nDATA\n").is_err());
+        assert_eq!(import_csv("image,size\na.iso,big\n").err().as_deref(), Some("Row 2 has a bad size."));
+    }
+}
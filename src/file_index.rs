@@ -0,0 +1,170 @@
+
+// An index of every file on every archived disk, so we can find which disk something is on without digging them all
+// out. It's kept next to the catalog in the same format, a header with the disk's catalog ID followed by its files.
+// It's only ever appended to, since it can get big.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+const INDEX_FILE: &str = "auto_archive.files";
+
+// Lists every file in an ISO. Joliet names first since they're what Windows burned, then Rock Ridge, which isoinfo
+// falls back to the plain ISO 9660 names for if it's missing.
+pub fn list_files(image: &str) -> Result<Vec<String>, String> {
+    for extension in ["-J", "-R"].iter() {
+        if let Some(files) = list_files_by(image, extension)? {
+            return Ok(files);
+        }
+    }
+
+    Err(format!("isoinfo couldn't list the files in {}.", image))
+}
+
+// Just the one set of names. None if there aren't any.
+fn list_files_by(image: &str, extension: &str) -> Result<Option<Vec<String>>, String> {
+    let output = Command::new("isoinfo")
+        .args(["-f", extension, &format!("-i{}", image)])
+        .output()
+        .map_err(|_| { String::from("Failed to launch isoinfo. Is it installed?") })?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stdout).lines()
+        .map(|line| line.trim_end_matches(";1")) // Version numbers on plain ISO 9660 names.
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()))
+}
+
+pub fn add_files(id: u64, files: &[String]) -> io::Result<()> {
+    let mut block = format!("[{}]\n", id);
+    for file in files.iter() {
+        block += &format!("{}\n", file);
+    }
+
+    // One write, so disks finishing at the same time don't get mixed together.
+    fs::OpenOptions::new().create(true).append(true).open(INDEX_FILE)?.write_all(block.as_bytes())
+}
+
+fn parse_header(line: &str) -> Option<u64> {
+    line.strip_prefix('[').and_then(|line| line.strip_suffix(']')).and_then(|id| id.parse().ok())
+}
+
+// Disks that already have their files in the index.
+pub fn indexed_ids() -> io::Result<HashSet<u64>> {
+    let file = match fs::File::open(INDEX_FILE) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(error) => return Err(error),
+    };
+
+    let mut ids = HashSet::new();
+    for line in BufReader::new(file).lines() {
+        if let Some(id) = parse_header(&line?) {
+            ids.insert(id);
+        }
+    }
+
+    Ok(ids)
+}
+
+// Finds files with the query anywhere in their path, ignoring case. Returns the disk's catalog ID and the file's path.
+pub fn search(query: &str, limit: usize) -> io::Result<Vec<(u64, String)>> {
+    let file = match fs::File::open(INDEX_FILE) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let query = query.to_lowercase();
+    let mut results = Vec::new();
+    let mut id = None;
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+
+        if let Some(header) = parse_header(&line) {
+            id = Some(header);
+        } else if let Some(id) = id {
+            if line.to_lowercase().contains(&query) {
+                results.push((id, line));
+
+                if results.len() >= limit {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+// Every file indexed for a disk. A disk indexed more than once goes by the last time.
+pub fn get_files(disk: u64) -> io::Result<Vec<String>> {
+    let file = match fs::File::open(INDEX_FILE) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let mut files = Vec::new();
+    let mut id = None;
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+
+        if let Some(header) = parse_header(&line) {
+            if header == disk {
+                files.clear();
+            }
+            id = Some(header);
+        } else if id == Some(disk) {
+            files.push(line);
+        }
+    }
+
+    Ok(files)
+}
+
+// Pulls one file out of an ISO, without mounting it. The path is as the index has it, so it's tried with the same
+// names list_files would have used.
+pub fn extract_file(image: &str, path: &str, output: &Path) -> Result<(), String> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| { format!("Failed to create {}: {}", parent.display(), e) })?;
+    }
+
+    for extension in ["-J", "-R"].iter() {
+        let file = fs::File::create(output).map_err(|e| { format!("Failed to create {}: {}", output.display(), e) })?;
+
+        let status = Command::new("isoinfo")
+            .args([*extension, &format!("-i{}", image), "-x", path])
+            .stdout(Stdio::from(file))
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|_| { String::from("Failed to launch isoinfo. Is it installed?") })?;
+
+        if !status.success() {
+            continue;
+        }
+
+        // isoinfo happily writes nothing for a name it can't find, so nothing written is only right if the file's
+        // there under these names, and empty.
+        let written = fs::metadata(output).map(|metadata| metadata.len()).unwrap_or(0);
+        if written > 0 || list_files_by(image, extension)?.unwrap_or_default().iter().any(|file| file == path) {
+            return Ok(());
+        }
+    }
+
+    // Don't leave an empty file looking like it came out.
+    let _ = fs::remove_file(output);
+    Err(format!("isoinfo couldn't find {} in {}.", path, image))
+}
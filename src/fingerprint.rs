@@ -0,0 +1,49 @@
+
+// Quick fingerprints of disks, made from the TOC, the volume name, and a few small pieces scattered across the disk.
+// They take seconds instead of the twenty minutes a full read does, so duplicates can be caught before we start.
+
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use crate::cdrom::TocEntry;
+
+// How many pieces of the disk we sample, and how big each one is.
+const EXTENTS: u64 = 8;
+const EXTENT_SIZE: u64 = 4096;
+
+// Keep the samples lined up with sectors, so the drive doesn't have to read any more than it needs to.
+const ALIGNMENT: u64 = 2048;
+
+// Raw disks get fingerprinted by their TOC alone, since audio tracks can't be read like a file.
+pub fn fingerprint_disk(drive: &str, volume: &str, length: u64, toc: Option<&[TocEntry]>) -> io::Result<String> {
+    let mut hasher = sha1_smol::Sha1::new();
+
+    hasher.update(volume.as_bytes());
+    hasher.update(&length.to_le_bytes());
+
+    if let Some(toc) = toc {
+        for entry in toc.iter() {
+            hasher.update(&[entry.session, entry.adr, entry.control, entry.point, entry.pmin, entry.psec, entry.pframe]);
+        }
+    } else {
+        let mut device = fs::File::open(drive)?;
+        let mut buffer = Vec::new();
+        let last = length.saturating_sub(EXTENT_SIZE);
+
+        for index in 0..EXTENTS {
+            let offset = last * index / (EXTENTS - 1) / ALIGNMENT * ALIGNMENT;
+
+            device.seek(SeekFrom::Start(offset))?;
+
+            buffer.clear();
+            (&mut device).take(EXTENT_SIZE).read_to_end(&mut buffer)?;
+            hasher.update(&buffer);
+        }
+    }
+
+    // The whole SHA-1 is more than anyone wants to read off a screen.
+    Ok(hasher.digest().to_string()[..16].to_string())
+}
@@ -0,0 +1,145 @@
+
+// Floppy disk support. Covers the legacy controller (/dev/fdN) and USB floppy drives, which show up as SCSI disks.
+// Floppies can't tell us when a disk goes in, and they're slow and fragile, so they get read a sector at a time with retries.
+
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+
+use crate::CopyError;
+use crate::mapfile::DamageMap;
+
+pub const SECTOR_SIZE: usize = 512;
+
+// How many times we try a bad sector before giving up and zero filling it.
+const SECTOR_RETRIES: usize = 5;
+
+// A standard 3.5" high density disk. Used when the drive won't tell us the geometry.
+const DEFAULT_FLOPPY_SIZE: usize = 1474560;
+
+// From linux/fd.h
+#[repr(C)]
+struct FloppyStruct {
+    size: libc::c_uint,  // Number of sectors.
+    sect: libc::c_uint,  // Sectors per track.
+    head: libc::c_uint,
+    track: libc::c_uint,
+    stretch: libc::c_uint,
+    gap: libc::c_uchar,
+    rate: libc::c_uchar,
+    spec1: libc::c_uchar,
+    fmt_gap: libc::c_uchar,
+    name: *const libc::c_char,
+}
+
+// FDGETPRM is _IOR(2, 0x04, struct floppy_struct), so its value depends on the size of the struct.
+fn fdgetprm() -> libc::c_ulong {
+    (2 << 30) | ((mem::size_of::<FloppyStruct>() as libc::c_ulong) << 16) | (2 << 8) | 0x04
+}
+
+// Floppy devices on the legacy controller. Anything else is a USB drive pretending to be a SCSI disk.
+pub fn is_legacy_floppy(drive: &str) -> bool {
+    drive.starts_with("/dev/fd")
+}
+
+// USB floppy drives identify themselves in their model string. Covers TEAC, Y-E Data, Mitsumi, and the no-name clones.
+pub fn is_usb_floppy(drive: &str) -> bool {
+    let name = drive.trim_start_matches("/dev/");
+
+    match fs::read_to_string(format!("/sys/block/{}/device/model", name)) {
+        Ok(model) => {
+            let model = model.to_uppercase();
+            model.contains("FLOPPY") || model.starts_with("FD-") || model.contains("UFD")
+        },
+        Err(_) => false,
+    }
+}
+
+pub fn list_legacy_floppies() -> Vec<String> {
+    (0..8)
+        .filter(|index| fs::metadata(format!("/sys/block/fd{}", index)).is_ok())
+        .map(|index| format!("/dev/fd{}", index))
+        .collect()
+}
+
+// Finds the size of the disk in the drive. The legacy driver only works out the geometry once something has been read.
+pub fn get_floppy_size(drive: &str) -> io::Result<usize> {
+    let mut device = fs::File::open(drive)?;
+
+    if is_legacy_floppy(drive) {
+        let mut sector = [0; SECTOR_SIZE];
+        device.read_exact(&mut sector)?;
+
+        let mut geometry: FloppyStruct = unsafe { mem::zeroed() };
+        let result = unsafe { libc::ioctl(device.as_raw_fd(), fdgetprm() as _, &mut geometry as *mut FloppyStruct) };
+
+        if result >= 0 && geometry.size > 0 {
+            return Ok(geometry.size as usize * SECTOR_SIZE);
+        }
+
+        return Ok(DEFAULT_FLOPPY_SIZE);
+    }
+
+    match device.seek(SeekFrom::End(0))? {
+        0 => Ok(DEFAULT_FLOPPY_SIZE),
+        size => Ok(size as usize),
+    }
+}
+
+// Reads the disk a sector at a time. Sectors that still fail after retrying are zero filled so the rest of the disk
+// can be saved. Returns a map of which sectors that happened to.
+pub fn copy_floppy_to_image<O, CB, ECB>(source: &str, target: &mut O, length: u64, cancel: &AtomicBool, mut callback: CB, mut error_callback: ECB)
+    -> Result<DamageMap, CopyError> where
+    O: Write,
+    CB: FnMut(usize),
+    ECB: FnMut(CopyError)
+{
+    let mut source_file = fs::File::open(source).map_err(|e| { CopyError::Read(format!("{}", e)) })?;
+    let mut sector = [0; SECTOR_SIZE];
+    let mut damage = DamageMap::new(length);
+
+    for index in 0..(length as usize / SECTOR_SIZE) {
+        if cancel.load(Relaxed) {
+            return Err(CopyError::Cancelled);
+        }
+
+        let mut good = false;
+
+        for _ in 0..SECTOR_RETRIES {
+            let offset = (index * SECTOR_SIZE) as u64;
+
+            let result = source_file.seek(SeekFrom::Start(offset))
+                .and_then(|_| source_file.read_exact(&mut sector));
+
+            match result {
+                Ok(()) => {
+                    good = true;
+                    break;
+                },
+                Err(error) => error_callback(CopyError::Read(format!("sector {}: {}", index, error))),
+            }
+        }
+
+        if good {
+            error_callback(CopyError::None);
+        } else {
+            sector = [0; SECTOR_SIZE];
+            damage.add_bad((index * SECTOR_SIZE) as u64, SECTOR_SIZE as u64);
+        }
+
+        target.write_all(&sector).map_err(|e| {
+            CopyError::Write(format!("{}", e))
+        })?;
+
+        callback(SECTOR_SIZE);
+    }
+
+    Ok(damage)
+}
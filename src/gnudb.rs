@@ -0,0 +1,125 @@
+
+// Looks up audio CDs on gnudb.org, which took over from freedb. It knows a lot of obscure disks MusicBrainz doesn't,
+// though what it knows is only as good as whoever typed it in. It speaks the old CDDB protocol over HTTP.
+
+use std::collections::HashMap;
+use std::process::Command;
+
+use crate::audio::DiscMetadata;
+use crate::audio::Track;
+
+const CDDB_ENDPOINT: &str = "https://gnudb.gnudb.org/~cddb/cddb.cgi";
+
+// Protocol level 6 is the one that speaks UTF-8.
+const PROTOCOL_LEVEL: u32 = 6;
+
+// The CDDB disc ID, worked out from how long the disk is and where its tracks start, in seconds.
+pub fn get_disc_id(tracks: &[Track], lead_out: u32) -> String {
+    let digit_sum = |seconds: u32| seconds.to_string().bytes().map(|digit| (digit - b'0') as u32).sum::<u32>();
+
+    let checksum: u32 = tracks.iter().map(|track| digit_sum((track.start + 150) / 75)).sum();
+    let first = tracks.first().map(|track| (track.start + 150) / 75).unwrap_or(0);
+    let length = (lead_out + 150) / 75 - first;
+
+    format!("{:08x}", ((checksum % 255) << 24) | (length << 8) | tracks.len() as u32)
+}
+
+fn request(email: &str, command: &str) -> Result<String, String> {
+    // The server wants to know who's asking, as a user and host, which is just an email address split in half.
+    let (user, host) = email.split_once('@').unwrap_or((email, "localhost"));
+    let url = format!("{}?cmd={}&hello={}+{}+auto_archive+0.1&proto={}", CDDB_ENDPOINT, command.replace(' ', "+"), user, host, PROTOCOL_LEVEL);
+
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--max-time", "30"])
+        .arg(&url)
+        .output()
+        .map_err(|_| { String::from("Failed to launch curl. Is it installed?") })?;
+
+    if !output.status.success() {
+        return Err(String::from(String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Parses an xmcd record. Long values are split over several lines with the same key.
+fn parse_record(record: &str, tracks: &[Track]) -> DiscMetadata {
+    let mut fields: HashMap<&str, String> = HashMap::new();
+
+    for line in record.lines().filter(|line| !line.starts_with('#')) {
+        if let Some((key, value)) = line.split_once('=') {
+            *fields.entry(key).or_default() += value;
+        }
+    }
+
+    // "Artist / Album", or just the album if there's no slash.
+    let (artist, album) = match fields.get("DTITLE").map(|title| title.trim()) {
+        Some(title) => match title.split_once(" / ") {
+            Some((artist, album)) => (Some(String::from(artist)), Some(String::from(album))),
+            None => (None, Some(String::from(title))),
+        },
+        None => (None, None),
+    };
+
+    let mut titles = HashMap::new();
+    let mut artists = HashMap::new();
+
+    // Tracks are numbered from zero, in the order they're on the disk.
+    for (index, track) in tracks.iter().enumerate() {
+        let title = match fields.get(format!("TTITLE{}", index).as_str()).map(|title| title.trim()) {
+            Some(title) if !title.is_empty() => title,
+            _ => continue,
+        };
+
+        // Compilations put the track's artist in front, same as the disk title.
+        match title.split_once(" / ") {
+            Some((track_artist, title)) if artist.as_deref() == Some("Various") || artist.as_deref() == Some("Various Artists") => {
+                titles.insert(track.number, String::from(title));
+                artists.insert(track.number, String::from(track_artist));
+            },
+            _ => {
+                titles.insert(track.number, String::from(title));
+            },
+        }
+    }
+
+    DiscMetadata {
+        album,
+        artist,
+        titles,
+        artists,
+        sources: vec![String::from("gnudb")],
+        ..DiscMetadata::default()
+    }
+}
+
+// Looks the disk up, and returns what's known about it. Disks gnudb has never heard of are Ok(None).
+pub fn lookup(email: &str, tracks: &[Track], lead_out: u32) -> Result<Option<DiscMetadata>, String> {
+    let disc_id = get_disc_id(tracks, lead_out);
+    let offsets: Vec<String> = tracks.iter().map(|track| (track.start + 150).to_string()).collect();
+
+    let query = request(email, &format!("cddb query {} {} {} {}", disc_id, tracks.len(), offsets.join(" "), (lead_out + 150) / 75))?;
+
+    // 200 is one match, on the same line. 210 and 211 are a list of them, one per line. The first is as good as any.
+    let mut lines = query.lines();
+    let status = lines.next().unwrap_or_default();
+    let found = match status.get(..3) {
+        Some("200") => status[3..].trim(),
+        Some("210") | Some("211") => lines.next().unwrap_or_default().trim(),
+        Some("202") => return Ok(None),
+        _ => return Err(format!("gnudb said: {}", status)),
+    };
+
+    let mut parts = found.split_whitespace();
+    let (category, found_id) = match (parts.next(), parts.next()) {
+        (Some(category), Some(found_id)) => (category, found_id),
+        _ => return Err(format!("gnudb said: {}", found)),
+    };
+
+    let record = request(email, &format!("cddb read {} {}", category, found_id))?;
+    if !record.starts_with("210") {
+        return Err(format!("gnudb said: {}", record.lines().next().unwrap_or_default()));
+    }
+
+    Ok(Some(parse_record(&record, tracks)))
+}
@@ -0,0 +1,255 @@
+
+// Drive control over gRPC, for orchestration systems that run more than one station: list the drives, name disks,
+// eject, pause, rescan, and a feed of every drive's progress. auto_archive.proto describes it for clients. The
+// messages here match it, and build.rs makes the service from them. Clients send grpc_token, or api_token if there
+// isn't one, as `authorization: Bearer <token>` metadata. Config parsing won't let it run without either.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio_stream::Stream;
+use tonic::service::Interceptor;
+use tonic::transport::Identity;
+use tonic::transport::ServerTlsConfig;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+
+use crate::api::get_drive_id;
+use crate::auth::Security;
+use crate::drive_list;
+use crate::eject_when_idle;
+use crate::get_drive_status_message_string;
+use crate::get_eject_error_message;
+use crate::is_drive_busy;
+use crate::name_drive;
+use crate::request_rescan;
+use crate::set_drive_paused;
+use crate::DiskDrive;
+
+mod generated {
+    tonic::include_proto!("auto_archive.DriveControl");
+}
+
+use generated::drive_control_server::DriveControl;
+use generated::drive_control_server::DriveControlServer;
+
+// How often the progress feed sends every drive.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Empty {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NameRequest {
+    #[prost(string, tag = "1")]
+    pub drive: String,
+    #[prost(string, tag = "2")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EjectRequest {
+    #[prost(string, tag = "1")]
+    pub drive: String,
+    #[prost(bool, tag = "2")]
+    pub abort: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PauseRequest {
+    #[prost(string, tag = "1")]
+    pub drive: String,
+    #[prost(bool, tag = "2")]
+    pub paused: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Reply {
+    #[prost(bool, tag = "1")]
+    pub ok: bool,
+    #[prost(string, tag = "2")]
+    pub message: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Drive {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub device: String,
+    #[prost(string, tag = "3")]
+    pub name: String,
+    #[prost(string, tag = "4")]
+    pub status: String,
+    #[prost(bool, tag = "5")]
+    pub busy: bool,
+    #[prost(bool, tag = "6")]
+    pub has_disk: bool,
+    #[prost(bool, tag = "7")]
+    pub paused: bool,
+    #[prost(uint64, tag = "8")]
+    pub done: u64,
+    #[prost(uint64, tag = "9")]
+    pub total: u64,
+    #[prost(string, tag = "10")]
+    pub unit: String,
+    #[prost(string, tag = "11")]
+    pub last_archived: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DriveList {
+    #[prost(message, repeated, tag = "1")]
+    pub drives: Vec<Drive>,
+}
+
+fn drive_to_message(drive: &DiskDrive) -> Drive {
+    let (done, total) = drive.progress.get();
+
+    Drive {
+        id: String::from(get_drive_id(drive)),
+        device: drive.file.clone(),
+        name: drive.name.lock().unwrap().clone().unwrap_or_default(),
+        status: get_drive_status_message_string(&drive.state.get()),
+        busy: is_drive_busy(drive),
+        has_disk: drive.has_disk.load(Relaxed),
+        paused: drive.paused.load(Relaxed),
+        done,
+        total,
+        unit: String::from(drive.progress.unit().name()),
+        last_archived: drive.activity.last_archived().map(|entry| entry.image).unwrap_or_default(),
+    }
+}
+
+fn reply(result: Result<String, String>) -> Response<Reply> {
+    Response::new(match result {
+        Ok(message) => Reply { ok: true, message },
+        Err(message) => Reply { ok: false, message },
+    })
+}
+
+fn no_such_drive(id: &str) -> Status {
+    Status::not_found(format!("There's no drive called {}.", id))
+}
+
+struct DriveService {
+    drives: Arc<drive_list::DriveList>,
+}
+
+impl DriveService {
+    fn find_drive(&self, id: &str) -> Option<Arc<DiskDrive>> {
+        self.drives.get().into_iter().find(|drive| get_drive_id(drive) == id)
+    }
+
+    fn list(&self) -> DriveList {
+        DriveList {
+            drives: self.drives.get().iter().map(|drive| drive_to_message(drive)).collect(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl DriveControl for DriveService {
+    async fn list_drives(&self, _request: Request<Empty>) -> Result<Response<DriveList>, Status> {
+        Ok(Response::new(self.list()))
+    }
+
+    async fn name(&self, request: Request<NameRequest>) -> Result<Response<Reply>, Status> {
+        let request = request.into_inner();
+        let drive = self.find_drive(&request.drive).ok_or_else(|| no_such_drive(&request.drive))?;
+
+        Ok(reply(name_drive(&drive, &request.name).map(|()| format!("Saving {} as {}.", drive.file, request.name))))
+    }
+
+    async fn eject(&self, request: Request<EjectRequest>) -> Result<Response<Reply>, Status> {
+        let request = request.into_inner();
+        let drive = self.find_drive(&request.drive).ok_or_else(|| no_such_drive(&request.drive))?;
+
+        if is_drive_busy(&drive) {
+            if !request.abort {
+                return Ok(reply(Err(String::from("The drive is still working on its disk. Set abort to throw that away and eject anyway."))));
+            }
+
+            drive.cancel.store(true, Relaxed);
+            drive.state.wake();
+        }
+
+        let result = tokio::task::spawn_blocking(move || eject_when_idle(&drive)).await
+            .map_err(|_| Status::internal("Ejecting crashed."))?;
+
+        Ok(reply(result.map(|()| String::from("Disk ejected.")).map_err(|error| get_eject_error_message(&error))))
+    }
+
+    async fn pause(&self, request: Request<PauseRequest>) -> Result<Response<Reply>, Status> {
+        let request = request.into_inner();
+        let drive = self.find_drive(&request.drive).ok_or_else(|| no_such_drive(&request.drive))?;
+
+        set_drive_paused(&drive, request.paused);
+        Ok(reply(Ok(String::from(if request.paused { "Paused." } else { "Resumed." }))))
+    }
+
+    async fn rescan(&self, _request: Request<Empty>) -> Result<Response<Reply>, Status> {
+        request_rescan();
+        Ok(reply(Ok(String::from("Rescanning."))))
+    }
+
+    type WatchProgressStream = Pin<Box<dyn Stream<Item = Result<DriveList, Status>> + Send>>;
+
+    async fn watch_progress(&self, _request: Request<Empty>) -> Result<Response<Self::WatchProgressStream>, Status> {
+        let service = DriveService { drives: self.drives.clone() };
+        let (sender, receiver) = tokio::sync::mpsc::channel(4);
+
+        tokio::spawn(async move {
+            // Stops once the client hangs up.
+            while sender.send(Ok(service.list())).await.is_ok() {
+                tokio::time::sleep(PROGRESS_INTERVAL).await;
+            }
+        });
+
+        Ok(Response::new(Box::pin(tokio_stream::wrappers::ReceiverStream::new(receiver))))
+    }
+}
+
+#[derive(Clone)]
+struct TokenCheck(Security);
+
+impl Interceptor for TokenCheck {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let authorization = request.metadata().get("authorization").and_then(|value| value.to_str().ok());
+
+        if self.0.is_authorized(authorization) {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("Send the API token as authorization: Bearer <token>."))
+        }
+    }
+}
+
+// Starts serving on `address`, on the runtime that runs the drives. Fails if something else already has it.
+pub fn spawn_grpc(address: &str, drives: &Arc<drive_list::DriveList>, security: &Security) -> io::Result<()> {
+    let mut builder = tonic::transport::Server::builder();
+    if let Some(tls) = &security.tls {
+        builder = builder.tls_config(ServerTlsConfig::new().identity(Identity::from_pem(&tls.certificate, &tls.key)))
+            .map_err(io::Error::other)?;
+    }
+
+    let listener = std::net::TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+
+    let service = DriveService { drives: drives.clone() };
+    let server = DriveControlServer::with_interceptor(service, TokenCheck(security.clone()));
+
+    tokio::spawn(async move {
+        let _ = builder
+            .add_service(server)
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+            .await;
+    });
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+
+// Hashes images as they're written, so we don't have to read them back afterwards to know what we've got.
+// CRC32 and SHA-1 are what redump.org and most other preservation databases use. MD5 and SHA-256 can be added for the
+// ones that want those. Each hash can get its own thread, so slow CPUs can keep up with fast drives.
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::SyncSender;
+use std::sync::Arc;
+use std::thread;
+
+use crate::digest::Md5;
+use crate::digest::Sha256;
+
+#[derive(Clone, Default, PartialEq)]
+pub struct Hashes {
+    pub size: u64,
+    pub crc32: String, // Lowercase hex, like the DAT files use.
+    pub sha1: String,
+    pub md5: Option<String>, // Only if they were asked for.
+    pub sha256: Option<String>,
+}
+
+// Hashes that can be made on top of CRC32 and SHA-1, which are always made.
+#[derive(Clone, Copy, PartialEq)]
+pub enum HashKind {
+    Md5,
+    Sha256,
+}
+
+#[derive(Clone)]
+enum Hasher {
+    Crc32(crc32fast::Hasher),
+    Sha1(sha1_smol::Sha1),
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    fn fill(&self, hashes: &mut Hashes) {
+        match self {
+            Hasher::Crc32(hasher) => hashes.crc32 = format!("{:08x}", hasher.clone().finalize()),
+            Hasher::Sha1(hasher) => hashes.sha1 = hasher.digest().to_string(),
+            Hasher::Md5(hasher) => hashes.md5 = Some(hasher.digest()),
+            Hasher::Sha256(hasher) => hashes.sha256 = Some(hasher.digest()),
+        }
+    }
+}
+
+enum WorkerMessage {
+    Data(Arc<Vec<u8>>),
+    Report(mpsc::Sender<Hasher>), // Send back the hash of everything so far.
+}
+
+// How much to collect before handing it to the hash threads. Disks are read a sector at a time, which would be a lot
+// of handing off.
+const BATCH_SIZE: usize = 1024 * 1024;
+
+// How many batches each hash thread can fall behind before the copy waits for it.
+const WORKER_BACKLOG: usize = 8;
+
+fn run_worker(mut hasher: Hasher, receiver: Receiver<WorkerMessage>) {
+    // Ends when the writer is dropped.
+    for message in receiver.iter() {
+        match message {
+            WorkerMessage::Data(data) => hasher.update(&data),
+            WorkerMessage::Report(reply) => {
+                let _ = reply.send(hasher.clone());
+            },
+        }
+    }
+}
+
+enum Hashers {
+    Inline(Vec<Hasher>),
+    Threaded(Vec<SyncSender<WorkerMessage>>, Vec<u8>), // One thread per hash, and what's waiting to go to them.
+}
+
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    size: u64,
+    hashers: Hashers,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn with_hashes(inner: W, extra: &[HashKind], threaded: bool) -> HashingWriter<W> {
+        let mut hashers = vec![Hasher::Crc32(crc32fast::Hasher::new()), Hasher::Sha1(sha1_smol::Sha1::new())];
+        hashers.extend(extra.iter().map(|kind| match kind {
+            HashKind::Md5 => Hasher::Md5(Md5::new()),
+            HashKind::Sha256 => Hasher::Sha256(Sha256::new()),
+        }));
+
+        let hashers = if threaded {
+            let senders = hashers.into_iter().map(|hasher| {
+                let (sender, receiver) = mpsc::sync_channel(WORKER_BACKLOG);
+                thread::spawn(move || run_worker(hasher, receiver));
+                sender
+            }).collect();
+
+            Hashers::Threaded(senders, Vec::with_capacity(BATCH_SIZE))
+        } else {
+            Hashers::Inline(hashers)
+        };
+
+        HashingWriter {
+            inner,
+            size: 0,
+            hashers,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    // Hands whatever's been collected to the hash threads.
+    fn send_batch(senders: &[SyncSender<WorkerMessage>], batch: &mut Vec<u8>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let data = Arc::new(std::mem::replace(batch, Vec::with_capacity(BATCH_SIZE)));
+        for sender in senders.iter() {
+            // The thread only goes away if it panicked, and then there's no hash to give anyway.
+            let _ = sender.send(WorkerMessage::Data(data.clone()));
+        }
+    }
+
+    // Hashes of everything written so far. Waits for the hash threads to catch up.
+    pub fn hashes(&mut self) -> Hashes {
+        let mut hashes = Hashes {
+            size: self.size,
+            ..Hashes::default()
+        };
+
+        match &mut self.hashers {
+            Hashers::Inline(hashers) => {
+                for hasher in hashers.iter() {
+                    hasher.fill(&mut hashes);
+                }
+            },
+            Hashers::Threaded(senders, batch) => {
+                HashingWriter::<W>::send_batch(senders, batch);
+
+                for sender in senders.iter() {
+                    let (reply, answer) = mpsc::channel();
+                    if sender.send(WorkerMessage::Report(reply)).is_ok() {
+                        if let Ok(hasher) = answer.recv() {
+                            hasher.fill(&mut hashes);
+                        }
+                    }
+                }
+            },
+        }
+
+        hashes
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+
+        self.size += written as u64;
+
+        match &mut self.hashers {
+            Hashers::Inline(hashers) => {
+                for hasher in hashers.iter_mut() {
+                    hasher.update(&buf[..written]);
+                }
+            },
+            Hashers::Threaded(senders, batch) => {
+                batch.extend_from_slice(&buf[..written]);
+
+                if batch.len() >= BATCH_SIZE {
+                    HashingWriter::<W>::send_batch(senders, batch);
+                }
+            },
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// For images we didn't write ourselves.
+pub fn hash_file(path: &str) -> io::Result<Hashes> {
+    hash_file_with(path, &[], false)
+}
+
+pub fn hash_file_with(path: &str, extra: &[HashKind], threaded: bool) -> io::Result<Hashes> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = HashingWriter::with_hashes(io::sink(), extra, threaded);
+
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(hasher.hashes())
+}
@@ -0,0 +1,101 @@
+
+// Finds the Mac side of Mac/PC hybrid disks. These have an HFS volume alongside the ISO 9660 one, usually behind an
+// Apple partition map, and the HFS volume often runs well past the end of the ISO 9660 one. isoinfo only knows about
+// the ISO 9660 side, so going by it alone cuts the Mac side off.
+// The layouts are in Inside Macintosh: Files, and Inside Macintosh: Devices.
+
+use std::fs;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+pub struct HfsInfo {
+    pub name: String,
+    pub length: usize, // Bytes from the start of the disk to the end of the HFS volume.
+}
+
+// Enough to cover the partition map on any disk I've seen.
+const HEADER_SIZE: usize = 64 * 1024;
+
+// Where the master directory block is, from the start of the volume.
+const MDB_OFFSET: usize = 1024;
+
+fn read_u16(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(((bytes[0] as usize) << 8) | bytes[1] as usize)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(((bytes[0] as usize) << 24) | ((bytes[1] as usize) << 16) | ((bytes[2] as usize) << 8) | bytes[3] as usize)
+}
+
+fn read_mdb(device: &mut fs::File, start: usize) -> Option<HfsInfo> {
+    let mut mdb = vec![0u8; 512];
+    device.seek(SeekFrom::Start((start + MDB_OFFSET) as u64)).ok()?;
+    device.read_exact(&mut mdb).ok()?;
+
+    // HFS+ disks are wrapped in an HFS volume with the same signature, so they turn up here too.
+    if mdb.get(0..2)? != b"BD" {
+        return None;
+    }
+
+    let allocation_blocks = read_u16(&mdb, 0x12)?;
+    let allocation_block_size = read_u32(&mdb, 0x14)?;
+    let first_allocation_block = read_u16(&mdb, 0x1C)? * 512;
+
+    // A Pascal string, in Mac Roman. Anything past ASCII gets replaced, since it's going in file names.
+    let name_length = (*mdb.get(0x24)? as usize).min(27);
+    let name: String = mdb.get(0x25..0x25 + name_length)?.iter()
+        .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '_' })
+        .collect();
+
+    // The alternate MDB sits in the second to last sector, after the last allocation block.
+    Some(HfsInfo {
+        name,
+        length: start + first_allocation_block + allocation_blocks * allocation_block_size + 1024,
+    })
+}
+
+// Looks for an HFS volume on the disk. Returns None for disks that don't have one, which is most of them.
+pub fn read_hfs_info(drive: &str) -> Option<HfsInfo> {
+    let mut device = fs::File::open(drive).ok()?;
+
+    let mut header = vec![0u8; HEADER_SIZE];
+    device.read_exact(&mut header).ok()?;
+
+    // No partition map, just a bare HFS volume at the start of the disk.
+    if header.get(0..2)? != b"ER" {
+        return read_mdb(&mut device, 0);
+    }
+
+    // The driver descriptor says how big a block is, but hybrid CD mastering tools don't agree on whether the map
+    // uses it or 512 byte blocks, so try both.
+    let block_size = read_u16(&header, 2)?.max(512);
+    let entry_size = if header.get(512..514)? == b"PM" { 512 } else { block_size };
+    let entry_count = read_u32(&header, entry_size + 4)?;
+
+    for index in 1..=entry_count {
+        let entry = match header.get(index * entry_size..(index + 1) * entry_size) {
+            Some(entry) if entry.starts_with(b"PM") => entry,
+            _ => break,
+        };
+
+        if !entry.get(48..57).map(|kind| kind == b"Apple_HFS").unwrap_or(false) {
+            continue;
+        }
+
+        let start = read_u32(entry, 8)?;
+        let blocks = read_u32(entry, 12)?;
+
+        for unit in [entry_size, block_size].iter() {
+            if let Some(mut info) = read_mdb(&mut device, start * unit) {
+                // Going by the partition too, in case the volume doesn't fill it.
+                info.length = info.length.max((start + blocks) * unit);
+                return Some(info);
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,212 @@
+
+// Shows every drive in Home Assistant through MQTT discovery: what it's doing, how far along it is, the last disk it
+// saved, and whether it needs someone to come swap the disk, for automations to flash a light over. Messages go out
+// with mosquitto_pub, so there's nothing to do if it isn't set up.
+
+use std::collections::HashMap;
+use std::fs;
+use std::fs::DirBuilder;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::drive_list::DriveList;
+use crate::drive_state::is_drive_finished;
+use crate::drive_state::DriveStatus;
+use crate::events;
+use crate::events::DriveEvent;
+use crate::get_drive_status_message_string;
+use crate::get_drive_title;
+use crate::DiskDrive;
+
+const TOPIC_PREFIX: &str = "auto_archive";
+
+// Progress changes too often to send every time, so it's sent this often instead.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+struct Broker {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    options: Option<PathBuf>, // Where mosquitto_pub finds the password, if there is one.
+}
+
+impl Broker {
+    // Retained messages are handed to anything that subscribes later, like Home Assistant after a restart.
+    fn publish(&self, topic: &str, payload: &str, retain: bool) {
+        let mut command = Command::new("mosquitto_pub");
+        command.args(["-h", &self.host, "-p", &self.port.to_string(), "-t", topic, "-m", payload]);
+
+        if let Some(username) = &self.username {
+            command.args(["-u", username]);
+        }
+        if let Some(options) = &self.options {
+            command.env("XDG_CONFIG_HOME", options);
+        }
+        if retain {
+            command.arg("-r");
+        }
+
+        // Losing a message isn't worth stopping a rip over.
+        let _ = command.stdout(Stdio::null()).stderr(Stdio::null()).status();
+    }
+}
+
+// Anything on the command line shows up in ps for everyone, so the password goes in an options file only we can
+// read instead. mosquitto_pub reads it from $XDG_CONFIG_HOME/mosquitto_pub by itself.
+fn write_options(password: &str) -> io::Result<PathBuf> {
+    let directory = std::env::temp_dir().join(format!("auto_archive-mqtt-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    DirBuilder::new().mode(0o700).create(&directory)?;
+
+    let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(directory.join("mosquitto_pub"))?;
+    writeln!(file, "-P {}", password)?;
+
+    Ok(directory)
+}
+
+// Topics can't have slashes in their names.
+fn get_object_id(drive: &DiskDrive) -> String {
+    drive.file.trim_start_matches('/').replace('/', "_")
+}
+
+fn get_topic(drive: &DiskDrive, entity: &str) -> String {
+    format!("{}/{}/{}", TOPIC_PREFIX, get_object_id(drive), entity)
+}
+
+// A disk's done one way or another and it's time to swap it, or someone has to name it.
+fn needs_attention(status: &DriveStatus) -> bool {
+    is_drive_finished(status) || matches!(status, DriveStatus::WaitingForName)
+}
+
+// Tells Home Assistant about the drive's entities. They all go under one device, named after the drive.
+fn announce_drive(broker: &Broker, prefix: &str, drive: &DiskDrive) {
+    let object_id = get_object_id(drive);
+
+    let mut device = json!({
+        "identifiers": [format!("auto_archive_{}", object_id)],
+        "name": get_drive_title(drive),
+    });
+
+    if let Some(identity) = &drive.identity {
+        device["manufacturer"] = json!(identity.vendor);
+        device["model"] = json!(identity.model);
+        device["sw_version"] = json!(identity.firmware);
+    }
+
+    let entities = [
+        ("sensor", "state", json!({ "name": "State" })),
+        ("sensor", "progress", json!({ "name": "Progress", "unit_of_measurement": "%" })),
+        ("sensor", "last_disk", json!({ "name": "Last disk" })),
+        ("binary_sensor", "attention", json!({ "name": "Needs attention", "device_class": "problem" })),
+    ];
+
+    for (component, entity, mut config) in entities {
+        config["unique_id"] = json!(format!("auto_archive_{}_{}", object_id, entity));
+        config["state_topic"] = json!(get_topic(drive, entity));
+        config["device"] = device.clone();
+
+        broker.publish(&format!("{}/{}/auto_archive_{}/{}/config", prefix, component, object_id, entity), &config.to_string(), true);
+    }
+}
+
+fn publish_status(broker: &Broker, drive: &DiskDrive, status: &DriveStatus) {
+    broker.publish(&get_topic(drive, "state"), &get_drive_status_message_string(status), true);
+    broker.publish(&get_topic(drive, "attention"), if needs_attention(status) { "ON" } else { "OFF" }, true);
+}
+
+pub fn spawn_home_assistant(config: &Config, drives: &Arc<DriveList>) -> io::Result<()> {
+    let host = match &config.mqtt_host {
+        Some(host) => host.clone(),
+        None => return Ok(()), // Not set up.
+    };
+
+    let options = match &config.mqtt_password {
+        Some(password) => Some(write_options(password)?),
+        None => None,
+    };
+
+    let broker = Arc::new(Broker {
+        host,
+        port: config.mqtt_port,
+        username: config.mqtt_username.clone(),
+        options,
+    });
+
+    let events = events::subscribe();
+    let prefix = config.mqtt_discovery_prefix.clone();
+    let (broker1, drives1) = (broker.clone(), drives.clone());
+
+    thread::spawn(move || {
+        for drive in drives1.get().iter() {
+            announce_drive(&broker1, &prefix, drive);
+            publish_status(&broker1, drive, &drive.state.get());
+        }
+
+        for event in events.iter() {
+            let drive = match drives1.find(&event.drive) {
+                Some(drive) => drive,
+                None => continue,
+            };
+
+            match &event.event {
+                DriveEvent::Status(status) => publish_status(&broker1, &drive, status),
+                DriveEvent::Archived(entry) => broker1.publish(&get_topic(&drive, "last_disk"), &entry.image, true),
+                // Announcing again is harmless, so drives that come back get announced again too.
+                DriveEvent::Plugged => {
+                    announce_drive(&broker1, &prefix, &drive);
+                    publish_status(&broker1, &drive, &drive.state.get());
+                },
+                _ => {},
+            }
+        }
+    });
+
+    let drives = drives.clone();
+
+    thread::spawn(move || {
+        let mut sent: HashMap<String, usize> = HashMap::new();
+
+        loop {
+            for drive in drives.get().iter() {
+                let percent = drive.progress.permille() / 10;
+
+                if sent.insert(drive.file.clone(), percent) != Some(percent) {
+                    broker.publish(&get_topic(drive, "progress"), &percent.to_string(), true);
+                }
+            }
+
+            thread::sleep(PROGRESS_INTERVAL);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn password_stays_private() {
+        let directory = write_options("hunter2").unwrap();
+        let file = directory.join("mosquitto_pub");
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "-P hunter2\n");
+        assert_eq!(fs::metadata(&file).unwrap().permissions().mode() & 0o777, 0o600);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+// Runs the operator's own Rhai script at points in a disk's life, so naming rules and workflow quirks don't need
+// changes here. The script is run inside auto_archive, so there's nothing else to install. It can have any of these
+// functions, each given what's known about the disk as an object map:
+//
+// detected(disk)  A disk was read and fingerprinted. Return a string to skip the disk, with it as the reason.
+// name(disk)      The disk needs a name. Return a string to suggest it instead of the default.
+// finished(disk)  The disk was archived, and `disk` is its catalog entry. What it returns is ignored.
+//
+// Functions that aren't there are skipped. The script's read again for every call, so changes to it take effect on the
+// next disk. Anything it prints goes in auto_archive.hooks.log, since the screen's busy.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use rhai::Dynamic;
+use rhai::Engine;
+use rhai::EvalAltResult;
+use rhai::Scope;
+use serde_json::Value;
+
+// A stuck script shouldn't hold the drive forever.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+const HOOK_LOG: &str = "auto_archive.hooks.log";
+
+pub enum HookError {
+    Load(String),   // Couldn't read or make sense of the script. Holds why.
+    TimedOut,
+    Failed(String), // The script threw an error or hit a bug. Holds what it was.
+}
+
+#[derive(Clone, Copy)]
+pub enum HookEvent {
+    Detected,
+    Name,
+    Finished,
+}
+
+impl HookEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            HookEvent::Detected => "detected",
+            HookEvent::Name => "name",
+            HookEvent::Finished => "finished",
+        }
+    }
+}
+
+// Losing something a script printed isn't worth stopping a rip over.
+fn log_output(event: HookEvent, text: &str) {
+    if let Ok(mut log) = OpenOptions::new().create(true).append(true).open(HOOK_LOG) {
+        let _ = writeln!(log, "{}: {}", event.name(), text);
+    }
+}
+
+fn make_engine(event: HookEvent) -> Engine {
+    let mut engine = Engine::new();
+
+    engine.on_print(move |text| log_output(event, text));
+    engine.on_debug(move |text, _, _| log_output(event, text));
+
+    let started = Instant::now();
+    engine.on_progress(move |_| if started.elapsed() > HOOK_TIMEOUT { Some(Dynamic::UNIT) } else { None });
+
+    engine
+}
+
+// Calls the script's function for an event, if it has one, and returns the string it returned, if it returned one.
+pub fn run_hook(script: &str, event: HookEvent, disk: &Value) -> Result<Option<String>, HookError> {
+    let engine = make_engine(event);
+    let ast = engine.compile_file(PathBuf::from(script)).map_err(|e| { HookError::Load(format!("{}", e)) })?;
+
+    if !ast.iter_functions().any(|function| function.name == event.name() && function.params.len() == 1) {
+        return Ok(None);
+    }
+
+    let disk = rhai::serde::to_dynamic(disk).map_err(|e| { HookError::Failed(format!("{}", e)) })?;
+
+    let result = engine.call_fn::<Dynamic>(&mut Scope::new(), &ast, event.name(), (disk,)).map_err(|error| {
+        match *error {
+            EvalAltResult::ErrorTerminated(..) => HookError::TimedOut,
+            error => HookError::Failed(format!("{}", error)),
+        }
+    })?;
+
+    Ok(result.into_string().ok().map(|text| String::from(text.trim())).filter(|text| !text.is_empty()))
+}
+
+pub fn get_hook_error_message(error: &HookError) -> String {
+    match error {
+        HookError::Load(message) => format!("Failed to load the hook script: {}", message),
+        HookError::TimedOut => format!("The hook script took longer than {} seconds and was stopped.", HOOK_TIMEOUT.as_secs()),
+        HookError::Failed(message) => format!("The hook script failed: {}", message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn run(name: &str, script: &str, event: HookEvent) -> Result<Option<String>, HookError> {
+        let path = std::env::temp_dir().join(format!("auto_archive-hook-{}-{}.rhai", name, std::process::id()));
+        fs::write(&path, script).unwrap();
+
+        let result = run_hook(path.to_str().unwrap(), event, &serde_json::json!({ "volume": "DATA", "size": 2048 }));
+        let _ = fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn name_gets_the_disk() {
+        let script = "fn name(disk) { disk.volume + \"-\" + disk.size }";
+        assert_eq!(run("name", script, HookEvent::Name).ok().flatten().as_deref(), Some("DATA-2048"));
+    }
+
+    #[test]
+    fn missing_functions_are_skipped() {
+        let script = "fn name(disk) { \"x\" }";
+        assert!(matches!(run("missing", script, HookEvent::Detected), Ok(None)));
+    }
+
+    #[test]
+    fn errors_come_back() {
+        assert!(matches!(run("throw", "fn detected(disk) { throw \"no\"; }", HookEvent::Detected), Err(HookError::Failed(_))));
+        assert!(matches!(run("broken", "fn detected(disk) {", HookEvent::Detected), Err(HookError::Load(_))));
+    }
+}
@@ -0,0 +1,228 @@
+
+// Disks part way through being archived, written down as they go, so there's something to go on after auto_archive
+// dies without a chance to clean up: a crash, a kill, or the power going out. Each disk comes out once it's done, and
+// the whole file goes on a clean exit, so anything in auto_archive.jobs at startup was cut short. Same format as the
+// drive settings, by the drive's device file.
+
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::os::unix::io::RawFd;
+use std::sync::Mutex;
+
+const JOBS_FILE: &str = "auto_archive.jobs";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Stage {
+    Ripping,
+    Naming, // All read, just waiting for a name.
+}
+
+#[derive(Clone)]
+pub struct Job {
+    pub drive: String,
+    pub fingerprint: String, // Blank if the disk couldn't be fingerprinted.
+    pub volume: String,
+    pub name: String,        // What it was going to be saved as, last anyone knew.
+    pub stage: Stage,
+    pub temp_fd: Option<RawFd>, // Of the image being written, so a crash can keep it as the disk's partial image.
+    pub length: u64,            // Of the whole disk.
+    pub damage: Vec<(u64, u64)>, // Start and length of everything zero filled so far, for the partial image's map.
+}
+
+static JOBS: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+
+// Disks to skip the next time they turn up, by fingerprint, since the operator said to forget about them.
+static DISCARDED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Takes the disk back out of the file when it's dropped, however the rip ended.
+pub struct JobRecord(String);
+
+impl Drop for JobRecord {
+    fn drop(&mut self) {
+        let mut jobs = JOBS.lock().unwrap();
+        jobs.retain(|job| job.drive != self.0);
+        write_jobs(&jobs);
+    }
+}
+
+fn build_jobs(jobs: &[Job]) -> String {
+    let mut output = String::new();
+
+    for job in jobs.iter() {
+        let _ = writeln!(output, "[{}]", job.drive);
+        let _ = writeln!(output, "fingerprint = {}", job.fingerprint);
+        let _ = writeln!(output, "volume = {}", job.volume);
+        let _ = writeln!(output, "name = {}", job.name);
+        let _ = writeln!(output, "stage = {}", if job.stage == Stage::Naming { "naming" } else { "ripping" });
+        let _ = writeln!(output);
+    }
+
+    output
+}
+
+// Losing track of a job isn't worth stopping a rip over. It just won't be asked about after a crash.
+fn write_jobs(jobs: &[Job]) {
+    let output = build_jobs(jobs);
+
+    let temp_file = format!("{}.tmp", JOBS_FILE);
+    if fs::write(&temp_file, output).is_ok() {
+        let _ = fs::rename(&temp_file, JOBS_FILE);
+    }
+}
+
+pub fn start(job: Job) -> JobRecord {
+    let drive = job.drive.clone();
+
+    let mut jobs = JOBS.lock().unwrap();
+    jobs.retain(|existing| existing.drive != drive);
+    jobs.push(job);
+    write_jobs(&jobs);
+
+    JobRecord(drive)
+}
+
+fn update<F>(drive: &str, change: F) where F: FnOnce(&mut Job) {
+    let mut jobs = JOBS.lock().unwrap();
+
+    if let Some(job) = jobs.iter_mut().find(|job| job.drive == drive) {
+        change(job);
+        write_jobs(&jobs);
+    }
+}
+
+pub fn set_stage(drive: &str, stage: Stage, name: &str) {
+    update(drive, |job| {
+        job.stage = stage;
+        job.name = String::from(name);
+    });
+}
+
+pub fn set_temp_fd(drive: &str, temp_fd: Option<RawFd>) {
+    update(drive, |job| job.temp_fd = temp_fd);
+}
+
+// Only the crash handler needs these, and only while the process is still up, so they don't go in the file.
+pub fn add_bad(drive: &str, start: u64, length: u64) {
+    if let Some(job) = JOBS.lock().unwrap().iter_mut().find(|job| job.drive == drive) {
+        job.damage.push((start, length));
+    }
+}
+
+pub fn find(drive: &str) -> Option<Job> {
+    JOBS.lock().unwrap().iter().find(|job| job.drive == drive).cloned()
+}
+
+// For the crash handler. Gives up instead of waiting, since the thread that panicked might have the list.
+pub fn try_get() -> Option<Vec<Job>> {
+    JOBS.try_lock().ok().map(|jobs| jobs.clone())
+}
+
+// Whatever was cut short last time. Takes it out of the file, so it's only asked about once.
+pub fn take_unfinished() -> Vec<Job> {
+    let text = match fs::read_to_string(JOBS_FILE) {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+    let _ = fs::remove_file(JOBS_FILE);
+
+    parse_jobs(&text)
+}
+
+fn parse_jobs(text: &str) -> Vec<Job> {
+    let mut jobs: Vec<Job> = Vec::new();
+
+    for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        if let Some(drive) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            jobs.push(Job {
+                drive: String::from(drive),
+                fingerprint: String::new(),
+                volume: String::new(),
+                name: String::new(),
+                stage: Stage::Ripping,
+                temp_fd: None,
+                length: 0,
+                damage: Vec::new(),
+            });
+            continue;
+        }
+
+        if let (Some(job), Some((key, value))) = (jobs.last_mut(), line.split_once('=')) {
+            let value = String::from(value.trim());
+
+            match key.trim() {
+                "fingerprint" => job.fingerprint = value,
+                "volume" => job.volume = value,
+                "name" => job.name = value,
+                "stage" => job.stage = if value == "naming" { Stage::Naming } else { Stage::Ripping },
+                _ => {},
+            }
+        }
+    }
+
+    jobs
+}
+
+// A clean exit. Whatever was still going was given up on knowingly.
+pub fn clear() {
+    let _ = fs::remove_file(JOBS_FILE);
+}
+
+pub fn discard(fingerprint: &str) {
+    DISCARDED.lock().unwrap().push(String::from(fingerprint));
+}
+
+// Whether to skip this disk. Only once, so putting it back in later rips it after all.
+pub fn take_discarded(fingerprint: &str) -> bool {
+    let mut discarded = DISCARDED.lock().unwrap();
+
+    match discarded.iter().position(|existing| existing == fingerprint) {
+        Some(index) => {
+            discarded.remove(index);
+            true
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(drive: &str, stage: Stage) -> Job {
+        Job {
+            drive: String::from(drive),
+            fingerprint: format!("{}-fingerprint", drive),
+            volume: String::from("DATA"),
+            name: String::from("DATA.iso"),
+            stage,
+            temp_fd: Some(5),
+            length: 2048,
+            damage: vec![(0, 2048)],
+        }
+    }
+
+    #[test]
+    fn unfinished_jobs_read_back() {
+        let jobs = parse_jobs(&build_jobs(&[job("/dev/sr0", Stage::Ripping), job("/dev/sr1", Stage::Naming)]));
+
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].drive, "/dev/sr0");
+        assert_eq!(jobs[0].fingerprint, "/dev/sr0-fingerprint");
+        assert!(jobs[0].stage == Stage::Ripping);
+        assert_eq!(jobs[1].volume, "DATA");
+        assert_eq!(jobs[1].name, "DATA.iso");
+        assert!(jobs[1].stage == Stage::Naming);
+
+        // Those only mean anything in the process that had them.
+        assert_eq!(jobs[1].temp_fd, None);
+        assert!(jobs[1].damage.is_empty());
+    }
+
+    #[test]
+    fn junk_is_skipped() {
+        let jobs = parse_jobs("name = before any drive\n[/dev/sr0]\nno equals sign\nsomething_new = 1\nvolume = DATA\n");
+
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].volume, "DATA");
+    }
+}
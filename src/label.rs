@@ -0,0 +1,86 @@
+
+// Labels for disks going back into storage, with a QR code of their catalog ID so a disk in a box can be tied back to
+// its image. Either a text file that can be printed as is, or a PNG of the QR code for a label printer.
+
+use png::ColorType;
+use png::Encoder;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::types::Color;
+use qrcode::QrCode;
+use std::fs;
+use std::io::BufWriter;
+
+use crate::catalog;
+use crate::catalog::CatalogEntry;
+
+// Size of a QR code module in the PNG, and how many modules of blank space go around it.
+const PNG_MODULE_SIZE: usize = 8;
+const PNG_QUIET_ZONE: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LabelFormat {
+    None,
+    Text,
+    Png,
+}
+
+fn get_qr_content(entry: &CatalogEntry) -> String {
+    format!("auto_archive:{}", entry.id)
+}
+
+fn write_text_label(entry: &CatalogEntry, code: &QrCode) -> Result<String, String> {
+    let path = format!("{}.label.txt", entry.image);
+
+    let label = format!("Catalog #{}\nVolume: {}\nImage: {}\nArchived: {} UTC\nSHA-1: {}\n\n{}\n",
+        entry.id, entry.volume, entry.image, catalog::format_date(entry.date), entry.sha1,
+        code.render::<Dense1x2>().build());
+
+    fs::write(&path, label).map_err(|e| { format!("{}", e) })?;
+
+    Ok(path)
+}
+
+fn write_png_label(entry: &CatalogEntry, code: &QrCode) -> Result<String, String> {
+    let path = format!("{}.label.png", entry.image);
+
+    let modules = code.width();
+    let size = (modules + PNG_QUIET_ZONE * 2) * PNG_MODULE_SIZE;
+    let colors = code.to_colors();
+
+    // Grayscale, one byte a pixel. Start all white and fill in the dark modules.
+    let mut pixels = vec![0xFFu8; size * size];
+    for y in 0..size {
+        for x in 0..size {
+            let module_x = (x / PNG_MODULE_SIZE).wrapping_sub(PNG_QUIET_ZONE);
+            let module_y = (y / PNG_MODULE_SIZE).wrapping_sub(PNG_QUIET_ZONE);
+
+            if module_x < modules && module_y < modules && colors[module_y * modules + module_x] == Color::Dark {
+                pixels[y * size + x] = 0;
+            }
+        }
+    }
+
+    let file = fs::File::create(&path).map_err(|e| { format!("{}", e) })?;
+    let mut encoder = Encoder::new(BufWriter::new(file), size as u32, size as u32);
+    encoder.set_color(ColorType::Grayscale);
+
+    let mut writer = encoder.write_header().map_err(|e| { format!("{}", e) })?;
+    writer.write_image_data(&pixels).map_err(|e| { format!("{}", e) })?;
+
+    Ok(path)
+}
+
+// Writes the label next to the image, and returns where it went.
+pub fn write_label(entry: &CatalogEntry, format: LabelFormat) -> Result<Option<String>, String> {
+    if format == LabelFormat::None {
+        return Ok(None);
+    }
+
+    let code = QrCode::new(get_qr_content(entry)).map_err(|e| { format!("{}", e) })?;
+
+    match format {
+        LabelFormat::Text => write_text_label(entry, &code).map(Some),
+        LabelFormat::Png => write_png_label(entry, &code).map(Some),
+        LabelFormat::None => Ok(None),
+    }
+}
@@ -0,0 +1,108 @@
+
+// The drive panels as they were left, so a session that got cut short picks up where it was: which panels were
+// collapsed, which drive had the focus, and names typed in for disks that hadn't been saved yet. Kept in
+// auto_archive.layout, same format as the drive settings, by the drive's serial if it has one. Typed in names only
+// come back for the same disk, by its fingerprint, since it gets ripped again from the start.
+
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+
+const LAYOUT_FILE: &str = "auto_archive.layout";
+
+#[derive(Clone, Default)]
+pub struct PanelLayout {
+    pub collapsed: bool,
+    pub focused: bool,
+    pub pending: Option<(String, String)>, // Fingerprint of the disk that was waiting, and the name typed in for it.
+}
+
+// Whichever drive's panel last had something happen in it.
+static FOCUSED: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn set_focused(key: &str) {
+    let mut focused = FOCUSED.lock().unwrap();
+
+    if focused.as_deref() != Some(key) {
+        *focused = Some(String::from(key));
+    }
+}
+
+pub fn get_focused() -> Option<String> {
+    FOCUSED.lock().unwrap().clone()
+}
+
+fn read_all() -> io::Result<Vec<(String, PanelLayout)>> {
+    let text = match fs::read_to_string(LAYOUT_FILE) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let mut panels: Vec<(String, PanelLayout)> = Vec::new();
+
+    for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        if let Some(key) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            panels.push((String::from(key), PanelLayout::default()));
+            continue;
+        }
+
+        if let (Some((_, layout)), Some((key, value))) = (panels.last_mut(), line.split_once('=')) {
+            let value = value.trim();
+
+            match key.trim() {
+                "collapsed" => layout.collapsed = value == "yes",
+                "focused" => layout.focused = value == "yes",
+                "pending_fingerprint" => layout.pending.get_or_insert_with(Default::default).0 = String::from(value),
+                "pending_name" => layout.pending.get_or_insert_with(Default::default).1 = String::from(value),
+                _ => {},
+            }
+        }
+    }
+
+    Ok(panels)
+}
+
+pub fn load(key: &str) -> PanelLayout {
+    // Same as the drive settings, a broken file just means nothing's remembered.
+    read_all().unwrap_or_default().into_iter()
+        .find(|(existing, _)| existing == key)
+        .map(|(_, layout)| layout)
+        .unwrap_or_default()
+}
+
+// The name typed in for this disk last time, if it was waiting for one when auto_archive was closed.
+pub fn find_pending_name(key: &str, fingerprint: &str) -> Option<String> {
+    load(key).pending.filter(|(pending, _)| pending == fingerprint).map(|(_, name)| name)
+}
+
+// Drives that weren't plugged in this time keep what they had.
+pub fn save(layouts: &[(String, PanelLayout)]) -> io::Result<()> {
+    let mut panels = read_all()?;
+
+    for (key, layout) in layouts.iter() {
+        match panels.iter_mut().find(|(existing, _)| existing == key) {
+            Some((_, existing)) => *existing = layout.clone(),
+            None => panels.push((key.clone(), layout.clone())),
+        }
+    }
+
+    let mut output = String::new();
+    for (key, layout) in panels.iter() {
+        let _ = writeln!(output, "[{}]", key);
+        let _ = writeln!(output, "collapsed = {}", if layout.collapsed { "yes" } else { "no" });
+        let _ = writeln!(output, "focused = {}", if layout.focused { "yes" } else { "no" });
+
+        if let Some((fingerprint, name)) = &layout.pending {
+            let _ = writeln!(output, "pending_fingerprint = {}", fingerprint);
+            let _ = writeln!(output, "pending_name = {}", name);
+        }
+
+        let _ = writeln!(output);
+    }
+
+    let temp_file = format!("{}.tmp", LAYOUT_FILE);
+    fs::write(&temp_file, output)?;
+    fs::rename(&temp_file, LAYOUT_FILE)
+}
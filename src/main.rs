@@ -16,6 +16,7 @@ use std::io;
 use std::io::Read;
 use std::io::Write;
 use cursive::Cursive;
+use cursive::CbSink;
 use cursive::views::TextView;
 use cursive::views::Dialog;
 use cursive::align::HAlign;
@@ -27,20 +28,133 @@ use cursive::views::Checkbox;
 use cursive::views::ListView;
 use cursive::views::EditView;
 use cursive::views::Button;
+use cursive::views::SelectView;
+use cursive::views::HideableView;
+use cursive::views::IdView;
+use cursive::views::OnEventView;
 use std::thread;
 use std::time::Duration;
-use cursive::utils::Counter;
+use std::time::Instant;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
 use cursive::event::Event;
+use cursive::event::EventTrigger;
 use std::sync::Mutex;
 use std::path::Path;
 use std::io::Seek;
 use std::io::SeekFrom;
+use std::os::unix::fs::FileExt;
+use tokio::io::AsyncSeekExt;
 
 extern crate tempfile_fast;
 
+mod activity;
+mod api;
+mod archive_org;
+mod audio;
+mod auth;
+mod backoff;
+mod backup;
+mod benchmark;
+mod buffer_pool;
+mod burn;
+mod catalog;
+mod cdrom;
+mod changer;
+mod chat_bot;
+mod cli;
+mod chd;
+mod clonecd;
+mod compare;
+mod config;
+mod crash;
+mod dedup;
+mod digest;
+mod drive_list;
+mod drive_settings;
+mod drive_state;
+mod dry_run;
+mod dvd;
+mod events;
+mod export;
+mod file_index;
+mod fingerprint;
+mod gnudb;
+mod grpc;
+mod floppy;
+mod hash;
+mod hfs;
+mod home_assistant;
+mod hooks;
+mod jobs;
+mod label;
+mod layout;
+mod mount_check;
+mod mapfile;
+mod metadata;
+mod musicbrainz;
+mod naming_map;
+mod nfo;
+mod par2;
+mod persist;
+mod photos;
+mod priority;
+mod progress;
+mod profile;
+mod provenance;
+mod queue;
+mod redump;
+mod remote;
+mod resume;
+mod role;
+mod sense_log;
+mod session;
+mod sidecar;
+mod sink;
+mod sparse;
+mod staging;
+mod stations;
+mod tape;
+mod turns;
+mod udf;
+#[cfg(feature = "webcam")]
+mod webcam;
+
+use activity::ActivityLog;
+use audio::DiscMetadata;
+use backoff::Backoff;
+use burn::BlankMode;
+use drive_list::DriveList;
+use drive_state::DriveStateMachine;
+use drive_state::DriveStatus;
+use drive_state::IllegalTransition;
+use catalog::Catalog;
+use catalog::CatalogEntry;
+use cdrom::TocEntry;
+use cdrom::TrayStatus;
+use changer::Changer;
+use config::BlockDevices;
+use config::Config;
+use config::LiveConfig;
+use config::OutputFormat;
+use hash::HashingWriter;
+use mapfile::DamageMap;
+use events::DriveEvent;
+use hooks::HookError;
+use hooks::HookEvent;
+use profile::RipProfile;
+use progress::Progress;
+use progress::ProgressUnit;
+use queue::QueueEntry;
+use queue::Stage;
+use remote::RemoteDrive;
+use role::Role;
+use stations::Station;
+use tape::TapeSpooler;
+use turns::Turn;
+use turns::TurnQueue;
+
 // Depends on the following being installed;
 //  libdvdcss - driver to decode DVDs
 //  lsscsi    - to discover disk drives.
@@ -49,51 +163,165 @@ extern crate tempfile_fast;
 
 // If you keep getting IO errors, you may need to set your computer's DVD region.
 
+// Read size when imaging block devices. They don't have a block size that matters like CDs do, so read in big chunks.
+const BLOCK_DEVICE_BUFFER_SIZE: usize = 1024 * 1024;
+
+// Read size when comparing a disk to an image. A multiple of every block size we'll see.
+const VERIFY_BUFFER_SIZE: usize = 64 * 1024;
+
+enum EjectError {
+    LaunchFail,       // Failed to launch eject.
+    Busy,             // Something has the device open.
+    Mounted,          // The disk is mounted and we couldn't unmount it.
+    NoMedium,         // Nothing in the drive to eject.
+    Hardware(String), // The drive refused for some other reason. Holds what eject had to say about it.
+}
+
 enum DiskInfoError {
     LaunchFail,   // Failed to launch application. No permission, out of memory, not installed, something else?
     ConvertToUTF, // Application output was not valid UTF8.
     Parse,        // Failed to parse the output of the application.
+    DeviceSize(String), // Couldn't tell how big the device is. Holds why.
 }
 
-#[derive(Clone)]
-enum DriveStatus {
-    Setup,
-    NoDisk,
-    Copying,
-    WaitingForName,
-    ConfirmingName,
-    Saving(String),
-    Done,
-
-    CopyWriteError(String),
-    CopyReadError(String),
-    NonFatalCopyWriteError(String),
-    NonFatalCopyReadError(String),
-    IsoFetchError,
+#[derive(Clone, PartialEq)]
+enum DriveMode {
+    Rip,
+    Burn(String), // Path of the image to write to every blank disk put in.
+    Erase(BlankMode),
+    Photos(bool), // Import photos into the library. True to archive the disk as usual too.
+    Verify(String), // Path of an image to compare every disk put in against.
+    Benchmark,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DriveKind {
+    Optical,
+    Block, // Hard drives, USB sticks, flash cards, ZIP disks. Anything that isn't a CD/DVD drive.
+    Floppy,
 }
 
 struct DiskDrive {
     file: String,
+    panel: String, // Names its views in the UI. Its file, unless another drive was plugged in there before it.
+    kind: DriveKind,
     has_disk: AtomicBool,
-    status_message: Mutex<DriveStatus>,
+    cancel: AtomicBool, // Set by the UI to abort the rip in progress.
+    auto_name: AtomicBool, // Save under the default name without asking, unless that would overwrite something.
+    paused: AtomicBool, // Finish the disk in the drive, but don't start on another.
+    tray_status: Mutex<TrayStatus>,
+    mode: Mutex<DriveMode>,
+    state: DriveStateMachine,
+    fingerprint: Mutex<Option<String>>, // Of the disk currently being ripped.
+    duplicate_of: Mutex<Option<String>>, // Image in the catalog with the same fingerprint, if there is one.
+    media: Mutex<Option<String>>, // Type and size of the disk in an optical drive.
+    capabilities: Option<cdrom::Capabilities>, // Probed at startup. None if the drive wouldn't say, or isn't optical.
+    identity: Option<cdrom::DriveIdentity>,
+    settings_key: Option<String>, // Its serial, or /dev/disk/by-id name if it doesn't have one. None if it has neither.
+    model: Option<String>, // What lsscsi calls it. For drives that won't answer an inquiry themselves.
+    name: Mutex<Option<String>>, // What the operator calls it, from its saved settings.
+    benchmark: Mutex<Option<benchmark::Profile>>, // How fast it was the last time it was benchmarked.
+    profile: Mutex<Option<RipProfile>>, // Used instead of the config's profile, if it has one.
+    suggested_name: Mutex<Option<String>>, // What the disk waiting for a name gets called if nobody says otherwise.
+    overwrite: AtomicBool, // The operator said to replace the file the disk's being saved as.
+    barcode: Mutex<Option<String>>, // Scanned off the box of the next disk to be archived. Goes with it into the catalog.
+    progress: Progress, // Of whatever it's doing right now.
+    activity: ActivityLog,
+    remote: Option<RemoteDrive>, // Where it really is, if it's on another machine.
+    released: AtomicBool, // A remote drive that was let go of to swap the disk. Not picked back up until it's closed.
 }
 
 #[derive(Clone)]
 struct ISOInfo {
     name: String,
     block_size: usize,
-    length: usize,
+    length: u64, // In bytes. Blu-rays are bigger than 32 bits can count.
+    details: VolumeDetails,
+}
+
+// Who made the disk, and when, from its primary volume descriptor. Usually blank on disks burned at home.
+#[derive(Clone, Default)]
+struct VolumeDetails {
+    publisher: Option<String>,
+    preparer: Option<String>,    // Data preparer.
+    application: Option<String>, // Whatever mastered it.
+    created: Option<String>,     // YYYY-MM-DD HH:MM:SS, in whatever time zone it was made in.
+}
+
+impl VolumeDetails {
+    fn to_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        let fields = [("publisher", &self.publisher), ("preparer", &self.preparer), ("application", &self.application), ("created", &self.created)];
+
+        fields.iter()
+            .filter_map(|(key, value)| value.as_ref().map(|value| (String::from(*key), serde_json::Value::String(value.clone()))))
+            .collect()
+    }
+}
+
+enum VerifyError {
+    Image(String),  // Couldn't read the image file.
+    Read(String),   // Couldn't read the disk.
+    ShortDisk(u64), // Disk ended before the image did. Holds how much we got.
+    Mismatch(u64),  // Offset of the first byte that differs.
 }
 
 enum CopyError {
     Read(String),
     Write(String),
+    Disconnected(u64, DamageMap), // The drive went away. Holds how far it got, and what was bad before that.
+    Cancelled,
     None
 }
 
 pub type ParserResult<'a, O> = IResult<&'a str, O, VerboseError<&'a str>>;
 
-fn parse_disk_drive_list(input: &str) -> ParserResult<Vec<Arc<DiskDrive>>> {
+fn new_disk_drive(file: String, kind: DriveKind) -> DiskDrive {
+    let capabilities = if kind == DriveKind::Optical { cdrom::probe_capabilities(&file) } else { None };
+    let identity = cdrom::inquire(&file);
+    let settings_key = drive_settings::get_settings_key(&file, identity.as_ref().and_then(|identity| identity.serial.as_deref()));
+    let settings = settings_key.as_deref().map(drive_settings::load).unwrap_or_default();
+
+    let state = DriveStateMachine::new(&file);
+
+    DiskDrive {
+        panel: file.clone(),
+        file,
+        kind,
+        has_disk: AtomicBool::new(false),
+        cancel: AtomicBool::new(false),
+        auto_name: AtomicBool::new(settings.auto_name),
+        paused: AtomicBool::new(false),
+        tray_status: Mutex::new(TrayStatus::Unknown),
+        mode: Mutex::new(DriveMode::Rip),
+        state,
+        fingerprint: Mutex::new(None),
+        duplicate_of: Mutex::new(None),
+        media: Mutex::new(None),
+        capabilities,
+        identity,
+        settings_key,
+        model: None,
+        name: Mutex::new(settings.name),
+        benchmark: Mutex::new(settings.benchmark),
+        profile: Mutex::new(settings.profile),
+        suggested_name: Mutex::new(None),
+        overwrite: AtomicBool::new(false),
+        barcode: Mutex::new(None),
+        progress: Progress::default(),
+        activity: ActivityLog::default(),
+        remote: None,
+        released: AtomicBool::new(false),
+    }
+}
+
+// A drive on this machine, as lsscsi sees it.
+struct FoundDrive {
+    file: String,
+    kind: DriveKind,
+    model: Option<String>, // Vendor, model, and revision.
+}
+
+fn parse_disk_drive_list(input: &str) -> ParserResult<'_, Vec<FoundDrive>> {
     let (input, lines) = many0(
             terminated(take_until("\n"), char_tag('\n'))
     )(input)?;
@@ -108,25 +336,31 @@ fn parse_disk_drive_list(input: &str) -> ParserResult<Vec<Arc<DiskDrive>>> {
             take_until("/")
         ))(line);
 
-        match result {
-            Ok(result) => {
-                let (name, result) = result;
-                let (_, _, drive_type, _) = result;
+        // Ignore invalid lines.
+        if let Ok(result) = result {
+            let (name, result) = result;
+            let (_, _, drive_type, model) = result;
 
-                if drive_type == "cd/dvd" {
-                    let len = name.len();
+            let mut file = String::from(name);
+            file.remove(file.len() - 1);
 
-                    let mut drive = DiskDrive {
-                        file: String::from(name),
-                        has_disk: AtomicBool::new(false),
-                        status_message: Mutex::new(DriveStatus::Setup),
-                    };
-                    drive.file.remove(len - 1);
+            let kind = match drive_type {
+                "cd/dvd" => Some(DriveKind::Optical),
+                "disk" if floppy::is_usb_floppy(&file) => Some(DriveKind::Floppy),
+                "disk" => Some(DriveKind::Block),
+                _ => None,
+            };
 
-                    drives.push(Arc::new(drive));
-                }
-            },
-            _ => {} // Ignore invalid lines.
+            // lsscsi pads the fields out into columns.
+            let model = model.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+            if let Some(kind) = kind {
+                drives.push(FoundDrive {
+                    file,
+                    kind,
+                    model: Some(model).filter(|model| !model.is_empty()),
+                });
+            }
         }
     }
 
@@ -137,31 +371,205 @@ fn get_drive_status_message_string(status: &DriveStatus) -> String {
     let message = match status {
         DriveStatus::Setup => String::from("Setting up..."),
         DriveStatus::NoDisk => String::from("No Disk."),
+        DriveStatus::Fingerprinting => String::from("Fingerprinting..."),
+        DriveStatus::ReadingMetadata => String::from("Looking up track names..."),
         DriveStatus::Copying => String::from("Copying..."),
+        DriveStatus::RippingTrack(0, _) => String::from("Ripping the hidden track before track 1..."),
+        DriveStatus::RippingTrack(track, count) => format!("Ripping track {} of {}...", track, count),
         DriveStatus::WaitingForName | DriveStatus::ConfirmingName => String::from("Check the \"Settings ready\" box to finish."),
         DriveStatus::Saving(_) => String::from("Saving..."),
+        DriveStatus::Converting(name) => format!("Compressing {} to CHD...", name),
+        DriveStatus::GeneratingParity(name) => format!("Generating PAR2 parity for {}...", name),
+        DriveStatus::Storing(name) => format!("Adding {} to the dedup store...", name),
+        DriveStatus::BackingUp(name) => format!("Sending {} to the backup repository...", name),
+        DriveStatus::ImportingPhotos(0, 0) => String::from("Reading photos off the disk..."),
+        DriveStatus::ImportingPhotos(done, total) => format!("Importing photos... {} of {}", done, total),
         DriveStatus::Done => String::from("Done."),
+        DriveStatus::DoneVerified(game) => format!("Done. Verified known-good dump of {}.", game),
+        DriveStatus::DoneShort(actual, expected) => format!("Done, but the disk ended after {} bytes, short of the {} its volume says it has. The image is short.", actual, expected),
+        DriveStatus::DoneUnmountable(message) => format!("Done, but the image won't mount. {} Try cleaning the disk or another drive.", message),
+        DriveStatus::DoneMismatch(message) => format!("Done, but reading the disk again didn't match the image. {} Don't trust this drive with it.", message),
+        DriveStatus::DoneWithoutMirror(message) => format!("Done, but only one copy was saved. {}", message),
+        DriveStatus::DoneWithDamage(bytes) => format!("Done, but incomplete. {} unreadable bytes were zero filled. See the .json and .map files next to the image.", bytes),
+        DriveStatus::DonePhotos(added, duplicates) => format!("Done. Added {} photos to the library, skipped {} already in it.", added, duplicates),
+        DriveStatus::Cancelled => String::from("Rip cancelled. Disk was ejected before it finished."),
+        DriveStatus::WaitingForFloppy => String::from("Insert a floppy and press \"Read disk\"."),
+        DriveStatus::Paused => String::from("Paused. Won't start on another disk until it's resumed."),
+        DriveStatus::AlreadyArchived(image) => format!("Skipped. This disk is already archived as {}.", image),
+        DriveStatus::SkippedByHook(reason) => format!("Skipped by the hook script: {}", reason),
+        DriveStatus::Discarded => String::from("Skipped. It was discarded after the crash. Put it back in to rip it after all."),
+        DriveStatus::ThreadStopped | DriveStatus::NeedsRestart => String::from("Needs restart. Something went wrong and this drive stopped. See the crash report."),
+        DriveStatus::Rehearsed(image, 0) => format!("Dry run. Would have saved {}.", image),
+        DriveStatus::Rehearsed(image, problems) => format!("Dry run. Saving {} would have had {} problem(s). See auto_archive.dry_run.", image, problems),
+        DriveStatus::WaitingForTurn(1) => String::from("Waiting for 1 drive ahead of this one to finish."),
+        DriveStatus::WaitingForTurn(ahead) => format!("Waiting for {} drives ahead of this one to finish.", ahead),
+        DriveStatus::WaitingForSpace(short) => format!("Waiting for other rips to finish. Needs {} more room than there is.", format_size(*short)),
+
+        DriveStatus::WaitingForBlank(image) => format!("Insert a blank disk to burn {}.", image),
+        DriveStatus::Burning(image) => format!("Burning {}...", image),
+        DriveStatus::Verifying(image) => format!("Verifying burn against {}...", image),
+        DriveStatus::BurnVerified => String::from("Burn verified. Disk matches the image."),
+
+        DriveStatus::OfferingPatch(image) | DriveStatus::ConfirmingPatch(image) => format!("This disk was ripped before as {}, with damage. Re-read just the bad parts?", image),
+        DriveStatus::Patching(image) => format!("Re-reading the bad parts of {}...", image),
+        DriveStatus::Patched(recovered, 0) => format!("Patched. Recovered all {} bytes. The image is complete.", recovered),
+        DriveStatus::Patched(recovered, remaining) => format!("Patched. Recovered {} bytes, {} are still unreadable. Try another drive.", recovered, remaining),
+        DriveStatus::WaitingForDiskToVerify(image) => format!("Insert the disk {} was made from.", image),
+        DriveStatus::VerifyingImage(image) => format!("Verifying disk against {}...", image),
+        DriveStatus::WaitingForDiskToBenchmark => String::from("Insert a disk to benchmark the drive with. A full one gives the best results."),
+        DriveStatus::Benchmarking => String::from("Benchmarking..."),
+        DriveStatus::Benchmarked(summary) => format!("Benchmark done. {}", summary),
+        DriveStatus::ImageVerified(image) => format!("Verified. Disk matches {}.", image),
+
+        DriveStatus::WaitingForRewritable(mode) => format!("Insert a rewritable disk to erase ({}).", mode.name()),
+        DriveStatus::Erasing(mode, elapsed) => format!("Erasing ({})... {}:{:02}", mode.name(), elapsed.as_secs() / 60, elapsed.as_secs() % 60),
+        DriveStatus::Erased => String::from("Erase finished."),
 
         DriveStatus::CopyReadError(message) => format!("Error reading disk: {}", message),
+        DriveStatus::Disconnected(Some(partial)) => format!("Disconnected. What was read so far is in {}, and the rip picks up from there when the disk's put back in.", partial),
+        DriveStatus::Disconnected(None) => String::from("Disconnected."),
         DriveStatus::CopyWriteError(message) => format!("Error writing to output file: {}", message),
         DriveStatus::NonFatalCopyWriteError(message) => format!("Non fatal error reading disk: {}", message),
         DriveStatus::NonFatalCopyReadError(message) => format!("Non fatal error writing to output file: {}", message),
         DriveStatus::IsoFetchError => String::from("Failed to fetch ISO data from disk drive. Is the isoinfo command installed?"),
+        DriveStatus::TocReadError => String::from("Failed to read the disk's table of contents."),
+        DriveStatus::BlankDisk => String::from("This disk is blank. Nothing to archive."),
+        DriveStatus::UnrecognizedFileSystem => String::from("The disk reads fine, but it has no file system we know. Try output_format = clonecd."),
+        DriveStatus::DamagedDisk(message) => format!("The disk is damaged or dirty. Clean it or try another drive. {}", message),
+        DriveStatus::Unsupported(message) => String::from(*message),
+        DriveStatus::DeviceSizeError(message) => message.clone(),
+        DriveStatus::BurnError(message) => message.clone(),
+        DriveStatus::ChdError(message) => format!("Saved the ISO, but couldn't make a CHD from it. {}", message),
+        DriveStatus::ParityError(message) => format!("Saved the image, but couldn't make parity for it. {}", message),
+        DriveStatus::CatalogError(message) => format!("Saved the image, but couldn't add it to the catalog. {}", message),
+        DriveStatus::LabelError(message) => format!("Saved the image, but couldn't make a label for it. {}", message),
+        DriveStatus::ProvenanceError(message) => format!("Saved the image, but couldn't write where it came from into it. {}", message),
+        DriveStatus::StoreError(message) => format!("Saved the image, but couldn't add it to the dedup store. {}", message),
+        DriveStatus::BackupError(message) => format!("Saved the image, but couldn't back it up. {}", message),
+        DriveStatus::PhotoError(message) => message.clone(),
+        DriveStatus::AudioError(message) => message.clone(),
+        DriveStatus::DvdError(message) => format!("Saved the image, but couldn't list its titles and chapters. {}", message),
+        DriveStatus::NfoError(message) => format!("Saved the image, but couldn't write its .nfo file. {}", message),
+        DriveStatus::NotEnoughSpace(needed, free) => format!("Not enough room for the image. It needs {}, and there's {} free.", format_size(*needed), format_size(*free)),
+        DriveStatus::VerifyFailed(message) => format!("Burn verification FAILED: {}", message),
+        DriveStatus::BenchmarkError(message) => format!("Benchmark failed: {}", message),
+        DriveStatus::HookError(message) => message.clone(),
+        DriveStatus::EraseError(message) => format!("Erase failed. {}", message),
     };
 
     message
 }
 
-fn list_disk_drives() -> Result<Vec<Arc<DiskDrive>>, DiskInfoError> {
+fn get_tray_status_message_string(status: TrayStatus) -> &'static str {
+    match status {
+        TrayStatus::Unknown => "Tray status unknown.",
+        TrayStatus::Open => "Tray open.",
+        TrayStatus::Empty => "Tray closed, empty.",
+        TrayStatus::NotReady => "Tray closed, drive not ready.",
+        TrayStatus::Loaded => "Disc loaded.",
+    }
+}
+
+fn is_removable_device(drive: &str) -> bool {
+    let name = drive.trim_start_matches("/dev/");
+
+    match fs::read_to_string(format!("/sys/block/{}/removable", name)) {
+        Ok(flag) => flag.trim() == "1",
+        Err(_) => false, // Can't tell, so play it safe.
+    }
+}
+
+// The drives on this machine we're allowed to use, without opening any of them.
+fn find_local_drives(config: &Config) -> Result<Vec<FoundDrive>, DiskInfoError> {
     let mut command = Command::new("lsscsi");
     let output = command.output().map_err(|_| { DiskInfoError::LaunchFail })?;
 
     let data = str::from_utf8(&output.stdout).map_err(|_| { DiskInfoError::ConvertToUTF })?;
 
-    Ok(parse_disk_drive_list(data).map_err(|_| { DiskInfoError::Parse })?.1)
+    let (_, drives) = parse_disk_drive_list(data).map_err(|_| { DiskInfoError::Parse })?;
+
+    // Only image other kinds of disks when asked to. We don't want to go ripping somebody's system drive.
+    let mut drives: Vec<FoundDrive> = drives.into_iter().filter(|FoundDrive { file, kind, .. }| {
+        match kind {
+            DriveKind::Optical => true,
+            DriveKind::Floppy => config.floppy_drives,
+            DriveKind::Block => match &config.block_devices {
+                BlockDevices::None => false,
+                BlockDevices::Removable => is_removable_device(file),
+                BlockDevices::List(devices) => devices.contains(file),
+            },
+        }
+    }).collect();
+
+    // The legacy floppy controller isn't SCSI, so lsscsi doesn't know about it.
+    if config.floppy_drives {
+        for file in floppy::list_legacy_floppies() {
+            drives.push(FoundDrive { file, kind: DriveKind::Floppy, model: None });
+        }
+    }
+
+    Ok(drives)
+}
+
+fn get_disk_info_error_message(error: &DiskInfoError) -> String {
+    get_helper_error_message("lsscsi", error)
+}
+
+// `tool` is whichever one it was, since blkid fails the same ways.
+fn get_helper_error_message(tool: &str, error: &DiskInfoError) -> String {
+    match error {
+        DiskInfoError::LaunchFail =>
+            format!("Failed to launch {}. Is it not installed?", tool),
+        DiskInfoError::ConvertToUTF =>
+            format!("Failed to convert {} output to UTF8 for parsing. Major bug?", tool),
+        DiskInfoError::Parse =>
+            format!("Failed to parse {} output. Has the application changed its formatting?", tool),
+        DiskInfoError::DeviceSize(message) =>
+            format!("Failed to find the size of the device: {}", message),
+    }
+}
+
+fn new_found_drive(found: FoundDrive) -> DiskDrive {
+    let mut drive = new_disk_drive(found.file, found.kind);
+    drive.model = found.model;
+    drive
+}
+
+fn list_disk_drives(config: &Config) -> Result<Vec<Arc<DiskDrive>>, DiskInfoError> {
+    let mut drives: Vec<Arc<DiskDrive>> = find_local_drives(config)?.into_iter()
+        .map(|found| Arc::new(new_found_drive(found)))
+        .collect();
+
+    // Config checks these parse. They're attached once there's a disk in them, by check_disks_in_drives.
+    let remotes: Vec<RemoteDrive> = config.remote_drives.iter().filter_map(|spec| remote::parse_remote_drive(spec)).collect();
+    for (remote, device) in remotes.into_iter().zip(remote::find_free_devices(config.remote_drives.len())) {
+        let mut drive = new_disk_drive(device, DriveKind::Block);
+        drive.remote = Some(remote);
+        drives.push(Arc::new(drive));
+    }
+
+    Ok(drives)
+}
+
+// Works for anything we can seek in. Card readers and ZIP drives report nothing or fail to open when empty.
+fn get_device_size(drive: &str) -> io::Result<u64> {
+    let mut device = fs::File::open(drive)?;
+    device.seek(SeekFrom::End(0))
+}
+
+// The same, for the drive poller, so a drive that's slow to open doesn't hold up anything else on the runtime.
+async fn fetch_device_size(drive: &str) -> io::Result<u64> {
+    let mut device = tokio::fs::File::open(drive).await?;
+    device.seek(SeekFrom::End(0)).await
+}
+
+// Opens the drive the way `cdrom::open_device` does, without tying up one of the runtime's threads while it does.
+async fn fetch_tray_status(drive: &str) -> io::Result<TrayStatus> {
+    let device = tokio::fs::OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(drive).await?;
+    cdrom::read_tray_status(&device)
 }
 
-fn parse_bulk_id_list(input: &str) -> ParserResult<Vec<(&str, &str)>> {
+fn parse_bulk_id_list(input: &str) -> ParserResult<'_, Vec<(&str, &str)>> {
     many0(
         tuple((
             terminated(take_until(":"), char_tag(':')),
@@ -170,29 +578,135 @@ fn parse_bulk_id_list(input: &str) -> ParserResult<Vec<(&str, &str)>> {
     )(input)
 }
 
-fn check_disks_in_drives(drives: &Vec<Arc<DiskDrive>>) -> Result<(), DiskInfoError> {
-    let mut command = Command::new("blkid");
-    let output = command.output().map_err(|_| { DiskInfoError::LaunchFail })?;
+async fn check_disks_in_drives(drives: &[Arc<DiskDrive>]) -> Result<(), DiskInfoError> {
+    let mut command = tokio::process::Command::new("blkid");
+    let output = command.output().await.map_err(|_| { DiskInfoError::LaunchFail })?;
 
     let data = str::from_utf8(&output.stdout).map_err(|_| { DiskInfoError::ConvertToUTF })?;
 
     let (_, disks) = parse_bulk_id_list(data).map_err(|_| { DiskInfoError::Parse })?;
 
+    // nbd-server can't open a drive with nothing in it, so attaching is how we find out there's a disk. They're all
+    // tried at once, so one machine that's gone quiet only holds the scan up as long as nbd-client is given.
+    let connections: Vec<_> = drives.iter()
+        .filter(|drive| !drive.released.load(Relaxed))
+        .filter_map(|drive| drive.remote.clone().map(|remote| (remote, drive.file.clone())))
+        .map(|(remote, device)| tokio::task::spawn_blocking(move || remote::connect(&remote, &device)))
+        .collect();
+
+    for connection in connections {
+        let _ = connection.await;
+    }
+
     for drive in drives.iter() {
-        drive.has_disk.swap(disks.iter().find(|e| drive.file.starts_with(e.0)).is_some(), Relaxed);
+        let has_disk = match drive.kind {
+            DriveKind::Optical => disks.iter().any(|e| drive.file.starts_with(e.0)),
+            DriveKind::Block => fetch_device_size(&drive.file).await.map(|size| size > 0).unwrap_or(false), // May not have a filesystem we know.
+            DriveKind::Floppy => continue, // Can't tell without grinding the drive. The operator tells us instead.
+        };
+
+        if drive.has_disk.swap(has_disk, Relaxed) != has_disk {
+            events::publish(&drive.file, if has_disk { DriveEvent::DiskInserted } else { DriveEvent::DiskRemoved });
+            drive.state.wake();
+        }
     }
 
     Ok(())
 }
 
-fn parse_iso_info(input: &str) -> ParserResult<ISOInfo> {
+fn get_capabilities_string(capabilities: &cdrom::Capabilities) -> String {
+    let families = cdrom::get_media_families(&capabilities.profiles);
+
+    let mut description = if families.is_empty() { String::from("Reads CD") } else { format!("Reads {}", families.join(", ")) };
+
+    if capabilities.can_write {
+        description += ", writes";
+    }
+
+    // 1x is 176kB/s on a CD, and that's what drives measure by here, even DVD drives.
+    if capabilities.max_read_speed > 0 {
+        description += &format!(". Up to {}x", capabilities.max_read_speed / 176);
+    }
+
+    if capabilities.buffer_size > 0 {
+        description += &format!(", {}kB buffer", capabilities.buffer_size);
+    }
+
+    if capabilities.changer {
+        description += ". Built in changer";
+    }
+
+    description + "."
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1_000_000_000 {
+        format!("{:.1} GB", bytes as f64 / 1_000_000_000.0)
+    } else {
+        format!("{} MB", bytes / 1_000_000)
+    }
+}
+
+// Describes the disk in an optical drive, so the operator catches the DVD-R that got mixed in with the pressed CDs.
+fn describe_media(drive: &str) -> Option<String> {
+    let device = cdrom::open_device(drive).ok()?;
+    let name = cdrom::get_profile_name(cdrom::read_media_profile(&device).ok()?);
+
+    let used = cdrom::read_capacity(&device).ok();
+    let free = cdrom::read_free_space(&device).ok();
+
+    Some(match (cdrom::read_disc_state(&device), used, free) {
+        (Ok(cdrom::DiscState::Blank), _, Some(free)) => format!("{}, blank, {} free", name, format_size(free)),
+        (Ok(cdrom::DiscState::Blank), _, None) => format!("{}, blank", name),
+        (Ok(cdrom::DiscState::Appendable), Some(used), Some(free)) => format!("{}, {} used, {} free", name, format_size(used), format_size(free)),
+        (_, Some(used), _) => format!("{}, {}", name, format_size(used)),
+        (_, None, _) => String::from(name),
+    })
+}
+
+// Returns true if any tray changed state since the last check.
+async fn check_drive_trays(drives: &[Arc<DiskDrive>]) -> bool {
+    let mut changed = false;
+
+    for drive in drives.iter().filter(|drive| drive.kind == DriveKind::Optical) {
+        // Not every drive supports this, so just call those unknown.
+        let status = fetch_tray_status(&drive.file).await.unwrap_or(TrayStatus::Unknown);
+
+        if std::mem::replace(&mut *drive.tray_status.lock().unwrap(), status) == status {
+            continue;
+        }
+
+        changed = true;
+
+        // Only worth asking once, when the disk goes in. The drive has to read the disk to answer, so it gets a
+        // thread of its own to wait on.
+        let media = if status == TrayStatus::Loaded {
+            let file = drive.file.clone();
+            tokio::task::spawn_blocking(move || describe_media(&file)).await.ok().flatten()
+        } else {
+            None
+        };
+        *drive.media.lock().unwrap() = media;
+
+        drive.state.wake();
+    }
+
+    changed
+}
+
+// isoinfo leaves the line there with nothing after it when the field's blank.
+fn parse_optional_field(line: &str, name: &str) -> Option<String> {
+    line.strip_prefix(name).map(|value| value.trim()).filter(|value| !value.is_empty()).map(String::from)
+}
+
+fn parse_iso_info(input: &str) -> ParserResult<'_, ISOInfo> {
     let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // Format
     let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // System id
     let (input, volume_id_line) = terminated(take_until("\n"), char_tag('\n'))(input)?;  // Volume id
     let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // Volume set id
-    let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // Publisher id
-    let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // Data preparer id
-    let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // Application id
+    let (input, publisher_line) = terminated(take_until("\n"), char_tag('\n'))(input)?;  // Publisher id
+    let (input, preparer_line) = terminated(take_until("\n"), char_tag('\n'))(input)?;   // Data preparer id
+    let (input, application_line) = terminated(take_until("\n"), char_tag('\n'))(input)?; // Application id
     let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // Copyright File id
     let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // Abstract File id
     let (input, _) = terminated(take_until("\n"), char_tag('\n'))(input)?;                     // Bibliographic File id
@@ -209,402 +723,4016 @@ fn parse_iso_info(input: &str) -> ParserResult<ISOInfo> {
     let block_size: usize = block_size.parse().unwrap(); // Only way it could panic is if it exceeds the machine's bit width.
 
     let (number_of_blocks, _) = tag("Volume size is: ")(number_of_blocks_line)?;
-    let number_of_blocks: usize = number_of_blocks.parse().unwrap(); // Only way it could panic is if it exceeds the machine's bit width.
+    let number_of_blocks: u64 = number_of_blocks.parse().unwrap(); // Only way it could panic is if it exceeds 64 bits.
+
+    // isoinfo doesn't say when the disk was made. That's read off the disk along with it.
+    let details = VolumeDetails {
+        publisher: parse_optional_field(publisher_line, "Publisher id:"),
+        preparer: parse_optional_field(preparer_line, "Data preparer id:"),
+        application: parse_optional_field(application_line, "Application id:"),
+        created: None,
+    };
 
     // Ship out the data.
     Ok((input, ISOInfo {
         name: String::from(volume_id),
         block_size,
-        length: number_of_blocks * block_size
+        length: number_of_blocks * block_size as u64,
+        details,
     }))
 }
 
+// Block devices don't have a volume id to go by, so name the image after a filesystem label if one turns up.
+fn fetch_block_info(drive: &str, kind: DriveKind) -> Result<ISOInfo, DiskInfoError> {
+    let length = match kind {
+        DriveKind::Floppy => floppy::get_floppy_size(drive).map(|size| size as u64),
+        _ => get_device_size(drive),
+    }.map_err(|e| { DiskInfoError::DeviceSize(format!("{}", e)) })?;
+
+    let mut command = Command::new("lsblk");
+    command.args(["-n", "-o", "LABEL", drive]);
+
+    let label = match command.output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty())
+            .map(String::from),
+        Err(_) => None,
+    };
+
+    let name = label.unwrap_or_else(|| format!("{}-image", drive.trim_start_matches("/dev/")));
+
+    Ok(ISOInfo {
+        name,
+        block_size: if kind == DriveKind::Floppy { floppy::SECTOR_SIZE } else { BLOCK_DEVICE_BUFFER_SIZE },
+        length,
+        details: VolumeDetails::default(),
+    })
+}
+
+// Raw rips cover every sector up to the lead-out, whatever the filesystem says.
+fn fetch_raw_info(drive: &str, toc: Option<&[TocEntry]>) -> Result<ISOInfo, DiskInfoError> {
+    let sectors = toc.and_then(clonecd::get_sector_count).ok_or(DiskInfoError::Parse)?;
+
+    // Protected and mixed mode disks often don't have a volume id we can read, and that's fine.
+    let volume = metadata::identify_volume(drive).ok();
+
+    Ok(ISOInfo {
+        name: volume.as_ref().map(|info| info.name.clone()).unwrap_or_else(|| String::from("disc")),
+        block_size: cdrom::RAW_SECTOR_SIZE,
+        length: sectors as u64 * cdrom::RAW_SECTOR_SIZE as u64,
+        details: volume.map(|info| info.details).unwrap_or_default(),
+    })
+}
+
+// Rounds up to a whole block, but never past the end of the disk, in case the partition map is lying.
+fn get_hybrid_length(drive: &str, length: u64, block_size: usize) -> u64 {
+    let length = length.div_ceil(block_size as u64) * block_size as u64;
+
+    match get_device_size(drive) {
+        Ok(size) if size > 0 => length.min(size),
+        _ => length,
+    }
+}
+
+// isoinfo couldn't make sense of the disk. Work out why, so the operator knows whether to toss it, clean it, or rip it
+// some other way.
+fn diagnose_unreadable_disk(drive: &str, error: &DiskInfoError) -> DriveStatus {
+    if matches!(error, DiskInfoError::LaunchFail) {
+        return DriveStatus::IsoFetchError;
+    }
+
+    let device = match cdrom::open_device(drive) {
+        Ok(device) => device,
+        Err(error) => return DriveStatus::DamagedDisk(format!("{}", error)),
+    };
+
+    // Drives that don't support READ DISC INFORMATION only read pressed disks anyway.
+    if let Ok(cdrom::DiscState::Blank) = cdrom::read_disc_state(&device) {
+        return DriveStatus::BlankDisk;
+    }
+
+    if let Err(error) = cdrom::read_full_toc(&device) {
+        return DriveStatus::DamagedDisk(format!("Couldn't read the table of contents: {}", cdrom::get_scsi_error_message(&error)));
+    }
+
+    // The TOC is fine, so if the sector the volume descriptor would be in reads fine too, the disk just isn't ISO 9660.
+    let mut sector = [0u8; 2048];
+    match device.read_exact_at(&mut sector, 16 * 2048) {
+        Ok(()) => DriveStatus::UnrecognizedFileSystem,
+        Err(error) => DriveStatus::DamagedDisk(format!("Couldn't read the volume descriptor: {}", error)),
+    }
+}
+
 fn fetch_iso_info(drive: &str) -> Result<ISOInfo, DiskInfoError> {
 
     let mut command = Command::new("isoinfo");
 
-    command.args(&["-d", &format!("-i{}", drive)]);
+    command.args(["-d", &format!("-i{}", drive)]);
 
     let output = command.output().map_err(|_| { DiskInfoError::LaunchFail })?;
 
     let data = str::from_utf8(&output.stdout).map_err(|_| { DiskInfoError::ConvertToUTF })?;
 
-    let (_, result) = parse_iso_info(data).map_err(|_| { DiskInfoError::Parse })?;
+    let (_, mut result) = parse_iso_info(data).map_err(|_| { DiskInfoError::Parse })?;
+    result.details.created = metadata::read_creation_date(drive);
 
     Ok(result)
 }
 
-fn copy_disk_to_iso<O, CB, ECB>(source: &str, target: &mut O, length: usize, buffer_len: usize, mut callback: CB, mut error_callback: ECB)
-    -> Result<(), CopyError> where
+// How many times in a row a block can fail in recovery mode before we give up on it and zero fill it.
+const RECOVERY_RETRIES: usize = 5;
+
+// Anything past `required` is a bonus. The disk ending early there, or failing to read, just ends the copy.
+// Drives with a `max_speed` are slowed down to retry blocks that won't read. The rest are left alone.
+// Starts at `start`, with `damage` saying what was bad before there, to pick up a rip the drive went away during.
+// `damage_callback` hears about every block that gets zero filled, as it is.
+#[allow(clippy::too_many_arguments)]
+fn copy_disk_to_iso<O, CB, ECB, DCB>(source: &str, target: &mut O, length: u64, required: u64, start: u64, mut damage: DamageMap, buffer_len: usize, recovery: bool, max_speed: Option<u16>, cancel: &AtomicBool, mut callback: CB, mut error_callback: ECB, mut damage_callback: DCB)
+    -> Result<DamageMap, CopyError> where
     O: Write,
     CB: FnMut(usize),
-    ECB: FnMut(CopyError)
+    ECB: FnMut(CopyError),
+    DCB: FnMut(u64, u64)
 {
 
     // For testing just dumbly return. Creates a lot of compiler warnings but saves hours waiting for disks to copy.
-    // return Ok(());
+    // return Ok(DamageMap::new(length));
+
+    let mut buffer = buffer_pool::get(buffer_len);
+    let mut failures = 0;
 
-    let mut buffer = vec![0; buffer_len];
+    let mut source_file = fs::File::open(source).map_err(|e| { CopyError::Read(format!("{}", e)) })?;
+    source_file.seek(SeekFrom::Start(start)).map_err(|e| { CopyError::Read(format!("{}", e)) })?;
+    let mut source_file = source_file.take(length - start);
+    let mut position: u64 = start;
 
-    let source_file = fs::File::open(source).unwrap(); // FIXME replace unwrap with a passed error.
-    let mut source_file = source_file.take(length as u64);
-    let mut position = 0;
+    let speed_control = max_speed.and_then(|max_speed| cdrom::open_device(source).ok().map(|device| (device, max_speed)));
 
     loop {
+        if cancel.load(Relaxed) {
+            return Err(CopyError::Cancelled);
+        }
+
         let len = match source_file.read(&mut buffer) {
             Ok(0) => {
+                // Some disks say they're bigger than they are. Better to know than to have a short image nobody noticed.
+                if position < required {
+                    damage.end_early(position);
+                }
+
                 break;
             },
             Ok(len) => {
                 callback(len);
                 error_callback(CopyError::None);
+
+                // Past the bad spot, so back up to full speed for the rest of the disk.
+                if failures > 0 {
+                    if let Some((device, _)) = &speed_control {
+                        let _ = cdrom::set_read_speed(device, cdrom::MAX_SPEED);
+                    }
+                }
+
+                failures = 0;
                 Ok(len)
             },
             Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
                 continue;
             },
+            // Track-at-once disks end in run-out sectors that never read, and we only got here by going past the volume.
+            Err(_) if position >= required => {
+                break;
+            },
+            // Pulled out. Reading something that isn't there again won't help.
+            Err(_) if !Path::new(source).exists() => {
+                return Err(CopyError::Disconnected(position, damage));
+            },
             Err(error) => {
-                error_callback(CopyError::Read(format!("{}", error)));
-                // Err(CopyError::Read) // FIXME Try re-opening the device to see if you can recover.
-                let mut new_source = fs::File::open(source).unwrap(); // FIXME replace unwrap with a passed error.
-                new_source.seek(SeekFrom::Start(position as u64)).map_err( |e| { CopyError::Read(format!("{}", e)) } )?;
-                source_file = new_source.take(length as u64);
+                // The kernel doesn't tell us why, so ask the drive ourselves.
+                let reason = cdrom::open_device(source).ok().and_then(|device| {
+                    let (lba, sense) = cdrom::probe_read_error(&device, position)?;
+                    sense_log::log_sense(&device, lba, &sense);
+                    Some(cdrom::describe_sense(&sense))
+                });
+
+                error_callback(CopyError::Read(match reason {
+                    Some(reason) => format!("{}, {}", error, reason),
+                    None => format!("{}", error),
+                }));
+                failures += 1;
+
+                // Give the drive a moment, and a slower speed, before the next try.
+                if let Some((device, max_speed)) = &speed_control {
+                    cdrom::step_down_speed(device, failures, *max_speed);
+                    thread::sleep(Duration::from_millis(250 * failures.min(8) as u64));
+                }
+
+                if recovery && failures >= RECOVERY_RETRIES {
+                    // This block isn't going to read. Zero fill it and move on to the rest of the disk.
+                    let skip = (buffer_len as u64).min(length - position) as usize;
+                    damage.add_bad(position, skip as u64);
+                    damage_callback(position, skip as u64);
+
+                    target.write_all(&vec![0; skip]).map_err(|e| {
+                        CopyError::Write(format!("{}", e))
+                    })?;
+
+                    callback(skip);
+                    position += skip as u64;
+                    failures = 0;
+                }
+
+                // Try re-opening the device to see if that gets it going again.
+                let mut new_source = match fs::File::open(source) {
+                    Ok(new_source) => new_source,
+                    Err(_) if !Path::new(source).exists() => return Err(CopyError::Disconnected(position, damage)),
+                    Err(e) => return Err(CopyError::Read(format!("{}", e))),
+                };
+                new_source.seek(SeekFrom::Start(position)).map_err( |e| { CopyError::Read(format!("{}", e)) } )?;
+                source_file = new_source.take(length - position);
 
                 continue;
             }
         }?;
 
-        position += len;
+        position += len as u64;
 
         target.write_all(&buffer[..len]).map_err(|e| {
             CopyError::Write(format!("{}", e))
         })?;
     }
 
-    Ok(())
+    Ok(damage)
 }
 
-fn eject_drive_disk(drive: &str) -> Result<bool, DiskInfoError> {
-    fn attempt_eject(drive: &str) -> Result<bool, DiskInfoError> {
-        let mut command = Command::new("eject");
-        command.arg(drive);
+// Reads just the bad regions of an earlier rip off the disk again, and writes whatever reads into the image in place.
+// Returns what's still bad. Regions are read a block at a time so one bad sector doesn't take good ones down with it.
+fn patch_image_from_disk<CB>(source: &str, image: &str, map: &DamageMap, block_size: usize, max_speed: Option<u16>, cancel: &AtomicBool, mut callback: CB)
+    -> Result<DamageMap, CopyError> where
+    CB: FnMut(usize)
+{
+    let source_file = fs::File::open(source).map_err(|e| { CopyError::Read(format!("{}", e)) })?;
+    let image_file = fs::OpenOptions::new().write(true).open(image).map_err(|e| { CopyError::Write(format!("{}", e)) })?;
 
-        let status = command.output().map_err(|_| { DiskInfoError::LaunchFail })?.status;
+    let speed_control = max_speed.and_then(|max_speed| cdrom::open_device(source).ok().map(|device| (device, max_speed)));
 
-        Ok(status.success())
-    }
+    let mut buffer = buffer_pool::get(block_size);
+    let mut damage = DamageMap::new(map.length());
 
-    // Set as though we failed.
-    let mut worked = false;
+    // A different pressing, or a drive that can't see the whole disk, can come up short of the image.
+    let readable = get_device_size(source).ok().filter(|size| *size > 0).unwrap_or(u64::MAX);
 
-    for _ in 0..5 {
-        if attempt_eject(drive)? {
-            // We got it opened!
-            worked = true;
-            break;
+    for (start, length) in map.get_bad_regions().iter() {
+        let end = start + length;
+        let mut position = *start;
+
+        while position < end {
+            if cancel.load(Relaxed) {
+                return Err(CopyError::Cancelled);
+            }
+
+            // There's nothing past the end to retry, so the rest of it stays damaged.
+            if position >= readable {
+                damage.add_bad(position, end - position);
+                callback((end - position) as usize);
+                break;
+            }
+
+            let len = (end - position).min(readable - position).min(block_size as u64) as usize;
+            let mut failures = 0;
+
+            loop {
+                match source_file.read_exact_at(&mut buffer[..len], position) {
+                    Ok(()) => {
+                        image_file.write_all_at(&buffer[..len], position).map_err(|e| { CopyError::Write(format!("{}", e)) })?;
+
+                        if failures > 0 {
+                            if let Some((device, _)) = &speed_control {
+                                let _ = cdrom::set_read_speed(device, cdrom::MAX_SPEED);
+                            }
+                        }
+                        break;
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => {
+                        failures += 1;
+
+                        if let Some((device, max_speed)) = &speed_control {
+                            if let Some((lba, sense)) = cdrom::probe_read_error(device, position) {
+                                sense_log::log_sense(device, lba, &sense);
+                            }
+
+                            cdrom::step_down_speed(device, failures, *max_speed);
+                            thread::sleep(Duration::from_millis(250 * failures.min(8) as u64));
+                        }
+
+                        // Still no good. It stays zero filled.
+                        if failures >= RECOVERY_RETRIES {
+                            damage.add_bad(position, len as u64);
+                            break;
+                        }
+                    },
+                }
+            }
+
+            callback(len);
+            position += len as u64;
         }
     }
 
-    // Report if we got it opened.
-    Ok(worked)
+    Ok(damage)
+}
+
+fn is_drive_mounted(drive: &str) -> bool {
+    match fs::read_to_string("/proc/mounts") {
+        Ok(mounts) => mounts.lines().any(|line| line.split(' ').next() == Some(drive)),
+        Err(_) => false, // Can't tell, so don't go trying to unmount things.
+    }
+}
+
+fn unmount_drive(drive: &str) -> bool {
+    let mut command = Command::new("umount");
+    command.arg(drive);
+
+    match command.output() {
+        Ok(output) => output.status.success(),
+        Err(_) => false,
+    }
+}
+
+fn diagnose_eject_failure(stderr: &str) -> EjectError {
+    let lower = stderr.to_lowercase();
+
+    if lower.contains("busy") {
+        EjectError::Busy
+    } else if lower.contains("no medium") {
+        EjectError::NoMedium
+    } else {
+        EjectError::Hardware(String::from(stderr.trim()))
+    }
 }
 
-fn close_drive_disk(drive: &str) -> Result<bool, DiskInfoError> {
-    fn attempt_close(drive: &str) -> Result<bool, DiskInfoError> {
+fn run_eject_with_backoff(drive: &str, args: &[&str]) -> Result<(), EjectError> {
+    fn attempt(drive: &str, args: &[&str]) -> Result<(), EjectError> {
         let mut command = Command::new("eject");
-        command.arg("-t");
+        command.args(args);
         command.arg(drive);
 
-        let status = command.output().map_err(|_| { DiskInfoError::LaunchFail })?.status;
+        let output = command.output().map_err(|_| { EjectError::LaunchFail })?;
 
-        Ok(status.success())
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(diagnose_eject_failure(&String::from_utf8_lossy(&output.stderr)))
+        }
     }
 
-    // Set as though we failed.
-    let mut worked = false;
+    let mut delay = Duration::from_millis(250);
+    let mut last_error = EjectError::LaunchFail;
 
     for _ in 0..5 {
-        if attempt_close(drive)? {
-            // We got it closed!
-            worked = true;
-            break;
+        match attempt(drive, args) {
+            Ok(()) => return Ok(()), // We got it!
+            Err(EjectError::LaunchFail) => return Err(EjectError::LaunchFail), // No point trying again.
+            Err(EjectError::NoMedium) => return Err(EjectError::NoMedium),     // Retrying won't put a disk in there.
+            Err(EjectError::Busy) => {
+                // Usually the desktop auto-mounted the disk. Let go of it and try again.
+                if is_drive_mounted(drive) && !unmount_drive(drive) {
+                    return Err(EjectError::Mounted);
+                }
+                last_error = EjectError::Busy;
+            },
+            Err(error) => last_error = error,
         }
+
+        thread::sleep(delay);
+        delay *= 2;
     }
 
-    // Report if we got it closed.
-    Ok(worked)
+    Err(last_error)
 }
 
-fn add_drive_ui_buttons(drive: &DiskDrive, linear: &mut LinearLayout) {
-
-    let drive1 = drive.file.clone();
-    let drive2 = drive.file.clone();
+// Reads the disk back and compares it to the image. Anything the disk has past the end of the image is ignored.
+fn verify_disk_against_image<CB>(source: &str, image: &str, buffer_len: usize, mut callback: CB) -> Result<(), VerifyError> where
+    CB: FnMut(usize)
+{
+    let mut image_file = fs::File::open(image).map_err(|e| { VerifyError::Image(format!("{}", e)) })?;
+    let mut source_file = fs::File::open(source).map_err(|e| { VerifyError::Read(format!("{}", e)) })?;
 
-    let buttons = LinearLayout::horizontal()
-        .child(Button::new("Eject", move |s| {
-            if let Ok(worked) = eject_drive_disk(&drive1) {
-                if worked {
-                    s.add_layer(Dialog::text("Disk ejected.")
-                        .button("Ok", |s| { s.pop_layer(); } ));
+    // One buffer cut in half, since waiting on the pool for a second one while holding the first can deadlock with
+    // other drives doing the same.
+    let mut buffer = buffer_pool::get(buffer_len * 2);
+    let (image_buffer, disk_buffer) = buffer.split_at_mut(buffer_len);
+    let mut position: u64 = 0;
 
-                    // Break out of this function before we can hit the fail case.
-                    return;
-                }
+    loop {
+        let len = match image_file.read(image_buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(VerifyError::Image(format!("{}", e))),
+        };
+
+        // The disk may hand us less than we asked for, so keep going until we have the same amount as the image.
+        let mut filled = 0;
+        while filled < len {
+            match source_file.read(&mut disk_buffer[filled..len]) {
+                Ok(0) => return Err(VerifyError::ShortDisk(position + filled as u64)),
+                Ok(read) => filled += read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(VerifyError::Read(format!("{}", e))),
             }
+        }
 
-            s.add_layer(Dialog::text("Failed to eject disk.")
-                .button("Ok", |s| { s.pop_layer(); } ));
+        if let Some(offset) = image_buffer[..len].iter().zip(disk_buffer[..len].iter()).position(|(a, b)| a != b) {
+            return Err(VerifyError::Mismatch(position + offset as u64));
+        }
 
-            // Failed to eject drive.
-        }))
-        .child(Button::new("Close", move |s| {
+        position += len as u64;
+        callback(len);
+    }
 
-            if let Ok(worked) = close_drive_disk(&drive2) {
-                if worked {
-                    s.add_layer(Dialog::text("Disk drive closed.")
-                        .button("Ok", |s| { s.pop_layer(); } ));
+    Ok(())
+}
 
-                    // Break out of this function before we can hit the fail case.
-                    return;
-                }
-            }
+fn get_verify_error_message(error: &VerifyError) -> String {
+    match error {
+        VerifyError::Image(message) => format!("Error reading image: {}", message),
+        VerifyError::Read(message) => format!("Error reading disk: {}", message),
+        VerifyError::ShortDisk(length) => format!("Disk ends after {} bytes, before the image does.", length),
+        VerifyError::Mismatch(offset) => format!("Disk differs from the image at byte {}.", offset),
+    }
+}
 
-            s.add_layer(Dialog::text("Failed to close disk drive.")
-                .button("Ok", |s| { s.pop_layer(); } ));
+fn eject_drive_disk(drive: &str) -> Result<(), EjectError> {
+    run_eject_with_backoff(drive, &[])
+}
 
-            // Failed to close drive.
-        }))
-        .full_width();
-    linear.add_child(buttons);
+fn close_drive_disk(drive: &str) -> Result<(), EjectError> {
+    run_eject_with_backoff(drive, &["-t"])
 }
 
-fn add_status_indicator(s: &mut Cursive, drive: &Arc<DiskDrive>, linear: &mut LinearLayout, status_id: &String) {
+fn get_eject_error_message(error: &EjectError) -> String {
+    match error {
+        EjectError::LaunchFail => String::from("Failed to launch eject. Is it installed?"),
+        EjectError::Busy => String::from("The drive is busy. Something else has the disk open."),
+        EjectError::Mounted => String::from("The disk is mounted and could not be unmounted."),
+        EjectError::NoMedium => String::from("There is no disk in the drive."),
+        EjectError::Hardware(message) => format!("The drive refused: {}", message),
+    }
+}
 
-    let drive = drive.clone();
-    let status_id = status_id.clone();
+// Moves the drive on. Moves that aren't allowed are logged, and the drive carries on with whatever it was doing.
+// Refused moves are logged. Whatever was going to happen after one shouldn't, since the drive's moved on without it.
+fn set_drive_status(drive: &DiskDrive, status: DriveStatus) -> Result<(), IllegalTransition> {
+    drive.state.transition(status)
+}
 
-    linear.add_child(TextView::new("----").with_id(&status_id));
-    s.add_global_callback(Event::Refresh, move |s| {
-        // Shouldn't fail since we made this.
-        let mut status = s.find_id::<TextView>(&status_id).unwrap();
+// For where nothing more happens after, like errors and being done. If that's refused, the drive's already somewhere
+// else and there's nothing to undo.
+fn show_drive_status(drive: &DiskDrive, status: DriveStatus) {
+    let _ = set_drive_status(drive, status);
+}
 
-        status.set_content(get_drive_status_message_string(&drive.status_message.lock().unwrap()));
-    });
+fn is_drive_busy(drive: &DiskDrive) -> bool {
+    matches!(*drive.state.lock(),
+        DriveStatus::Fingerprinting
+        | DriveStatus::ReadingMetadata
+        | DriveStatus::Copying
+        | DriveStatus::WaitingForSpace(_)
+        | DriveStatus::RippingTrack(_, _)
+        | DriveStatus::Saving(_)
+        | DriveStatus::Converting(_)
+        | DriveStatus::GeneratingParity(_)
+        | DriveStatus::Storing(_)
+        | DriveStatus::BackingUp(_)
+        | DriveStatus::ImportingPhotos(_, _)
+        | DriveStatus::Burning(_)
+        | DriveStatus::Verifying(_)
+        | DriveStatus::VerifyingImage(_)
+        | DriveStatus::Benchmarking
+        | DriveStatus::Patching(_)
+        | DriveStatus::Erasing(_, _)
+        | DriveStatus::NonFatalCopyReadError(_)
+        | DriveStatus::NonFatalCopyWriteError(_))
 }
 
-fn spawn_drive_thread(s: &mut Cursive, drive: &Arc<DiskDrive>, counter: Counter, name_id: &str, ready_id: &str) {
-    let drive = drive.clone();
+fn show_eject_result(s: &mut Cursive, result: Result<(), EjectError>, success: &str, failure: &str) {
+    let message = match result {
+        Ok(()) => String::from(success),
+        Err(error) => format!("{}\n{}", failure, get_eject_error_message(&error)),
+    };
 
-    let cb = s.cb_sink().clone();
+    s.add_layer(Dialog::text(message)
+        .button("Ok", |s| { s.pop_layer(); } ));
+}
 
-    let name_id = String::from(name_id);
-    let ready_id = String::from(ready_id);
+// The drive won't open its tray while we still hold the device, so give any cancelled copy a moment to wind down.
+fn eject_when_idle(drive: &DiskDrive) -> Result<(), EjectError> {
+    for _ in 0..50 {
+        if !is_drive_busy(drive) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
 
-    thread::spawn(move || {
-        loop {
-            // Wait for a disk
+    eject_disk(drive)
+}
 
-            *drive.status_message.lock().unwrap() = DriveStatus::NoDisk;
+// Remote drives can't be ejected from here, so they're let go of instead, for someone over there to swap the disk.
+fn eject_disk(drive: &DiskDrive) -> Result<(), EjectError> {
+    if drive.remote.is_none() {
+        return eject_drive_disk(&drive.file);
+    }
 
-            while !drive.has_disk.load(Relaxed) {
-                thread::sleep(Duration::from_millis(5000));
-            }
+    drive.released.store(true, Relaxed);
+    remote::disconnect(&drive.file).map_err(|error| EjectError::Hardware(remote::get_remote_error_message(&error)))
+}
 
-            if let Ok(info) = fetch_iso_info(&drive.file) {
-                *drive.status_message.lock().unwrap() = DriveStatus::Copying;
+fn close_disk(drive: &DiskDrive) -> Result<(), EjectError> {
+    match &drive.remote {
+        Some(remote) => {
+            drive.released.store(false, Relaxed);
+            remote::connect(remote, &drive.file).map_err(|error| EjectError::Hardware(remote::get_remote_error_message(&error)))
+        },
+        None => close_drive_disk(&drive.file),
+    }
+}
 
-                let name_id = name_id.clone();
-                let ready_id = ready_id.clone();
+fn spawn_eject(s: &mut Cursive, drive: &Arc<DiskDrive>) {
+    let drive = drive.clone();
+    let cb = s.cb_sink().clone();
 
-                let default_iso_name = format!("{}.iso", info.name);
+    // Retries can take a few seconds, so keep them off the UI thread.
+    thread::spawn(move || {
+        let result = eject_when_idle(&drive);
+        cb.send(Box::new(move |s| show_eject_result(s, result, "Disk ejected.", "Failed to eject disk."))).unwrap();
+    });
+}
 
-                cb.send(Box::new(move |s| {
-                    let mut text_box = s.find_id::<EditView>(&name_id).unwrap();
-                    let mut ready_checkbox = s.find_id::<Checkbox>(&ready_id).unwrap();
+fn spawn_close(s: &mut Cursive, drive: &Arc<DiskDrive>) {
+    let drive = drive.clone();
+    let cb = s.cb_sink().clone();
 
-                    ready_checkbox.set_checked(false);
-                    text_box.set_content(default_iso_name);
-                })).unwrap();
+    thread::spawn(move || {
+        let result = close_disk(&drive);
+        cb.send(Box::new(move |s| show_eject_result(s, result, "Disk drive closed.", "Failed to close disk drive."))).unwrap();
+    });
+}
 
-                let mut target = tempfile_fast::PersistableTempFile::new_in("./").unwrap();
-                // let mut target = fs::OpenOptions::new().write(true).create(true).open(format!("{}.iso", info.name)).unwrap();
+fn set_drive_mode(s: &mut Cursive, drive: &DiskDrive, mode: DriveMode) {
+    if is_drive_busy(drive) {
+        s.add_layer(Dialog::text("This drive is busy. Wait for it to finish before switching modes.")
+            .button("Ok", |s| { s.pop_layer(); } ));
+        return;
+    }
 
-                counter.set(0);
+    *drive.mode.lock().unwrap() = mode;
+    drive.state.wake();
+}
 
-                let mut progress: usize = 0;
-                let length = info.length as f64;
+// Turns away burning and erasing on drives that can't write, rather than letting wodim fail on every disk.
+fn check_drive_can_write(s: &mut Cursive, drive: &DiskDrive) -> bool {
+    if drive.capabilities.as_ref().map(|capabilities| capabilities.can_write).unwrap_or(true) {
+        return true;
+    }
 
-                match copy_disk_to_iso(&drive.file, &mut target, info.length, info.block_size, |read| {
-                    progress += read;
-                    counter.set((((progress as f64) / length) * 1000.0) as usize);
-                },
-                |error| {
-                    // Called when there's a non-fatal error.
-                    *drive.status_message.lock().unwrap() = match error {
-                        CopyError::Read(err) => DriveStatus::NonFatalCopyReadError(err),
-                        CopyError::Write(err) => DriveStatus::NonFatalCopyWriteError(err),
-                        CopyError::None => DriveStatus::Copying,
-                    };
-                }) {
-                    Ok(()) => {
-                        *drive.status_message.lock().unwrap() = DriveStatus::WaitingForName;
+    s.add_layer(Dialog::text("This drive can't write disks.")
+        .button("Ok", |s| { s.pop_layer(); } ));
+    false
+}
 
-                        // Wait for name.
-                        loop {
-                            let status = drive.status_message.lock().unwrap().clone();
+fn show_burn_dialog(s: &mut Cursive, drive: &Arc<DiskDrive>) {
+    if !check_drive_can_write(s, drive) {
+        return;
+    }
 
-                            match status {
+    let drive = drive.clone();
 
-                                DriveStatus::Saving(name) => { // We have the name! Save it!
-                                    target.persist_by_rename(name).unwrap();
-                                    break;
-                                }
+    s.add_layer(Dialog::around(ListView::new()
+            .child("Image to burn: ", EditView::new().with_id("burn-image").min_width(30)))
+        .title(format!("Burn Mode: {}", drive.file))
+        .button("Cancel", |s| { s.pop_layer(); })
+        .button("Start", move |s| {
+            let image = s.find_id::<EditView>("burn-image").unwrap().get_content();
+
+            if !Path::new(image.as_ref()).is_file() {
+                s.add_layer(Dialog::text(format!("{} is not an image file.", image))
+                    .button("Ok", |s| { s.pop_layer(); } ));
+                return;
+            }
 
-                                _=> { // Wait.
-                                    thread::sleep(Duration::from_millis(5000));
-                                }
-                            }
-                        }
+            s.pop_layer();
+            set_drive_mode(s, &drive, DriveMode::Burn(image.as_ref().clone()));
+        })
+    );
+}
 
-                        *drive.status_message.lock().unwrap() = DriveStatus::Done;
-                    },
-                    Err(error) => {
-                        *drive.status_message.lock().unwrap() = match error {
-                            CopyError::Read(err) => DriveStatus::CopyReadError(err),
-                            CopyError::Write(err) => DriveStatus::CopyWriteError(err),
-                            CopyError::None => DriveStatus::Copying, // Should never happen.
-                        };
-                    }
-                }
+fn show_erase_dialog(s: &mut Cursive, drive: &Arc<DiskDrive>) {
+    if !check_drive_can_write(s, drive) {
+        return;
+    }
+
+    let drive1 = drive.clone();
+    let drive2 = drive.clone();
+
+    s.add_layer(Dialog::text("Erase every rewritable disk put in this drive?\nA quick erase only wipes the table of contents. A full erase overwrites the whole disk and takes much longer.")
+        .title(format!("Erase Mode: {}", drive.file))
+        .h_align(HAlign::Center)
+        .button("Cancel", |s| { s.pop_layer(); })
+        .button("Quick", move |s| {
+            s.pop_layer();
+            set_drive_mode(s, &drive1, DriveMode::Erase(BlankMode::Quick));
+        })
+        .button("Full", move |s| {
+            s.pop_layer();
+            set_drive_mode(s, &drive2, DriveMode::Erase(BlankMode::Full));
+        })
+    );
+}
+
+fn fill_verify_images(s: &mut Cursive, catalog: &Mutex<Catalog>, query: &str) {
+    // Shouldn't fail since we made this.
+    let mut results = s.find_id::<SelectView<String>>("verify-results").unwrap();
+    results.clear();
+
+    // Only images still sitting here. Ones that went to CHD or the dedup store aren't the bytes on the disk anymore.
+    for entry in catalog.lock().unwrap().search(query) {
+        if Path::new(&entry.image).is_file() && !entry.image.ends_with(".chd") && !entry.image.ends_with(".manifest") {
+            results.add_item(format!("{}  {}  {}", catalog::format_date(entry.date), entry.image, entry.volume), entry.image.clone());
+        }
+    }
+}
+
+// For spot checking images made earlier, or by some other tool, against the disk they came from.
+fn show_verify_dialog(s: &mut Cursive, drive: &Arc<DiskDrive>, catalog: &Arc<Mutex<Catalog>>) {
+    let drive = drive.clone();
+    let catalog1 = catalog.clone();
+
+    let results = SelectView::<String>::new()
+        .on_submit(|s, image: &String| {
+            s.find_id::<EditView>("verify-image").unwrap().set_content(image.clone());
+        })
+        .with_id("verify-results")
+        .scrollable()
+        .fixed_height(10);
+
+    let search = EditView::new()
+        .on_edit(move |s, query, _| {
+            fill_verify_images(s, &catalog1, query);
+        });
+
+    let layout = LinearLayout::vertical()
+        .child(ListView::new().child("Search catalog: ", search))
+        .child(results)
+        .child(ListView::new().child("Image to verify: ", EditView::new().with_id("verify-image").min_width(30)));
+
+    s.add_layer(Dialog::around(layout.min_width(70))
+        .title(format!("Verify Mode: {}", drive.file))
+        .button("Cancel", |s| { s.pop_layer(); })
+        .button("Start", move |s| {
+            let image = s.find_id::<EditView>("verify-image").unwrap().get_content();
+
+            if !Path::new(image.as_ref()).is_file() {
+                s.add_layer(Dialog::text(format!("{} is not an image file.", image))
+                    .button("Ok", |s| { s.pop_layer(); } ));
+                return;
+            }
+
+            s.pop_layer();
+            set_drive_mode(s, &drive, DriveMode::Verify(image.as_ref().clone()));
+        })
+    );
+
+    fill_verify_images(s, catalog, "");
+}
+
+fn show_photos_dialog(s: &mut Cursive, drive: &Arc<DiskDrive>) {
+    let drive1 = drive.clone();
+    let drive2 = drive.clone();
+
+    s.add_layer(Dialog::text("Import the photos from every disk put in this drive into the photo library?\nPhotos already in the library are skipped. The disk can be archived as usual too, or just have its photos taken.")
+        .title(format!("Photo Mode: {}", drive.file))
+        .h_align(HAlign::Center)
+        .button("Cancel", |s| { s.pop_layer(); })
+        .button("Photos only", move |s| {
+            s.pop_layer();
+            set_drive_mode(s, &drive1, DriveMode::Photos(false));
+        })
+        .button("Photos and image", move |s| {
+            s.pop_layer();
+            set_drive_mode(s, &drive2, DriveMode::Photos(true));
+        })
+    );
+}
+
+fn get_catalog_entry_description(entry: &CatalogEntry) -> String {
+    let mut description = format!("Image: {}\nVolume: {}\n", entry.image, entry.volume);
+
+    if let Some(hfs_volume) = &entry.hfs_volume {
+        description += &format!("Mac volume: {}\n", hfs_volume);
+    }
+
+    if let Some(publisher) = &entry.publisher {
+        description += &format!("Publisher: {}\n", publisher);
+    }
+
+    description += &format!("Archived: {} UTC\nSize: {} bytes\nCRC32: {}\nSHA-1: {}\n",
+        catalog::format_date(entry.date), entry.size, entry.crc32, entry.sha1);
+
+    if let Some(md5) = &entry.md5 {
+        description += &format!("MD5: {}\n", md5);
+    }
+
+    if let Some(sha256) = &entry.sha256 {
+        description += &format!("SHA-256: {}\n", sha256);
+    }
+
+    if !entry.fingerprint.is_empty() {
+        description += &format!("Fingerprint: {}\n", entry.fingerprint);
+    }
+
+    if let Some(redump) = &entry.redump {
+        description += &format!("Verified known-good dump of {}\n", redump);
+    }
+
+    if let Some(backup) = &entry.backup {
+        description += &format!("Backed up to {}\n", backup);
+    }
+
+    if let Some(tape) = &entry.tape {
+        description += &format!("Tape: {}\n", tape);
+    }
+
+    if let Some(ia_item) = &entry.ia_item {
+        description += &format!("Published at https://archive.org/details/{}\n", ia_item);
+    }
+
+    for parity in entry.parity.iter() {
+        description += &format!("Parity: {}\n", parity);
+    }
+
+    for note in entry.notes.iter() {
+        description += &format!("Note: {}\n", note);
+    }
+
+    if let Some(damage) = &entry.damage {
+        description += &format!("Incomplete: {}\n", damage);
+    }
+
+    if let Some(label_photo) = &entry.label_photo {
+        description += &format!("Label photo: {}\n", label_photo);
+    }
+
+    if let Some(inventory_id) = &entry.inventory_id {
+        description += &format!("Inventory ID: {}\n", inventory_id);
+    }
+
+    if let Some(mirror) = &entry.mirror {
+        description += &format!("Mirror: {}\n", mirror);
+    }
+
+    description
+}
+
+// Everything the disk said about itself, which is a lot more than anyone usually wants to see.
+fn get_disc_details(entry: &CatalogEntry) -> String {
+    let fields = [("Volume", Some(&entry.volume)), ("Mac volume", entry.hfs_volume.as_ref()), ("Publisher", entry.publisher.as_ref()),
+        ("Data preparer", entry.preparer.as_ref()), ("Application", entry.application.as_ref()), ("Created", entry.created.as_ref())];
+
+    fields.iter()
+        .map(|(name, value)| format!("{}: {}\n", name, value.map(|value| value.as_str()).unwrap_or("(not set)")))
+        .collect()
+}
+
+fn fill_catalog_results(s: &mut Cursive, catalog: &Mutex<Catalog>, query: &str) {
+    // Shouldn't fail since we made this.
+    let mut results = s.find_id::<SelectView<u64>>("catalog-results").unwrap();
+    results.clear();
+
+    for entry in catalog.lock().unwrap().search(query) {
+        results.add_item(format!("{}  {}  {}", catalog::format_date(entry.date), entry.image, entry.volume), entry.id);
+    }
+}
+
+fn show_catalog_search(s: &mut Cursive, catalog: &Arc<Mutex<Catalog>>) {
+    let catalog1 = catalog.clone();
+    let catalog2 = catalog.clone();
+
+    let results = SelectView::<u64>::new()
+        .on_submit(move |s, id| {
+            show_catalog_entry(s, &catalog1, *id);
+        })
+        .with_id("catalog-results")
+        .scrollable()
+        .fixed_height(15);
+
+    let search = EditView::new()
+        .on_edit(move |s, query, _| {
+            fill_catalog_results(s, &catalog2, query);
+        });
+
+    let layout = LinearLayout::vertical()
+        .child(ListView::new().child("Search: ", search))
+        .child(results);
+
+    s.add_layer(Dialog::around(layout.min_width(70))
+        .title("Catalog")
+        .button("Close", |s| { s.pop_layer(); } ));
+
+    fill_catalog_results(s, catalog, "");
+}
+
+fn show_catalog_entry(s: &mut Cursive, catalog: &Mutex<Catalog>, id: u64) {
+    let (description, image, details) = match catalog.lock().unwrap().get(id) {
+        Some(entry) => (get_catalog_entry_description(entry), entry.image.clone(), get_disc_details(entry)),
+        None => return,
+    };
+
+    s.add_layer(Dialog::text(description)
+        .title("Catalog Entry")
+        .button("Files...", move |s| { show_disk_files(s, id, &image); })
+        .button("Disc details...", move |s| {
+            s.add_layer(Dialog::text(details.clone())
+                .title("Disc Details")
+                .button("Ok", |s| { s.pop_layer(); } ));
+        })
+        .button("Ok", |s| { s.pop_layer(); } ));
+}
+
+const FILE_UNCHECKED: &str = "[ ] ";
+const FILE_CHECKED: &str = "[x] ";
+
+// Lets the operator pick files off an archived disk and pull them out of its ISO.
+fn show_disk_files(s: &mut Cursive, id: u64, image: &str) {
+    // CHDs, dedup manifests, and images that only live in a backup repository can't be read by isoinfo.
+    if !image.to_lowercase().ends_with(".iso") || !Path::new(image).is_file() {
+        s.add_layer(Dialog::text(format!("{} isn't an ISO that's still here, so its files can't be extracted.", image))
+            .button("Ok", |s| { s.pop_layer(); } ));
+        return;
+    }
+
+    let files = match file_index::get_files(id) {
+        Ok(files) if !files.is_empty() => files,
+        Ok(_) => {
+            s.add_layer(Dialog::text("This disk's files aren't in the index. Run \"auto_archive index\" to add them.")
+                .button("Ok", |s| { s.pop_layer(); } ));
+            return;
+        },
+        Err(error) => {
+            s.add_layer(Dialog::text(format!("Failed to read the file index: {}", error))
+                .button("Ok", |s| { s.pop_layer(); } ));
+            return;
+        },
+    };
+
+    let mut list = SelectView::<String>::new()
+        .on_submit(|s, _: &String| {
+            // Shouldn't fail since we made this.
+            let mut list = s.find_id::<SelectView<String>>("disk-files").unwrap();
+
+            if let Some((label, file)) = list.selected_id().and_then(|index| list.get_item_mut(index)) {
+                let checked = label.source().starts_with(FILE_CHECKED);
+                *label = format!("{}{}", if checked { FILE_UNCHECKED } else { FILE_CHECKED }, file).into();
+            }
+        });
+
+    for file in files {
+        list.add_item(format!("{}{}", FILE_UNCHECKED, file), file);
+    }
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Press enter on a file to select it."))
+        .child(list.with_id("disk-files").scrollable().fixed_height(15))
+        .child(ListView::new().child("Extract to: ", EditView::new().content(".").with_id("extract-to").min_width(30)));
+
+    let image = String::from(image);
+
+    s.add_layer(Dialog::around(layout.min_width(70))
+        .title(format!("Files on {}", image))
+        .button("Close", |s| { s.pop_layer(); })
+        .button("Extract", move |s| {
+            let destination = s.find_id::<EditView>("extract-to").unwrap().get_content();
+            let selected: Vec<String> = s.find_id::<SelectView<String>>("disk-files").unwrap().iter()
+                .filter(|(label, _)| label.starts_with(FILE_CHECKED))
+                .map(|(_, file)| file.clone())
+                .collect();
+
+            if selected.is_empty() {
+                s.add_layer(Dialog::text("Select some files first.")
+                    .button("Ok", |s| { s.pop_layer(); } ));
+                return;
+            }
+
+            spawn_extract(s, &image, selected, destination.as_ref());
+        })
+    );
+}
+
+// Big files can take a while to come out, so keep it off the UI thread.
+fn spawn_extract(s: &mut Cursive, image: &str, files: Vec<String>, destination: &str) {
+    let image = String::from(image);
+    let destination = String::from(destination);
+    let cb = s.cb_sink().clone();
+
+    s.add_layer(Dialog::text(format!("Extracting {} files...", files.len())));
+
+    thread::spawn(move || {
+        let mut result = Ok(());
+
+        for file in files.iter() {
+            // Keep the disk's directories, so files with the same name in different places don't overwrite each other.
+            let output = Path::new(&destination).join(file.trim_start_matches('/'));
+
+            result = file_index::extract_file(&image, file, &output);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        let message = match result {
+            Ok(()) => format!("Extracted {} files to {}.", files.len(), destination),
+            Err(message) => format!("Extraction stopped. {}", message),
+        };
+
+        cb.send(Box::new(move |s| {
+            s.pop_layer();
+            s.add_layer(Dialog::text(message)
+                .button("Ok", |s| { s.pop_layer(); } ));
+        })).unwrap();
+    });
+}
+
+// There's no telling how big the file index is, so cap how much of it we show.
+const FILE_SEARCH_LIMIT: usize = 500;
+
+fn show_file_search(s: &mut Cursive, catalog: &Arc<Mutex<Catalog>>) {
+    let catalog1 = catalog.clone();
+    let catalog2 = catalog.clone();
+
+    let results = SelectView::<u64>::new()
+        .on_submit(move |s, id| {
+            show_catalog_entry(s, &catalog1, *id);
+        })
+        .with_id("file-results")
+        .scrollable()
+        .fixed_height(15);
+
+    // Searched on enter rather than as you type, since it reads through the whole index.
+    let search = EditView::new()
+        .on_submit(move |s, query| {
+            let found = match file_index::search(query, FILE_SEARCH_LIMIT) {
+                Ok(found) => found,
+                Err(error) => {
+                    s.add_layer(Dialog::text(format!("Failed to read the file index: {}", error))
+                        .button("Ok", |s| { s.pop_layer(); } ));
+                    return;
+                },
+            };
+
+            // Shouldn't fail since we made this.
+            let mut results = s.find_id::<SelectView<u64>>("file-results").unwrap();
+            results.clear();
+
+            let catalog = catalog2.lock().unwrap();
+            for (id, file) in found {
+                // Disks can be replaced in the catalog after their files were indexed.
+                if let Some(entry) = catalog.get(id) {
+                    results.add_item(format!("{}  on {} ({})", file, entry.volume, entry.image), id);
+                }
+            }
+        });
+
+    let layout = LinearLayout::vertical()
+        .child(ListView::new().child("File name: ", search))
+        .child(results);
+
+    s.add_layer(Dialog::around(layout.min_width(70))
+        .title("Find a File")
+        .button("Close", |s| { s.pop_layer(); } ));
+}
+
+fn get_drive_title(drive: &DiskDrive) -> String {
+    let mut title = match drive.name.lock().unwrap().as_ref() {
+        Some(name) => format!("Drive: {} ({})", name, drive.file),
+        None => format!("Drive: {}", drive.file),
+    };
+
+    if let Some(remote) = &drive.remote {
+        title += &format!(" on {}", remote.describe());
+    }
+
+    if let Some(identity) = &drive.identity {
+        title += &format!(" - {} {} {}", identity.vendor, identity.model, identity.firmware);
+    } else if let Some(model) = &drive.model {
+        title += &format!(" - {}", model);
+    }
+
+    if let Some(key) = get_hotkey(drive) {
+        title += &format!(" [Alt+{}]", key);
+    }
+
+    title
+}
+
+fn show_drive_settings(s: &mut Cursive, drive: &Arc<DiskDrive>) {
+    let drive = drive.clone();
+    let name = drive.name.lock().unwrap().clone().unwrap_or_default();
+
+    let mut auto_name = Checkbox::new();
+    auto_name.set_checked(drive.auto_name.load(Relaxed));
+
+    let current_profile = *drive.profile.lock().unwrap();
+    let mut profile = SelectView::new().popup();
+    profile.add_item("Same as the config file", None);
+    for (index, choice) in profile::PROFILES.iter().enumerate() {
+        profile.add_item(choice.name(), Some(*choice));
+
+        if current_profile == Some(*choice) {
+            profile.set_selection(index + 1);
+        }
+    }
+
+    let key = drive.settings_key.clone();
+    let mut note = match &key {
+        Some(key) => format!("Kept for {}.", key),
+        None => String::from("This drive has no serial number, so these only last until auto_archive is closed."),
+    };
+
+    if let Some(profile) = drive.benchmark.lock().unwrap().as_ref() {
+        note += &format!("\nBenchmarked {}. {}", catalog::format_date(profile.measured), profile.summary());
+    }
+
+    s.add_layer(Dialog::around(LinearLayout::vertical()
+            .child(TextView::new(note))
+            .child(ListView::new()
+                .child("Name: ", EditView::new().content(name).with_id("drive-name").min_width(30))
+                .child("Save without asking for a name: ", auto_name.with_id("drive-auto-name"))
+                .child("Rip profile: ", profile.with_id("drive-profile"))))
+        .title(format!("Settings: {}", drive.file))
+        .button("Cancel", |s| { s.pop_layer(); })
+        .button("Save", move |s| {
+            let name = s.find_id::<EditView>("drive-name").unwrap().get_content();
+            let settings = drive_settings::DriveSettings {
+                name: if name.trim().is_empty() { None } else { Some(String::from(name.trim())) },
+                auto_name: s.find_id::<Checkbox>("drive-auto-name").unwrap().is_checked(),
+                benchmark: drive.benchmark.lock().unwrap().clone(),
+                profile: *s.find_id::<SelectView<Option<RipProfile>>>("drive-profile").unwrap().selection().unwrap(),
+            };
+
+            *drive.name.lock().unwrap() = settings.name.clone();
+            drive.auto_name.store(settings.auto_name, Relaxed);
+            *drive.profile.lock().unwrap() = settings.profile;
+
+            if let Some(mut dialog) = s.find_id::<Dialog>(&format!("drive-{}", drive.panel)) {
+                dialog.set_title(get_drive_title(&drive));
+            }
+
+            s.pop_layer();
+
+            if let Some(key) = &key {
+                if let Err(error) = drive_settings::save(key, &settings) {
+                    s.add_layer(Dialog::text(format!("Failed to save the drive's settings: {}", error))
+                        .button("Ok", |s| { s.pop_layer(); } ));
+                }
+            }
+        })
+    );
+}
+
+fn add_drive_ui_buttons(drive: &Arc<DiskDrive>, catalog: &Arc<Mutex<Catalog>>, linear: &mut LinearLayout) {
+
+    let drive1 = drive.clone();
+    let drive2 = drive.clone();
+    let drive3 = drive.clone();
+    let drive4 = drive.clone();
+    let drive5 = drive.clone();
+    let drive6 = drive.clone();
+
+    let mut buttons = LinearLayout::horizontal();
+
+    let drive7 = drive.clone();
+    if role::is_admin() {
+        buttons.add_child(Button::new("Settings...", move |s| {
+            show_drive_settings(s, &drive7);
+        }));
+    }
+
+    let drive10 = drive.clone();
+    buttons.add_child(Button::new("Activity...", move |s| {
+        show_drive_activity(s, &drive10);
+    }));
+
+    // Floppies eject with a button on the drive, and can't tell us when a disk is in.
+    if drive.kind == DriveKind::Floppy {
+        let drive = drive.clone();
+
+        buttons.add_child(Button::new("Read disk", move |s| {
+            if is_drive_busy(&drive) || drive.has_disk.load(Relaxed) {
+                s.add_layer(Dialog::text("This drive is already reading a disk.")
+                    .button("Ok", |s| { s.pop_layer(); } ));
+                return;
+            }
+
+            drive.has_disk.store(true, Relaxed);
+            drive.state.wake();
+        }));
+
+        linear.add_child(buttons.full_width());
+        return;
+    }
+
+    buttons.add_child(Button::new("Eject", move |s| {
+        if is_drive_busy(&drive1) {
+            // Ejecting now would throw away the rip, so make sure that's what they want.
+            let drive = drive1.clone();
+
+            s.add_layer(Dialog::text("This drive is still copying. Ejecting now will abort the rip and discard what has been copied so far. Eject anyway?")
+                .title("Confirm Eject")
+                .h_align(HAlign::Center)
+                .button("No", |s| { s.pop_layer(); })
+                .button("Yes", move |s| {
+                    s.pop_layer();
+
+                    drive.cancel.store(true, Relaxed);
+                    drive.state.wake();
+                    spawn_eject(s, &drive);
+                })
+            );
+        } else {
+            spawn_eject(s, &drive1);
+        }
+    }));
+
+    // Block devices don't have trays and can't be burned.
+    if drive.kind == DriveKind::Optical {
+        buttons.add_child(Button::new("Close", move |s| {
+            spawn_close(s, &drive2);
+        }));
+
+        // Operators only get to rip.
+        if role::is_admin() {
+            buttons.add_child(Button::new("Burn...", move |s| {
+                show_burn_dialog(s, &drive3);
+            }));
+            buttons.add_child(Button::new("Erase...", move |s| {
+                show_erase_dialog(s, &drive5);
+            }));
+            buttons.add_child(Button::new("Photos...", move |s| {
+                show_photos_dialog(s, &drive6);
+            }));
+
+            let drive8 = drive.clone();
+            let catalog = catalog.clone();
+            buttons.add_child(Button::new("Verify...", move |s| {
+                show_verify_dialog(s, &drive8, &catalog);
+            }));
+
+            let drive9 = drive.clone();
+            buttons.add_child(Button::new("Benchmark", move |s| {
+                set_drive_mode(s, &drive9, DriveMode::Benchmark);
+            }));
+        }
+
+        buttons.add_child(Button::new("Rip", move |s| {
+            set_drive_mode(s, &drive4, DriveMode::Rip);
+        }));
+    }
+
+    linear.add_child(buttons.full_width());
+}
+
+// Everything that's happened to the drive since auto_archive started. Scrolled to the bottom, since that's the newest.
+fn show_drive_activity(s: &mut Cursive, drive: &DiskDrive) {
+    let mut activity = drive.activity.describe();
+    if activity.is_empty() {
+        activity = String::from("Nothing's happened yet.");
+    }
+
+    let mut view = TextView::new(activity).scrollable();
+    view.set_scroll_strategy(cursive::view::ScrollStrategy::StickToBottom);
+
+    s.add_layer(Dialog::around(view.max_height(20))
+        .title(format!("Activity: {}", drive.file))
+        .button("Close", |s| { s.pop_layer(); }));
+}
+
+// Calls `handler` on the UI thread with everything that happens to the drive from now on.
+fn on_drive_events<F>(s: &mut Cursive, drive: &DiskDrive, handler: F) where
+    F: Fn(&mut Cursive, &DriveEvent) + Send + Sync + 'static {
+
+    let events = events::subscribe();
+    let file = drive.file.clone();
+    let cb = s.cb_sink().clone();
+    let handler = Arc::new(handler);
+
+    thread::spawn(move || {
+        for event in events.iter().filter(|event| event.drive == file) {
+            let handler = handler.clone();
+
+            // The UI is gone, so there's no one left to tell.
+            if cb.send(Box::new(move |s| handler(s, &event.event))).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// The bar only goes to 1000, so the real numbers go on its label.
+fn add_progress_bar(s: &mut Cursive, drive: &Arc<DiskDrive>, linear: &mut LinearLayout, progress_id: &str) {
+    let progress = drive.progress.clone();
+    linear.add_child(ProgressBar::new().max(1000).with_label(move |_, _| progress.describe()).with_id(progress_id));
+
+    let progress = drive.progress.clone();
+    let progress_id = String::from(progress_id);
+    s.add_global_callback(Event::Refresh, move |s| {
+        // Shouldn't fail since we made this.
+        s.find_id::<ProgressBar>(&progress_id).unwrap().set_value(progress.permille());
+    });
+}
+
+fn add_status_indicator(s: &mut Cursive, drive: &Arc<DiskDrive>, linear: &mut LinearLayout, status_id: &str, tray_id: &str) {
+
+    let drive = drive.clone();
+    let status_id = String::from(status_id);
+    let tray_id = String::from(tray_id);
+
+    linear.add_child(TextView::new("----").with_id(&tray_id));
+    linear.add_child(TextView::new(get_drive_status_message_string(&drive.state.lock())).with_id(&status_id));
+
+    // The status moves on once the next disk goes in, but it's handy to know what the last one was saved as.
+    let archived_id = format!("archived-{}", drive.panel);
+    linear.add_child(TextView::new("").with_id(&archived_id));
+
+    // Shouldn't fail since we made these.
+    on_drive_events(s, &drive, move |s, event| match event {
+        DriveEvent::Status(status) => {
+            s.find_id::<TextView>(&status_id).unwrap().set_content(get_drive_status_message_string(status));
+        },
+        DriveEvent::Archived(entry) => {
+            s.find_id::<TextView>(&archived_id).unwrap().set_content(format!("Last archived as {}.", entry.image));
+        },
+        _ => {},
+    });
+
+    s.add_global_callback(Event::Refresh, move |s| {
+        // Shouldn't fail since we made this.
+        let mut tray = s.find_id::<TextView>(&tray_id).unwrap();
+
+        let mut tray_message = String::from(match drive.kind {
+            DriveKind::Optical => get_tray_status_message_string(*drive.tray_status.lock().unwrap()),
+            DriveKind::Block => "Block device.",
+            DriveKind::Floppy => "Floppy drive.",
+        });
+
+        if let Some(media) = drive.media.lock().unwrap().as_ref() {
+            tray_message += &format!(" {}.", media);
+        }
+
+        if let Some(fingerprint) = drive.fingerprint.lock().unwrap().as_ref() {
+            tray_message += &format!(" Fingerprint: {}", fingerprint);
+        }
+
+        if let Some(image) = drive.duplicate_of.lock().unwrap().as_ref() {
+            tray_message += &format!(" (Already archived as {}!)", image);
+        }
+
+        tray.set_content(tray_message);
+    });
+}
+
+// Waits until there's a disk this drive can work on in its current mode, and returns that mode.
+async fn wait_for_disk(drive: &DiskDrive, live_config: &LiveConfig) -> DriveMode {
+    let mut blank_seen = false;
+
+    loop {
+        // Made before checking, so a change in between isn't missed.
+        let changed = drive.state.changed();
+        let mode = drive.mode.lock().unwrap().clone();
+
+        if drive.paused.load(Relaxed) {
+            show_drive_status(drive, DriveStatus::Paused);
+            changed.await;
+            continue;
+        }
+
+        match &mode {
+            DriveMode::Rip | DriveMode::Photos(_) => {
+                if drive.has_disk.load(Relaxed) {
+                    return mode;
+                }
+
+                if drive.kind != DriveKind::Floppy {
+                    show_drive_status(drive, DriveStatus::NoDisk);
+                } else if drive.state.get() == DriveStatus::Setup {
+                    // Floppies don't tell us when they're pulled, so leave the last result up until the next one is read.
+                    show_drive_status(drive, DriveStatus::WaitingForFloppy);
+                }
+            },
+            DriveMode::Burn(image) => {
+                // A loaded disk blkid can't see is (probably) blank. Make sure blkid has had a chance to look first.
+                let blank = !drive.has_disk.load(Relaxed) && *drive.tray_status.lock().unwrap() == TrayStatus::Loaded;
+                if blank && blank_seen {
+                    return mode;
+                }
+                blank_seen = blank;
+
+                show_drive_status(drive, DriveStatus::WaitingForBlank(image.clone()));
+            },
+            DriveMode::Verify(image) => {
+                if drive.has_disk.load(Relaxed) {
+                    return mode;
+                }
+
+                show_drive_status(drive, DriveStatus::WaitingForDiskToVerify(image.clone()));
+            },
+            DriveMode::Benchmark => {
+                if drive.has_disk.load(Relaxed) {
+                    return mode;
+                }
+
+                show_drive_status(drive, DriveStatus::WaitingForDiskToBenchmark);
+            },
+            DriveMode::Erase(blank_mode) => {
+                // Anything in the tray is fair game. wodim will refuse disks that aren't rewritable.
+                if *drive.tray_status.lock().unwrap() == TrayStatus::Loaded {
+                    return mode;
+                }
+
+                show_drive_status(drive, DriveStatus::WaitingForRewritable(*blank_mode));
+            },
+        }
+
+        if blank_seen {
+            // Nothing changes when blkid doesn't find anything, so just give it a chance to look.
+            tokio::time::sleep(live_config.get().polling.tray).await;
+        } else {
+            changed.await;
+        }
+    }
+}
+
+// An earlier rip of this disk that still has bad regions to fill in, and its image is still here to fill them into.
+fn find_patchable_rip(catalog: &Mutex<Catalog>, fingerprint: Option<&str>) -> Option<CatalogEntry> {
+    let catalog = catalog.lock().unwrap();
+    let entry = catalog.find_fingerprint(fingerprint?)?;
+
+    let patchable = entry.damage.is_some()
+        && Path::new(&entry.image).is_file()
+        && Path::new(&format!("{}.map", entry.image)).is_file();
+
+    if patchable { Some(entry.clone()) } else { None }
+}
+
+// Asks the operator whether to patch the earlier rip, and waits for an answer. None if the disk went away first.
+fn offer_patch(drive: &DiskDrive, image: &str) -> Option<bool> {
+    if set_drive_status(drive, DriveStatus::OfferingPatch(String::from(image))).is_err() {
+        return None;
+    }
+
+    drive.state.wait_until(|status| match status {
+        DriveStatus::OfferingPatch(_) | DriveStatus::ConfirmingPatch(_) if drive.has_disk.load(Relaxed) => None,
+        DriveStatus::OfferingPatch(_) | DriveStatus::ConfirmingPatch(_) => Some(None),
+        DriveStatus::Patching(_) => Some(Some(true)),
+        _ => Some(Some(false)),
+    })
+}
+
+// Re-reads the bad regions of an earlier rip and fills in what it can, ddrescue style, instead of ripping it all again.
+fn patch_disk(drive: &DiskDrive, config: &Config, catalog: &Mutex<Catalog>, entry: &CatalogEntry, block_size: usize) {
+    let image = &entry.image;
+    let map_path = format!("{}.map", image);
+
+    let map = match mapfile::read_mapfile(&map_path) {
+        Ok(map) => map,
+        Err(error) => {
+            show_drive_status(drive, DriveStatus::CopyReadError(format!("Couldn't read {}: {}", map_path, error)));
+            return;
+        },
+    };
+
+    if set_drive_status(drive, DriveStatus::Patching(image.clone())).is_err() {
+        return;
+    }
+    drive.progress.start(map.bad_bytes() as u64, ProgressUnit::Bytes);
+
+    let max_speed = match drive.kind {
+        DriveKind::Optical => Some(drive.capabilities.as_ref().map(|capabilities| capabilities.max_read_speed).unwrap_or(0)),
+        _ => None,
+    };
+
+    let result = patch_image_from_disk(&drive.file, image, &map, block_size, max_speed, &drive.cancel, |read| {
+        drive.progress.add(read as u64);
+    });
+
+    let damage = match result {
+        Ok(damage) => damage,
+        Err(error) => {
+            show_drive_status(drive, match error {
+                CopyError::Read(err) => DriveStatus::CopyReadError(err),
+                CopyError::Write(err) => DriveStatus::CopyWriteError(err),
+                // Whatever was patched is already in the image, and the rest can be patched next time.
+                CopyError::Disconnected(_, _) => DriveStatus::Disconnected(None),
+                CopyError::Cancelled | CopyError::None => DriveStatus::Cancelled,
+            });
+            return;
+        },
+    };
+
+    // Done with the mapfile once there's nothing left in it. Otherwise it's what's left for the next drive to try.
+    let mut report = serde_json::Map::new();
+    report.insert(String::from("incomplete"), serde_json::Value::Bool(!damage.is_clean()));
+    report.insert(String::from("damage"), if damage.is_clean() { serde_json::Value::Null } else { damage.report() });
+
+    let result = if damage.is_clean() { fs::remove_file(&map_path) } else { damage.write_mapfile(&map_path) }
+        .and_then(|()| sidecar::update(image, report))
+        .and_then(|()| hash::hash_file_with(image, &config.extra_hashes, config.parallel_hashing));
+
+    let hashes = match result {
+        Ok(hashes) => hashes,
+        Err(error) => {
+            show_drive_status(drive, DriveStatus::CopyWriteError(format!("{}", error)));
+            return;
+        },
+    };
+
+    // The old parity was made from the damaged image, and would put the damage back if it was used to repair it.
+    for file in entry.parity.iter() {
+        let _ = fs::remove_file(file);
+    }
+
+    let parity = match config.par2_redundancy {
+        Some(redundancy) if !entry.parity.is_empty() => {
+            show_drive_status(drive, DriveStatus::GeneratingParity(image.clone()));
+
+            // Along with whatever else of the disk's it was made for.
+            let path = Path::new(image);
+            let mut files = vec![image.clone()];
+            files.extend(["sub", "ccd"].iter()
+                .map(|extension| path.with_extension(extension))
+                .filter(|file| file.exists())
+                .map(|file| file.to_string_lossy().into_owned()));
+
+            par2::create_parity(&files, redundancy)
+        },
+        _ => Ok(Vec::new()),
+    };
+
+    // The image changed, so its hashes did too.
+    let mut catalog = catalog.lock().unwrap();
+    if let Some(entry) = catalog.get_mut(entry.id) {
+        entry.parity = parity.as_ref().cloned().unwrap_or_default();
+        entry.size = hashes.size;
+        entry.crc32 = hashes.crc32;
+        entry.sha1 = hashes.sha1;
+        entry.md5 = hashes.md5;
+        entry.sha256 = hashes.sha256;
+        entry.damage = if damage.is_clean() { None } else { Some(damage.summary()) };
+    }
+
+    show_drive_status(drive, match (catalog.save(), parity) {
+        (Err(error), _) => DriveStatus::CatalogError(catalog::get_catalog_error_message(&error)),
+        (Ok(()), Err(error)) => DriveStatus::ParityError(par2::get_par2_error_message(&error)),
+        (Ok(()), Ok(_)) => DriveStatus::Patched(map.bad_bytes().saturating_sub(damage.bad_bytes()), damage.bad_bytes()),
+    });
+}
+
+// Flags the disk as already archived if its fingerprint is in the catalog. Returns true if it should be skipped.
+fn check_for_duplicate(drive: &DiskDrive, config: &Config, catalog: &Mutex<Catalog>, fingerprint: Option<&str>) -> bool {
+    let duplicate = fingerprint.and_then(|fingerprint| {
+        let local = catalog.lock().unwrap().find_fingerprint(fingerprint).map(|entry| entry.image.clone());
+        local.or_else(|| stations::find_duplicate(config, fingerprint))
+    });
+
+    if let Some(image) = duplicate {
+        if config.skip_duplicates {
+            show_drive_status(drive, DriveStatus::AlreadyArchived(image));
+            return true;
+        }
+
+        *drive.duplicate_of.lock().unwrap() = Some(image);
+    }
+
+    false
+}
+
+fn suggest_name(cb: &CbSink, name_id: &str, ready_id: &str, name: &str) {
+    let name_id = String::from(name_id);
+    let ready_id = String::from(ready_id);
+    let name = String::from(name);
+
+    cb.send(Box::new(move |s| {
+        let mut text_box = s.find_id::<EditView>(&name_id).unwrap();
+        let mut ready_checkbox = s.find_id::<Checkbox>(&ready_id).unwrap();
+
+        ready_checkbox.set_checked(false);
+        text_box.set_content(name);
+    })).unwrap();
+}
+
+// Changes the name in the box, unless the operator's already put in one of their own.
+fn update_suggested_name(cb: &CbSink, name_id: &str, old_name: &str, name: &str) {
+    let name_id = String::from(name_id);
+    let old_name = String::from(old_name);
+    let name = String::from(name);
+
+    cb.send(Box::new(move |s| {
+        let mut text_box = s.find_id::<EditView>(&name_id).unwrap();
+
+        if *text_box.get_content() == old_name {
+            text_box.set_content(name);
+        }
+    })).unwrap();
+}
+
+// Waits for the operator to name the rip, and returns the name. None if the disk was ejected before it got one.
+fn wait_for_name(drive: &DiskDrive, default_name: &str) -> Option<String> {
+    *drive.suggested_name.lock().unwrap() = Some(String::from(default_name));
+    drive.overwrite.store(false, Relaxed);
+
+    let status = if drive.auto_name.load(Relaxed) && !Path::new(default_name).exists() {
+        DriveStatus::Saving(String::from(default_name))
+    } else {
+        DriveStatus::WaitingForName
+    };
+
+    if set_drive_status(drive, status).is_err() {
+        return None;
+    }
+
+    drive.state.wait_until(|status| match status {
+        DriveStatus::Saving(name) => Some(Some(name.clone())), // We have the name!
+        _ if drive.cancel.load(Relaxed) => Some(None),
+        _ => None, // Wait.
+    })
+}
+
+// Takes the name for the drive's image by making an empty file under it, so no other drive can be saved under it too.
+// The image replaces the empty file once it's saved. Names the operator said to overwrite are already theirs. False
+// if something else got the name first. Names in a folder, like movies, get their folder made.
+fn claim_name(drive: &DiskDrive, name: &str) -> io::Result<bool> {
+    if drive.overwrite.load(Relaxed) {
+        return Ok(true);
+    }
+
+    if let Some(folder) = Path::new(name).parent().filter(|folder| !folder.as_os_str().is_empty()) {
+        fs::create_dir_all(folder)?;
+    }
+
+    match fs::OpenOptions::new().write(true).create_new(true).open(name) {
+        Ok(_) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+// Names a drive that's waiting for one, from somewhere other than the drive's own name box. Files that already exist
+// are turned down, since there's no one to ask about overwriting them.
+fn name_drive(drive: &DiskDrive, name: &str) -> Result<(), String> {
+    check_remote_name(name)?;
+
+    if Path::new(name).exists() {
+        return Err(format!("{} already exists. Pick another name.", name));
+    }
+
+    drive.state.transition_if(|status| *status == DriveStatus::WaitingForName, DriveStatus::Saving(String::from(name)))
+        .map_err(|error| match error.from {
+            DriveStatus::WaitingForName => drive_state::get_illegal_transition_message(&error),
+            _ => format!("{} isn't waiting for a name.", drive.file),
+        })
+}
+
+// Names from elsewhere are just a file name in the working directory. Anything else could put an image anywhere we
+// can write.
+fn check_remote_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(String::from("That's not a name."));
+    }
+
+    if name.contains('/') || name.contains('\0') {
+        return Err(String::from("Names can't have a / in them. Images are always saved in the working directory."));
+    }
+
+    Ok(())
+}
+
+// Finishes the disk in the drive, if there is one, and then waits until it's resumed.
+fn set_drive_paused(drive: &DiskDrive, paused: bool) {
+    drive.paused.store(paused, Relaxed);
+    drive.state.wake();
+}
+
+// Runs the hook script for an event, if there is one. What the drive knows about the disk goes along with `disk`.
+fn run_disk_hook(drive: &DiskDrive, config: &Config, event: HookEvent, mut disk: serde_json::Map<String, serde_json::Value>) -> Result<Option<String>, HookError> {
+    let script = match &config.hook_script {
+        Some(script) => script,
+        None => return Ok(None),
+    };
+
+    disk.insert(String::from("drive"), serde_json::Value::from(drive.file.clone()));
+    disk.insert(String::from("fingerprint"), serde_json::Value::from(drive.fingerprint.lock().unwrap().clone()));
+    disk.insert(String::from("duplicate_of"), serde_json::Value::from(drive.duplicate_of.lock().unwrap().clone()));
+
+    hooks::run_hook(script, event, &serde_json::Value::Object(disk))
+}
+
+// Tells the hook script a disk is done, with its catalog entry.
+fn run_finished_hook(drive: &DiskDrive, config: &Config, entry: &CatalogEntry) -> Result<(), HookError> {
+    let disk = match export::entry_to_json(entry) {
+        serde_json::Value::Object(disk) => disk,
+        _ => serde_json::Map::new(),
+    };
+
+    run_disk_hook(drive, config, HookEvent::Finished, disk).map(|_| ())
+}
+
+// Asks the hook script whether to rip the disk, and then what to call it. None if it shouldn't be ripped, in which
+// case the drive's status says why.
+fn run_naming_hooks(drive: &DiskDrive, config: &Config, volume: &str, size: u64, default_name: &str) -> Option<String> {
+    let mut disk = serde_json::Map::new();
+    disk.insert(String::from("volume"), serde_json::Value::from(volume));
+    disk.insert(String::from("size"), serde_json::Value::from(size));
+
+    let skip = match run_disk_hook(drive, config, HookEvent::Detected, disk.clone()) {
+        Ok(skip) => skip,
+        Err(error) => {
+            show_drive_status(drive, DriveStatus::HookError(hooks::get_hook_error_message(&error)));
+            return None;
+        },
+    };
+
+    if let Some(reason) = skip {
+        show_drive_status(drive, DriveStatus::SkippedByHook(reason));
+        return None;
+    }
+
+    disk.insert(String::from("default_name"), serde_json::Value::from(default_name));
+
+    match run_disk_hook(drive, config, HookEvent::Name, disk) {
+        Ok(name) => Some(name.unwrap_or_else(|| String::from(default_name))),
+        Err(error) => {
+            show_drive_status(drive, DriveStatus::HookError(hooks::get_hook_error_message(&error)));
+            None
+        },
+    }
+}
+
+// Dry runs stop once the disk has a name, and just write down what would have happened.
+fn rehearse_disk(drive: &DiskDrive, catalog: &Mutex<Catalog>, default_name: &str, volume: &str, size: u64) {
+    let image = match wait_for_name(drive, default_name) {
+        Some(name) => name,
+        None => {
+            show_drive_status(drive, DriveStatus::Cancelled);
+            return;
+        },
+    };
+
+    let problems = dry_run::check_name(&catalog.lock().unwrap(), &image);
+    let problem_count = problems.len();
+
+    let rehearsal = dry_run::Rehearsal {
+        image: image.clone(),
+        volume: String::from(volume),
+        size,
+        fingerprint: drive.fingerprint.lock().unwrap().clone(),
+        duplicate_of: drive.duplicate_of.lock().unwrap().clone(),
+        problems,
+    };
+
+    show_drive_status(drive, match dry_run::record(&rehearsal) {
+        Ok(()) => DriveStatus::Rehearsed(image, problem_count),
+        Err(error) => DriveStatus::CopyWriteError(format!("Couldn't write the dry run report: {}", error)),
+    });
+}
+
+// What to call the image, before its extension. The naming map's title for its barcode, or the next one in the map's
+// sequence if it wasn't scanned, or the barcode itself, or failing all that the volume name. A title from the sequence
+// comes with a hold on it, for saving the image to use up.
+// The name a disk gets before anyone types one in, and whether it's just the volume name.
+fn get_default_base_name(drive: &DiskDrive, config: &Config, volume: &str, extension: &str) -> (String, bool, Option<naming_map::HeldTitle>) {
+    let barcode = drive.barcode.lock().unwrap().clone();
+
+    let (title, held) = match &barcode {
+        Some(barcode) => (naming_map::find_title(barcode), None),
+        None => match naming_map::next_title(|title| Path::new(&format!("{}.{}", get_safe_file_name(title), extension)).exists()) {
+            Some((title, held)) => (Some(title), Some(held)),
+            None => (None, None),
+        },
+    };
+
+    let (name, from_volume) = match (title, barcode) {
+        (Some(title), _) => (get_safe_file_name(&title), false),
+        (None, Some(barcode)) if config.barcode_names => (get_safe_file_name(&barcode), false),
+        _ => (String::from(volume), true),
+    };
+
+    (name, from_volume, held)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rip_disk(drive: &DiskDrive, config: &Config, catalog: &Mutex<Catalog>, cb: &CbSink, name_id: &str, ready_id: &str) {
+    drive.cancel.store(false, Relaxed);
+    *drive.fingerprint.lock().unwrap() = None;
+    *drive.duplicate_of.lock().unwrap() = None;
+
+    let clonecd = config.output_format == OutputFormat::CloneCd && drive.kind == DriveKind::Optical;
+
+    // Drives that never said either way get to try.
+    if clonecd && drive.capabilities.as_ref().map(|capabilities| !capabilities.reads_subchannels).unwrap_or(false) {
+        show_drive_status(drive, DriveStatus::Unsupported("This drive can't read raw subchannels, which CloneCD images need."));
+        return;
+    }
+
+    // Read the TOC now. The disk might be long gone by the time it gets a name and we write the CCD.
+    let raw_toc = if drive.kind == DriveKind::Optical { clonecd::read_toc(&drive.file).ok() } else { None };
+
+    // Audio CDs have no file system to copy, so they get ripped track by track instead. CloneCD images already
+    // have everything an audio CD does.
+    if let Some(toc) = raw_toc.as_deref().filter(|toc| !clonecd && audio::is_audio_disk(toc)) {
+        rip_audio_disk(drive, config, catalog, toc, cb, name_id, ready_id);
+        return;
+    }
+
+    let lead_out = raw_toc.as_deref().and_then(clonecd::get_sector_count);
+    let raw_toc = if clonecd { raw_toc } else { None };
+
+    let (info, extension) = match drive.kind {
+        DriveKind::Optical if clonecd => (fetch_raw_info(&drive.file, raw_toc.as_deref()), "img"),
+        DriveKind::Optical => (metadata::identify_volume(&drive.file), "iso"),
+        DriveKind::Block | DriveKind::Floppy => (fetch_block_info(&drive.file, drive.kind), "img"),
+    };
+
+    // Mac/PC hybrids have an HFS volume isoinfo can't see, which often runs past the end of the ISO 9660 one.
+    // Mac only disks don't have an ISO 9660 volume at all.
+    let hfs = if drive.kind == DriveKind::Optical && !clonecd { hfs::read_hfs_info(&drive.file) } else { None };
+    let info = match (info, &hfs) {
+        (Ok(info), Some(hfs)) => Ok(ISOInfo { length: get_hybrid_length(&drive.file, info.length.max(hfs.length as u64), info.block_size), ..info }),
+        (Err(_), Some(hfs)) => Ok(ISOInfo { name: hfs.name.clone(), block_size: 2048, length: get_hybrid_length(&drive.file, hfs.length as u64, 2048), details: VolumeDetails::default() }),
+        (info, None) => info,
+    };
+
+    // How much of the image has to be there. Anything past it came from reading to the lead-out.
+    let volume_length = info.as_ref().map(|info| info.length).unwrap_or(0);
+
+    // Padding, extra sessions, and whatever the mastering tool tucked outside the file system all come along.
+    // DVDs and BDs don't have a TOC like CDs do, but the drive knows where their data ends.
+    let info = match info {
+        Ok(info) if config.read_to_lead_out && drive.kind == DriveKind::Optical && !clonecd => {
+            let end = match lead_out {
+                Some(sectors) => Some(sectors as u64 * 2048),
+                None => cdrom::open_device(&drive.file).ok().and_then(|device| cdrom::read_capacity(&device).ok()),
+            };
+
+            Ok(ISOInfo { length: end.unwrap_or(0).max(info.length), ..info })
+        },
+        info => info,
+    };
+
+    if let Ok(info) = info {
+        if set_drive_status(drive, DriveStatus::Fingerprinting).is_err() {
+            return;
+        }
+
+        // Not being able to fingerprint a disk shouldn't stop us from ripping it. Going by the volume, so reading to the
+        // lead-out doesn't make the same disk look like a different one.
+        let fingerprint = fingerprint::fingerprint_disk(&drive.file, &info.name, volume_length, raw_toc.as_deref()).ok();
+        *drive.fingerprint.lock().unwrap() = fingerprint.clone();
+
+        if fingerprint.as_deref().map(jobs::take_discarded).unwrap_or(false) {
+            show_drive_status(drive, DriveStatus::Discarded);
+            return;
+        }
+
+        // Going back for the bad parts of an earlier rip is a lot quicker than ripping it all again. CloneCD images
+        // are in raw sectors, which the mapfile doesn't know about. Dry runs don't touch anything.
+        if let Some(entry) = find_patchable_rip(catalog, fingerprint.as_deref()).filter(|_| !clonecd && !config.dry_run) {
+            match offer_patch(drive, &entry.image) {
+                Some(true) => {
+                    patch_disk(drive, config, catalog, &entry, info.block_size);
+                    return;
+                },
+                Some(false) => {},
+                None => {
+                    show_drive_status(drive, DriveStatus::Cancelled);
+                    return;
+                },
+            }
+        }
+
+        if check_for_duplicate(drive, config, catalog, fingerprint.as_deref()) {
+            return;
+        }
+
+        if set_drive_status(drive, DriveStatus::Copying).is_err() {
+            return;
+        }
+
+        let mut notes = Vec::new();
+
+        // A bad picture isn't worth stopping the rip over.
+        let label_photo = match photograph_label(drive, config) {
+            Ok(photo) => photo.map(LabelPhoto),
+            Err(message) => {
+                notes.push(format!("Couldn't photograph the label. {}", message));
+                None
+            },
+        };
+
+        // The volume size is only what the disk was mastered to say. The drive knows where the disk really ends.
+        // Track-at-once CD-Rs end with a couple of run-out sectors nobody can read, so those don't count.
+        if drive.kind == DriveKind::Optical && !clonecd {
+            let readable = cdrom::open_device(&drive.file).ok().and_then(|device| cdrom::read_capacity(&device).ok()).unwrap_or(0);
+
+            if readable > info.length + 2 * 2048 {
+                notes.push(format!("The disk has {} bytes past the end of its volume that weren't ripped.", readable - info.length));
+            }
+        }
+
+        let (base_name, from_volume, held_title) = get_default_base_name(drive, config, &info.name, extension);
+
+        // Movies go in a folder of their own for media servers, named after the movie instead of its volume. Found
+        // out from the disk now, so the name's right from the start.
+        let movie = config.nfo_files && config.output_format == OutputFormat::Iso && drive.kind == DriveKind::Optical && !clonecd
+            && nfo::is_movie(&drive.file, &file_index::list_files(&drive.file).unwrap_or_default());
+
+        let base_iso_name = if movie && from_volume {
+            nfo::get_movie_path(&nfo::get_movie_name(&info.name, &info.details), extension)
+        } else if movie {
+            nfo::get_movie_path(&base_name, extension)
+        } else {
+            format!("{}.{}", base_name, extension)
+        };
+
+        let default_iso_name = match run_naming_hooks(drive, config, &info.name, info.length, &base_iso_name) {
+            Some(name) => name,
+            None => return,
+        };
+
+        // Whatever was typed in for this disk before auto_archive was last closed beats anything made up for it.
+        let pending_name = fingerprint.as_deref().and_then(|fingerprint| layout::find_pending_name(&get_layout_key(drive), fingerprint));
+        let default_iso_name = pending_name.unwrap_or(default_iso_name);
+        suggest_name(cb, name_id, ready_id, &default_iso_name);
+
+        if config.dry_run {
+            rehearse_disk(drive, catalog, &default_iso_name, &info.name, info.length);
+            return;
+        }
+
+        let _job = jobs::start(jobs::Job {
+            drive: drive.file.clone(),
+            fingerprint: fingerprint.clone().unwrap_or_default(),
+            volume: info.name.clone(),
+            name: default_iso_name.clone(),
+            stage: jobs::Stage::Ripping,
+            temp_fd: None,
+            length: info.length,
+            damage: Vec::new(),
+        });
+
+        // Other sinks keep the image somewhere else while it's ripped.
+        let space = if config.output_sink == sink::LOCAL_FILE {
+            match wait_for_space(drive, config, info.length) {
+                Some(space) => Some(space),
+                None => return,
+            }
+        } else {
+            None
+        };
+
+        let mut target = match sink::create(config) {
+            Ok(target) => target,
+            Err(error) => {
+                show_drive_status(drive, DriveStatus::CopyWriteError(format!("{}", error)));
+                return;
+            },
+        };
+
+        jobs::set_temp_fd(&drive.file, target.temp_fd());
+        drive.progress.start(info.length, ProgressUnit::Bytes);
+
+        let progress_callback = |read| {
+            drive.progress.add(read as u64);
+
+            if let Some(space) = &space {
+                space.wrote(read as u64);
+            }
+        };
+
+        let error_callback = |error| {
+            // Called when there's a non-fatal error.
+            show_drive_status(drive, match error {
+                CopyError::Read(err) => DriveStatus::NonFatalCopyReadError(err),
+                CopyError::Write(err) => DriveStatus::NonFatalCopyWriteError(err),
+                CopyError::Disconnected(_, _) | CopyError::Cancelled | CopyError::None => DriveStatus::Copying,
+            });
+        };
+
+        // Only optical drives have a speed to turn down. Zero if the drive never said how fast it goes.
+        let max_speed = match drive.kind {
+            DriveKind::Optical => Some(drive.capabilities.as_ref().map(|capabilities| capabilities.max_read_speed).unwrap_or(0)),
+            _ => None,
+        };
+
+        // Goes wrong on its own, without stopping the rip.
+        let mut mirror = config.mirror_directory.as_deref().map(|directory| sink::Mirror::create(directory, config));
+
+        let mut output = HashingWriter::with_hashes(sink::Tee::new(&mut *target, mirror.as_mut()), &config.extra_hashes, config.parallel_hashing);
+
+        // Pick up where the last drive this disk was in left off, if it went away part way through. Floppies and
+        // CloneCD images are read their own way, and other sinks can't have the partial image read back.
+        let resumable = config.output_sink == sink::LOCAL_FILE && !clonecd && drive.kind != DriveKind::Floppy;
+        let partial = fingerprint.as_deref().filter(|_| resumable).and_then(resume::find_partial).filter(|partial| partial.length <= info.length);
+
+        let (start, damage) = match partial {
+            Some(mut partial) => {
+                if let Err(error) = io::copy(&mut partial.file, &mut output) {
+                    show_drive_status(drive, DriveStatus::CopyWriteError(format!("{}", error)));
+                    return;
+                }
+                drive.progress.add(partial.length);
+
+                if let Some(space) = &space {
+                    space.wrote(partial.length);
+                }
+
+                // Anything from where it left off on is still to be read.
+                let mut damage = DamageMap::new(info.length);
+                for (start, length) in partial.damage.get_bad_regions().iter().filter(|(start, _)| *start < partial.length) {
+                    damage.add_bad(*start, (*length).min(partial.length - start));
+                    jobs::add_bad(&drive.file, *start, (*length).min(partial.length - start));
+                }
+
+                (partial.length, damage)
+            },
+            None => (0, DamageMap::new(info.length)),
+        };
+
+        let mut subchannels = if clonecd { Some(tempfile_fast::PersistableTempFile::new_in("./").unwrap()) } else { None };
+
+        let result = match drive.kind {
+            DriveKind::Floppy => floppy::copy_floppy_to_image(&drive.file, &mut output, info.length, &drive.cancel, progress_callback, error_callback),
+            _ if clonecd => {
+                let sectors = (info.length / cdrom::RAW_SECTOR_SIZE as u64) as u32;
+                clonecd::copy_disk_raw(&drive.file, &mut output, subchannels.as_mut().unwrap(), sectors, max_speed.unwrap_or(0), &drive.cancel, progress_callback, error_callback)
+                    .map(|()| DamageMap::new(info.length))
+            },
+            _ => copy_disk_to_iso(&drive.file, &mut output, info.length, volume_length, start, damage, info.block_size, config.recovery_mode, max_speed, &drive.cancel, progress_callback, error_callback,
+                |start, length| jobs::add_bad(&drive.file, start, length)),
+        }.and_then(|damage| {
+            output.get_mut().finish().map_err(|e| { CopyError::Write(format!("{}", e)) })?;
+            Ok(damage)
+        });
+
+        match result {
+            Ok(damage) => {
+                let hashes = output.hashes();
+                let redump = metadata::identify_title(&hashes);
+
+                // Now that what's on it is known, disks that would have gone by their volume name alone get some of
+                // their hash too, so a stack of disks all called DATA don't trip over each other. Names from the naming
+                // map, a scan, a hook, or last time are left alone.
+                let default_iso_name = if config.hash_names && from_volume && !movie && default_iso_name == base_iso_name {
+                    let hashed_name = format!("{}-{}.{}", base_name, &hashes.sha1[..8], extension);
+                    update_suggested_name(cb, name_id, &default_iso_name, &hashed_name);
+                    hashed_name
+                } else {
+                    default_iso_name
+                };
+
+                jobs::set_stage(&drive.file, jobs::Stage::Naming, &default_iso_name);
+
+                // If another drive got saved under the same name while this one was waiting, it's asked about again,
+                // the same as any other name that's taken.
+                let saved_name = loop {
+                    let saved_name = match wait_for_name(drive, &default_iso_name) {
+                        Some(name) => name,
+                        None => { // The temp file is dropped with `target`.
+                            show_drive_status(drive, DriveStatus::Cancelled);
+                            return;
+                        },
+                    };
+
+                    match claim_name(drive, &saved_name) {
+                        Ok(true) => break saved_name,
+                        Ok(false) => continue,
+                        Err(error) => {
+                            show_drive_status(drive, DriveStatus::CopyWriteError(format!("{}", error)));
+                            return;
+                        },
+                    }
+                };
+
+                if let Err(error) = target.persist(&saved_name) {
+                    // Take back the claim, and the folder it was in if that was made for it. Anything that was going
+                    // to be overwritten is still there.
+                    if !drive.overwrite.load(Relaxed) {
+                        let _ = fs::remove_file(&saved_name);
+                        if let Some(folder) = Path::new(&saved_name).parent().filter(|folder| !folder.as_os_str().is_empty()) {
+                            let _ = fs::remove_dir(folder);
+                        }
+                    }
+                    show_drive_status(drive, DriveStatus::CopyWriteError(format!("{}", error)));
+                    return;
+                }
+
+                // This disk had its place in the naming map's list, whatever it ended up being called.
+                if let Some(held) = held_title {
+                    held.saved();
+                }
+
+                // Finished off once everything else has been made for the image.
+                let mirror = mirror.map(|mirror| mirror.persist(&saved_name));
+
+                // Anything that goes wrong from here on still leaves a good image, so it goes in the catalog anyway,
+                // with a note about what went wrong, instead of getting ripped all over again. The drive shows the
+                // first thing that did.
+                let mut problem = None;
+
+                let label_photo = match label_photo.map(|photo| keep_label_photo(&photo, &saved_name)).transpose() {
+                    Ok(photo) => photo,
+                    Err(error) => {
+                        add_problem(&mut notes, &mut problem, DriveStatus::CopyWriteError(format!("Couldn't keep the label photo: {}", error)));
+                        None
+                    },
+                };
+
+                // All of it's in the image now.
+                if let Some(fingerprint) = fingerprint.as_deref().filter(|_| start > 0) {
+                    resume::remove_partial(fingerprint);
+                }
+
+                let details = info.details.to_json();
+                if !details.is_empty() {
+                    if let Err(error) = sidecar::update(&saved_name, details) {
+                        add_problem(&mut notes, &mut problem, DriveStatus::CopyWriteError(format!("{}", error)));
+                    }
+                }
+
+                // Leave a map of what we couldn't read, so recovery can continue with ddrescue, and a report of it
+                // in the sidecar for anyone wondering how much of the image they can trust.
+                if !damage.is_clean() {
+                    let mut report = serde_json::Map::new();
+                    report.insert(String::from("incomplete"), serde_json::Value::Bool(true));
+                    report.insert(String::from("damage"), damage.report());
+
+                    let result = damage.write_mapfile(&format!("{}.map", saved_name))
+                        .and_then(|()| sidecar::update(&saved_name, report));
+
+                    if let Err(error) = result {
+                        add_problem(&mut notes, &mut problem, DriveStatus::CopyWriteError(format!("{}", error)));
+                    }
+                }
+
+                if let (Some(subchannels), Some(toc)) = (subchannels.take(), &raw_toc) {
+                    let path = Path::new(&saved_name);
+                    let result = persist::persist(subchannels, path.with_extension("sub")).map_err(|error| { format!("{}", error) })
+                        .and_then(|()| clonecd::write_ccd(path, toc));
+
+                    if let Err(message) = result {
+                        add_problem(&mut notes, &mut problem, DriveStatus::CopyWriteError(message));
+                    }
+                }
+
+                // A drive that reads the same thing twice probably read it right. Zero filled parts won't match, and
+                // CloneCD images aren't laid out like the disk reads.
+                let reread_problem = if config.verify_rips && damage.is_clean() && !clonecd {
+                    show_drive_status(drive, DriveStatus::VerifyingImage(saved_name.clone()));
+                    drive.progress.start(info.length, ProgressUnit::Bytes);
+
+                    let result = verify_disk_against_image(&drive.file, &saved_name, VERIFY_BUFFER_SIZE, |read| {
+                        drive.progress.add(read as u64);
+                    });
+
+                    let mut values = serde_json::Map::new();
+                    values.insert(String::from("reread_matches"), serde_json::Value::Bool(result.is_ok()));
+
+                    if let Err(error) = sidecar::update(&saved_name, values) {
+                        add_problem(&mut notes, &mut problem, DriveStatus::CopyWriteError(format!("{}", error)));
+                    }
+
+                    result.err().map(|error| {
+                        let message = get_verify_error_message(&error);
+                        notes.push(format!("Reading the disk again didn't match the image. {}", message));
+                        message
+                    })
+                } else {
+                    None
+                };
+
+                // Find out now if the image is useless, while the disk is still here to try again with.
+                // CloneCD images are raw sectors, which nothing mounts anyway.
+                let mount_problem = if drive.kind == DriveKind::Optical && !clonecd {
+                    let result = mount_check::check_image(&saved_name);
+
+                    let mut values = serde_json::Map::new();
+                    values.insert(String::from("mountable"), serde_json::Value::Bool(result.is_ok()));
+                    if let Err(message) = &result {
+                        values.insert(String::from("mount_error"), serde_json::Value::String(message.clone()));
+                        notes.push(format!("The image won't mount. {}", message));
+                    }
+
+                    if let Err(error) = sidecar::update(&saved_name, values) {
+                        add_problem(&mut notes, &mut problem, DriveStatus::CopyWriteError(format!("{}", error)));
+                    }
+
+                    result.err()
+                } else {
+                    None
+                };
+
+                // The list of files and any photos have to come out of the ISO before anything gets a chance to compress
+                // or remove it. Not every disk has a file system isoinfo can read, so no list is fine.
+                let files = if drive.kind == DriveKind::Optical && !clonecd {
+                    file_index::list_files(&saved_name).unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+
+                // Whatever transcodes a DVD later wants to know its titles and chapters, without digging through the IFOs itself.
+                let dvd_info = if dvd::is_dvd_video(&files) {
+                    match dvd::write_sidecar(&saved_name) {
+                        Ok(info) => Some(info),
+                        Err(error) => {
+                            add_problem(&mut notes, &mut problem, DriveStatus::DvdError(dvd::get_dvd_error_message(&error)));
+                            None
+                        },
+                    }
+                } else {
+                    None
+                };
+
+                let mut photo_result = None;
+
+                if *drive.mode.lock().unwrap() == DriveMode::Photos(true) {
+                    match import_photos(drive, config, &saved_name) {
+                        Ok(result) => photo_result = Some(result),
+                        Err(message) => {
+                            add_problem(&mut notes, &mut problem, DriveStatus::PhotoError(format!("Saved the image, but {}", message)));
+                        },
+                    }
+                }
+
+                if let Some(end) = damage.ended_early() {
+                    notes.push(format!("The disk ended after {} bytes, short of the {} its volume says it has. The image is short.", end, info.length));
+                }
+
+                let mut image = saved_name.clone();
+
+                if config.output_format == OutputFormat::Chd && drive.kind == DriveKind::Optical {
+                    show_drive_status(drive, DriveStatus::Converting(saved_name.clone()));
+
+                    match chd::convert_to_chd(&saved_name) {
+                        Ok(chd_name) => {
+                            // The CHD has everything the ISO did.
+                            let _ = fs::remove_file(&saved_name);
+                            image = chd_name;
+                        },
+                        Err(error) => { // Kept as an ISO.
+                            add_problem(&mut notes, &mut problem, DriveStatus::ChdError(chd::get_chd_error_message(&error)));
+                        },
+                    }
+                }
+
+                let mut stored = false;
+
+                if config.output_format == OutputFormat::Dedup {
+                    show_drive_status(drive, DriveStatus::Storing(image.clone()));
+
+                    match dedup::store_image(&image, &config.dedup_store) {
+                        Ok(manifest) => {
+                            // Everything in the image is in the store now.
+                            let _ = fs::remove_file(&image);
+                            image = manifest;
+                            stored = true;
+                        },
+                        Err(message) => { // Kept as it is.
+                            add_problem(&mut notes, &mut problem, DriveStatus::StoreError(message));
+                        },
+                    }
+                }
+
+                let mut backup = None;
+                let mut keep_image = true;
+
+                if let Some(tool) = config.backup_tool {
+                    show_drive_status(drive, DriveStatus::BackingUp(image.clone()));
+
+                    match backup::backup_image(tool, &config.backup_repository, config.backup_password_file.as_deref(), &image) {
+                        Ok(id) => {
+                            backup = Some(format!("{} {}", tool.name(), id));
+
+                            if !config.backup_keep_image {
+                                let _ = fs::remove_file(&image);
+                                keep_image = false;
+                            }
+                        },
+                        Err(error) => {
+                            add_problem(&mut notes, &mut problem, DriveStatus::BackupError(backup::get_backup_error_message(tool, &error)));
+                        },
+                    }
+                }
+
+                // Media servers play ISOs, but not CHDs or anything in a store.
+                let is_movie = movie || dvd_info.is_some() || nfo::is_movie(&image, &files);
+                if config.nfo_files && is_movie && keep_image && config.output_format == OutputFormat::Iso {
+                    let runtime = dvd_info.as_ref().and_then(dvd::get_longest_title).or_else(|| nfo::get_blu_ray_runtime(&image));
+
+                    if let Err(error) = nfo::write_nfo(&image, &info.name, &info.details, runtime) {
+                        add_problem(&mut notes, &mut problem, DriveStatus::NfoError(format!("{}", error)));
+                    }
+                }
+
+                let parity = match config.par2_redundancy {
+                    Some(redundancy) if keep_image => {
+                        show_drive_status(drive, DriveStatus::GeneratingParity(image.clone()));
+
+                        let mut files = vec![image.clone()];
+                        if clonecd {
+                            let path = Path::new(&image);
+                            files.push(path.with_extension("sub").to_string_lossy().into_owned());
+                            files.push(path.with_extension("ccd").to_string_lossy().into_owned());
+                        }
+
+                        match par2::create_parity(&files, redundancy) {
+                            Ok(parity) => parity,
+                            Err(error) => {
+                                add_problem(&mut notes, &mut problem, DriveStatus::ParityError(par2::get_par2_error_message(&error)));
+                                Vec::new()
+                            },
+                        }
+                    },
+                    // A manifest's only a list of chunks, so parity for it wouldn't protect any of the disk. The chunks are
+                    // checked against their hashes when it's restored.
+                    Some(_) if stored => {
+                        notes.push(String::from("No parity was made, since the image is in the dedup store."));
+                        Vec::new()
+                    },
+                    _ => Vec::new(), // No point protecting an image that's only in the backup repository.
+                };
+
+                let (mirror, mirror_problem) = match mirror.map(|result| {
+                    result.and_then(|mirrored| finish_mirror(config, &saved_name, &image, keep_image, &parity, &label_photo, mirrored))
+                }) {
+                    Some(Ok(path)) => (Some(path), None),
+                    Some(Err(message)) => {
+                        notes.push(format!("The second copy wasn't saved. {}", message));
+                        (None, Some(message))
+                    },
+                    None => (None, None),
+                };
+
+                let entry = CatalogEntry {
+                    size: hashes.size,
+                    image,
+                    volume: info.name.clone(),
+                    hfs_volume: hfs.map(|hfs| hfs.name),
+                    publisher: info.details.publisher.clone(),
+                    preparer: info.details.preparer.clone(),
+                    application: info.details.application.clone(),
+                    created: info.details.created.clone(),
+                    date: catalog::now(),
+                    parity,
+                    crc32: hashes.crc32,
+                    sha1: hashes.sha1,
+                    md5: hashes.md5,
+                    sha256: hashes.sha256,
+                    redump: redump.clone(),
+                    fingerprint: fingerprint.unwrap_or_default(),
+                    backup,
+                    // Dedup images are just a manifest by now, so there's nothing worth putting on tape.
+                    tape: config.tape_device.as_ref()
+                        .filter(|_| keep_image && config.output_format != OutputFormat::Dedup)
+                        .map(|_| String::from(tape::PENDING)),
+                    notes,
+                    damage: if damage.bad_bytes() > 0 { Some(damage.summary()) } else { None },
+                    label_photo,
+                    inventory_id: drive.barcode.lock().unwrap().take(),
+                    mirror,
+                    ..CatalogEntry::default()
+                };
+
+                let id = match catalog.lock().unwrap().add(entry.clone()) {
+                    Ok(id) => id,
+                    Err(error) => {
+                        show_drive_status(drive, DriveStatus::CatalogError(catalog::get_catalog_error_message(&error)));
+                        return;
+                    },
+                };
+
+                if !files.is_empty() {
+                    if let Err(error) = file_index::add_files(id, &files) {
+                        show_drive_status(drive, DriveStatus::CatalogError(format!("Failed to add its files to the index: {}", error)));
+                        return;
+                    }
+                }
+
+                let entry = CatalogEntry { id, ..entry };
+                events::publish(&drive.file, DriveEvent::Archived(Box::new(entry.clone())));
+
+                // Images that went into a dedup store or a backup aren't files of their own anymore.
+                if config.provenance_xattrs && Path::new(&entry.image).is_file() {
+                    let source = drive.settings_key.as_deref().unwrap_or(&drive.file);
+
+                    if let Err(error) = provenance::write_provenance(&entry.image, source, &entry) {
+                        show_drive_status(drive, DriveStatus::ProvenanceError(format!("{}", error)));
+                        return;
+                    }
+                }
+
+                if let Err(message) = label::write_label(&entry, config.labels) {
+                    show_drive_status(drive, DriveStatus::LabelError(message));
+                    return;
+                }
+
+                if let Err(error) = run_finished_hook(drive, config, &entry) {
+                    show_drive_status(drive, DriveStatus::HookError(hooks::get_hook_error_message(&error)));
+                    return;
+                }
+
+                // Policy said two copies, so the missing one comes before anything but something that went wrong.
+                show_drive_status(drive, if let Some(problem) = problem {
+                    problem
+                } else if let Some(message) = mirror_problem {
+                    DriveStatus::DoneWithoutMirror(message)
+                } else if let Some(game) = redump {
+                    DriveStatus::DoneVerified(game)
+                } else if let Some(message) = reread_problem {
+                    DriveStatus::DoneMismatch(message)
+                } else if let Some(message) = mount_problem {
+                    DriveStatus::DoneUnmountable(message)
+                } else if let Some(end) = damage.ended_early() {
+                    DriveStatus::DoneShort(end, info.length)
+                } else if !damage.is_clean() {
+                    DriveStatus::DoneWithDamage(damage.bad_bytes())
+                } else if let Some(result) = photo_result {
+                    DriveStatus::DonePhotos(result.added, result.duplicates)
+                } else {
+                    DriveStatus::Done
+                });
+            },
+            // Keep what was read, for when the disk turns up again. The drive's task goes back to waiting for a disk,
+            // and the drive itself gets picked back up if it's plugged back in.
+            Err(CopyError::Disconnected(position, mut damage)) if config.output_sink == sink::LOCAL_FILE => {
+                damage.end_early(position);
+                let key = fingerprint.clone().unwrap_or_else(|| info.name.clone());
+
+                show_drive_status(drive, match resume::save_partial(target, &key, &damage) {
+                    Ok(partial) => DriveStatus::Disconnected(Some(partial)),
+                    Err(error) => DriveStatus::CopyWriteError(format!("The drive went away, and what was read so far couldn't be kept: {}", error)),
+                });
+            },
+            Err(error) => {
+                show_drive_status(drive, match error {
+                    CopyError::Read(err) => DriveStatus::CopyReadError(err),
+                    CopyError::Write(err) => DriveStatus::CopyWriteError(err),
+                    CopyError::Disconnected(_, _) => DriveStatus::Disconnected(None),
+                    CopyError::Cancelled => DriveStatus::Cancelled,
+                    CopyError::None => DriveStatus::Copying, // Should never happen.
+                });
+            }
+        }
+    } else if let Err(error) = info {
+        show_drive_status(drive, match drive.kind {
+            DriveKind::Optical if clonecd => DriveStatus::TocReadError,
+            DriveKind::Optical => diagnose_unreadable_disk(&drive.file, &error),
+            DriveKind::Block | DriveKind::Floppy => DriveStatus::DeviceSizeError(get_helper_error_message("lsblk", &error)),
+        });
+    }
+}
+
+// Takes a picture of the disk's label while it's ripped, if there's a webcam set up. It's kept under a temporary name
+// until the image has one.
+#[cfg(feature = "webcam")]
+fn photograph_label(drive: &DiskDrive, config: &Config) -> Result<Option<String>, String> {
+    let device = match &config.webcam_device {
+        Some(device) => device,
+        None => return Ok(None),
+    };
+
+    let photo = format!(".label-{}.jpg", drive.file.trim_start_matches("/dev/").replace('/', "-"));
+
+    // Left over from a crash. fswebcam doesn't always fail when it didn't get anything, so this would pass for it.
+    let _ = fs::remove_file(&photo);
+    webcam::capture(device, &photo).map_err(|error| { webcam::get_webcam_error_message(&error) })?;
+
+    Ok(Some(photo))
+}
+
+#[cfg(not(feature = "webcam"))]
+fn photograph_label(_drive: &DiskDrive, _config: &Config) -> Result<Option<String>, String> {
+    Ok(None)
+}
+
+// Copies everything made for an image since it was saved into the mirror, next to the mirror's copy of it, which is
+// at `mirrored`. A CHD takes the ISO's place there too. Images that went into a dedup store or a backup stay whole in
+// the mirror, since neither of those are mirrored. Returns where the mirror's image ended up.
+#[allow(clippy::too_many_arguments)]
+fn finish_mirror(config: &Config, saved_name: &str, image: &str, keep_image: bool, parity: &[String], label_photo: &Option<String>, mirrored: String) -> Result<String, String> {
+    let directory = match &config.mirror_directory {
+        Some(directory) => directory,
+        None => return Ok(mirrored),
+    };
+
+    let saved = Path::new(saved_name);
+    let converted = keep_image && image != saved_name && config.output_format == OutputFormat::Chd;
+
+    let mut files = vec![
+        sidecar::get_sidecar_path(saved_name).to_string_lossy().into_owned(),
+        format!("{}.map", saved_name),
+        saved.with_extension("sub").to_string_lossy().into_owned(),
+        saved.with_extension("ccd").to_string_lossy().into_owned(),
+        Path::new(image).with_extension("nfo").to_string_lossy().into_owned(),
+    ];
+    files.extend(parity.iter().cloned());
+    files.extend(label_photo.iter().cloned());
+
+    if converted {
+        files.push(String::from(image));
+    }
+
+    files.retain(|file| Path::new(file).is_file());
+    sink::copy_to_mirror(directory, &files)?;
+
+    if converted {
+        // Same as the first copy, the CHD has everything the ISO did.
+        let _ = fs::remove_file(&mirrored);
+        return Ok(sink::get_mirror_path(directory, image).to_string_lossy().into_owned());
+    }
+
+    Ok(mirrored)
+}
+
+// Notes down something that went wrong after the image was saved, and keeps it to show if it's the first.
+fn add_problem(notes: &mut Vec<String>, problem: &mut Option<DriveStatus>, status: DriveStatus) {
+    notes.push(get_drive_status_message_string(&status));
+    problem.get_or_insert(status);
+}
+
+// A label photo that hasn't been named after its image yet. If the rip stops before then, it goes with it.
+struct LabelPhoto(String);
+
+impl Drop for LabelPhoto {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// Names the label photo after the image.
+fn keep_label_photo(photo: &LabelPhoto, image: &str) -> io::Result<String> {
+    let name = Path::new(image).with_extension("label.jpg").to_string_lossy().into_owned();
+    fs::rename(&photo.0, &name)?;
+    Ok(name)
+}
+
+// Imports photos from the disk, or an image of it, into the photo library.
+fn import_photos(drive: &DiskDrive, config: &Config, source: &str) -> Result<photos::PhotoResult, String> {
+    show_drive_status(drive, DriveStatus::ImportingPhotos(0, 0));
+    drive.progress.start(0, ProgressUnit::Items);
+
+    photos::import_photos(source, &config.photo_library, |done, total| {
+        show_drive_status(drive, DriveStatus::ImportingPhotos(done, total));
+        drive.progress.set_total(total as u64);
+        drive.progress.set(done as u64);
+    }).map_err(|e| { photos::get_photo_error_message(&e) })
+}
+
+// Photos only, straight off the disk. No image is kept.
+fn import_disk_photos(drive: &DiskDrive, config: &Config) {
+    show_drive_status(drive, match import_photos(drive, config, &drive.file) {
+        Ok(result) => DriveStatus::DonePhotos(result.added, result.duplicates),
+        Err(message) => DriveStatus::PhotoError(message),
+    });
+}
+
+fn get_audio_disk_name(metadata: &DiscMetadata, disc_id: &str) -> String {
+    match (&metadata.artist, &metadata.album) {
+        (Some(artist), Some(album)) => audio::sanitize_file_name(&format!("{} - {}", artist, album)),
+        (None, Some(album)) => audio::sanitize_file_name(album),
+        _ => format!("Audio CD {}", disc_id),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rip_audio_disk(drive: &DiskDrive, config: &Config, catalog: &Mutex<Catalog>, toc: &[TocEntry], cb: &CbSink, name_id: &str, ready_id: &str) {
+    let mut tracks = audio::get_audio_tracks(toc);
+    let lead_out = audio::get_lead_out(toc).unwrap_or(0);
+
+    if set_drive_status(drive, DriveStatus::Fingerprinting).is_err() {
+        return;
+    }
+
+    let fingerprint = fingerprint::fingerprint_disk(&drive.file, "", lead_out as u64 * cdrom::RAW_SECTOR_SIZE as u64, Some(toc)).ok();
+    *drive.fingerprint.lock().unwrap() = fingerprint.clone();
+
+    if check_for_duplicate(drive, config, catalog, fingerprint.as_deref()) {
+        return;
+    }
+
+    if set_drive_status(drive, DriveStatus::ReadingMetadata).is_err() {
+        return;
+    }
+
+    let disc_id = musicbrainz::get_disc_id(&tracks, lead_out);
+    let mut metadata = metadata::identify_audio(&metadata::AudioQuery {
+        device: &drive.file,
+        tracks: &tracks,
+        lead_out,
+        disc_id: &disc_id,
+    }, config);
+
+    let volume = metadata.album.clone().unwrap_or_default();
+    let default_name = match run_naming_hooks(drive, config, &volume, lead_out as u64 * cdrom::RAW_SECTOR_SIZE as u64, &get_audio_disk_name(&metadata, &disc_id)) {
+        Some(name) => name,
+        None => return,
+    };
+    suggest_name(cb, name_id, ready_id, &default_name);
+
+    if config.dry_run {
+        rehearse_disk(drive, catalog, &default_name, &volume, lead_out as u64 * cdrom::RAW_SECTOR_SIZE as u64);
+        return;
+    }
+
+    // Tracks come out no bigger than the raw audio, give or take their headers.
+    let space = match wait_for_space(drive, config, lead_out as u64 * cdrom::RAW_SECTOR_SIZE as u64) {
+        Some(space) => space,
+        None => return,
+    };
+
+    // Tracks are ripped into here until the disk has a name, then it gets renamed.
+    let staging = format!(".audio-{}", Path::new(&drive.file).file_name().unwrap_or_default().to_string_lossy());
+    let _ = fs::remove_dir_all(&staging); // Left over from a crash.
+
+    // The gaps have to be found before the cue sheet can be written, and it has to be written before any tracks are,
+    // since it goes in their tags.
+    let result = cdrom::open_device(&drive.file)
+        .map_err(|e| { audio::AudioError::Read(format!("{}", e)) })
+        .and_then(|device| {
+            audio::detect_pregaps(&device, &mut tracks);
+            audio::read_codes(&device, &tracks, &mut metadata);
+            let hidden_track = audio::get_hidden_track(&device, &tracks)?;
+
+            fs::create_dir_all(&staging)
+                .and_then(|()| fs::write(Path::new(&staging).join(audio::CUE_SHEET_FILE), audio::build_cue_sheet(&metadata, &tracks, hidden_track.as_ref())))
+                .map_err(|e| { audio::AudioError::Write(format!("{}", e)) })?;
+
+            Ok((device, hidden_track))
+        });
+
+    let (device, hidden_track) = match result {
+        Ok(result) => result,
+        Err(error) => {
+            let _ = fs::remove_dir_all(&staging);
+            show_drive_status(drive, DriveStatus::AudioError(audio::get_audio_error_message(&error)));
+            return;
+        },
+    };
+
+    // The hidden track is ripped like any other, it just comes first.
+    let tracks: Vec<audio::Track> = hidden_track.into_iter().chain(tracks).collect();
+    let track_count = tracks.iter().filter(|track| track.number > 0).count();
+
+    let total = tracks.iter().map(|track| track.length as u64).sum::<u64>() * cdrom::RAW_SECTOR_SIZE as u64;
+    drive.progress.start(total, ProgressUnit::Bytes);
+    let mut disc = HashingWriter::with_hashes(io::sink(), &config.extra_hashes, config.parallel_hashing);
+    let mut checksums = Vec::new();
+
+    for track in tracks.iter() {
+        show_drive_status(drive, DriveStatus::RippingTrack(track.number, track_count as u8));
+
+        let result = audio::rip_track(&device, track, track_count, &metadata, Path::new(&staging), &mut disc, &drive.cancel, |read| {
+            drive.progress.add(read as u64);
+            space.wrote(read as u64);
+        }, |message| {
+            show_drive_status(drive, DriveStatus::NonFatalCopyReadError(message));
+        });
+
+        match result {
+            Ok(crc32) => checksums.push(crc32),
+            Err(error) => {
+                let _ = fs::remove_dir_all(&staging);
+
+                show_drive_status(drive, match error {
+                    audio::AudioError::Cancelled => DriveStatus::Cancelled,
+                    _ => DriveStatus::AudioError(audio::get_audio_error_message(&error)),
+                });
+                return;
+            },
+        }
+    }
+
+    let result = audio::write_checksums(Path::new(&staging), &metadata, &tracks, &checksums)
+        .and_then(|()| audio::write_sidecar(Path::new(&staging), &metadata, &tracks, &checksums, &disc_id));
+
+    if let Err(error) = result {
+        let _ = fs::remove_dir_all(&staging);
+        show_drive_status(drive, DriveStatus::AudioError(audio::get_audio_error_message(&error)));
+        return;
+    }
+
+    let name = match wait_for_name(drive, &default_name) {
+        Some(name) => name,
+        None => {
+            let _ = fs::remove_dir_all(&staging);
+            show_drive_status(drive, DriveStatus::Cancelled);
+            return;
+        },
+    };
+
+    if let Err(error) = fs::rename(&staging, &name) {
+        show_drive_status(drive, DriveStatus::AudioError(format!("Failed to move the tracks to {}: {}", name, error)));
+        return;
+    }
+
+    let hashes = disc.hashes();
+    let entry = CatalogEntry {
+        image: name,
+        volume: metadata.album.clone().unwrap_or_else(|| String::from("Audio CD")),
+        date: catalog::now(),
+        size: hashes.size,
+        crc32: hashes.crc32,
+        sha1: hashes.sha1,
+        md5: hashes.md5,
+        sha256: hashes.sha256,
+        fingerprint: fingerprint.unwrap_or_default(),
+        inventory_id: drive.barcode.lock().unwrap().take(),
+        ..CatalogEntry::default()
+    };
+
+    let id = match catalog.lock().unwrap().add(entry.clone()) {
+        Ok(id) => id,
+        Err(error) => {
+            show_drive_status(drive, DriveStatus::CatalogError(catalog::get_catalog_error_message(&error)));
+            return;
+        },
+    };
+
+    let entry = CatalogEntry { id, ..entry };
+    events::publish(&drive.file, DriveEvent::Archived(Box::new(entry.clone())));
+
+    show_drive_status(drive, match label::write_label(&entry, config.labels) {
+        Ok(_) => match run_finished_hook(drive, config, &entry) {
+            Ok(()) => DriveStatus::Done,
+            Err(error) => DriveStatus::HookError(hooks::get_hook_error_message(&error)),
+        },
+        Err(message) => DriveStatus::LabelError(message),
+    });
+}
+
+// Drives take a while to spin a disk up once the tray's closed. Past this, it's read anyway, and the read says what's
+// wrong.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Waits until the drive says there's a disk in it and it's ready, or until it's been `timeout`.
+fn wait_for_disk_to_load(drive: &str, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+
+    while Instant::now() < deadline && !matches!(cdrom::get_tray_status(drive), Ok(TrayStatus::Loaded)) {
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+fn burn_disk(drive: &DiskDrive, image: &str) {
+    if set_drive_status(drive, DriveStatus::Burning(String::from(image))).is_err() {
+        return;
+    }
+    drive.progress.start(0, ProgressUnit::Bytes);
+
+    let result = burn::burn_image(&drive.file, image, |written, total| {
+        drive.progress.set_total(total);
+        drive.progress.set(written);
+    });
+
+    if let Err(error) = result {
+        show_drive_status(drive, DriveStatus::BurnError(burn::get_burn_error_message(&error)));
+        return;
+    }
+
+    // An unverified burn is worthless, so read it all back before calling it done.
+    if set_drive_status(drive, DriveStatus::Verifying(String::from(image))).is_err() {
+        return;
+    }
+
+    // Lots of burners won't read back what they just wrote until they've had the disk out and back in. If this fails,
+    // the read back says so.
+    let _ = eject_drive_disk(&drive.file).and_then(|()| close_drive_disk(&drive.file));
+    wait_for_disk_to_load(&drive.file, LOAD_TIMEOUT);
+
+    drive.progress.start(fs::metadata(image).map(|metadata| metadata.len()).unwrap_or(0), ProgressUnit::Bytes);
+
+    let result = verify_disk_against_image(&drive.file, image, VERIFY_BUFFER_SIZE, |read| {
+        drive.progress.add(read as u64);
+    });
+
+    show_drive_status(drive, match result {
+        Ok(()) => DriveStatus::BurnVerified,
+        Err(error) => DriveStatus::VerifyFailed(get_verify_error_message(&error)),
+    });
+
+    // Hand the disk back whether it checked out or not, since there's nothing more to do with it. If this fails the
+    // operator can still eject it themselves.
+    let _ = eject_disk(drive);
+}
+
+fn verify_disk(drive: &DiskDrive, image: &str) {
+    if set_drive_status(drive, DriveStatus::VerifyingImage(String::from(image))).is_err() {
+        return;
+    }
+
+    drive.progress.start(fs::metadata(image).map(|metadata| metadata.len()).unwrap_or(0), ProgressUnit::Bytes);
+
+    let result = verify_disk_against_image(&drive.file, image, VERIFY_BUFFER_SIZE, |read| {
+        drive.progress.add(read as u64);
+    });
+
+    show_drive_status(drive, match result {
+        Ok(()) => DriveStatus::ImageVerified(String::from(image)),
+        Err(error) => DriveStatus::VerifyFailed(get_verify_error_message(&error)),
+    });
+}
+
+fn benchmark_drive(drive: &DiskDrive) {
+    if set_drive_status(drive, DriveStatus::Benchmarking).is_err() {
+        return;
+    }
+    drive.cancel.store(false, Relaxed);
+    drive.progress.start(1000, ProgressUnit::Steps);
+
+    let length = match cdrom::open_device(&drive.file).map_err(|e| { format!("{}", e) })
+        .and_then(|device| cdrom::read_capacity(&device).map_err(|e| { cdrom::get_scsi_error_message(&e) })) {
+        Ok(length) => length,
+        Err(message) => {
+            show_drive_status(drive, DriveStatus::BenchmarkError(format!("Couldn't tell how big the disk is: {}", message)));
+            return;
+        },
+    };
+
+    let profile = match benchmark::run_benchmark(&drive.file, length, &drive.cancel, |progress| drive.progress.set(progress as u64)) {
+        Ok(profile) => profile,
+        Err(message) => {
+            show_drive_status(drive, DriveStatus::BenchmarkError(message));
+            return;
+        },
+    };
+
+    *drive.benchmark.lock().unwrap() = Some(profile.clone());
+
+    // Drives without a serial number only keep it until auto_archive closes, same as their other settings.
+    if let Some(key) = &drive.settings_key {
+        let settings = drive_settings::DriveSettings {
+            benchmark: Some(profile.clone()),
+            ..drive_settings::load(key)
+        };
+
+        if let Err(error) = drive_settings::save(key, &settings) {
+            show_drive_status(drive, DriveStatus::BenchmarkError(format!("Failed to save the results: {}", error)));
+            return;
+        }
+    }
+
+    show_drive_status(drive, DriveStatus::Benchmarked(profile.summary()));
+}
+
+fn erase_disk(drive: &DiskDrive, mode: BlankMode) {
+    drive.progress.start(1000, ProgressUnit::Steps);
+
+    let expected = mode.expected_duration().as_secs_f64();
+
+    let result = burn::blank_disk(&drive.file, mode, |elapsed| {
+        show_drive_status(drive, DriveStatus::Erasing(mode, elapsed));
+
+        // Just an estimate, so never claim to be done before we are.
+        drive.progress.set(((elapsed.as_secs_f64() / expected).min(0.99) * 1000.0) as u64);
+    });
+
+    match result {
+        Ok(()) => {
+            drive.progress.finish();
+            show_drive_status(drive, DriveStatus::Erased);
+
+            // Hand the disk back. If this fails the operator can still eject it themselves.
+            let _ = eject_disk(drive);
+        },
+        Err(error) => {
+            show_drive_status(drive, DriveStatus::EraseError(burn::get_burn_error_message(&error)));
+        },
+    }
+}
+
+// Waits for the drive's turn, when drives take turns. None if the disk was taken out while it waited.
+async fn wait_for_turn<'a>(drive: &DiskDrive, config: &Config, turns: &'a TurnQueue, mode: &DriveMode) -> Option<Turn<'a>> {
+    let turn = turns.join(&drive.file);
+
+    loop {
+        let ahead = turn.position();
+        if ahead == 0 {
+            return Some(turn);
+        }
+
+        show_drive_status(drive, DriveStatus::WaitingForTurn(ahead));
+
+        // Blank disks don't show up as a disk, just a loaded tray.
+        let present = match mode {
+            DriveMode::Burn(_) | DriveMode::Erase(_) => *drive.tray_status.lock().unwrap() == TrayStatus::Loaded,
+            _ => drive.has_disk.load(Relaxed),
+        };
+
+        if !present {
+            return None;
+        }
+
+        tokio::time::sleep(config.polling.tray).await;
+    }
+}
+
+// Waits for room for the image in the working directory, while other rips finish up and make some. None if there
+// won't ever be enough, or the disk was taken out while it waited.
+fn wait_for_space(drive: &DiskDrive, config: &Config, size: u64) -> Option<staging::Reservation> {
+    let doing = drive.state.get();
+    let mut waited = false;
+
+    loop {
+        let shortfall = match staging::reserve(size) {
+            // Back to whatever it was doing before it had to wait.
+            Ok(_) if waited && set_drive_status(drive, doing.clone()).is_err() => return None,
+            Ok(reservation) => return Some(reservation),
+            Err(shortfall) => shortfall,
+        };
+
+        if shortfall.others == 0 || size > shortfall.free {
+            show_drive_status(drive, DriveStatus::NotEnoughSpace(size, shortfall.free));
+            return None;
+        }
+
+        if set_drive_status(drive, DriveStatus::WaitingForSpace(shortfall.needed - shortfall.free)).is_err() {
+            return None;
+        }
+        waited = true;
+
+        // Other rips finishing don't wake the drive, so it looks again every so often. Ejecting or cancelling does.
+        let stopped = drive.state.wait_until_timeout(config.polling.tray, |_| {
+            (!drive.has_disk.load(Relaxed) || drive.cancel.load(Relaxed)).then_some(())
+        });
+
+        if stopped.is_some() {
+            show_drive_status(drive, DriveStatus::Cancelled);
+            return None;
+        }
+    }
+}
+
+// Lives as long as a drive's task does. If the task dies, marks the drive as needing a restart instead of leaving
+// it looking like it's still busy with whatever it was doing.
+struct TaskWatch(Arc<DiskDrive>);
+
+impl Drop for TaskWatch {
+    fn drop(&mut self) {
+        // If it died holding one of these, the next task shouldn't die for it too.
+        self.0.state.clear_poison();
+        self.0.fingerprint.clear_poison();
+        self.0.duplicate_of.clear_poison();
+        self.0.media.clear_poison();
+
+        show_drive_status(&self.0, DriveStatus::ThreadStopped);
+    }
+}
+
+// Does whatever the drive's mode says to do with the disk in it.
+#[allow(clippy::too_many_arguments)]
+fn process_disk(drive: &DiskDrive, mode: &DriveMode, config: &Config, catalog: &Mutex<Catalog>, cb: &CbSink, name_id: &str, ready_id: &str) {
+    match mode {
+        DriveMode::Rip | DriveMode::Photos(true) => {
+            rip_disk(drive, config, catalog, cb, name_id, ready_id);
+
+            // There's no telling when a floppy comes out, so just wait to be told about the next one.
+            if drive.kind == DriveKind::Floppy {
+                drive.has_disk.store(false, Relaxed);
+            }
+        },
+        DriveMode::Photos(false) => import_disk_photos(drive, config),
+        DriveMode::Burn(image) => burn_disk(drive, image),
+        DriveMode::Verify(image) => verify_disk(drive, image),
+        DriveMode::Benchmark => benchmark_drive(drive),
+        DriveMode::Erase(mode) => erase_disk(drive, *mode),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_drive_task(s: &mut Cursive, drive: &Arc<DiskDrive>, live_config: &Arc<LiveConfig>, catalog: &Arc<Mutex<Catalog>>, turns: &Arc<TurnQueue>, name_id: &str, ready_id: &str) {
+    let drive = drive.clone();
+    let live_config = live_config.clone();
+    let catalog = catalog.clone();
+    let turns = turns.clone();
+
+    let cb = s.cb_sink().clone();
+
+    let name_id = String::from(name_id);
+    let ready_id = String::from(ready_id);
+
+    tokio::spawn(async move {
+        let _watch = TaskWatch(drive.clone());
+
+        loop {
+            let mode = wait_for_disk(&drive, &live_config).await;
+
+            // Whatever the config says now. It stays this way until the disk is done.
+            let config = live_config.get();
+            let config = match drive.profile.lock().unwrap().or(config.profile) {
+                Some(profile) => Arc::new(profile.apply(&config)),
+                None => config,
+            };
+
+            let turn = if config.sequential_rips {
+                match wait_for_turn(&drive, &config, &turns, &mode).await {
+                    Some(turn) => Some(turn),
+                    None => continue,
+                }
+            } else {
+                None
+            };
+
+            // Reading and writing disks is all blocking, so it gets a thread from the pool to do it on. That's everything
+            // from the first read to the last file written, waiting for room in the working directory included. Only the
+            // waiting around between disks is async.
+            let job = {
+                let (drive, mode, catalog, cb, name_id, ready_id) =
+                    (drive.clone(), mode.clone(), catalog.clone(), cb.clone(), name_id.clone(), ready_id.clone());
+
+                tokio::task::spawn_blocking(move || {
+                    // Already checked at startup, and telling the operator for every drive wouldn't help. The pool's
+                    // threads get shared around, so it's set every time.
+                    let _ = priority::set_thread_priority(config.copy_nice, config.copy_io_priority);
+
+                    process_disk(&drive, &mode, &config, &catalog, &cb, &name_id, &ready_id);
+                })
+            };
+
+            // It panicked. The watch takes it from here.
+            if job.await.is_err() {
+                return;
+            }
+
+            // A scan goes with the disk it was made for, whether it got archived or not. Anything scanned after this
+            // is for the next one.
+            drive.barcode.lock().unwrap().take();
+
+            // Done with the drive, so the next one can go while this one waits for its disk to come out.
+            drop(turn);
+
+            match mode {
+                // A burn gets ejected once it's been read back, pass or fail, and so does an erased disk. One that failed
+                // to burn or erase is left in for the operator.
+                DriveMode::Burn(_) | DriveMode::Erase(_) => {
+                    drive.state.wait_for(|| *drive.tray_status.lock().unwrap() != TrayStatus::Loaded).await;
+                },
+                // Wait for disk to be removed.
+                _ => drive.state.wait_for(|| !drive.has_disk.load(Relaxed)).await,
+            }
+        }
+    });
+}
+
+// Barcodes and titles can have anything in them, but file names can't have slashes, and can't be all dots either,
+// since . and .. are directories.
+fn get_safe_file_name(barcode: &str) -> String {
+    let name = barcode.replace(['/', '\0'], "_");
+
+    if name.chars().all(|c| c == '.') {
+        name.replace('.', "_")
+    } else {
+        name
+    }
+}
+
+// Scanners are keyboards as far as anyone can tell, so a scan is some typing and then Enter. Scanning an empty box, or
+// hitting Enter on one, forgets the last scan.
+fn scan_barcode(s: &mut Cursive, drive: &DiskDrive, config: &LiveConfig, barcode_id: &str, name_id: &str, code: &str) {
+    s.find_id::<EditView>(barcode_id).unwrap().set_content("");
+
+    let code = code.trim();
+    *drive.barcode.lock().unwrap() = if code.is_empty() { None } else { Some(String::from(code)) };
+
+    // A disk that's already waiting for a name gets renamed now. Otherwise it's named after the scan when it's ripped.
+    if code.is_empty() || drive.state.get() != DriveStatus::WaitingForName {
+        return;
+    }
+
+    let title = match naming_map::find_title(code) {
+        Some(title) => title,
+        None if config.get().barcode_names => String::from(code),
+        None => return,
+    };
+
+    let mut text_box = s.find_id::<EditView>(name_id).unwrap();
+    if !text_box.is_enabled() {
+        return;
+    }
+
+    let current = text_box.get_content();
+    let name = match Path::new(current.as_str()).extension() {
+        Some(extension) => format!("{}.{}", get_safe_file_name(&title), extension.to_string_lossy()),
+        None => get_safe_file_name(&title),
+    };
+    text_box.set_content(name);
+}
+
+fn add_name_settings(s: &mut Cursive, linear: &mut LinearLayout, name_id: &str, ready_id: &str, drive: &Arc<DiskDrive>, config: &Arc<LiveConfig>) {
+    let barcode_id = format!("barcode-{}", drive.panel);
+    let scanned_id = format!("scanned-{}", drive.panel);
+
+    let (drive1, config, barcode_id1, name_id1) = (drive.clone(), config.clone(), barcode_id.clone(), String::from(name_id));
+    let scanner = EditView::new().on_submit(move |s, code| {
+        scan_barcode(s, &drive1, &config, &barcode_id1, &name_id1, code);
+    });
+
+    let settings = ListView::new()
+        .child("Settings ready: ", Checkbox::new().with_id(ready_id))
+        .child("File name: ", EditView::new().with_id(name_id))
+        .child("Barcode: ", scanner.with_id(barcode_id))
+        .child("Inventory ID: ", TextView::new("").with_id(scanned_id.as_str()));
+    linear.add_child(settings);
+
+    let name_id = String::from(name_id);
+    let ready_id = String::from(ready_id);
+    let drive = drive.clone();
+
+    s.add_global_callback(Event::Refresh, move |s| {
+        let scanned = drive.barcode.lock().unwrap().clone().unwrap_or_default();
+        s.find_id::<TextView>(&scanned_id).unwrap().set_content(scanned);
+
+        let mut text_box = s.find_id::<EditView>(&name_id).unwrap();
+        let mut ready_checkbox = s.find_id::<Checkbox>(&ready_id).unwrap();
+
+        // Only go through with save if box is checked.
+        if drive.state.get() == DriveStatus::WaitingForName && ready_checkbox.is_checked() {
+
+            let path = text_box.get_content().clone();
+
+            if Path::new(path.as_ref()).exists() && !role::is_admin() {
+                // Overwriting is for admins.
+                ready_checkbox.set_checked(false);
+                s.add_layer(Dialog::text("A file with this name already exists. Pick another name.")
+                    .button("Ok", |s| { s.pop_layer(); } ));
+            } else if Path::new(path.as_ref()).exists() {
+                // Path exists. Check if they really want to overwrite it.
+
+                let ready_id1 = ready_id.clone();
+
+                let drive1 = drive.clone();
+                let drive2 = drive.clone();
+
+                s.add_layer(Dialog::text("A file with this name exists. Do you want to overwrite it?")
+                    .title("Confirm Overwrite")
+                    .h_align(HAlign::Center)
+                    .button("No", move |s| {
+                        s.pop_layer();
+
+                        let mut ready_checkbox = s.find_id::<Checkbox>(&ready_id1).unwrap();
+                        ready_checkbox.set_checked(false);
+
+                        // Go back to waiting for a name.
+                        show_drive_status(&drive1, DriveStatus::WaitingForName);
+                    })
+                    .button("Yes", move |s| {
+                        s.pop_layer();
+
+                        // Okay, save it, unless the disk went away while they were making up their mind.
+                        drive2.overwrite.store(true, Relaxed);
+                        if let Err(error) = drive2.state.transition(DriveStatus::Saving(path.as_ref().clone())) {
+                            s.add_layer(Dialog::text(drive_state::get_illegal_transition_message(&error))
+                                .button("Ok", |s| { s.pop_layer(); } ));
+                        }
+                    })
+                );
+
+                // We are now confirming the name. This is needed to prevent infinite spawning of confirmation windows.
+                show_drive_status(&drive, DriveStatus::ConfirmingName);
             } else {
-                *drive.status_message.lock().unwrap() = DriveStatus::IsoFetchError;
+                // No problem just save it, unless the disk's already gone.
+                if let Err(error) = set_drive_status(&drive, DriveStatus::Saving(path.as_ref().clone())) {
+                    s.add_layer(Dialog::text(drive_state::get_illegal_transition_message(&error))
+                        .button("Ok", |s| { s.pop_layer(); } ));
+                }
+            }
+        }
+
+        // Do not permit editing while we are set as ready.
+        text_box.set_enabled(!ready_checkbox.is_checked());
+    });
+}
+
+// Asks whether to patch an earlier damaged rip, when a drive finds the disk it came from.
+fn add_patch_prompt(s: &mut Cursive, drive: &Arc<DiskDrive>) {
+    let drive = drive.clone();
+
+    on_drive_events(s, &drive.clone(), move |s, event| {
+        let image = match event {
+            DriveEvent::Status(DriveStatus::OfferingPatch(image)) => image.clone(),
+            _ => return,
+        };
+
+        // Same as confirming a name, so the question only gets asked once. If it's not still on offer, the disk's
+        // gone and there's nothing to ask.
+        if drive.state.transition(DriveStatus::ConfirmingPatch(image.clone())).is_err() {
+            return;
+        }
+
+        let drive1 = drive.clone();
+        let drive2 = drive.clone();
+        let image1 = image.clone();
+
+        s.add_layer(Dialog::text(format!("This disk was ripped before as {}, but parts of it couldn't be read. \
+                Re-read just those parts and patch them into the image? Otherwise the whole disk is ripped again.", image))
+            .title(format!("Patch Damaged Rip: {}", drive.file))
+            .h_align(HAlign::Center)
+            .button("Rip again", move |s| {
+                s.pop_layer();
+
+                // The disk may have been pulled while the question was up, in which case there's nothing to rip.
+                show_drive_status(&drive1, DriveStatus::Copying);
+            })
+            .button("Patch", move |s| {
+                s.pop_layer();
+
+                if let Err(error) = drive2.state.transition(DriveStatus::Patching(image1.clone())) {
+                    s.add_layer(Dialog::text(drive_state::get_illegal_transition_message(&error))
+                        .button("Ok", |s| { s.pop_layer(); } ));
+                }
+            })
+        );
+    });
+}
+
+// Offers to restart a drive's task once it's died. `respawn` starts a new one.
+fn add_restart_prompt<F>(s: &mut Cursive, drive: &Arc<DiskDrive>, respawn: F) where
+    F: Fn(&mut Cursive) + Send + Sync + 'static {
+
+    let drive = drive.clone();
+    let respawn = Arc::new(respawn);
+
+    on_drive_events(s, &drive.clone(), move |s, event| {
+        if !matches!(event, DriveEvent::Status(DriveStatus::ThreadStopped)) {
+            return;
+        }
+
+        // Only ask once.
+        if drive.state.transition(DriveStatus::NeedsRestart).is_err() {
+            return;
+        }
+
+        let drive = drive.clone();
+        let respawn = respawn.clone();
+
+        s.add_layer(Dialog::text(format!("Something went wrong and {} stopped working. A crash report was written to the working directory.
+                Restart it? Any disk in it will be started over.", drive.file))
+            .title(format!("Drive Stopped: {}", drive.file))
+            .h_align(HAlign::Center)
+            .button("Leave it", |s| { s.pop_layer(); })
+            .button("Restart", move |s| {
+                s.pop_layer();
+
+                drive.cancel.store(false, Relaxed);
+                show_drive_status(&drive, DriveStatus::Setup);
+                respawn(s);
+            })
+        );
+    });
+}
+
+fn add_changer_panel(s: &mut Cursive, root_view: &mut LinearLayout, config: &Config, drives: &DriveList) {
+    let device = match &config.changer {
+        Some(device) => device,
+        None => return, // No changer, nothing to show.
+    };
+
+    let message = match Changer::new(device, &config.changer_drives, &drives.get()) {
+        Ok(changer) => {
+            let changer = Arc::new(changer);
+            changer::spawn_changer_thread(&changer);
+
+            s.add_global_callback(Event::Refresh, move |s| {
+                // Shouldn't fail since we made this.
+                let mut status = s.find_id::<TextView>("changer-status").unwrap();
+                status.set_content(changer.status_message.lock().unwrap().clone());
+            });
+
+            String::from("----")
+        },
+        Err(message) => message,
+    };
+
+    root_view.add_child(Dialog::around(TextView::new(message).with_id("changer-status")).title(format!("Changer: {}", device)));
+}
+
+// Every drive at a glance, and a guess at when the disks that are in now will all be done. Hidden while there's only
+// one drive, since it's not worth it for one.
+fn add_overview_panel(s: &mut Cursive, root_view: &mut LinearLayout, drives: &Arc<DriveList>) {
+    let drives = drives.clone();
+    let mut shown: Vec<String> = Vec::new();
+
+    s.add_global_callback(Event::Refresh, move |s| {
+        let drives = drives.get();
+
+        // Shouldn't fail since we made these.
+        s.find_id::<HideableView<Dialog>>("overview").unwrap().set_visible(drives.len() >= 2);
+
+        // Drives come and go, so the bars get made over when they do.
+        let files: Vec<String> = drives.iter().map(|drive| drive.file.clone()).collect();
+        if files != shown {
+            let mut bars = s.find_id::<ListView>("overview-bars").unwrap();
+            bars.clear();
+
+            for drive in drives.iter() {
+                let progress = drive.progress.clone();
+                let bar_id = format!("overview-{}", drive.panel);
+                bars.add_child(&drive.file, ProgressBar::new().max(1000).with_label(move |_, _| progress.describe()).with_id(&bar_id));
             }
 
-            // Wait for disk to be removed.
-            while drive.has_disk.load(Relaxed) {
-                thread::sleep(Duration::from_millis(5000));
-            }
+            shown = files;
+        }
+
+        let busy: Vec<&Arc<DiskDrive>> = drives.iter().filter(|drive| is_drive_busy(drive)).collect();
+
+        for drive in drives.iter() {
+            let value = if busy.iter().any(|busy| Arc::ptr_eq(busy, drive)) { drive.progress.permille() } else { 0 };
+            s.find_id::<ProgressBar>(&format!("overview-{}", drive.panel)).unwrap().set_value(value);
+        }
+
+        // They all run at once, so the batch is done when the slowest one is.
+        let remaining: u64 = busy.iter().filter_map(|drive| drive.progress.bytes_remaining()).sum();
+        let time = busy.iter().filter_map(|drive| drive.progress.time_remaining()).max();
+
+        let message = match (busy.len(), time) {
+            (0, _) => String::from("Nothing's running."),
+            (count, Some(time)) => format!("{} of {} drives busy. {} left to go, done in about {}:{:02}.",
+                count, drives.len(), format_size(remaining), time.as_secs() / 60, time.as_secs() % 60),
+            (count, None) => format!("{} of {} drives busy. {} left to go.", count, drives.len(), format_size(remaining)),
+        };
+
+        s.find_id::<TextView>("overview-status").unwrap().set_content(message);
+    });
+
+    let mut linear = LinearLayout::vertical();
+    linear.add_child(TextView::new("----").with_id("overview-status"));
+    linear.add_child(ListView::new().with_id("overview-bars"));
+
+    root_view.add_child(HideableView::new(Dialog::around(linear).title("Overview")).hidden().with_id("overview"));
+}
+
+// What the other stations' drives are doing, with totals for all of them and this one together.
+fn add_stations_panel(s: &mut Cursive, root_view: &mut LinearLayout, config: &Config, drives: &Arc<DriveList>) {
+    if config.stations.is_empty() {
+        return;
+    }
+
+    let stations: Arc<Vec<Station>> = Arc::new(config.stations.iter().map(|address| Station::new(address)).collect());
+    stations::spawn_station_poller(config, &stations);
+
+    let mut linear = LinearLayout::vertical();
+    linear.add_child(TextView::new("----").with_id("stations-total"));
+
+    for station in stations.iter() {
+        linear.add_child(TextView::new(format!("{}:", station.address)));
+        linear.add_child(TextView::new("----").with_id(format!("station-{}", station.address)));
+    }
+
+    let drives = drives.clone();
+    s.add_global_callback(Event::Refresh, move |s| {
+        let mut total = drives.len();
+        let mut busy = drives.get().iter().filter(|drive| is_drive_busy(drive)).count();
+        let mut archived = session::get_stats(&drives)["disks_archived"].as_u64().unwrap_or(0);
+
+        for station in stations.iter() {
+            total += *station.drives.lock().unwrap();
+            busy += *station.busy_drives.lock().unwrap();
+            archived += *station.disks_archived.lock().unwrap();
+
+            // Shouldn't fail since we made these.
+            let mut status = s.find_id::<TextView>(&format!("station-{}", station.address)).unwrap();
+            status.set_content(station.status_message.lock().unwrap().clone());
+        }
+
+        let message = format!("{} of {} drives busy across {} stations, and {} disks archived between them.",
+            busy, total, stations.len() + 1, archived);
+        s.find_id::<TextView>("stations-total").unwrap().set_content(message);
+    });
+
+    root_view.add_child(Dialog::around(linear).title("Stations"));
+}
+
+fn add_tape_panel(s: &mut Cursive, root_view: &mut LinearLayout, config: &Config, catalog: &Arc<Mutex<Catalog>>) {
+    let device = match &config.tape_device {
+        Some(device) => device,
+        None => return, // Not spooling to tape.
+    };
+
+    let spooler = Arc::new(TapeSpooler::new(device, config.tape_batch_size, config.tape_keep_images));
+    tape::spawn_spooler_thread(&spooler, catalog);
+
+    s.add_global_callback(Event::Refresh, move |s| {
+        // Shouldn't fail since we made this.
+        let mut status = s.find_id::<TextView>("tape-status").unwrap();
+        status.set_content(spooler.status_message.lock().unwrap().clone());
+    });
+
+    root_view.add_child(Dialog::around(TextView::new("----").with_id("tape-status")).title(format!("Tape: {}", device)));
+}
+
+// Checks the config file for changes every so often, and tells the operator what happened when there are some.
+fn spawn_config_watch(s: &mut Cursive, config: &Arc<LiveConfig>) {
+    let config = config.clone();
+    let cb = s.cb_sink().clone();
+
+    thread::spawn(move || {
+        loop {
+            thread::sleep(config.get().polling.config);
+
+            let message = match config.reload() {
+                None => continue,
+                Some(Ok(changes)) if changes.is_empty() => format!("Reloaded {}. Disks put in from now on will use the new settings.", config::CONFIG_FILE),
+                Some(Ok(changes)) => format!("Reloaded {}. Disks put in from now on will use the new settings, but changes to {} won't do anything until auto_archive is restarted.",
+                    config::CONFIG_FILE, changes.join(", ")),
+                Some(Err(error)) => format!("{}
+The old settings are still being used.", config::get_config_error_message(&error)),
+            };
+
+            let sent = cb.send(Box::new(move |s| {
+                s.add_layer(Dialog::text(message)
+                    .title("Settings")
+                    .button("Ok", |s| { s.pop_layer(); } ));
+            }));
+
+            // The UI's gone, so there's nobody to tell.
+            if sent.is_err() {
+                return;
+            }
+        }
+    });
+}
+
+// The panel for one drive, with its task started. Hidden rather than taken out when the drive's pulled out, since
+// the callbacks that keep it up to date expect it to be there.
+fn add_drive_panel(s: &mut Cursive, panels: &mut LinearLayout, drive: &Arc<DiskDrive>, config: &Arc<LiveConfig>, catalog: &Arc<Mutex<Catalog>>, turns: &Arc<TurnQueue>) {
+
+    // Build drive UI.
+    let mut linear = LinearLayout::vertical();
+
+    if let Some(capabilities) = &drive.capabilities {
+        linear.add_child(TextView::new(get_capabilities_string(capabilities)));
+    }
+
+    let progress_id = format!("progress-{}", drive.panel);
+    add_progress_bar(s, drive, &mut linear, &progress_id);
+
+    let name_id = format!("name-{}", drive.panel);
+    let ready_id = format!("ready-{}", drive.panel);
+
+    add_name_settings(s, &mut linear, &name_id, &ready_id, drive, config);
+    add_patch_prompt(s, drive);
+
+    add_drive_ui_buttons(drive, catalog, &mut linear);
+
+    let status_id = format!("status-{}", drive.panel);
+    let tray_id = format!("tray-{}", drive.panel);
+
+    add_status_indicator(s, drive, &mut linear, &status_id, &tray_id);
+
+    spawn_drive_task(s, drive, config, catalog, turns, &name_id, &ready_id);
+
+    let (drive1, config, catalog, turns) = (drive.clone(), config.clone(), catalog.clone(), turns.clone());
+    add_restart_prompt(s, drive, move |s| {
+        spawn_drive_task(s, &drive1, &config, &catalog, &turns, &name_id, &ready_id);
+    });
+
+    HOTKEYS.lock().unwrap().push(drive.panel.clone());
+    let ready_id = format!("ready-{}", drive.panel);
+
+    if let Some(key) = get_hotkey(drive) {
+        let (drive, ready_id) = (drive.clone(), ready_id.clone());
+        s.add_global_callback(Event::AltChar(key), move |s| { accept_name(s, &drive, &ready_id); });
+    }
+
+    // Collapsed panels are just their title and the button to bring the rest back.
+    let layout_key = get_layout_key(drive);
+    let collapsed = layout::load(&layout_key).collapsed;
+
+    let body_id = format!("drive-body-{}", drive.panel);
+    let mut body = HideableView::new(linear);
+    body.set_visible(!collapsed);
+    let body = body.with_id(body_id.as_str());
+
+    // Now add that to the scrollable list. F2 goes to whichever panel has the focus, even from inside its text boxes.
+    let dialog_id = format!("drive-{}", drive.panel);
+    let dialog_id1 = dialog_id.clone();
+    let dialog = Dialog::around(body)
+        .title(get_drive_title(drive))
+        .button(if collapsed { "Expand" } else { "Collapse" }, move |s| { toggle_drive_panel(s, &dialog_id1, &body_id); })
+        .with_id(dialog_id);
+
+    let drive1 = drive.clone();
+    let dialog = OnEventView::new(dialog)
+        .on_pre_event(cursive::event::Key::F2, move |s| { accept_name(s, &drive1, &ready_id); })
+        .on_pre_event_inner(EventTrigger::any(), move |_, _| {
+            // Anything that gets here came through the focus.
+            layout::set_focused(&layout_key);
+            None
+        });
+    panels.add_child(HideableView::new(dialog).with_id(format!("drive-panel-{}", drive.panel)));
+}
+
+fn toggle_drive_panel(s: &mut Cursive, dialog_id: &str, body_id: &str) {
+    let mut body = s.find_id::<HideableView<LinearLayout>>(body_id).unwrap();
+    let collapsed = body.is_visible();
+    body.set_visible(!collapsed);
+
+    let mut dialog = s.find_id::<Dialog>(dialog_id).unwrap();
+    for button in dialog.buttons_mut().take(1) {
+        button.set_label(if collapsed { "Expand" } else { "Collapse" });
+    }
+}
+
+// What a drive's layout is kept under. Its settings key, so it follows the drive, or where it is if it has none.
+fn get_layout_key(drive: &DiskDrive) -> String {
+    drive.settings_key.clone().unwrap_or_else(|| drive.file.clone())
+}
+
+// Writes down the panels as they are now, for the next time auto_archive starts. Does nothing if it never got as far
+// as the drives.
+fn save_layout(s: &mut Cursive, drives: &DriveList) -> io::Result<()> {
+    if s.find_id::<LinearLayout>("drive-panels").is_none() {
+        return Ok(());
+    }
+
+    let focused = layout::get_focused();
+
+    let layouts: Vec<(String, layout::PanelLayout)> = drives.everything().iter().map(|drive| {
+        let key = get_layout_key(drive);
+
+        let collapsed = s.find_id::<HideableView<LinearLayout>>(&format!("drive-body-{}", drive.panel))
+            .map(|body| !body.is_visible())
+            .unwrap_or(false);
+
+        // Only disks asking for a name. Before that the box only has the name made up for it, which shouldn't beat
+        // a better one next time, and after that it keeps whatever was in it.
+        let waiting = matches!(drive.state.get(), DriveStatus::WaitingForName | DriveStatus::ConfirmingName);
+        let fingerprint = drive.fingerprint.lock().unwrap().clone().filter(|_| waiting);
+        let pending = fingerprint.and_then(|fingerprint| {
+            let name = s.find_id::<EditView>(&format!("name-{}", drive.panel))?.get_content();
+            Some((fingerprint, String::from(name.as_str()))).filter(|(_, name)| !name.is_empty())
+        });
+
+        let focused = focused.as_deref() == Some(key.as_str());
+        (key, layout::PanelLayout { collapsed, focused, pending })
+    }).collect();
+
+    layout::save(&layouts)
+}
+
+// Disks that were part way through when auto_archive last went down without cleaning up. Anything left alone is
+// resumed if there's a partial image of it, and ripped from the start if not, like any other disk.
+fn show_unfinished_jobs(s: &mut Cursive, unfinished: Vec<jobs::Job>) {
+    let mut list = LinearLayout::vertical();
+
+    for (index, job) in unfinished.into_iter().enumerate() {
+        let row_id = format!("unfinished-{}", index);
+        let partial = Some(&job.fingerprint).filter(|fingerprint| !fingerprint.is_empty()).and_then(|fingerprint| resume::find_partial(fingerprint));
+
+        let mut description = match job.stage {
+            jobs::Stage::Ripping => format!("{}: {}, while ripping", job.drive, job.volume),
+            jobs::Stage::Naming => format!("{}: {}, waiting to be saved as {}", job.drive, job.volume, job.name),
+        };
+
+        description += &match &partial {
+            Some(partial) => format!(". {} of it was kept.", format_size(partial.length)),
+            None if job.fingerprint.is_empty() => String::from(". It couldn't be fingerprinted, so it'll have to be ripped again."),
+            None => String::from(". Nothing was kept, so it'll have to be ripped again."),
+        };
+
+        let mut row = LinearLayout::vertical().child(TextView::new(description));
+        let mut buttons = LinearLayout::horizontal();
+
+        if partial.is_some() {
+            let row_id1 = row_id.clone();
+            buttons.add_child(Button::new("Resume", move |s| { hide_unfinished_job(s, &row_id1); }));
         }
-    });
+
+        if !job.fingerprint.is_empty() {
+            let (fingerprint1, row_id1) = (job.fingerprint.clone(), row_id.clone());
+            buttons.add_child(Button::new("Re-rip", move |s| {
+                resume::remove_partial(&fingerprint1);
+                hide_unfinished_job(s, &row_id1);
+            }));
+
+            let (fingerprint1, row_id1) = (job.fingerprint.clone(), row_id.clone());
+            buttons.add_child(Button::new("Discard", move |s| {
+                resume::remove_partial(&fingerprint1);
+                jobs::discard(&fingerprint1);
+                hide_unfinished_job(s, &row_id1);
+            }));
+        }
+
+        row.add_child(buttons);
+        list.add_child(HideableView::new(row).with_id(row_id));
+    }
+
+    s.add_layer(Dialog::around(LinearLayout::vertical()
+            .child(TextView::new("auto_archive didn't shut down cleanly last time. These disks were part way through. \
+                Resume picks up where it left off when the disk goes in, Re-rip starts it over, and Discard skips it \
+                the next time it goes in."))
+            .child(list)
+            .scrollable())
+        .title("Unfinished Disks")
+        .button("Done", |s| { s.pop_layer(); }));
 }
 
-fn add_name_settings(s: &mut Cursive, linear: &mut LinearLayout, name_id: &str, ready_id: &str, drive: &Arc<DiskDrive>) {
-    let settings = ListView::new()
-        .child("Settings ready: ", Checkbox::new().with_id(ready_id))
-        .child("File name: ", EditView::new().with_id(name_id));
-    linear.add_child(settings);
+fn hide_unfinished_job(s: &mut Cursive, row_id: &str) {
+    s.find_id::<HideableView<LinearLayout>>(row_id).unwrap().hide();
+}
 
-    let name_id = String::from(name_id);
-    let ready_id = String::from(ready_id);
-    let drive = drive.clone();
+// Puts the focus back on the drive that had it, going by the saved layout.
+fn restore_focus(s: &mut Cursive, drives: &DriveList) {
+    if let Some(drive) = drives.get().iter().find(|drive| layout::load(&get_layout_key(drive)).focused) {
+        let _ = s.focus_id(&format!("name-{}", drive.panel));
+        layout::set_focused(&get_layout_key(drive));
+    }
+}
 
-    s.add_global_callback(Event::Refresh, move |s| {
+type DrivePanel = HideableView<OnEventView<IdView<Dialog>>>;
 
-        let mut status = drive.status_message.lock().unwrap();
+// Drives, in the order their panels were made. Alt+1 accepts the name for the first, up to Alt+9. Pulled out drives
+// keep their number, so it doesn't change out from under the operator.
+static HOTKEYS: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
-        let mut text_box = s.find_id::<EditView>(&name_id).unwrap();
-        let ready_checkbox = s.find_id::<Checkbox>(&ready_id).unwrap();
-
-        match *status {
-            DriveStatus::WaitingForName => {
-                // Only go through with save if box is checked.
-                if ready_checkbox.is_checked() {
-
-                    let path = text_box.get_content().clone();
-
-                    if Path::new(path.as_ref()).exists() {
-                        // Path exists. Check if they really want to overwrite it.
-
-                        let ready_id1 = ready_id.clone();
-
-                        let drive1 = drive.clone();
-                        let drive2 = drive.clone();
-
-                        s.add_layer(Dialog::text("A file with this name exists. Do you want to overwrite it?")
-                            .title("Confirm Overwrite")
-                            .h_align(HAlign::Center)
-                            .button("No", move |s| {
-                                s.pop_layer();
-
-                                let mut ready_checkbox = s.find_id::<Checkbox>(&ready_id1).unwrap();
-                                ready_checkbox.set_checked(false);
-
-                                // Go back to waiting for a name.
-                                let mut status = drive1.status_message.lock().unwrap();
-                                *status = DriveStatus::WaitingForName;
-                            })
-                            .button("Yes", move |s| {
-                                s.pop_layer();
-
-                                // Okay, save it.
-                                let mut status = drive2.status_message.lock().unwrap();
-                                *status = DriveStatus::Saving(path.as_ref().clone());
-                            })
-                        );
-
-                        // We are now confirming the name. This is needed to prevent infinite spawning of confirmation windows.
-                        *status = DriveStatus::ConfirmingName;
-                    } else {
-                        // No problem just save it.
-                        *status = DriveStatus::Saving(path.as_ref().clone());
+fn get_hotkey(drive: &DiskDrive) -> Option<char> {
+    let position = HOTKEYS.lock().unwrap().iter().position(|panel| *panel == drive.panel)?;
+    std::char::from_digit(position as u32 + 1, 10).filter(|_| position < 9)
+}
+
+// Saves the disk waiting for a name under whatever's in the name box, same as checking "Settings ready".
+fn accept_name(s: &mut Cursive, drive: &DiskDrive, ready_id: &str) {
+    if drive.state.get() == DriveStatus::WaitingForName {
+        s.find_id::<Checkbox>(ready_id).unwrap().set_checked(true);
+    }
+}
+
+// For batches where the volume names are good enough. Names that would overwrite something still get asked about,
+// and so do drives that would be saved under the same name as another one.
+fn accept_all_names(s: &mut Cursive, drives: &DriveList) {
+    let drives = drives.get();
+
+    let names: Vec<(Arc<DiskDrive>, String)> = drives.iter()
+        .filter(|drive| drive.state.get() == DriveStatus::WaitingForName)
+        .map(|drive| {
+            let name = s.find_id::<EditView>(&format!("name-{}", drive.panel)).unwrap().get_content();
+            (drive.clone(), String::from(name.as_str()))
+        })
+        .collect();
+
+    // Names other drives are already being saved under count too.
+    let saving: Vec<String> = drives.iter()
+        .filter_map(|drive| match drive.state.get() {
+            DriveStatus::Saving(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+
+    let mut clashes = Vec::new();
+
+    for (drive, name) in names.iter() {
+        let same = names.iter().filter(|(_, other)| other == name).count();
+
+        if same > 1 || saving.contains(name) {
+            clashes.push(format!("{} ({})", drive.file, name));
+        } else {
+            accept_name(s, drive, &format!("ready-{}", drive.panel));
+        }
+    }
+
+    if !clashes.is_empty() {
+        s.add_layer(Dialog::text(format!("These drives would be saved under the same name as another drive, so they still need names:\n{}",
+            clashes.join("\n")))
+            .title("Accept All Names")
+            .button("Ok", |s| { s.pop_layer(); } ));
+    }
+}
+
+#[derive(Default)]
+struct HotPlug {
+    plugged: Vec<Arc<DiskDrive>>,   // New this session, so they need panels.
+    returned: Vec<Arc<DiskDrive>>,  // Pulled out earlier, and plugged back in.
+    unplugged: Vec<Arc<DiskDrive>>,
+}
+
+// Drives plugged in or pulled out since the last look, sorted out in the drive list. Blocks, since it runs lsscsi and
+// asks new drives what they are.
+fn check_for_hot_plug(drives: &DriveList, config: &Config) -> Result<HotPlug, DiskInfoError> {
+    let mut changes = HotPlug::default();
+
+    for found in find_local_drives(config)? {
+        if drives.find_plugged(&found.file).is_some() {
+            continue;
+        }
+
+        // Device names get reused, by whatever's plugged in next, so it's only the same drive if it says it is.
+        let mut drive = new_found_drive(found);
+        match drives.bring_back(&drive) {
+            Some(returned) => changes.returned.push(returned),
+            None => {
+                // A different drive where one was pulled out gets a panel of its own.
+                let before = drives.everything().iter().filter(|other| other.file == drive.file).count();
+                if before > 0 {
+                    drive.panel = format!("{}#{}", drive.file, before + 1);
+                }
+
+                let drive = Arc::new(drive);
+                drives.add(drive.clone());
+                changes.plugged.push(drive);
+            },
+        }
+    }
+
+    // Remote drives come and go with their disks, so they're never counted as pulled out.
+    let gone: Vec<String> = drives.get().iter()
+        .filter(|drive| drive.remote.is_none() && !Path::new(&drive.file).exists())
+        .map(|drive| drive.file.clone())
+        .collect();
+    changes.unplugged = gone.iter().filter_map(|file| drives.retire(file)).collect();
+
+    for drive in changes.plugged.iter().chain(changes.returned.iter()) {
+        events::publish(&drive.file, DriveEvent::Plugged);
+    }
+
+    for drive in changes.unplugged.iter() {
+        // Nothing's in a drive that isn't there. Lets its task go back to waiting for a disk.
+        if drive.has_disk.swap(false, Relaxed) {
+            events::publish(&drive.file, DriveEvent::DiskRemoved);
+        }
+        drive.state.wake();
+        events::publish(&drive.file, DriveEvent::Unplugged);
+    }
+
+    Ok(changes)
+}
+
+// A failure or two is nothing to worry about, like blkid getting caught out by a disk going in.
+const HELPER_ALERT_FAILURES: u32 = 3;
+
+// What to warn the operator about, if blkid or lsscsi has been failing. None once they both work again.
+fn get_helper_alert(lsscsi: &Backoff, blkid: &Backoff) -> Option<String> {
+    let mut alert = Vec::new();
+
+    if blkid.failures() >= HELPER_ALERT_FAILURES {
+        alert.push(format!("{} Disks going in and coming out won't be noticed until it works again.", blkid.reason().unwrap_or_default()));
+    }
+
+    if lsscsi.failures() >= HELPER_ALERT_FAILURES {
+        alert.push(format!("{} Drives plugged in or pulled out won't be noticed until it works again.", lsscsi.reason().unwrap_or_default()));
+    }
+
+    if alert.is_empty() {
+        None
+    } else {
+        alert.push(String::from("Trying again less and less often. Press F5 to try now."));
+        Some(alert.join("\n"))
+    }
+}
+
+fn show_helper_alert(s: &mut Cursive, alert: Option<String>) {
+    let mut banner = s.find_id::<HideableView<Dialog>>("helper-alert").unwrap();
+    banner.set_visible(alert.is_some());
+
+    if let Some(alert) = alert {
+        banner.get_inner_mut().set_content(TextView::new(alert));
+    }
+}
+
+fn build_main_menu(s: &mut Cursive, drives: &Arc<DriveList>, config: &Arc<LiveConfig>, catalog: &Arc<Mutex<Catalog>>) {
+    let mut root_view = LinearLayout::vertical();
+
+    // Hidden unless blkid or lsscsi keeps failing.
+    root_view.add_child(HideableView::new(Dialog::new().title("Degraded")).hidden().with_id("helper-alert"));
+
+    add_overview_panel(s, &mut root_view, drives);
+    add_stations_panel(s, &mut root_view, &config.get(), drives);
+    add_changer_panel(s, &mut root_view, &config.get(), drives);
+    add_tape_panel(s, &mut root_view, &config.get(), catalog);
+    spawn_config_watch(s, config);
+
+    let turns = Arc::new(TurnQueue::default());
+
+    let mut panels = LinearLayout::vertical();
+    for drive in drives.get().iter() {
+        add_drive_panel(s, &mut panels, drive, config, catalog, &turns);
+    }
+    root_view.add_child(panels.with_id("drive-panels"));
+
+    let catalog1 = catalog.clone();
+    let catalog2 = catalog.clone();
+    let drives2 = drives.clone();
+    let drives3 = drives.clone();
+    let turns1 = turns.clone();
+
+    s.add_fullscreen_layer(Dialog::around(root_view.full_width())
+        .title("All Disk Drives")
+        .button("Search catalog", move |s| { show_catalog_search(s, &catalog1); })
+        .button("Find a file", move |s| { show_file_search(s, &catalog2); })
+        .button("Queue", move |s| { show_queue(s, &drives3, &turns1); })
+        .button("Export session", show_session_export)
+        .button("Accept all names (F3)", move |s| { accept_all_names(s, &drives2); })
+        .button("Rescan (F5)", |_| { request_rescan(); })
+        .scrollable());
+    s.set_autorefresh(true);
+
+    s.add_global_callback(cursive::event::Key::F5, |_| { request_rescan(); });
+
+    let drives4 = drives.clone();
+    s.add_global_callback(cursive::event::Key::F3, move |s| { accept_all_names(s, &drives4); });
+
+    restore_focus(s, drives);
+
+    let (drives, config, catalog) = (drives.clone(), config.clone(), catalog.clone());
+    let cb = s.cb_sink().clone();
+
+    // Drives are opened and asked about with async file I/O, so one that's slow to answer only holds up the scan, not
+    // every other drive's task.
+    tokio::spawn(async move {
+        let mut last_scan: Option<Instant> = None;
+        let mut rescan = false;
+
+        // A rescan tries them again right away, even if they're backing off.
+        let mut lsscsi = Backoff::default();
+        let mut blkid = Backoff::default();
+        let mut alert = None;
+
+        loop {
+            let polling = config.get().polling;
+
+            // Checking the trays is cheap, so do it often and only run blkid when something happened or it's been a while.
+            let trays_changed = check_drive_trays(&drives.get()).await;
+            let scan_due = last_scan.is_none_or(|time| time.elapsed() >= polling.media);
+
+            // Looking for drives is done along with the slow scan, since lsscsi isn't cheap either.
+            if (scan_due && lsscsi.is_due()) || rescan {
+                let (drives1, config1) = (drives.clone(), config.get());
+                let result = tokio::task::spawn_blocking(move || check_for_hot_plug(&drives1, &config1)).await;
+
+                if let Ok(Err(error)) = &result {
+                    lsscsi.failed(polling.media, get_helper_error_message("lsscsi", error));
+                }
+
+                if let Ok(Ok(changes)) = result {
+                    lsscsi.succeeded();
+
+                    if !changes.plugged.is_empty() || !changes.returned.is_empty() || !changes.unplugged.is_empty() {
+                        let (config, catalog, turns) = (config.clone(), catalog.clone(), turns.clone());
+
+                        let _ = cb.send(Box::new(move |s| {
+                            // Shouldn't fail since we made these.
+                            for drive in changes.plugged.iter() {
+                                let mut panels = s.find_id::<LinearLayout>("drive-panels").unwrap();
+                                add_drive_panel(s, &mut panels, drive, &config, &catalog, &turns);
+                            }
+
+                            for drive in changes.returned.iter() {
+                                s.find_id::<DrivePanel>(&format!("drive-panel-{}", drive.panel)).unwrap().unhide();
+                            }
+
+                            for drive in changes.unplugged.iter() {
+                                s.find_id::<DrivePanel>(&format!("drive-panel-{}", drive.panel)).unwrap().hide();
+                            }
+                        }));
                     }
                 }
             }
-            _ => {} // Ignore all other cases.
-        }
 
-        // Do not permit editing while we are set as ready.
-        text_box.set_enabled(!ready_checkbox.is_checked());
+            if ((trays_changed || scan_due) && blkid.is_due()) || rescan {
+                match check_disks_in_drives(&drives.get()).await {
+                    Ok(()) => blkid.succeeded(),
+                    Err(error) => blkid.failed(polling.media, get_helper_error_message("blkid", &error)),
+                }
+                last_scan = Some(Instant::now());
+            }
+
+            let new_alert = get_helper_alert(&lsscsi, &blkid);
+            if new_alert != alert {
+                alert = new_alert.clone();
+                let _ = cb.send(Box::new(move |s| { show_helper_alert(s, new_alert); }));
+            }
+
+            rescan = tokio::time::timeout(polling.tray, RESCAN.notified()).await.is_ok();
+        }
     });
 }
 
-fn build_main_menu(s: &mut Cursive, drives: &Vec<Arc<DiskDrive>>) {
-    let mut root_view = LinearLayout::vertical();
+// Asked for when something wants to know what's in the drives right now, instead of on the next scan.
+static RESCAN: tokio::sync::Notify = tokio::sync::Notify::const_new();
 
-    for drive in drives.iter() {
+fn request_rescan() {
+    RESCAN.notify_one();
+}
+
+// Patrons shouldn't be able to quit by accident, or at all without the admin password, which kiosks always have.
+// Ctrl-C asks for it the same as Esc.
+fn lock_kiosk_quit(s: &mut Cursive, password: String) {
+    s.clear_global_callbacks(cursive::event::Key::Esc);
+    s.clear_global_callbacks(Event::CtrlChar('c'));
+
+    let ask = move |s: &mut Cursive| {
+        let password = password.clone();
+
+        s.add_layer(Dialog::around(EditView::new().secret().with_id("quit-password").fixed_width(30))
+            .title("Admin Password to Quit")
+            .button("Cancel", |s| { s.pop_layer(); })
+            .button("Quit", move |s| {
+                // Shouldn't fail since we made this.
+                let entered = s.find_id::<EditView>("quit-password").unwrap().get_content();
+
+                if *entered == password {
+                    s.quit();
+                } else {
+                    s.pop_layer();
+                }
+            }));
+    };
+
+    let ask = Arc::new(ask);
+    let ask1 = ask.clone();
+    s.add_global_callback(cursive::event::Key::Esc, move |s| ask(s));
+    s.add_global_callback(Event::CtrlChar('c'), move |s| ask1(s));
+}
+
+// Shown over the intro, so the role's settled before the drives' buttons are made.
+fn ask_for_admin_password(s: &mut Cursive, password: String) {
+    s.add_layer(Dialog::around(EditView::new().secret().with_id("admin-password").fixed_width(30))
+        .title("Admin Password")
+        .button("Run as operator", |s| { s.pop_layer(); })
+        .button("Ok", move |s| {
+            // Shouldn't fail since we made this.
+            let entered = s.find_id::<EditView>("admin-password").unwrap().get_content();
+            s.pop_layer();
+
+            if *entered == password {
+                role::set_role(Role::Admin);
+            } else {
+                s.add_layer(Dialog::text("That's not the password. Running as an operator.")
+                    .button("Ok", |s| { s.pop_layer(); } ));
+            }
+        }));
+}
+
+fn show_session_export(s: &mut Cursive) {
+    let message = match session::export_session() {
+        Ok(name) => format!("Saved the session to {}.", name),
+        Err(error) => format!("Failed to save the session: {}", error),
+    };
 
-        // Build drive UI.
-        let mut linear = LinearLayout::vertical();
+    s.add_layer(Dialog::text(message)
+        .button("Ok", |s| { s.pop_layer(); } ));
+}
 
-        let progress_id = format!("progress-{}", drive.file);
-        let counter = Counter::new(0);
-        let view = ProgressBar::new().max(1000).with_value(counter.clone()).with_id(&progress_id);
-        linear.add_child(view);
+// The disks at one stage, oldest first.
+fn describe_queue(entries: &[QueueEntry], stage: Stage) -> String {
+    let mut description: Vec<String> = Vec::new();
 
-        let name_id = format!("name-{}", drive.file);
-        let ready_id = format!("ready-{}", drive.file);
+    for entry in entries.iter().filter(|entry| entry.stage == stage) {
+        // Disks still going haven't been saved yet, but might have a name lined up for them already.
+        let name = match jobs::find(&entry.drive) {
+            Some(job) if entry.name.is_empty() && !entry.finished => job.name,
+            _ => entry.name.clone(),
+        };
 
-        add_name_settings(s, &mut linear, &name_id, &ready_id, &drive);
+        let mut lines = format!("{} UTC  {}  {}", catalog::format_date(entry.started), entry.drive, entry.status);
+        if !name.is_empty() {
+            lines += &format!("\n    Name: {}", name);
+        }
+        if !entry.destination.is_empty() {
+            lines += &format!("\n    Saved to: {}", entry.destination);
+        }
 
-        add_drive_ui_buttons(drive, &mut linear);
+        description.push(lines);
+    }
 
-        let status_id = format!("status-{}", drive.file);
+    if description.is_empty() {
+        return String::from("None.");
+    }
 
-        add_status_indicator(s, drive, &mut linear, &status_id);
+    description.join("\n")
+}
 
-        spawn_drive_thread(s, &drive, counter, &name_id, &ready_id);
+// Fills in the queue screen with how things are now. Whatever drive was picked in the line stays picked, wherever it
+// ended up.
+fn refresh_queue(s: &mut Cursive, drives: &DriveList, turns: &TurnQueue) {
+    // Shouldn't fail since we made these.
+    let entries = queue::entries();
+    s.find_id::<TextView>("queue-active").unwrap().set_content(describe_queue(&entries, Stage::Active));
+    s.find_id::<TextView>("queue-finished").unwrap().set_content(describe_queue(&entries, Stage::Finished));
+
+    let mut waiting = s.find_id::<SelectView<String>>("queue-waiting").unwrap();
+    let picked = waiting.selection();
+    waiting.clear();
+
+    for (place, file) in turns.waiting().into_iter().enumerate() {
+        let title = drives.find(&file).map(|drive| get_drive_title(&drive)).unwrap_or_else(|| file.clone());
+        waiting.add_item(format!("{}. {}", place + 1, title), file);
+    }
 
-        // Now add that to the scrollable list.
-        root_view.add_child(Dialog::around(linear).title(&format!("Drive: {}", drive.file)));
+    if let Some(index) = picked.and_then(|picked| waiting.iter().position(|(_, file)| *file == *picked)) {
+        waiting.set_selection(index);
     }
+}
 
-    s.add_fullscreen_layer(Dialog::around(root_view.full_width()).title("All Disk Drives").scrollable());
-    s.set_autorefresh(true);
+// Moves the drive picked in the line on the queue screen.
+fn reorder_queue<F>(s: &mut Cursive, drives: &DriveList, turns: &TurnQueue, reorder: F) where F: FnOnce(&TurnQueue, &str) {
+    // Shouldn't fail since we made this.
+    let picked = s.find_id::<SelectView<String>>("queue-waiting").unwrap().selection();
+
+    if let Some(file) = picked {
+        reorder(turns, &file);
+    }
 
+    refresh_queue(s, drives, turns);
+}
+
+// Every disk this session, split up into the ones waiting for their turn, the ones being worked on, and the ones that
+// are done. Drives with sequential_rips waiting for their turn can be moved up or down the line. The finished list is
+// scrolled to the bottom, since that's the newest.
+fn show_queue(s: &mut Cursive, drives: &Arc<DriveList>, turns: &Arc<TurnQueue>) {
+    let mut finished = TextView::new("").with_id("queue-finished").scrollable();
+    finished.set_scroll_strategy(cursive::view::ScrollStrategy::StickToBottom);
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Pending, next first:"))
+        .child(SelectView::<String>::new().with_id("queue-waiting").scrollable().max_height(6))
+        .child(TextView::new("\nActive:"))
+        .child(TextView::new("").with_id("queue-active").scrollable().max_height(8))
+        .child(TextView::new("\nFinished:"))
+        .child(finished.max_height(10));
+
+    let (drives1, turns1) = (drives.clone(), turns.clone());
+    let (drives2, turns2) = (drives.clone(), turns.clone());
+    let (drives3, turns3) = (drives.clone(), turns.clone());
+    let (drives4, turns4) = (drives.clone(), turns.clone());
+
+    s.add_layer(Dialog::around(layout.min_width(70))
+        .title("Queue")
+        .button("Rush", move |s| { reorder_queue(s, &drives1, &turns1, |turns, file| turns.rush(file)); })
+        .button("Move up", move |s| { reorder_queue(s, &drives2, &turns2, |turns, file| turns.move_by(file, 1)); })
+        .button("Move down", move |s| { reorder_queue(s, &drives3, &turns3, |turns, file| turns.move_by(file, -1)); })
+        .button("Refresh", move |s| { refresh_queue(s, &drives4, &turns4); })
+        .button("Close", |s| { s.pop_layer(); }));
+
+    refresh_queue(s, drives, turns);
+}
+
+// Writes down everything that happens to every drive, for looking back on later.
+fn spawn_activity_recorder(drives: &Arc<DriveList>) {
+    let events = events::subscribe();
     let drives = drives.clone();
 
     thread::spawn(move || {
-        loop {
-            if !check_disks_in_drives(&drives).is_ok() {
-                // TODO something.
+        for event in events.iter() {
+            if let Some(drive) = drives.find(&event.drive) {
+                drive.activity.record(&event.event);
             }
-            thread::sleep(Duration::from_millis(5000));
+
+            queue::record(&event.drive, &event.event);
         }
     });
 }
 
+// What every drive is up to, for crash reports. Anything locked is skipped, since whatever has it might be what crashed.
+fn describe_drives(drives: &DriveList) -> String {
+    let drives = match drives.try_get() {
+        Some(drives) => drives,
+        None => return String::from("(the drive list was locked)\n"),
+    };
+
+    let mut description = String::new();
+
+    for drive in drives.iter() {
+        let status = drive.state.try_get().map(|status| get_drive_status_message_string(&status))
+            .unwrap_or_else(|| String::from("(locked)"));
+        let fingerprint = drive.fingerprint.try_lock().ok().and_then(|fingerprint| fingerprint.clone())
+            .unwrap_or_else(|| String::from("none"));
+
+        description += &format!("{}: {}
+    Disk in: {}, fingerprint: {}
+",
+            drive.file, status, if drive.has_disk.load(Relaxed) { "yes" } else { "no" }, fingerprint);
+    }
+
+    description
+}
+
 fn main() {
 
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let role_flag = role::take_role_flag(&mut args);
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
     let mut siv = Cursive::default();
 
     siv.add_global_callback(cursive::event::Key::Esc, |s| {
@@ -616,40 +4744,203 @@ fn main() {
         );
     });
 
-    let drives = list_disk_drives();
+    let config = match config::load_config() {
+        Ok(config) => config,
+        Err(error) => {
+            siv.add_layer(
+                Dialog::text(config::get_config_error_message(&error))
+                    .title("Mass Disk Archiver")
+                    .button("Exit", |s| s.quit())
+            );
+
+            siv.run();
+            return;
+        }
+    };
+
+    let catalog = match catalog::load_catalog() {
+        Ok(catalog) => catalog,
+        Err(error) => {
+            siv.add_layer(
+                Dialog::text(catalog::get_catalog_error_message(&error))
+                    .title("Mass Disk Archiver")
+                    .button("Exit", |s| s.quit())
+            );
+
+            siv.run();
+            return;
+        }
+    };
+
+    let dat = match redump::load_dats(&config.redump_dats) {
+        Ok(dat) => dat,
+        Err(error) => {
+            siv.add_layer(
+                Dialog::text(redump::get_dat_error_message(&error))
+                    .title("Mass Disk Archiver")
+                    .button("Exit", |s| s.quit())
+            );
+
+            siv.run();
+            return;
+        }
+    };
+
+    if let Some(Err(error)) = config.naming_map.as_ref().map(|file| naming_map::load_naming_map(file)) {
+        siv.add_layer(
+            Dialog::text(naming_map::get_naming_map_error_message(&error))
+                .title("Mass Disk Archiver")
+                .button("Exit", |s| s.quit())
+        );
+
+        siv.run();
+        return;
+    }
+
+    // Admin has to be earned with the password, if there is one, so start out as an operator until then. Kiosks are
+    // always run by operators.
+    let role = if config.kiosk { Role::Operator } else { role_flag.unwrap_or(config.role) };
+    let admin_password = config.admin_password.clone().filter(|_| role == Role::Admin);
+    role::set_role(if admin_password.is_some() { Role::Operator } else { role });
+
+    if config.kiosk {
+        // Config parsing won't let a kiosk go without one.
+        lock_kiosk_quit(&mut siv, config.admin_password.clone().unwrap_or_default());
+    }
+
+    buffer_pool::init(config.buffer_pool_size);
+    sink::register(sink::LOCAL_FILE, sink::LocalFileSink::create);
+
+    session::started();
+    let drives = list_disk_drives(&config);
+    let config = Arc::new(LiveConfig::new(config));
+    let catalog = Arc::new(Mutex::new(catalog));
+    metadata::register_defaults(Arc::new(dat));
+
+    // The drives and everything watching them run on this. The UI keeps the main thread to itself.
+    let runtime = match tokio::runtime::Builder::new_multi_thread().enable_all().thread_name("drives").build() {
+        Ok(runtime) => runtime,
+        Err(error) => {
+            siv.add_layer(
+                Dialog::text(format!("Failed to start the threads to run the drives on: {}", error))
+                    .title("Mass Disk Archiver")
+                    .button("Exit", |s| s.quit())
+            );
+
+            siv.run();
+            return;
+        }
+    };
+    let entered_runtime = runtime.enter();
+
+    let exit_config = config.clone();
+    let mut session_drives = None;
 
     match drives {
         Ok(drives) => {
-            let drives = Arc::new(drives);
+            // Before any drive starts on a disk, since what the operator says decides how those go.
+            let unfinished = jobs::take_unfinished();
+
+            let drives = Arc::new(DriveList::new(drives));
+            session_drives = Some(drives.clone());
+
+            spawn_activity_recorder(&drives);
+            chat_bot::spawn_chat_bots(&config.get(), &drives);
+
+            let crashed_drives = drives.clone();
+            crash::install(move || describe_drives(&crashed_drives), config.get().kiosk);
 
             let mut intro_text = format!("Press <esc> at any time to quit.\nFound {} disk drives.\n", drives.len());
-            for drive in drives.iter() {
-                intro_text += &format!("{}\n", drive.file);
+            for drive in drives.get().iter() {
+                match (&drive.remote, &drive.model) {
+                    (Some(remote), _) => intro_text += &format!("{} on {}\n", drive.file, remote.describe()),
+                    (None, Some(model)) => intro_text += &format!("{} - {}\n", drive.file, model),
+                    (None, None) => intro_text += &format!("{}\n", drive.file),
+                }
             }
 
-            siv.add_layer(
-                Dialog::text(intro_text)
-                    .title("Mass Disk Archiver")
-                    .h_align(HAlign::Center)
-                    .button("Continue", move |s| {
-                        s.pop_layer();
+            // Anything added from here on is a problem, which kiosks show on their own.
+            let drive_list_length = intro_text.len();
 
-                        build_main_menu(s, &drives);
-                    })
-            );
-        },
-        Err(error) => {
-            let message = match error {
-                DiskInfoError::LaunchFail =>
-                    "Failed to launch lsscsi. Is it not installed?",
-                DiskInfoError::ConvertToUTF =>
-                    "Failed to convert lsscsi output to UTF8 for parsing. Major bug?",
-                DiskInfoError::Parse =>
-                    "Failed to parse lsscsi output. Has the application changed its formatting?",
+            let remotes = drives.get().iter().filter(|drive| drive.remote.is_some()).count();
+            if remotes < config.get().remote_drives.len() {
+                intro_text += &format!("\nOnly found room for {} of the {} remote drives. Is the nbd module loaded? (modprobe nbd)\n",
+                    remotes, config.get().remote_drives.len());
+            }
+
+            if config.get().webcam_device.is_some() && !cfg!(feature = "webcam") {
+                intro_text += "\nwebcam_device is set, but this auto_archive was built without the webcam feature. Labels won't be photographed.\n";
+            }
+
+            if let Err(error) = home_assistant::spawn_home_assistant(&config.get(), &drives) {
+                intro_text += &format!("\nCouldn't start Home Assistant: {}\n", error);
+            }
+
+            // Better to not start them at all than to start them open to anyone when they weren't meant to be.
+            let security = match auth::load_security(&config.get()) {
+                Ok(security) => Some(security),
+                Err(error) => {
+                    intro_text += &format!("\n{}\nThe API and gRPC weren't started.\n", auth::get_auth_error_message(&error));
+                    None
+                },
             };
 
+            if let (Some(address), Some(security)) = (&config.get().api_address, &security) {
+                if let Err(error) = api::spawn_api(address, &drives, &catalog, security) {
+                    intro_text += &format!("\nCouldn't start the API on {}: {}\n", address, error);
+                }
+            }
+
+            if let (Some(address), Some(security)) = (&config.get().grpc_address, &security) {
+                if let Err(error) = grpc::spawn_grpc(address, &drives, &security.for_grpc(&config.get())) {
+                    intro_text += &format!("\nCouldn't start gRPC on {}: {}\n", address, error);
+                }
+            }
+
+            // Try it on a thread of its own, so a priority meant for the copies doesn't stick to the UI.
+            let (nice, io_priority) = (config.get().copy_nice, config.get().copy_io_priority);
+            if let Ok(Err(message)) = thread::spawn(move || priority::set_thread_priority(nice, io_priority)).join() {
+                intro_text += &format!("\n{}\nDrives will copy at normal priority.\n", message);
+            }
+
+            if config.get().kiosk {
+                // Nobody's there to press continue, especially after it's restarted itself.
+                build_main_menu(&mut siv, &drives, &config, &catalog);
+
+                if intro_text.len() > drive_list_length {
+                    siv.add_layer(Dialog::text(intro_text.split_off(drive_list_length).trim())
+                        .title("Mass Disk Archiver")
+                        .button("Ok", |s| { s.pop_layer(); } ));
+                }
+
+                // Left up until someone deals with it. Left alone, anything with a partial image is resumed anyway.
+                if !unfinished.is_empty() {
+                    show_unfinished_jobs(&mut siv, unfinished);
+                }
+            } else {
+                siv.add_layer(
+                    Dialog::text(intro_text)
+                        .title("Mass Disk Archiver")
+                        .h_align(HAlign::Center)
+                        .button("Continue", move |s| {
+                            s.pop_layer();
+
+                            build_main_menu(s, &drives, &config, &catalog);
+                        })
+                );
+
+                if !unfinished.is_empty() {
+                    show_unfinished_jobs(&mut siv, unfinished);
+                }
+
+                if let Some(password) = admin_password {
+                    ask_for_admin_password(&mut siv, password);
+                }
+            }
+        },
+        Err(error) => {
             siv.add_layer(
-                Dialog::text(message)
+                Dialog::text(get_disk_info_error_message(&error))
                     .title("Mass Disk Archiver")
                     .button("Exit", |s| s.quit())
             );
@@ -657,6 +4948,35 @@ fn main() {
     }
 
     siv.run();
+
+    // Anything still going was given up on on purpose, so there's nothing to ask about next time.
+    jobs::clear();
+
+    // The UI's gone by now, so the terminal's the only place left to say anything.
+    if let Some(drives) = session_drives.as_ref() {
+        if let Err(error) = save_layout(&mut siv, drives) {
+            eprintln!("Failed to save the layout: {}", error);
+        }
+    }
+
+    // Only once it got as far as the drives, or there's nothing to say.
+    if session_drives.is_some() && exit_config.get().export_session_on_exit {
+        match session::export_session() {
+            Ok(name) => println!("Saved the session to {}.", name),
+            Err(error) => eprintln!("Failed to save the session: {}", error),
+        }
+    }
+
+    // Don't leave remote drives attached with nobody using them.
+    for drive in session_drives.iter().flat_map(|drives| drives.everything()).filter(|drive| drive.remote.is_some()) {
+        if let Err(error) = remote::disconnect(&drive.file) {
+            eprintln!("Failed to let go of {}: {}", drive.file, remote::get_remote_error_message(&error));
+        }
+    }
+
+    // Anything still ripping is abandoned, same as it always was when the process ended.
+    drop(entered_runtime);
+    runtime.shutdown_background();
 }
 
 #[cfg(test)]
@@ -0,0 +1,209 @@
+
+// Keeps track of which parts of a disk couldn't be read, and writes them out as a GNU ddrescue mapfile
+// so recovery of a stubborn disk can be picked back up with ddrescue on another machine or drive.
+
+use serde_json::json;
+use serde_json::Value;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+
+#[derive(Clone, Default)]
+pub struct DamageMap {
+    bad: Vec<(u64, u64)>, // Start and length of each unreadable region, in order.
+    length: u64,          // Size of the whole disk.
+    ended_at: Option<u64>, // Where the disk actually ended, if it was before `length`.
+}
+
+impl DamageMap {
+    pub fn new(length: u64) -> DamageMap {
+        DamageMap {
+            bad: Vec::new(),
+            length,
+            ended_at: None,
+        }
+    }
+
+    // The disk ran out before the length it said it had. Everything after this never got read.
+    pub fn end_early(&mut self, position: u64) {
+        self.ended_at = Some(position);
+    }
+
+    pub fn ended_early(&self) -> Option<u64> {
+        self.ended_at
+    }
+
+    // Regions have to be added in order. Ones that touch the last region are merged into it.
+    pub fn add_bad(&mut self, start: u64, length: u64) {
+        if let Some(last) = self.bad.last_mut() {
+            if last.0 + last.1 == start {
+                last.1 += length;
+                return;
+            }
+        }
+
+        self.bad.push((start, length));
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.bad.is_empty() && self.ended_at.is_none()
+    }
+
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    pub fn get_bad_regions(&self) -> &[(u64, u64)] {
+        &self.bad
+    }
+
+    pub fn bad_bytes(&self) -> u64 {
+        self.bad.iter().map(|(_, length)| length).sum()
+    }
+
+    // How much of the disk is zeros instead of what was on it, as a percentage.
+    fn get_bad_percent(&self) -> f64 {
+        if self.length == 0 {
+            return 0.0;
+        }
+
+        self.bad_bytes() as f64 * 100.0 / self.length as f64
+    }
+
+    // One line for the catalog.
+    pub fn summary(&self) -> String {
+        format!("{} unreadable region{}, {} bytes ({:.3}% of the disk) zero filled",
+            self.bad.len(), if self.bad.len() == 1 { "" } else { "s" }, self.bad_bytes(), self.get_bad_percent())
+    }
+
+    // The full report for the sidecar, with every region, so nobody has to learn to read a mapfile.
+    pub fn report(&self) -> Value {
+        json!({
+            "regions": self.bad.len(),
+            "bytes": self.bad_bytes(),
+            "percent": self.get_bad_percent(),
+            "ranges": self.bad.iter().map(|(start, length)| json!({
+                "start": start,
+                "length": length,
+            })).collect::<Vec<Value>>(),
+            "ended_at": self.ended_at,
+        })
+    }
+
+    fn build_mapfile(&self) -> String {
+        let mut map = String::new();
+
+        // Writing to a String can't fail.
+        let _ = writeln!(map, "# Mapfile. Created by auto_archive {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(map, "# current_pos  current_status  current_pass");
+        let _ = writeln!(map, "0x{:08X}     +               1", 0);
+        let _ = writeln!(map, "#      pos        size  status");
+
+        // Everything between the bad regions was read fine.
+        let mut position = 0;
+        for (start, length) in self.bad.iter() {
+            if *start > position {
+                let _ = writeln!(map, "0x{:08X}  0x{:08X}  +", position, start - position);
+            }
+            let _ = writeln!(map, "0x{:08X}  0x{:08X}  -", start, length);
+            position = start + length;
+        }
+
+        let end = self.ended_at.unwrap_or(self.length);
+
+        if end > position {
+            let _ = writeln!(map, "0x{:08X}  0x{:08X}  +", position, end - position);
+        }
+
+        // Never tried, as far as ddrescue is concerned.
+        if self.length > end.max(position) {
+            let _ = writeln!(map, "0x{:08X}  0x{:08X}  ?", end.max(position), self.length - end.max(position));
+        }
+
+        map
+    }
+
+    pub fn write_mapfile(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.build_mapfile())
+    }
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+// Reads back a mapfile, ours or one ddrescue has been working on since. Anything that wasn't read, including what
+// was never tried after a disk ended early, counts as bad.
+pub fn read_mapfile(path: &str) -> io::Result<DamageMap> {
+    let text = fs::read_to_string(path)?;
+    let mut map = DamageMap::new(0);
+    let mut seen_status_line = false;
+
+    for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        // The first line is ddrescue's own progress, not a block.
+        if !seen_status_line {
+            seen_status_line = true;
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (start, length, status) = match fields.as_slice() {
+            [start, length, status, ..] => match (parse_number(start), parse_number(length)) {
+                (Some(start), Some(length)) => (start, length, *status),
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        map.length = map.length.max(start + length);
+
+        if status != "+" {
+            map.add_bad(start, length);
+        }
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapfiles_read_back() {
+        let mut map = DamageMap::new(100000);
+        map.add_bad(2048, 2048);
+        map.add_bad(4096, 2048); // Touches the last one, so it's merged.
+        map.add_bad(50000, 512);
+
+        let path = std::env::temp_dir().join(format!("auto_archive-mapfile-{}.map", std::process::id()));
+        let path = path.to_str().unwrap();
+        map.write_mapfile(path).unwrap();
+        let read = read_mapfile(path);
+        let _ = fs::remove_file(path);
+        let read = read.unwrap();
+
+        assert_eq!(read.length(), 100000);
+        assert_eq!(read.get_bad_regions(), &[(2048, 4096), (50000, 512)]);
+    }
+
+    #[test]
+    fn ending_early_leaves_the_rest_bad() {
+        let mut map = DamageMap::new(10000);
+        map.add_bad(1000, 1000);
+        map.end_early(6000);
+
+        let path = std::env::temp_dir().join(format!("auto_archive-mapfile-early-{}.map", std::process::id()));
+        let path = path.to_str().unwrap();
+        map.write_mapfile(path).unwrap();
+        let read = read_mapfile(path);
+        let _ = fs::remove_file(path);
+        let read = read.unwrap();
+
+        assert_eq!(read.length(), 10000);
+        assert_eq!(read.get_bad_regions(), &[(1000, 1000), (6000, 4000)]);
+    }
+}
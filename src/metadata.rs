@@ -0,0 +1,239 @@
+
+// Everywhere we can find out what a disk is. Each provider knows about some of it: the volume from the disk itself,
+// an audio CD's tracks from MusicBrainz, gnudb, or CD-TEXT, or a finished rip's title from the redump.org DATs.
+// They're asked in priority order, highest first. More can be registered before the drives start, for lookups in
+// catalogs of your own.
+
+use std::fs;
+use std::os::unix::fs::FileExt;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use crate::audio;
+use crate::audio::DiscMetadata;
+use crate::audio::Track;
+use crate::config::Config;
+use crate::gnudb;
+use crate::hash::Hashes;
+use crate::musicbrainz;
+use crate::redump::Dat;
+use crate::DiskInfoError;
+use crate::ISOInfo;
+use crate::VolumeDetails;
+
+pub struct AudioQuery<'a> {
+    pub device: &'a str,
+    pub tracks: &'a [Track],
+    pub lead_out: u32,
+    pub disc_id: &'a str, // MusicBrainz's.
+}
+
+// Anything a provider doesn't know about, it leaves as the default, which says so.
+pub trait MetadataProvider: Send + Sync {
+    // The volume on a data disk. None if this provider doesn't read volumes.
+    fn volume(&self, _device: &str) -> Option<Result<ISOInfo, DiskInfoError>> {
+        None
+    }
+
+    // What's on an audio CD. `found` is everything higher priority providers already found.
+    fn audio(&self, _query: &AudioQuery, _config: &Config, _found: &DiscMetadata) -> Option<DiscMetadata> {
+        None
+    }
+
+    // What a finished rip is, going by its hashes.
+    fn title(&self, _hashes: &Hashes) -> Option<String> {
+        None
+    }
+}
+
+static PROVIDERS: Mutex<Vec<(i32, Arc<dyn MetadataProvider>)>> = Mutex::new(Vec::new());
+
+pub fn register(priority: i32, provider: Arc<dyn MetadataProvider>) {
+    let mut providers = PROVIDERS.lock().unwrap();
+    providers.push((priority, provider));
+
+    // Stable, so providers with the same priority go in the order they were registered.
+    providers.sort_by_key(|(priority, _)| -priority);
+}
+
+// Copied out, so a slow lookup doesn't hold up every other drive.
+fn get_providers() -> Vec<Arc<dyn MetadataProvider>> {
+    PROVIDERS.lock().unwrap().iter().map(|(_, provider)| provider.clone()).collect()
+}
+
+// The first volume any provider finds. If none do, what went wrong with the first one that tried.
+pub fn identify_volume(device: &str) -> Result<ISOInfo, DiskInfoError> {
+    let mut first_error = None;
+
+    for provider in get_providers() {
+        match provider.volume(device) {
+            Some(Ok(info)) => return Ok(info),
+            Some(Err(error)) => {
+                first_error.get_or_insert(error);
+            },
+            None => {},
+        }
+    }
+
+    Err(first_error.unwrap_or(DiskInfoError::Parse))
+}
+
+// Everything every provider knows about an audio CD, with higher priority ones winning where they disagree.
+pub fn identify_audio(query: &AudioQuery, config: &Config) -> DiscMetadata {
+    let mut metadata = DiscMetadata::default();
+
+    for provider in get_providers() {
+        if let Some(found) = provider.audio(query, config, &metadata) {
+            metadata.merge(found);
+        }
+    }
+
+    metadata
+}
+
+pub fn identify_title(hashes: &Hashes) -> Option<String> {
+    get_providers().iter().find_map(|provider| provider.title(hashes))
+}
+
+// isoinfo, which knows every ISO 9660 extension there is.
+struct IsoinfoProvider;
+
+impl MetadataProvider for IsoinfoProvider {
+    fn volume(&self, device: &str) -> Option<Result<ISOInfo, DiskInfoError>> {
+        Some(crate::fetch_iso_info(device))
+    }
+}
+
+// Reads the primary volume descriptor ourselves, for when isoinfo isn't installed or can't make sense of the disk.
+// The layout is in ECMA-119.
+struct PvdProvider;
+
+const SECTOR_SIZE: u64 = 2048;
+
+fn read_u16_le(data: &[u8], offset: usize) -> usize {
+    data[offset] as usize | (data[offset + 1] as usize) << 8
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> usize {
+    data[offset..offset + 4].iter().rev().fold(0, |value, byte| (value << 8) | *byte as usize)
+}
+
+fn read_pvd(device: &str) -> Option<Vec<u8>> {
+    let mut pvd = vec![0u8; SECTOR_SIZE as usize];
+    let read = fs::File::open(device).and_then(|device| device.read_exact_at(&mut pvd, 16 * SECTOR_SIZE));
+
+    if read.is_err() || pvd[0] != 1 || &pvd[1..6] != b"CD001" {
+        return None;
+    }
+
+    Some(pvd)
+}
+
+// Text fields are padded out with spaces.
+fn read_text(pvd: &[u8], start: usize, end: usize) -> Option<String> {
+    Some(String::from_utf8_lossy(&pvd[start..end]).trim().to_string()).filter(|text| !text.is_empty())
+}
+
+// Digits for the date and time, and a time zone byte we don't bother with. All zeros when it was never set.
+fn read_date(pvd: &[u8], start: usize) -> Option<String> {
+    let digits = std::str::from_utf8(&pvd[start..start + 14]).ok()?;
+
+    if !digits.bytes().all(|digit| digit.is_ascii_digit()) || digits.bytes().all(|digit| digit == b'0') {
+        return None;
+    }
+
+    Some(format!("{}-{}-{} {}:{}:{}", &digits[0..4], &digits[4..6], &digits[6..8], &digits[8..10], &digits[10..12], &digits[12..14]))
+}
+
+fn read_volume_details(pvd: &[u8]) -> VolumeDetails {
+    VolumeDetails {
+        publisher: read_text(pvd, 318, 446),
+        preparer: read_text(pvd, 446, 574),
+        application: read_text(pvd, 574, 702),
+        created: read_date(pvd, 813),
+    }
+}
+
+// isoinfo doesn't say when the volume was made, so that's always read here.
+pub fn read_creation_date(device: &str) -> Option<String> {
+    read_date(&read_pvd(device)?, 813)
+}
+
+impl MetadataProvider for PvdProvider {
+    fn volume(&self, device: &str) -> Option<Result<ISOInfo, DiskInfoError>> {
+        let pvd = match read_pvd(device) {
+            Some(pvd) => pvd,
+            None => return Some(Err(DiskInfoError::Parse)),
+        };
+
+        let block_size = read_u16_le(&pvd, 128);
+        let blocks = read_u32_le(&pvd, 80);
+        if block_size == 0 || blocks == 0 {
+            return Some(Err(DiskInfoError::Parse));
+        }
+
+        Some(Ok(ISOInfo {
+            name: String::from_utf8_lossy(&pvd[40..72]).trim().to_string(),
+            block_size,
+            length: blocks as u64 * block_size as u64,
+            details: read_volume_details(&pvd),
+        }))
+    }
+}
+
+// MusicBrainz knows more than the disk does, when it knows anything at all. Being offline shouldn't stop the rip.
+struct MusicBrainzProvider;
+
+impl MetadataProvider for MusicBrainzProvider {
+    fn audio(&self, query: &AudioQuery, config: &Config, _found: &DiscMetadata) -> Option<DiscMetadata> {
+        if !config.musicbrainz {
+            return None;
+        }
+
+        musicbrainz::lookup(query.disc_id).ok().flatten()
+    }
+}
+
+// gnudb knows a lot of the disks MusicBrainz doesn't, but it's wrong more often, so it only gets asked when nothing
+// else knew the album.
+struct GnudbProvider;
+
+impl MetadataProvider for GnudbProvider {
+    fn audio(&self, query: &AudioQuery, config: &Config, found: &DiscMetadata) -> Option<DiscMetadata> {
+        if found.album.is_some() {
+            return None;
+        }
+
+        let email = config.gnudb_email.as_ref()?;
+        gnudb::lookup(email, query.tracks, query.lead_out).ok().flatten()
+    }
+}
+
+// Whatever the disk says about itself. Usually nothing.
+struct CdTextProvider;
+
+impl MetadataProvider for CdTextProvider {
+    fn audio(&self, query: &AudioQuery, _config: &Config, _found: &DiscMetadata) -> Option<DiscMetadata> {
+        Some(audio::read_cd_text(query.device))
+    }
+}
+
+struct RedumpProvider(Arc<Dat>);
+
+impl MetadataProvider for RedumpProvider {
+    fn title(&self, hashes: &Hashes) -> Option<String> {
+        self.0.find(hashes).map(|rom| rom.game.clone())
+    }
+}
+
+// The providers that come with auto_archive.
+pub fn register_defaults(dat: Arc<Dat>) {
+    register(100, Arc::new(IsoinfoProvider));
+    register(50, Arc::new(PvdProvider));
+
+    register(100, Arc::new(MusicBrainzProvider));
+    register(50, Arc::new(GnudbProvider));
+    register(10, Arc::new(CdTextProvider));
+
+    register(100, Arc::new(RedumpProvider(dat)));
+}
@@ -0,0 +1,63 @@
+
+// Makes sure a finished image has a file system something could actually mount, by finding its root directory and
+// reading it, the same way the kernel would. Better to find out a rip is useless while the disk is still sitting in
+// the drive than years later when it's gone.
+// The ISO 9660 layout is in ECMA-119.
+
+use std::fs;
+use std::os::unix::fs::FileExt;
+
+use crate::hfs;
+
+const SECTOR_SIZE: u64 = 2048;
+
+// Where the primary volume descriptor is.
+const PVD_OFFSET: u64 = 16 * SECTOR_SIZE;
+
+// Where the root directory's record is in the PVD.
+const ROOT_RECORD_OFFSET: usize = 156;
+
+fn read_u32_le(data: &[u8], offset: usize) -> u64 {
+    data[offset..offset + 4].iter().rev().fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+
+fn check_iso9660(image: &fs::File, length: u64) -> Result<(), String> {
+    let mut pvd = vec![0u8; SECTOR_SIZE as usize];
+    image.read_exact_at(&mut pvd, PVD_OFFSET).map_err(|_| { String::from("The image is too short to have a file system.") })?;
+
+    if pvd[0] != 1 || &pvd[1..6] != b"CD001" {
+        return Err(String::from("The image has no ISO 9660 volume descriptor."));
+    }
+
+    let root_sector = read_u32_le(&pvd, ROOT_RECORD_OFFSET + 2);
+    let root_length = read_u32_le(&pvd, ROOT_RECORD_OFFSET + 10);
+
+    if root_length == 0 || (root_sector * SECTOR_SIZE) + root_length > length {
+        return Err(format!("The root directory at sector {} is outside the image.", root_sector));
+    }
+
+    // No point reading all of a huge root directory, the first sector says whether it's there.
+    let mut root = vec![0u8; SECTOR_SIZE as usize];
+    image.read_exact_at(&mut root, root_sector * SECTOR_SIZE)
+        .map_err(|_| { format!("Couldn't read the root directory at sector {}.", root_sector) })?;
+
+    // Every directory starts with a record for itself, named with a single zero byte. Zero filled damage doesn't.
+    if root[0] < 34 || root[32] != 1 || root[33] != 0 || read_u32_le(&root, 2) != root_sector {
+        return Err(format!("The root directory at sector {} is unreadable.", root_sector));
+    }
+
+    Ok(())
+}
+
+// Ok if the image has an ISO 9660 or HFS file system with a readable root. Otherwise says what's wrong with it.
+pub fn check_image(image: &str) -> Result<(), String> {
+    let file = fs::File::open(image).map_err(|e| { format!("Couldn't open the image: {}", e) })?;
+    let length = file.metadata().map_err(|e| { format!("Couldn't open the image: {}", e) })?.len();
+
+    match check_iso9660(&file, length) {
+        Ok(()) => Ok(()),
+        // Mac only disks don't have an ISO 9660 side at all.
+        Err(_) if hfs::read_hfs_info(image).is_some() => Ok(()),
+        Err(message) => Err(message),
+    }
+}
@@ -0,0 +1,116 @@
+
+// Looks up audio CDs on MusicBrainz by their disc ID, which is worked out from the track layout in the TOC.
+// See https://musicbrainz.org/doc/Disc_ID_Calculation
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::process::Command;
+
+use crate::audio::DiscMetadata;
+use crate::audio::Track;
+
+const API_ENDPOINT: &str = "https://musicbrainz.org/ws/2/discid";
+
+// MusicBrainz blocks clients that don't say who they are.
+const USER_AGENT: &str = "auto_archive/0.1 ( https://github.com/IamTheCarl/DiskArchiver )";
+
+// Plain base64, except with characters that are safe in URLs.
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789._";
+
+    let mut output = String::new();
+
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let value = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | bytes[2] as u32;
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                output.push(ALPHABET[(value >> (18 - index * 6)) as usize & 0x3F] as char);
+            } else {
+                output.push('-');
+            }
+        }
+    }
+
+    output
+}
+
+// Offsets are counted from the start of the lead-in, 150 sectors before LBA 0.
+pub fn get_disc_id(tracks: &[Track], lead_out: u32) -> String {
+    let first = tracks.first().map(|track| track.number).unwrap_or(1);
+    let last = tracks.last().map(|track| track.number).unwrap_or(1);
+
+    let mut text = format!("{:02X}{:02X}{:08X}", first, last, lead_out + 150);
+
+    for number in 1..=99 {
+        let offset = tracks.iter().find(|track| track.number == number).map(|track| track.start + 150).unwrap_or(0);
+        text += &format!("{:08X}", offset);
+    }
+
+    encode_base64(&sha1_smol::Sha1::from(text).digest().bytes())
+}
+
+fn get_artist_credit(value: &Value) -> Option<String> {
+    let credits = value["artist-credit"].as_array()?;
+
+    Some(credits.iter()
+        .map(|credit| format!("{}{}", credit["name"].as_str().unwrap_or_default(), credit["joinphrase"].as_str().unwrap_or_default()))
+        .collect())
+}
+
+// Looks the disk up, and returns what's known about it. Disks MusicBrainz has never heard of are Ok(None).
+pub fn lookup(disc_id: &str) -> Result<Option<DiscMetadata>, String> {
+    let url = format!("{}/{}?inc=artist-credits+recordings&fmt=json", API_ENDPOINT, disc_id);
+
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--max-time", "30", "--user-agent", USER_AGENT])
+        .arg(&url)
+        .output()
+        .map_err(|_| { String::from("Failed to launch curl. Is it installed?") })?;
+
+    if !output.status.success() {
+        return Err(String::from(String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let value: Value = serde_json::from_slice(&output.stdout).map_err(|e| { format!("MusicBrainz sent back nonsense: {}", e) })?;
+
+    // The first release is as good a guess as any when the same disk was released more than once.
+    let release = match value["releases"].as_array().and_then(|releases| releases.first()) {
+        Some(release) => release,
+        None => return Ok(None),
+    };
+
+    // Multi-disk releases list every disk, so find the one that's ours.
+    let medium = release["media"].as_array().and_then(|media| media.iter().find(|medium| {
+        medium["discs"].as_array().map(|discs| discs.iter().any(|disc| disc["id"].as_str() == Some(disc_id))).unwrap_or(false)
+    }));
+
+    let artist = get_artist_credit(release);
+    let mut titles = HashMap::new();
+    let mut artists = HashMap::new();
+
+    for track in medium.and_then(|medium| medium["tracks"].as_array()).into_iter().flatten() {
+        let number = match track["position"].as_u64() {
+            Some(number) if number <= 99 => number as u8,
+            _ => continue,
+        };
+
+        if let Some(title) = track["title"].as_str() {
+            titles.insert(number, String::from(title));
+        }
+
+        if let Some(track_artist) = get_artist_credit(track).filter(|track_artist| Some(track_artist) != artist.as_ref()) {
+            artists.insert(number, track_artist);
+        }
+    }
+
+    Ok(Some(DiscMetadata {
+        album: release["title"].as_str().map(String::from),
+        artist,
+        titles,
+        artists,
+        sources: vec![String::from("MusicBrainz")],
+        ..DiscMetadata::default()
+    }))
+}
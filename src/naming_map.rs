@@ -0,0 +1,157 @@
+
+// Names for disks from an inventory list that already exists, for digitization projects that were catalogued before
+// anyone started ripping. The list is a CSV file with two columns: a barcode or a sequence number, and the title to
+// name the disk with. Disks with a scanned barcode get the title for that barcode. Disks without one get the next
+// title by sequence number, lowest first, so a stack of disks in the same order as the list names itself. A title
+// handed out is held for its disk until it's saved, and goes back to the next disk if it isn't.
+// Anything else in the file, like a header row, is never looked up, so it doesn't hurt.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+
+use crate::export;
+
+pub enum NamingMapError {
+    Read(String, String), // File, and why it couldn't be read.
+    Empty(String),        // File that didn't have any titles in it.
+}
+
+pub fn get_naming_map_error_message(error: &NamingMapError) -> String {
+    match error {
+        NamingMapError::Read(file, reason) => format!("Failed to read naming map {}: {}", file, reason),
+        NamingMapError::Empty(file) => format!("Naming map {} doesn't have any titles in it.", file),
+    }
+}
+
+pub struct NamingMap {
+    titles: HashMap<String, String>,    // By barcode, exactly as it's in the file.
+    sequence: BTreeMap<u64, String>,    // Every key that's a number, so 001 and 1 are the same disk.
+    used: BTreeSet<u64>,                // Sequence numbers saved under, or that already were before we started.
+    held: BTreeSet<u64>,                // Handed out to disks that haven't been saved yet.
+}
+
+impl NamingMap {
+    fn parse(input: &str) -> Option<NamingMap> {
+        let titles: HashMap<String, String> = export::parse_csv(input).into_iter()
+            .filter(|row| row.len() >= 2 && !row[0].trim().is_empty() && !row[1].trim().is_empty())
+            .map(|row| (String::from(row[0].trim()), String::from(row[1].trim())))
+            .collect();
+
+        if titles.is_empty() {
+            return None;
+        }
+
+        let sequence = titles.iter()
+            .filter_map(|(key, title)| key.parse::<u64>().ok().map(|number| (number, title.clone())))
+            .collect();
+
+        Some(NamingMap { titles, sequence, used: BTreeSet::new(), held: BTreeSet::new() })
+    }
+
+    fn next_title(&mut self, taken: impl Fn(&str) -> bool) -> Option<(u64, String)> {
+        for (number, title) in self.sequence.iter() {
+            if self.used.contains(number) || self.held.contains(number) {
+                continue;
+            }
+
+            // Done before, so it's never coming back.
+            if taken(title) {
+                self.used.insert(*number);
+                continue;
+            }
+
+            self.held.insert(*number);
+            return Some((*number, title.clone()));
+        }
+
+        None
+    }
+}
+
+// Loaded once at startup, if the config has one.
+static NAMING_MAP: Mutex<Option<NamingMap>> = Mutex::new(None);
+
+pub fn load_naming_map(file: &str) -> Result<(), NamingMapError> {
+    let input = fs::read_to_string(file).map_err(|e| NamingMapError::Read(String::from(file), format!("{}", e)))?;
+    let map = NamingMap::parse(&input).ok_or_else(|| NamingMapError::Empty(String::from(file)))?;
+
+    *NAMING_MAP.lock().unwrap() = Some(map);
+    Ok(())
+}
+
+// The title for a scanned barcode.
+pub fn find_title(barcode: &str) -> Option<String> {
+    NAMING_MAP.lock().unwrap().as_ref()?.titles.get(barcode.trim()).cloned()
+}
+
+// A title from the sequence, held for one disk. Saving the disk uses it up. Dropping it without saving lets the next
+// disk have it.
+pub struct HeldTitle {
+    number: u64,
+}
+
+impl HeldTitle {
+    pub fn saved(self) {
+        if let Some(map) = NAMING_MAP.lock().unwrap().as_mut() {
+            map.used.insert(self.number);
+        }
+    }
+}
+
+impl Drop for HeldTitle {
+    fn drop(&mut self) {
+        if let Some(map) = NAMING_MAP.lock().unwrap().as_mut() {
+            map.held.remove(&self.number);
+        }
+    }
+}
+
+// The title for the next disk in the list. `taken` says whether a title's already been used, so picking back up
+// after a restart skips the disks that were done before it.
+pub fn next_title(taken: impl Fn(&str) -> bool) -> Option<(String, HeldTitle)> {
+    let (number, title) = NAMING_MAP.lock().unwrap().as_mut()?.next_title(taken)?;
+    Some((title, HeldTitle { number }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_and_barcodes_are_both_keys() {
+        let map = NamingMap::parse("Key,Title\n001,First\nABC-7,Boxed\n2, Second \n3,\n").unwrap();
+
+        assert_eq!(map.titles.get("ABC-7").map(String::as_str), Some("Boxed"));
+        assert_eq!(map.titles.get("2").map(String::as_str), Some("Second"));
+        assert!(!map.titles.contains_key("3"));
+        assert_eq!(map.sequence.keys().copied().collect::<Vec<u64>>(), vec![1, 2]);
+
+        assert!(NamingMap::parse("Key,\n,Title\n").is_none());
+    }
+
+    #[test]
+    fn titles_are_held_until_saved() {
+        let mut map = NamingMap::parse("1,First\n2,Second\n3,Third\n").unwrap();
+
+        // First was done before a restart.
+        let (second, title) = map.next_title(|title| title == "First").unwrap();
+        assert_eq!((second, title.as_str()), (2, "Second"));
+        assert_eq!(map.next_title(|_| false).map(|(_, title)| title).as_deref(), Some("Third"));
+        assert!(map.next_title(|_| false).is_none());
+
+        // The second disk was never saved, so the next one gets its title.
+        map.held.remove(&second);
+        assert_eq!(map.next_title(|_| false).map(|(number, _)| number), Some(2));
+    }
+
+    #[test]
+    fn the_last_number_works() {
+        let mut map = NamingMap::parse(&format!("{},Last\n", u64::MAX)).unwrap();
+
+        assert_eq!(map.next_title(|_| false).map(|(_, title)| title).as_deref(), Some("Last"));
+        assert!(map.next_title(|_| false).is_none());
+    }
+}
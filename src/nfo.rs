@@ -0,0 +1,173 @@
+
+// .nfo files for Kodi and Jellyfin, so a folder of DVD and Blu-ray rips can be pointed at as a movie library as is.
+// Both want each movie in a folder of its own, named like Title (Year)/Title (Year).iso, with an .nfo of the same name
+// next to it, and play ISOs directly. What goes in it is only what the disk knows about itself, which is enough for
+// them to show something better than the volume name and go looking up the rest.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::dvd;
+use crate::udf::UdfImage;
+use crate::VolumeDetails;
+
+const DVD_INDEX: &str = "/VIDEO_TS/VIDEO_TS.IFO";
+const BLU_RAY_INDEX: &str = "/BDMV/INDEX.BDMV";
+const BLU_RAY_PLAYLISTS: &str = "/BDMV/PLAYLIST";
+
+// Blu-ray times are counted in these.
+const BLU_RAY_TICKS_PER_SECOND: f64 = 45000.0;
+
+// Is this a DVD or Blu-ray movie? Going by its files, as the file index has them, or its UDF side when they aren't
+// there, since Blu-rays don't have to have anything isoinfo can read. Works on a drive as well as an image.
+pub fn is_movie(image: &str, files: &[String]) -> bool {
+    if dvd::is_dvd_video(files) || files.iter().any(|file| file.eq_ignore_ascii_case(BLU_RAY_INDEX)) {
+        return true;
+    }
+
+    UdfImage::open(image).map(|udf| udf.exists(DVD_INDEX) || udf.exists(BLU_RAY_INDEX)).unwrap_or(false)
+}
+
+// How long a Blu-ray playlist plays for, in seconds. Playlists are big-endian, unlike UDF.
+fn get_playlist_length(playlist: &[u8]) -> Option<f64> {
+    let read_u16 = |offset: usize| playlist.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize);
+    let read_u32 = |offset: usize| playlist.get(offset..offset + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+
+    if playlist.get(0..4)? != b"MPLS" {
+        return None;
+    }
+
+    let start = read_u32(8)? as usize;
+    let mut item = start + 10;
+    let mut ticks = 0u64;
+
+    // Each item's a clip, played from its in time to its out time.
+    for _ in 0..read_u16(start + 6)? {
+        ticks += read_u32(item + 18)?.saturating_sub(read_u32(item + 14)?) as u64;
+        item += 2 + read_u16(item)?;
+    }
+
+    Some(ticks as f64 / BLU_RAY_TICKS_PER_SECOND)
+}
+
+// How long the main feature is, in seconds, going by it being the longest playlist on the disk.
+pub fn get_blu_ray_runtime(image: &str) -> Option<f64> {
+    let udf = UdfImage::open(image)?;
+
+    udf.list(BLU_RAY_PLAYLISTS)?.iter()
+        .filter(|name| name.to_lowercase().ends_with(".mpls"))
+        .filter_map(|name| udf.read(&format!("{}/{}", BLU_RAY_PLAYLISTS, name)))
+        .filter_map(|playlist| get_playlist_length(&playlist))
+        .reduce(f64::max)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Volume names are usually shouted with underscores for spaces, like THE_BIG_MOVIE.
+fn get_title(volume: &str) -> String {
+    volume.split(['_', ' '])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let word = word.to_lowercase();
+            let mut letters = word.chars();
+            match letters.next() {
+                Some(first) => first.to_uppercase().chain(letters).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn get_year(details: &VolumeDetails) -> Option<&str> {
+    details.created.as_deref().and_then(|created| created.get(0..4))
+}
+
+// What to call a movie that hasn't been named anything else, like The Big Movie (2004).
+pub fn get_movie_name(volume: &str, details: &VolumeDetails) -> String {
+    match get_year(details) {
+        Some(year) => format!("{} ({})", get_title(volume), year),
+        None => get_title(volume),
+    }
+}
+
+// Where a movie goes, in a folder named after it.
+pub fn get_movie_path(name: &str, extension: &str) -> String {
+    format!("{0}/{0}.{1}", name, extension)
+}
+
+// The title's whatever the image ended up being called, without the year the layout puts on the end.
+fn get_image_title(image: &str) -> String {
+    let name = Path::new(image).file_stem().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+    match name.rsplit_once(" (") {
+        Some((title, year)) if year.len() == 5 && year.ends_with(')') && year[..4].chars().all(|c| c.is_ascii_digit()) => String::from(title),
+        _ => name,
+    }
+}
+
+// `runtime` is the longest title's, in seconds, when it's known.
+pub fn write_nfo(image: &str, volume: &str, details: &VolumeDetails, runtime: Option<f64>) -> io::Result<()> {
+    let mut nfo = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<movie>\n");
+
+    nfo += &format!("    <title>{}</title>\n", escape_xml(&get_image_title(image)));
+    nfo += &format!("    <originaltitle>{}</originaltitle>\n", escape_xml(volume));
+
+    // When it was mastered, which is about when it came out.
+    if let (Some(year), Some(created)) = (get_year(details), details.created.as_deref().and_then(|created| created.get(0..10))) {
+        nfo += &format!("    <year>{}</year>\n", year);
+        nfo += &format!("    <premiered>{}</premiered>\n", created);
+    }
+
+    if let Some(publisher) = &details.publisher {
+        nfo += &format!("    <studio>{}</studio>\n", escape_xml(publisher));
+    }
+
+    if let Some(runtime) = runtime {
+        nfo += &format!("    <runtime>{}</runtime>\n", (runtime / 60.0).round() as u64);
+    }
+
+    nfo += "</movie>\n";
+
+    fs::write(Path::new(image).with_extension("nfo"), nfo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn movies_are_named_like_media_servers_want() {
+        let details = VolumeDetails { created: Some(String::from("2004-06-01 00:00:00")), ..VolumeDetails::default() };
+        let name = get_movie_name("THE_BIG_MOVIE", &details);
+
+        assert_eq!(name, "The Big Movie (2004)");
+        assert_eq!(get_movie_path(&name, "iso"), "The Big Movie (2004)/The Big Movie (2004).iso");
+        assert_eq!(get_image_title(&get_movie_path(&name, "iso")), "The Big Movie");
+        assert_eq!(get_image_title("Alien (Director's Cut).iso"), "Alien (Director's Cut)");
+    }
+
+    #[test]
+    fn playlists_add_up_their_clips() {
+        let mut playlist = b"MPLS0200".to_vec();
+        playlist.extend(16u32.to_be_bytes());
+        playlist.extend([0u8; 4]);
+
+        // The playlist, with two clips of a minute and a half each.
+        playlist.extend([0u8; 6]);
+        playlist.extend(2u16.to_be_bytes());
+        playlist.extend([0u8; 2]);
+        for start in [0u32, 90 * 45000] {
+            playlist.extend(20u16.to_be_bytes());
+            playlist.extend([0u8; 12]);
+            playlist.extend(start.to_be_bytes());
+            playlist.extend((start + 90 * 45000).to_be_bytes());
+        }
+
+        assert_eq!(get_playlist_length(&playlist), Some(180.0));
+        assert_eq!(get_playlist_length(b"junk"), None);
+    }
+}
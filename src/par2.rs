@@ -0,0 +1,58 @@
+
+// PAR2 parity for finished images, made with par2cmdline. Optical disks and hard drives rot,
+// and a few percent of recovery data is enough to repair an image years from now.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+pub enum Par2Error {
+    LaunchFail,     // Failed to launch par2.
+    Failed(String), // par2 ran but failed. Holds what it had to say about it.
+}
+
+// Makes recovery files covering `files`, named after the first of them. Returns the paths of the recovery files.
+pub fn create_parity(files: &[String], redundancy: u32) -> Result<Vec<String>, Par2Error> {
+    let base = format!("{}.par2", files[0]);
+
+    let mut command = Command::new("par2");
+    command.arg("create");
+    command.arg(format!("-r{}", redundancy));
+    command.arg("-q").arg("-q");
+    command.arg(&base);
+    command.args(files);
+
+    let output = command.output().map_err(|_| { Par2Error::LaunchFail })?;
+
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("par2 failed.");
+        return Err(Par2Error::Failed(String::from(reason.trim())));
+    }
+
+    // par2 makes an index file plus a handful of volume files, all starting with the base name.
+    let base_path = Path::new(&base);
+    let directory = base_path.parent().unwrap_or_else(|| Path::new(""));
+    let prefix = format!("{}.", base_path.file_stem().unwrap_or_default().to_string_lossy());
+
+    let listing = if directory.as_os_str().is_empty() { fs::read_dir(".") } else { fs::read_dir(directory) };
+
+    let mut parity: Vec<String> = listing.map(|entries| {
+        entries.filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(&prefix) && name.ends_with(".par2"))
+            .map(|name| directory.join(name).to_string_lossy().into_owned())
+            .collect()
+    }).unwrap_or_default();
+
+    parity.sort();
+
+    Ok(parity)
+}
+
+pub fn get_par2_error_message(error: &Par2Error) -> String {
+    match error {
+        Par2Error::LaunchFail => String::from("Failed to launch par2. Is it installed?"),
+        Par2Error::Failed(message) => format!("par2 failed: {}", message),
+    }
+}
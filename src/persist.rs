@@ -0,0 +1,152 @@
+
+// Moves a finished image from where it was ripped to where it was asked to go. On the same file system that's just a
+// link. Anywhere else the kernel copies it with copy_file_range, or sendfile on kernels that don't have that, so a
+// multi-gigabyte image doesn't go through our memory a second time. Hashes were already made while it was written.
+
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use tempfile_fast::PersistableTempFile;
+
+// Most the kernel is asked to copy in one go, so a cancel or an error doesn't wait on a whole image.
+const CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+// Errors that mean the fast way isn't there on this kernel or file system, not that anything went wrong.
+fn is_unsupported(error: &io::Error) -> bool {
+    match error.raw_os_error() {
+        Some(code) => code == libc::ENOSYS || code == libc::EXDEV || code == libc::EINVAL || code == libc::EOPNOTSUPP,
+        None => false,
+    }
+}
+
+fn copy_file_range(source: &fs::File, target: &fs::File, offset: u64, length: usize) -> io::Result<usize> {
+    let mut source_offset = offset as libc::loff_t;
+    let mut target_offset = offset as libc::loff_t;
+
+    let result = unsafe {
+        libc::syscall(libc::SYS_copy_file_range, source.as_raw_fd(), &mut source_offset, target.as_raw_fd(), &mut target_offset, length, 0)
+    };
+
+    if result < 0 { Err(io::Error::last_os_error()) } else { Ok(result as usize) }
+}
+
+fn sendfile(source: &fs::File, target: &mut fs::File, offset: u64, length: usize) -> io::Result<usize> {
+    // sendfile writes wherever the target's position is.
+    target.seek(SeekFrom::Start(offset))?;
+
+    let mut source_offset = offset as libc::off_t;
+    let result = unsafe { libc::sendfile(target.as_raw_fd(), source.as_raw_fd(), &mut source_offset, length) };
+
+    if result < 0 { Err(io::Error::last_os_error()) } else { Ok(result as usize) }
+}
+
+// The slow way, for when neither of the others work.
+fn read_write(source: &mut fs::File, target: &mut fs::File, offset: u64, length: usize) -> io::Result<usize> {
+    source.seek(SeekFrom::Start(offset))?;
+    target.seek(SeekFrom::Start(offset))?;
+
+    io::copy(&mut source.take(length as u64), target).map(|copied| copied as usize)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Method {
+    CopyFileRange,
+    Sendfile,
+    ReadWrite,
+}
+
+// Copies one range, dropping down to a slower method whenever a faster one turns out not to work here.
+fn copy_range(source: &mut fs::File, target: &mut fs::File, method: &mut Method, mut offset: u64, end: u64) -> io::Result<()> {
+    while offset < end {
+        let length = (end - offset).min(CHUNK_SIZE as u64) as usize;
+
+        let result = match *method {
+            Method::CopyFileRange => copy_file_range(source, target, offset, length),
+            Method::Sendfile => sendfile(source, target, offset, length),
+            Method::ReadWrite => read_write(source, target, offset, length),
+        };
+
+        match result {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The image got shorter while it was being moved.")),
+            Ok(copied) => offset += copied as u64,
+            Err(ref error) if *method == Method::CopyFileRange && is_unsupported(error) => *method = Method::Sendfile,
+            Err(ref error) if *method == Method::Sendfile && is_unsupported(error) => *method = Method::ReadWrite,
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {},
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(())
+}
+
+// Where the next data starts at or after the offset, and where it ends. None once there's nothing but holes left.
+// Sparse images stay sparse, and their holes don't get copied as zeros.
+fn next_data(source: &fs::File, offset: u64, length: u64) -> Option<(u64, u64)> {
+    let fd = source.as_raw_fd();
+
+    let start = unsafe { libc::lseek(fd, offset as libc::off_t, libc::SEEK_DATA) };
+    if start < 0 {
+        // ENXIO means only holes are left. Anything else means the file system doesn't know, so it's all data.
+        return match io::Error::last_os_error().raw_os_error() {
+            Some(libc::ENXIO) => None,
+            _ => Some((offset, length)),
+        };
+    }
+
+    let end = unsafe { libc::lseek(fd, start, libc::SEEK_HOLE) };
+    let end = if end < 0 { length } else { (end as u64).min(length) };
+
+    Some((start as u64, end))
+}
+
+fn copy_image(source: &mut fs::File, target: &mut fs::File) -> io::Result<()> {
+    let length = source.metadata()?.len();
+    let mut method = Method::CopyFileRange;
+    let mut offset = 0;
+
+    while offset < length {
+        let (start, end) = match next_data(source, offset, length) {
+            Some(range) => range,
+            None => break,
+        };
+
+        copy_range(source, target, &mut method, start, end)?;
+        offset = end;
+    }
+
+    // Any hole at the end only exists if the length says so.
+    target.set_len(length)?;
+    target.flush()
+}
+
+// Saves the file under its new name, wherever that is.
+pub fn persist<P: AsRef<Path>>(file: PersistableTempFile, destination: P) -> io::Result<()> {
+    let destination = destination.as_ref();
+
+    let error = match file.persist_by_rename(destination) {
+        Ok(()) => return Ok(()),
+        Err(error) => error,
+    };
+
+    if error.error.raw_os_error() != Some(libc::EXDEV) {
+        return Err(error.error);
+    }
+
+    // A different file system. Copy into a temp file over there first, so a half copied image never has the name.
+    let directory = match destination.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("./"),
+    };
+
+    let mut source = error.file;
+    let mut target = PersistableTempFile::new_in(directory)?;
+
+    copy_image(&mut source, &mut target)?;
+    target.persist_by_rename(destination).map_err(|error| { error.error })
+}
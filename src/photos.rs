@@ -0,0 +1,201 @@
+
+// Pulls the photos off of photo CDs and files them into a library, organized by the date they were taken. Photos already
+// in the library, going by their hash, are skipped, so the same pictures burned to a dozen disks only show up once.
+// Photos without an EXIF date taken get one filled in from whatever date they do have, so photo managers sort them right.
+
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::hash;
+
+// Hashes of every photo already in the library, one per line.
+const INDEX_FILE: &str = ".auto_archive_photos";
+
+const PHOTO_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "heic",
+    "pcd", // Kodak Photo CD.
+    "crw", "cr2", "nef", "orf", "raf", "dng",
+];
+
+pub enum PhotoError {
+    LaunchFail(&'static str), // Failed to launch bsdtar or exiftool.
+    Extract(String),          // bsdtar couldn't read the disk. Holds what it had to say about it.
+    Library(String),          // Couldn't write to the library.
+}
+
+pub struct PhotoResult {
+    pub added: usize,
+    pub duplicates: usize,
+}
+
+fn get_extension(path: &Path) -> String {
+    path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+fn find_photos(directory: &Path, photos: &mut Vec<PathBuf>) {
+    // Whatever we can't read, we can't import either.
+    if let Ok(entries) = fs::read_dir(directory) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+
+            if path.is_dir() {
+                find_photos(&path, photos);
+            } else if PHOTO_EXTENSIONS.contains(&get_extension(&path).as_str()) {
+                photos.push(path);
+            }
+        }
+    }
+}
+
+// Gets the best date exiftool can find for each photo, as "YYYY:MM:DD HH:MM:SS", and whether it came from the EXIF date taken.
+fn read_dates(directory: &Path) -> Result<Vec<(PathBuf, String, bool)>, PhotoError> {
+    let output = Command::new("exiftool")
+        .args(["-json", "-quiet", "-recurse", "-DateTimeOriginal", "-CreateDate", "-FileModifyDate"])
+        .arg(directory)
+        .output()
+        .map_err(|_| { PhotoError::LaunchFail("exiftool") })?;
+
+    // exiftool fails if even one file is unreadable, but still tells us about the rest.
+    let list: Value = serde_json::from_slice(&output.stdout).unwrap_or(Value::Null);
+
+    Ok(list.as_array().map(|list| list.iter().filter_map(|item| {
+        let file = PathBuf::from(item["SourceFile"].as_str()?);
+        let taken = item["DateTimeOriginal"].as_str();
+        let date = taken.or_else(|| item["CreateDate"].as_str()).or_else(|| item["FileModifyDate"].as_str())?;
+
+        // Cameras with no clock set write all zeros, which is worse than nothing.
+        if !is_valid_date(date) || date.starts_with("0000") {
+            return None;
+        }
+
+        Some((file, String::from(&date[..19]), taken.is_some()))
+    }).collect()).unwrap_or_default())
+}
+
+fn is_valid_date(date: &str) -> bool {
+    let bytes = date.as_bytes();
+
+    bytes.len() >= 19 && bytes[..19].iter().enumerate().all(|(index, byte)| match index {
+        4 | 7 | 13 | 16 => *byte == b':',
+        10 => *byte == b' ',
+        _ => byte.is_ascii_digit(),
+    })
+}
+
+fn load_index(library: &Path) -> HashSet<String> {
+    fs::read_to_string(library.join(INDEX_FILE))
+        .map(|text| text.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+// Picks where in the library a photo goes: YYYY/MM/YYYY-MM-DD_HHMMSS_name, or undated/name if there's no date at all.
+fn get_library_path(library: &Path, photo: &Path, date: Option<&str>, sha1: &str) -> PathBuf {
+    let name = photo.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+    let path = match date {
+        Some(date) => library.join(&date[0..4]).join(&date[5..7])
+            .join(format!("{}-{}-{}_{}{}{}_{}", &date[0..4], &date[5..7], &date[8..10], &date[11..13], &date[14..16], &date[17..19], name)),
+        None => library.join("undated").join(&name),
+    };
+
+    // Different photo, same name and time. Happens with burst shots and cameras that restart their numbering.
+    if path.exists() {
+        path.with_file_name(format!("{}_{}", &sha1[..8], path.file_name().unwrap_or_default().to_string_lossy()))
+    } else {
+        path
+    }
+}
+
+// Extracts the photos from a disk, or an image of one, and files the new ones into the library.
+pub fn import_photos(source: &str, library: &str, mut progress: impl FnMut(usize, usize)) -> Result<PhotoResult, PhotoError> {
+    let library = Path::new(library);
+
+    // Staging in the library keeps it on the same file system, so photos can be moved in without copying them again.
+    let name = Path::new(source).file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let staging = library.join(format!(".staging-{}", name));
+    let _ = fs::remove_dir_all(&staging); // Left over from a crash.
+    fs::create_dir_all(&staging).map_err(|e| { PhotoError::Library(format!("{}", e)) })?;
+
+    let result = import_from_staging(source, library, &staging, &mut progress);
+    let _ = fs::remove_dir_all(&staging);
+
+    result
+}
+
+fn import_from_staging(source: &str, library: &Path, staging: &Path, progress: &mut impl FnMut(usize, usize)) -> Result<PhotoResult, PhotoError> {
+    // bsdtar reads ISO 9660, Joliet, and Rock Ridge straight off the disk, so nothing has to be mounted.
+    let output = Command::new("bsdtar")
+        .args(["-x", "-f", source, "-C"])
+        .arg(staging)
+        .output()
+        .map_err(|_| { PhotoError::LaunchFail("bsdtar") })?;
+
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no reason given.");
+        return Err(PhotoError::Extract(String::from(reason.trim())));
+    }
+
+    let mut photos = Vec::new();
+    find_photos(staging, &mut photos);
+
+    let dates = read_dates(staging)?;
+    let mut index = load_index(library);
+    let mut index_file = fs::OpenOptions::new().create(true).append(true).open(library.join(INDEX_FILE))
+        .map_err(|e| { PhotoError::Library(format!("{}", e)) })?;
+
+    let mut result = PhotoResult { added: 0, duplicates: 0 };
+    let mut undated = Vec::new();
+
+    for (done, photo) in photos.iter().enumerate() {
+        progress(done, photos.len());
+
+        // Hashed as it came off the disk, before we touch its dates, so copies on other disks still match.
+        let sha1 = hash::hash_file(&photo.to_string_lossy()).map_err(|e| { PhotoError::Extract(format!("{}", e)) })?.sha1;
+        if !index.insert(sha1.clone()) {
+            result.duplicates += 1;
+            continue;
+        }
+
+        let date = dates.iter().find(|(file, _, _)| file == photo);
+        let target = get_library_path(library, photo, date.map(|(_, date, _)| date.as_str()), &sha1);
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| { PhotoError::Library(format!("{}", e)) })?;
+        }
+        fs::rename(photo, &target).map_err(|e| { PhotoError::Library(format!("{}", e)) })?;
+        writeln!(index_file, "{}", sha1).map_err(|e| { PhotoError::Library(format!("{}", e)) })?;
+
+        if let Some((_, date, false)) = date {
+            undated.push((target, date.clone()));
+        }
+
+        result.added += 1;
+    }
+
+    // Fill in the date taken where it's missing. Some formats can't hold one, and that's fine, the file name still has it.
+    for (photo, date) in undated.iter() {
+        let _ = Command::new("exiftool")
+            .args(["-quiet", "-overwrite_original", "-preserve"])
+            .arg(format!("-DateTimeOriginal={}", date))
+            .arg(photo)
+            .output();
+    }
+
+    progress(photos.len(), photos.len());
+
+    Ok(result)
+}
+
+pub fn get_photo_error_message(error: &PhotoError) -> String {
+    match error {
+        PhotoError::LaunchFail(program) => format!("Failed to launch {}. Is it installed?", program),
+        PhotoError::Extract(message) => format!("Failed to read the photos off the disk: {}", message),
+        PhotoError::Library(message) => format!("Failed to add photos to the library: {}", message),
+    }
+}
@@ -0,0 +1,70 @@
+
+// Sets how much of the machine the copy threads get. Polite on a shared workstation, greedy on a dedicated rip box.
+// Both the CPU and I/O priority are per thread on Linux, and threads started afterwards inherit them, so setting them
+// at the top of a drive's thread covers its hash threads too.
+
+use std::io;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum IoPriority {
+    Idle,           // Only gets the disk when nothing else wants it.
+    BestEffort(u8), // The normal class. 0 is the highest of its levels, 7 the lowest.
+    Realtime(u8),   // Goes ahead of everything else. Needs root.
+}
+
+impl IoPriority {
+    pub fn parse(value: &str) -> Option<IoPriority> {
+        let (class, level) = match value.split_once(':') {
+            Some((class, level)) => (class, Some(level.trim().parse::<u8>().ok().filter(|level| *level <= 7)?)),
+            None => (value, None),
+        };
+
+        match class.trim() {
+            "idle" if level.is_none() => Some(IoPriority::Idle),
+            "best_effort" => Some(IoPriority::BestEffort(level.unwrap_or(4))),
+            "realtime" => Some(IoPriority::Realtime(level.unwrap_or(4))),
+            _ => None,
+        }
+    }
+}
+
+// From linux/ioprio.h.
+const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+
+fn get_thread_id() -> libc::c_long {
+    unsafe { libc::syscall(libc::SYS_gettid) }
+}
+
+fn set_nice(nice: i32) -> io::Result<()> {
+    // Going by thread ID only changes this thread, not the whole process.
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, get_thread_id() as libc::id_t, nice) };
+
+    if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+fn set_io_priority(priority: IoPriority) -> io::Result<()> {
+    let (class, level) = match priority {
+        IoPriority::Realtime(level) => (1, level),
+        IoPriority::BestEffort(level) => (2, level),
+        IoPriority::Idle => (3, 0),
+    };
+
+    let value = (class << IOPRIO_CLASS_SHIFT) | level as libc::c_long;
+    let result = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, get_thread_id(), value) };
+
+    if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+// Sets the priority of the calling thread. Raising either one usually needs root.
+pub fn set_thread_priority(nice: Option<i32>, io_priority: Option<IoPriority>) -> Result<(), String> {
+    if let Some(nice) = nice {
+        set_nice(nice).map_err(|e| { format!("Couldn't set copy_nice to {}: {}", nice, e) })?;
+    }
+
+    if let Some(io_priority) = io_priority {
+        set_io_priority(io_priority).map_err(|e| { format!("Couldn't set copy_io_priority: {}", e) })?;
+    }
+
+    Ok(())
+}
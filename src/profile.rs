@@ -0,0 +1,67 @@
+
+// Rip profiles. Each one is a handful of settings that go together for a kind of disk, so switching from a pile of
+// software CDs to a pile of music CDs is one setting instead of a dozen. Whatever a profile doesn't mention comes from
+// the config file as usual.
+
+use crate::config::Config;
+use crate::config::OutputFormat;
+use crate::hash::HashKind;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RipProfile {
+    Data,     // Plain ISOs, read once.
+    Audio,    // Audio CDs ripped to FLAC, looked up online.
+    Video,    // DVDs and BDs, where a few zero filled sectors beat never finishing.
+    Paranoid, // Everything read to the end, read twice, and hashed every way.
+}
+
+pub const PROFILES: [RipProfile; 4] = [RipProfile::Data, RipProfile::Audio, RipProfile::Video, RipProfile::Paranoid];
+
+impl RipProfile {
+    pub fn parse(name: &str) -> Option<RipProfile> {
+        PROFILES.iter().find(|profile| profile.name() == name).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RipProfile::Data => "data",
+            RipProfile::Audio => "audio",
+            RipProfile::Video => "video",
+            RipProfile::Paranoid => "paranoid",
+        }
+    }
+
+    // The config with this profile's settings on top.
+    pub fn apply(&self, config: &Config) -> Config {
+        let mut config = config.clone();
+
+        match self {
+            RipProfile::Data => {
+                config.output_format = OutputFormat::Iso;
+                config.recovery_mode = false;
+                config.read_to_lead_out = false;
+                config.verify_rips = false;
+            },
+            RipProfile::Audio => {
+                // CloneCD images would swallow audio CDs whole instead of ripping their tracks.
+                config.output_format = OutputFormat::Iso;
+                config.musicbrainz = true;
+                config.verify_rips = false;
+            },
+            RipProfile::Video => {
+                config.output_format = OutputFormat::Iso;
+                config.recovery_mode = true;
+                config.read_to_lead_out = false;
+                config.verify_rips = false;
+            },
+            RipProfile::Paranoid => {
+                config.recovery_mode = false;
+                config.read_to_lead_out = true;
+                config.verify_rips = true;
+                config.extra_hashes = vec![HashKind::Md5, HashKind::Sha256];
+            },
+        }
+
+        config
+    }
+}
@@ -0,0 +1,118 @@
+
+// How far along each drive is with whatever it's doing. Counted in real bytes wherever there are bytes to count, in
+// 64 bits so a BD-XL doesn't wrap around on a 32 bit rip box, and only scaled down for the progress bar at the end.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::format_size;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ProgressUnit {
+    #[default]
+    Bytes,
+    Items, // Counting things, like photos.
+    Steps, // Out of some made up total, like how long erasing usually takes.
+}
+
+impl ProgressUnit {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProgressUnit::Bytes => "bytes",
+            ProgressUnit::Items => "items",
+            ProgressUnit::Steps => "steps",
+        }
+    }
+}
+
+#[derive(Default)]
+struct ProgressState {
+    done: AtomicU64,
+    total: AtomicU64,
+    unit: Mutex<ProgressUnit>,
+    started: Mutex<Option<Instant>>,
+}
+
+// Cheap to clone. Every clone is the same progress.
+#[derive(Clone, Default)]
+pub struct Progress(Arc<ProgressState>);
+
+impl Progress {
+    // Starts over, counting up to `total`.
+    pub fn start(&self, total: u64, unit: ProgressUnit) {
+        *self.0.unit.lock().unwrap() = unit;
+        *self.0.started.lock().unwrap() = Some(Instant::now());
+        self.0.done.store(0, Relaxed);
+        self.0.total.store(total, Relaxed);
+    }
+
+    // For when the total isn't known until part way through.
+    pub fn set_total(&self, total: u64) {
+        self.0.total.store(total, Relaxed);
+    }
+
+    pub fn set(&self, done: u64) {
+        self.0.done.store(done, Relaxed);
+    }
+
+    pub fn add(&self, amount: u64) {
+        self.0.done.fetch_add(amount, Relaxed);
+    }
+
+    pub fn finish(&self) {
+        self.0.done.store(self.0.total.load(Relaxed), Relaxed);
+    }
+
+    // How much is done, out of how much.
+    pub fn get(&self) -> (u64, u64) {
+        (self.0.done.load(Relaxed), self.0.total.load(Relaxed))
+    }
+
+    pub fn unit(&self) -> ProgressUnit {
+        *self.0.unit.lock().unwrap()
+    }
+
+    // Out of 1000, for the progress bar.
+    pub fn permille(&self) -> usize {
+        let (done, total) = self.get();
+        (done.min(total) as f64 / total.max(1) as f64 * 1000.0) as usize
+    }
+
+    // Bytes left to go. None if it isn't counting bytes.
+    pub fn bytes_remaining(&self) -> Option<u64> {
+        let (done, total) = self.get();
+
+        match *self.0.unit.lock().unwrap() {
+            ProgressUnit::Bytes => Some(total.saturating_sub(done)),
+            _ => None,
+        }
+    }
+
+    // A guess at how long until it's done, going by how fast it's gone so far. None until it's gotten going.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        let (done, total) = self.get();
+        let elapsed = (*self.0.started.lock().unwrap())?.elapsed().as_secs_f64();
+
+        if done == 0 || elapsed < 1.0 {
+            return None;
+        }
+
+        let rate = done as f64 / elapsed;
+        Some(Duration::from_secs_f64(total.saturating_sub(done) as f64 / rate))
+    }
+
+    pub fn describe(&self) -> String {
+        let (done, total) = self.get();
+
+        match *self.0.unit.lock().unwrap() {
+            _ if total == 0 => String::new(),
+            ProgressUnit::Bytes => format!("{} of {}", format_size(done), format_size(total)),
+            ProgressUnit::Items => format!("{} of {}", done, total),
+            ProgressUnit::Steps => format!("{}%", self.permille() / 10),
+        }
+    }
+}
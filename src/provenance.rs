@@ -0,0 +1,55 @@
+
+// Where an image came from, written into its extended attributes, so it travels with the file even after the .json
+// next to it gets lost. Only file systems with user xattrs keep them, and only copies made with something that copies
+// xattrs too, like `cp --preserve=xattr` or `rsync -X`. Read them back with `getfattr -d <image>`.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::catalog;
+use crate::catalog::CatalogEntry;
+
+const PREFIX: &str = "user.auto_archive.";
+
+fn set_xattr(path: &CString, name: &str, value: &str) -> io::Result<()> {
+    let name = CString::new(format!("{}{}", PREFIX, name)).map_err(|e| { io::Error::new(io::ErrorKind::InvalidInput, e) })?;
+
+    let result = unsafe {
+        libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr() as *const libc::c_void, value.len(), 0)
+    };
+
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// `drive` is whatever the source drive goes by, its serial if it has one.
+pub fn write_provenance(image: &str, drive: &str, entry: &CatalogEntry) -> io::Result<()> {
+    let path = CString::new(Path::new(image).as_os_str().as_bytes()).map_err(|e| { io::Error::new(io::ErrorKind::InvalidInput, e) })?;
+
+    let mut values = vec![
+        ("drive", String::from(drive)),
+        ("date", catalog::format_date(entry.date)),
+        ("crc32", entry.crc32.clone()),
+        ("sha1", entry.sha1.clone()),
+        ("version", format!("auto_archive {}", env!("CARGO_PKG_VERSION"))),
+    ];
+
+    if let Some(sha256) = &entry.sha256 {
+        values.push(("sha256", sha256.clone()));
+    }
+
+    if !entry.fingerprint.is_empty() {
+        values.push(("fingerprint", entry.fingerprint.clone()));
+    }
+
+    for (name, value) in values.iter() {
+        set_xattr(&path, name, value)?;
+    }
+
+    Ok(())
+}
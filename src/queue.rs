@@ -0,0 +1,139 @@
+
+// Every disk this session, whether it's done, still going, or waiting for its turn, for the queue screen. The drive
+// panels only show what's in each drive right now, and the activity logs are by drive, so this is the one place to
+// see the whole session's work as a list of disks. Worked out from the drives' events as they happen, and only kept
+// while auto_archive is running. Nothing's ever dropped from it, so it's also the session's record for exporting.
+
+use std::sync::Mutex;
+
+use crate::catalog;
+use crate::catalog::CatalogEntry;
+use crate::drive_state::is_drive_finished;
+use crate::drive_state::is_waiting_for_disk;
+use crate::drive_state::DriveStatus;
+use crate::events::DriveEvent;
+use crate::get_drive_status_message_string;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    Pending,  // The disk's in, but waiting for its turn.
+    Active,   // Being read, named, or saved.
+    Finished, // Done with, one way or another.
+}
+
+#[derive(Clone)]
+pub struct QueueEntry {
+    pub drive: String,
+    pub started: u64,        // Seconds since the Unix epoch.
+    pub status: String,
+    pub name: String,        // What it was saved as. Blank until it's saved.
+    pub destination: String, // Everywhere the image went. Blank until it's saved.
+    pub archived: Option<Box<CatalogEntry>>, // What went in the catalog for it, once it's saved.
+    pub stage: Stage,
+    pub finished: bool,      // The disk's come out, or another went in after it.
+}
+
+static QUEUE: Mutex<Vec<QueueEntry>> = Mutex::new(Vec::new());
+
+fn get_stage(status: &DriveStatus) -> Stage {
+    match status {
+        DriveStatus::WaitingForTurn(_) => Stage::Pending,
+        status if is_drive_finished(status) => Stage::Finished,
+        _ => Stage::Active,
+    }
+}
+
+fn get_destination(entry: &CatalogEntry) -> String {
+    let mut destination = vec![entry.image.clone()];
+
+    if let Some(mirror) = &entry.mirror {
+        destination.push(mirror.clone());
+    }
+
+    if let Some(tape) = &entry.tape {
+        destination.push(format!("tape {}", tape));
+    }
+    if let Some(backup) = &entry.backup {
+        destination.push(format!("backup {}", backup));
+    }
+    if let Some(item) = &entry.ia_item {
+        destination.push(format!("archive.org {}", item));
+    }
+
+    destination.join(", ")
+}
+
+fn start(queue: &mut Vec<QueueEntry>, drive: &str) {
+    finish(queue, drive);
+
+    queue.push(QueueEntry {
+        drive: String::from(drive),
+        started: catalog::now(),
+        status: String::from("Disk inserted."),
+        name: String::new(),
+        destination: String::new(),
+        archived: None,
+        stage: Stage::Active,
+        finished: false,
+    });
+}
+
+fn finish(queue: &mut [QueueEntry], drive: &str) {
+    if let Some(entry) = find_open(queue, drive) {
+        entry.stage = Stage::Finished;
+        entry.finished = true;
+    }
+}
+
+fn find_open<'a>(queue: &'a mut [QueueEntry], drive: &str) -> Option<&'a mut QueueEntry> {
+    queue.iter_mut().rev().find(|entry| entry.drive == drive && !entry.finished)
+}
+
+pub fn record(drive: &str, event: &DriveEvent) {
+    let mut queue = QUEUE.lock().unwrap();
+
+    match event {
+        DriveEvent::DiskInserted => start(&mut queue, drive),
+        DriveEvent::DiskRemoved | DriveEvent::Unplugged => finish(&mut queue, drive),
+        DriveEvent::Status(status) if is_waiting_for_disk(status) => finish(&mut queue, drive),
+        DriveEvent::Status(status) => {
+            // Floppies don't say when they go in, so the first thing done with one has to stand in for it.
+            if find_open(&mut queue, drive).is_none() {
+                start(&mut queue, drive);
+            }
+
+            if let Some(entry) = find_open(&mut queue, drive) {
+                entry.status = get_drive_status_message_string(status);
+                entry.stage = get_stage(status);
+            }
+        },
+        DriveEvent::Archived(archived) => {
+            if let Some(entry) = find_open(&mut queue, drive) {
+                entry.name = archived.image.clone();
+                entry.destination = get_destination(archived);
+                entry.archived = Some(archived.clone());
+            }
+        },
+        DriveEvent::Plugged => {},
+    }
+}
+
+// Copied out, oldest first.
+pub fn entries() -> Vec<QueueEntry> {
+    QUEUE.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disks_waiting_their_turn_are_pending() {
+        assert_eq!(get_stage(&DriveStatus::WaitingForTurn(2)), Stage::Pending);
+        assert_eq!(get_stage(&DriveStatus::WaitingForName), Stage::Active);
+        assert_eq!(get_stage(&DriveStatus::Done), Stage::Finished);
+
+        // There's a disk in for its turn, so it doesn't end it.
+        assert!(!is_waiting_for_disk(&DriveStatus::WaitingForTurn(2)));
+    }
+}
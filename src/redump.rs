@@ -0,0 +1,113 @@
+
+// Checks rips against redump.org DAT files. A rip whose size, CRC32, and SHA-1 all match an entry is a known-good
+// dump of that disk, which is the best confirmation a preservationist can get.
+
+use nom::bytes::complete::tag;
+use nom::bytes::complete::take_until;
+use nom::bytes::complete::take_while1;
+use nom::character::complete::char as char_tag;
+use nom::character::complete::multispace1;
+use nom::multi::many0;
+use nom::sequence::delimited;
+use nom::sequence::preceded;
+use nom::sequence::separated_pair;
+use std::fs;
+
+use crate::hash::Hashes;
+use crate::ParserResult;
+
+pub enum DatError {
+    Read(String, String), // File, and why it couldn't be read.
+    Empty(String),        // File that had no ROM entries in it. Probably not a DAT.
+}
+
+pub struct DatRom {
+    pub game: String, // Name of the disk this belongs to.
+    pub size: u64,
+    pub crc32: String,
+    pub sha1: String,
+}
+
+#[derive(Default)]
+pub struct Dat {
+    roms: Vec<DatRom>,
+}
+
+// One `key="value"` attribute of an XML tag.
+fn parse_attribute(input: &str) -> ParserResult<'_, (&str, &str)> {
+    preceded(multispace1, separated_pair(
+        take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+        char_tag('='),
+        delimited(char_tag('"'), take_until("\""), char_tag('"'))
+    ))(input)
+}
+
+fn parse_tag<'a>(name: &'static str) -> impl Fn(&'a str) -> ParserResult<'a, Vec<(&'a str, &'a str)>> {
+    preceded(tag(name), many0(parse_attribute))
+}
+
+fn unescape(value: &str) -> String {
+    value.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn get_attribute(attributes: &[(&str, &str)], key: &str) -> String {
+    attributes.iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| unescape(value))
+        .unwrap_or_default()
+}
+
+// We only care about `game` and `rom` tags, so skip everything else rather than parsing the XML properly.
+fn parse_dat(input: &str, dat: &mut Dat) {
+    let mut game = String::new();
+
+    for element in input.split('<') {
+        if let Ok((_, attributes)) = parse_tag("game")(element) {
+            game = get_attribute(&attributes, "name");
+        } else if let Ok((_, attributes)) = parse_tag("rom")(element) {
+            dat.roms.push(DatRom {
+                game: game.clone(),
+                size: get_attribute(&attributes, "size").parse().unwrap_or(0),
+                crc32: get_attribute(&attributes, "crc").to_lowercase(),
+                sha1: get_attribute(&attributes, "sha1").to_lowercase(),
+            });
+        }
+    }
+}
+
+pub fn load_dats(files: &[String]) -> Result<Dat, DatError> {
+    let mut dat = Dat::default();
+
+    for file in files.iter() {
+        let text = fs::read_to_string(file).map_err(|e| { DatError::Read(file.clone(), format!("{}", e)) })?;
+
+        let before = dat.roms.len();
+        parse_dat(&text, &mut dat);
+
+        if dat.roms.len() == before {
+            return Err(DatError::Empty(file.clone()));
+        }
+    }
+
+    Ok(dat)
+}
+
+impl Dat {
+    // Finds the DAT entry a rip matches exactly, if there is one.
+    pub fn find(&self, hashes: &Hashes) -> Option<&DatRom> {
+        self.roms.iter().find(|rom| {
+            rom.size == hashes.size && rom.crc32 == hashes.crc32 && rom.sha1 == hashes.sha1
+        })
+    }
+}
+
+pub fn get_dat_error_message(error: &DatError) -> String {
+    match error {
+        DatError::Read(file, message) => format!("Failed to read DAT file {}: {}", file, message),
+        DatError::Empty(file) => format!("{} doesn't have any ROM entries in it. Is it a redump DAT file?", file),
+    }
+}
@@ -0,0 +1,161 @@
+
+// Drives on another machine, like a headless box full of optical drives, read over the network with NBD. The other
+// machine shares each drive with nbd-server, and here they're attached with nbd-client and imaged like any other block
+// device. nbd-server only opens the drive when we connect, so a disk has to be in it for the connection to work.
+// Ejecting a remote drive lets go of it. Swap the disk over there, then close the tray here to pick the new one up.
+
+use std::fs;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+// A machine that's gone quiet would otherwise keep nbd-client waiting, and everything waiting on it, for minutes.
+const NBD_CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub enum RemoteError {
+    LaunchFail,       // Failed to launch nbd-client.
+    Refused(String),  // nbd-client couldn't connect or let go. Holds what it had to say about it.
+    TimedOut,         // nbd-client didn't get anywhere before NBD_CLIENT_TIMEOUT, so it was stopped.
+}
+
+// Where a drive is, like ripbox.local:10809/sr0, or [fd00::2]:10809/sr0. The port can be left out.
+#[derive(Clone)]
+pub struct RemoteDrive {
+    pub host: String,
+    pub port: String,
+    pub export: String, // The name nbd-server shares the drive under.
+}
+
+impl RemoteDrive {
+    pub fn describe(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}/{}", self.host, self.port, self.export)
+        } else {
+            format!("{}:{}/{}", self.host, self.port, self.export)
+        }
+    }
+}
+
+pub fn parse_remote_drive(spec: &str) -> Option<RemoteDrive> {
+    let (address, export) = spec.split_once('/')?;
+
+    // IPv6 addresses go in brackets, so their colons aren't taken for the port's.
+    let (host, port) = match address.strip_prefix('[') {
+        Some(address) => match address.split_once(']')? {
+            (host, "") => (host, "10809"),
+            (host, port) => (host, port.strip_prefix(':')?),
+        },
+        None => address.split_once(':').unwrap_or((address, "10809")),
+    };
+
+    if host.is_empty() || export.is_empty() || port.parse::<u16>().is_err() {
+        return None;
+    }
+
+    Some(RemoteDrive {
+        host: String::from(host),
+        port: String::from(port),
+        export: String::from(export),
+    })
+}
+
+fn is_connected(device: &str) -> bool {
+    let name = device.trim_start_matches("/dev/");
+    fs::metadata(format!("/sys/block/{}/pid", name)).is_ok()
+}
+
+// The first `count` NBD devices nothing's using, to attach remote drives to. The nbd kernel module has to be loaded
+// for there to be any.
+pub fn find_free_devices(count: usize) -> Vec<String> {
+    (0..64)
+        .map(|index| format!("/dev/nbd{}", index))
+        .filter(|device| fs::metadata(device).is_ok() && !is_connected(device))
+        .take(count)
+        .collect()
+}
+
+fn run_nbd_client(args: &[&str]) -> Result<(), RemoteError> {
+    let mut child = Command::new("nbd-client")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| { RemoteError::LaunchFail })?;
+
+    // It only ever says a line or two, so there's no filling up its pipe while we wait.
+    let deadline = Instant::now() + NBD_CLIENT_TIMEOUT;
+    while child.try_wait().map_err(|_| { RemoteError::LaunchFail })?.is_none() {
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(RemoteError::TimedOut);
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let output = child.wait_with_output().map_err(|_| { RemoteError::LaunchFail })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(RemoteError::Refused(String::from(String::from_utf8_lossy(&output.stderr).trim())))
+    }
+}
+
+// Does nothing if it's already attached.
+pub fn connect(remote: &RemoteDrive, device: &str) -> Result<(), RemoteError> {
+    if is_connected(device) {
+        return Ok(());
+    }
+
+    // Optical disks are read 2048 bytes at a time anyway.
+    run_nbd_client(&[&remote.host, &remote.port, device, "-N", &remote.export, "-b", "2048"])
+}
+
+pub fn disconnect(device: &str) -> Result<(), RemoteError> {
+    if !is_connected(device) {
+        return Ok(());
+    }
+
+    run_nbd_client(&["-d", device])
+}
+
+pub fn get_remote_error_message(error: &RemoteError) -> String {
+    match error {
+        RemoteError::LaunchFail => String::from("Failed to launch nbd-client. Is it installed?"),
+        RemoteError::Refused(message) => format!("nbd-client failed: {}", message),
+        RemoteError::TimedOut => format!("nbd-client didn't get anywhere in {} seconds.", NBD_CLIENT_TIMEOUT.as_secs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_are_checked() {
+        let drive = parse_remote_drive("ripbox.local/sr0").unwrap();
+        assert_eq!((drive.host.as_str(), drive.port.as_str(), drive.export.as_str()), ("ripbox.local", "10809", "sr0"));
+
+        assert_eq!(parse_remote_drive("ripbox.local:10900/sr0").unwrap().port, "10900");
+        assert!(parse_remote_drive("ripbox.local:nbd/sr0").is_none());
+        assert!(parse_remote_drive("ripbox.local:70000/sr0").is_none());
+        assert!(parse_remote_drive("ripbox.local:/sr0").is_none());
+    }
+
+    #[test]
+    fn ipv6_addresses_go_in_brackets() {
+        let drive = parse_remote_drive("[fd00::2]:10900/sr0").unwrap();
+        assert_eq!((drive.host.as_str(), drive.port.as_str()), ("fd00::2", "10900"));
+        assert_eq!(drive.describe(), "[fd00::2]:10900/sr0");
+
+        assert_eq!(parse_remote_drive("[fd00::2]/sr0").unwrap().port, "10809");
+        assert!(parse_remote_drive("fd00::2/sr0").is_none());
+        assert!(parse_remote_drive("[fd00::2/sr0").is_none());
+        assert!(parse_remote_drive("[fd00::2]10900/sr0").is_none());
+    }
+}
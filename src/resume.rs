@@ -0,0 +1,55 @@
+
+// Partial images from drives that went away part way through a rip, like a USB drive that got bumped out. What was
+// read so far is kept next to the images, under the disk's fingerprint, and when that disk turns up again in any
+// drive the rip picks up where it left off instead of starting over. Only works with the local file sink, since the
+// partial image has to be read back.
+
+use std::fs;
+use std::io;
+
+use crate::mapfile;
+use crate::mapfile::DamageMap;
+use crate::sink::OutputSink;
+
+pub struct Partial {
+    pub file: fs::File,
+    pub length: u64,       // How much was read before the drive went away.
+    pub damage: DamageMap, // What couldn't be read before that.
+}
+
+pub fn get_partial_name(key: &str) -> String {
+    format!("{}.partial", key)
+}
+
+pub fn get_map_name(key: &str) -> String {
+    format!("{}.partial.map", key)
+}
+
+// Keeps what's been written so far. Disks without a fingerprint are kept under their volume name, for the operator to
+// do something with, but can't be picked back up since there's no telling it's the same disk.
+pub fn save_partial(mut target: Box<dyn OutputSink>, key: &str, damage: &DamageMap) -> io::Result<String> {
+    target.finish()?;
+    target.persist(&get_partial_name(key))?;
+    damage.write_mapfile(&get_map_name(key))?;
+
+    Ok(get_partial_name(key))
+}
+
+// The partial image for a disk, if there is one.
+pub fn find_partial(fingerprint: &str) -> Option<Partial> {
+    let file = fs::File::open(get_partial_name(fingerprint)).ok()?;
+    let length = file.metadata().ok()?.len();
+    let damage = mapfile::read_mapfile(&get_map_name(fingerprint)).unwrap_or_default();
+
+    Some(Partial {
+        file,
+        length,
+        damage,
+    })
+}
+
+// For once the disk's been ripped the rest of the way.
+pub fn remove_partial(fingerprint: &str) {
+    let _ = fs::remove_file(get_partial_name(fingerprint));
+    let _ = fs::remove_file(get_map_name(fingerprint));
+}
@@ -0,0 +1,48 @@
+
+// Who's running the station. Operators can rip, name, and eject disks, and that's it. Admins also get the drive
+// settings and anything that destroys something, like burning, erasing, and overwriting images. Picked at launch with
+// --operator or --admin, or the config's role if neither is given, for archives where volunteers run the station.
+// The command line tools aren't limited, so keep volunteers off the shell.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Role {
+    Operator,
+    Admin,
+}
+
+// Admin until told otherwise, like before there were roles.
+static ADMIN: AtomicBool = AtomicBool::new(true);
+
+pub fn parse_role(value: &str) -> Option<Role> {
+    match value {
+        "operator" => Some(Role::Operator),
+        "admin" => Some(Role::Admin),
+        _ => None,
+    }
+}
+
+// Takes the role flags out of the arguments, leaving the rest for the command line tools.
+pub fn take_role_flag(args: &mut Vec<String>) -> Option<Role> {
+    let mut role = None;
+
+    args.retain(|arg| match arg.strip_prefix("--").and_then(parse_role) {
+        Some(flag) => {
+            role = Some(flag);
+            false
+        },
+        None => true,
+    });
+
+    role
+}
+
+pub fn set_role(role: Role) {
+    ADMIN.store(role == Role::Admin, Relaxed);
+}
+
+pub fn is_admin() -> bool {
+    ADMIN.load(Relaxed)
+}
@@ -0,0 +1,39 @@
+
+// A log of every read error a drive explained with sense data. Looking back over it shows whether one drive keeps
+// failing on disks the others read fine, which means it's the drive, or one disk fails in every drive, which means
+// it's the disk.
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+
+use crate::catalog;
+use crate::cdrom;
+use crate::cdrom::ScsiError;
+use crate::cdrom::SenseData;
+
+const LOG_FILE: &str = "auto_archive.sense.log";
+
+// Works out which drive it was from the open file, so the code doing the reading doesn't have to carry its name around.
+fn get_device_name(device: &fs::File) -> String {
+    fs::read_link(format!("/proc/self/fd/{}", device.as_raw_fd()))
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| String::from("unknown drive"))
+}
+
+pub fn log_sense(device: &fs::File, lba: u64, sense: &SenseData) {
+    let line = format!("{} UTC {} sector {}: sense key {:X}, ASC {:02X}, ASCQ {:02X}, {}\n",
+        catalog::format_date(catalog::now()), get_device_name(device), lba, sense.key, sense.asc, sense.ascq, cdrom::describe_sense(sense));
+
+    // Losing a log line isn't worth stopping a rip over.
+    if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(LOG_FILE) {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+// Logs errors that came with sense data. The rest have nothing worth writing down.
+pub fn log_error(device: &fs::File, lba: u64, error: &ScsiError) {
+    if let ScsiError::Check(sense) = error {
+        log_sense(device, lba, sense);
+    }
+}
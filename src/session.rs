@@ -0,0 +1,108 @@
+
+// Writes down every disk this session, for the records: what it was saved as, its hashes, and how it ended up, from
+// the queue, which keeps every disk since auto_archive started. One CSV row per disk, oldest first, all with the same
+// columns, so it opens in a spreadsheet.
+
+use std::fs;
+use std::io;
+use std::sync::OnceLock;
+
+use serde_json::json;
+use serde_json::Value;
+
+use crate::catalog;
+use crate::drive_list::DriveList;
+use crate::export::escape_csv;
+use crate::queue;
+use crate::queue::QueueEntry;
+
+static STARTED: OnceLock<u64> = OnceLock::new();
+
+// When auto_archive started, in seconds since the Unix epoch. The first call decides.
+pub fn started() -> u64 {
+    *STARTED.get_or_init(catalog::now)
+}
+
+// Totals for the session so far, as JSON.
+pub fn get_stats(drives: &DriveList) -> Value {
+    let plugged_in = drives.get();
+
+    // Drives that have been pulled out still count for what they did before that.
+    let archived: Vec<_> = queue::entries().into_iter()
+        .filter_map(|entry| entry.archived)
+        .collect();
+
+    json!({
+        "started": started(),
+        "uptime": catalog::now().saturating_sub(started()),
+        "drives": plugged_in.len(),
+        "busy_drives": plugged_in.iter().filter(|drive| crate::is_drive_busy(drive)).count(),
+        "disks_archived": archived.len(),
+        "bytes_archived": archived.iter().map(|entry| entry.size).sum::<u64>(),
+    })
+}
+
+const CSV_HEADER: &str = "started,drive,status,image,volume,size,crc32,sha1,destination";
+
+fn describe_disks(entries: &[QueueEntry]) -> String {
+    let mut output = format!("{}\n", CSV_HEADER);
+
+    for entry in entries.iter() {
+        // Disks that never got saved leave the catalog's columns blank.
+        let (image, volume, size, crc32, sha1) = match &entry.archived {
+            Some(archived) => (archived.image.clone(), archived.volume.clone(), archived.size.to_string(), archived.crc32.clone(), archived.sha1.clone()),
+            None => Default::default(),
+        };
+
+        let fields = [catalog::format_date(entry.started), entry.drive.clone(), entry.status.clone(), image, volume, size, crc32, sha1,
+            entry.destination.clone()];
+        let line: Vec<String> = fields.iter().map(|field| escape_csv(field)).collect();
+        output += &format!("{}\n", line.join(","));
+    }
+
+    output
+}
+
+pub fn describe_session() -> String {
+    describe_disks(&queue::entries())
+}
+
+// Saves the session to a file named after when it was saved. Returns the file's name.
+pub fn export_session() -> io::Result<String> {
+    let name = format!("auto_archive.session-{}.csv", catalog::format_date(catalog::now()).replace([' ', ':'], "-"));
+    fs::write(&name, describe_session())?;
+
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::CatalogEntry;
+
+    fn disk(status: &str, archived: Option<CatalogEntry>) -> QueueEntry {
+        QueueEntry {
+            drive: String::from("/dev/sr0"),
+            started: 0,
+            status: String::from(status),
+            name: String::new(),
+            destination: String::new(),
+            archived: archived.map(Box::new),
+            stage: queue::Stage::Finished,
+            finished: true,
+        }
+    }
+
+    #[test]
+    fn every_row_has_every_column() {
+        let saved = CatalogEntry { image: String::from("DATA, 2.iso"), size: 2048, ..CatalogEntry::default() };
+        let output = describe_disks(&[disk("Done.", Some(saved)), disk("Couldn't read the disk.", None)]);
+
+        let columns = CSV_HEADER.split(',').count();
+        for line in output.lines() {
+            assert_eq!(crate::export::parse_csv(line)[0].len(), columns, "{}", line);
+        }
+
+        assert!(output.contains("\"DATA, 2.iso\""));
+    }
+}
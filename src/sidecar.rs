@@ -0,0 +1,32 @@
+
+// The JSON file that sits next to an image with everything worth knowing about it that isn't in the image itself.
+// Different parts of a rip add their own keys to it, so each one reads what's there and adds to it.
+
+use serde_json::Map;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+// Same name as the image, so it follows the image around, even when it gets compressed to a CHD.
+pub fn get_sidecar_path(image: &str) -> PathBuf {
+    Path::new(image).with_extension("json")
+}
+
+// Adds these keys to the image's sidecar, replacing any that are already there.
+pub fn update(image: &str, values: Map<String, Value>) -> io::Result<()> {
+    let path = get_sidecar_path(image);
+
+    // One that doesn't parse gets started over. It only has what we put there.
+    let mut sidecar = match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str::<Map<String, Value>>(&text).unwrap_or_default(),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Map::new(),
+        Err(error) => return Err(error),
+    };
+
+    sidecar.extend(values);
+
+    // Can't fail, it's all strings and numbers.
+    fs::write(&path, serde_json::to_string_pretty(&sidecar).unwrap_or_default())
+}
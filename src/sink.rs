@@ -0,0 +1,379 @@
+
+// Where images get written while they're ripped. Every sink takes the bytes as they come off the disk, and once the
+// disk has a name, puts the finished image wherever it keeps things. The built in one writes a local file, sparse if
+// that's turned on. Others just need registering before the drives start, under a name `output_sink` can pick.
+// Any sink can have a mirror written alongside it, as a second copy that doesn't hold up the first if it fails. The
+// mirror gets the image as it's read, and then everything else made for the image once it's finished.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::RawFd;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::SyncSender;
+use std::sync::mpsc::TrySendError;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use tempfile_fast::PersistableTempFile;
+
+use crate::config::Config;
+use crate::persist;
+use crate::sparse::SparseWriter;
+
+pub trait OutputSink: Write + Send {
+    // Called once everything's been written, before anything looks at what was written.
+    fn finish(&mut self) -> io::Result<()>;
+
+    // Saves what was written under its name. There's nothing left to write to after this.
+    fn persist(self: Box<Self>, name: &str) -> io::Result<()>;
+
+    // The local file it's writing to, if it is, so a crash can keep what's been written.
+    fn temp_fd(&self) -> Option<RawFd> {
+        None
+    }
+}
+
+// Makes a new sink for one image.
+pub type SinkFactory = fn(config: &Config) -> io::Result<Box<dyn OutputSink>>;
+
+pub const LOCAL_FILE: &str = "file";
+
+static SINKS: Mutex<Vec<(&'static str, SinkFactory)>> = Mutex::new(Vec::new());
+
+// A file in the working directory with no name until it's done, so a rip that dies part way through leaves nothing.
+pub struct LocalFileSink {
+    writer: SparseWriter<PersistableTempFile>,
+}
+
+impl LocalFileSink {
+    pub fn create(config: &Config) -> io::Result<Box<dyn OutputSink>> {
+        Ok(Box::new(LocalFileSink {
+            writer: SparseWriter::new(PersistableTempFile::new_in("./")?, config.sparse_output),
+        }))
+    }
+}
+
+impl Write for LocalFileSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl OutputSink for LocalFileSink {
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.finish()
+    }
+
+    fn persist(self: Box<Self>, name: &str) -> io::Result<()> {
+        persist::persist(self.writer.into_inner(), name)
+    }
+
+    fn temp_fd(&self) -> Option<RawFd> {
+        Some(self.writer.get_ref().as_raw_fd())
+    }
+}
+
+// Makes a sink available as `output_sink = <name>`. Replaces any sink already registered under that name.
+pub fn register(name: &'static str, factory: SinkFactory) {
+    let mut sinks = SINKS.lock().unwrap();
+    sinks.retain(|(existing, _)| *existing != name);
+    sinks.push((name, factory));
+}
+
+// Makes a sink for one image, of whichever kind the config asks for.
+pub fn create(config: &Config) -> io::Result<Box<dyn OutputSink>> {
+    let factory = SINKS.lock().unwrap().iter()
+        .find(|(name, _)| *name == config.output_sink)
+        .map(|(_, factory)| *factory);
+
+    match factory {
+        Some(factory) => factory(config),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, format!("There's no output sink called \"{}\".", config.output_sink))),
+    }
+}
+
+// A second copy of an image, in another directory, written as the image is. Whatever goes wrong with it is kept for
+// later instead of stopping the rip, since the first copy's still fine. It's written on a thread of its own, so a
+// network share that stops answering doesn't hold the rip up. One that falls too far behind is given up on.
+pub struct Mirror {
+    chunks: Option<SyncSender<Vec<u8>>>, // To the thread writing it. None once it's finished or failed.
+    done: Option<Receiver<io::Result<MirrorWriter>>>, // What the thread ends up with, once it's out of chunks.
+    writer: Option<MirrorWriter>, // Finished, and waiting for a name.
+    directory: String,
+    error: Option<String>,
+}
+
+type MirrorWriter = SparseWriter<PersistableTempFile>;
+
+// Writes the mirror can fall behind by before it's given up on. Enough to ride out a slow moment on a share.
+const MIRROR_BACKLOG: usize = 256;
+
+// How long it gets to catch up once the image is done.
+const MIRROR_FINISH_TIMEOUT: Duration = Duration::from_secs(60);
+
+impl Mirror {
+    pub fn create(directory: &str, config: &Config) -> Mirror {
+        let mut mirror = Mirror {
+            chunks: None,
+            done: None,
+            writer: None,
+            directory: String::from(directory),
+            error: None,
+        };
+
+        let mut writer = match PersistableTempFile::new_in(directory) {
+            Ok(file) => SparseWriter::new(file, config.sparse_output),
+            Err(error) => {
+                mirror.fail(&error);
+                return mirror;
+            },
+        };
+
+        let (chunks, received) = mpsc::sync_channel::<Vec<u8>>(MIRROR_BACKLOG);
+        let (result, done) = mpsc::channel();
+
+        thread::spawn(move || {
+            // Stops at the first thing that goes wrong. Dropping `received` is what tells the rip.
+            let written = received.iter().try_for_each(|chunk| writer.write_all(&chunk))
+                .and_then(|()| writer.finish())
+                .map(|()| writer);
+
+            let _ = result.send(written);
+        });
+
+        mirror.chunks = Some(chunks);
+        mirror.done = Some(done);
+        mirror
+    }
+
+    // The temp file goes with whatever had it.
+    fn fail(&mut self, error: &dyn fmt::Display) {
+        self.chunks = None;
+        self.done = None;
+        self.error = Some(format!("Couldn't write to {}: {}", self.directory, error));
+    }
+
+    fn write(&mut self, buf: &[u8]) {
+        let sent = match &self.chunks {
+            Some(chunks) => chunks.try_send(buf.to_vec()),
+            None => return,
+        };
+
+        match sent {
+            Ok(()) => {},
+            Err(TrySendError::Full(_)) => self.fail(&"it fell too far behind"),
+            Err(TrySendError::Disconnected(_)) => self.finish(), // Picks up what went wrong.
+        }
+    }
+
+    // Waits for the thread to write everything it was sent.
+    fn finish(&mut self) {
+        self.chunks = None;
+
+        let done = match self.done.take() {
+            Some(done) => done,
+            None => return,
+        };
+
+        match done.recv_timeout(MIRROR_FINISH_TIMEOUT) {
+            Ok(Ok(writer)) => self.writer = Some(writer),
+            Ok(Err(error)) => self.fail(&error),
+            Err(_) => self.fail(&"it didn't catch up"),
+        }
+    }
+
+    // Saves the copy under the same name the image got, in the mirror's directory. Returns where it went, or why it
+    // didn't.
+    pub fn persist(mut self, name: &str) -> Result<String, String> {
+        self.finish();
+
+        let writer = match (self.writer, self.error) {
+            (_, Some(error)) => return Err(error),
+            (Some(writer), None) => writer,
+            (None, None) => return Err(format!("Nothing was written to {}.", self.directory)),
+        };
+
+        let path = get_mirror_path(&self.directory, name);
+        let result = path.parent().map(fs::create_dir_all).unwrap_or(Ok(()))
+            .and_then(|()| persist::persist(writer.into_inner(), &path));
+
+        match result {
+            Ok(()) => Ok(path.to_string_lossy().into_owned()),
+            Err(error) => Err(format!("Couldn't save {}: {}", path.display(), error)),
+        }
+    }
+}
+
+// Writes to a sink and its mirror at once. Only the sink's errors count. The mirror's are kept in the mirror.
+pub struct Tee<'a> {
+    sink: &'a mut dyn OutputSink,
+    mirror: Option<&'a mut Mirror>,
+}
+
+impl<'a> Tee<'a> {
+    pub fn new(sink: &'a mut dyn OutputSink, mirror: Option<&'a mut Mirror>) -> Tee<'a> {
+        Tee { sink, mirror }
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(mirror) = self.mirror.as_mut() {
+            mirror.finish();
+        }
+
+        self.sink.finish()
+    }
+}
+
+impl Write for Tee<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.sink.write(buf)?;
+
+        if let Some(mirror) = self.mirror.as_mut() {
+            mirror.write(&buf[..written]);
+        }
+
+        Ok(written)
+    }
+
+    // The mirror's thread writes as it goes, so there's nothing of its to flush.
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+}
+
+// Where a file goes in the mirror. Always somewhere inside it, even for names that start with / or have .. in them.
+pub fn get_mirror_path(directory: &str, name: &str) -> PathBuf {
+    let inside: PathBuf = Path::new(name).components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+
+    Path::new(directory).join(inside)
+}
+
+// Copies files made after the image was saved, like its sidecar or parity, into the mirror beside its copy of the
+// image. Stops at the first one that fails, and says which.
+pub fn copy_to_mirror(directory: &str, files: &[String]) -> Result<(), String> {
+    for file in files.iter() {
+        let path = get_mirror_path(directory, file);
+
+        let result = path.parent().map(fs::create_dir_all).unwrap_or(Ok(()))
+            .and_then(|()| fs::copy(file, &path));
+
+        if let Err(error) = result {
+            return Err(format!("Couldn't copy {} to {}: {}", file, path.display(), error));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MemorySink(Vec<u8>);
+
+    impl Write for MemorySink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl OutputSink for MemorySink {
+        fn finish(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn persist(self: Box<Self>, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn get_test_directory(name: &str) -> String {
+        let directory = std::env::temp_dir().join(format!("auto_archive-mirror-{}-{}", name, std::process::id()));
+        directory.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn mirror_gets_the_same_image() {
+        let directory = get_test_directory("same");
+        fs::create_dir_all(&directory).unwrap();
+
+        let mut sink = MemorySink(Vec::new());
+        let mut mirror = Mirror::create(&directory, &Config::default());
+
+        let mut tee = Tee::new(&mut sink, Some(&mut mirror));
+        tee.write_all(b"disk image").unwrap();
+        tee.finish().unwrap();
+
+        let path = mirror.persist("sub/image.iso").unwrap();
+        let copied = fs::read(&path).unwrap();
+        let _ = fs::remove_dir_all(&directory);
+
+        assert_eq!(sink.0, b"disk image");
+        assert_eq!(copied, b"disk image");
+    }
+
+    #[test]
+    fn broken_mirror_doesnt_stop_the_sink() {
+        let directory = get_test_directory("missing");
+
+        let mut sink = MemorySink(Vec::new());
+        let mut mirror = Mirror::create(&directory, &Config::default());
+
+        let mut tee = Tee::new(&mut sink, Some(&mut mirror));
+        tee.write_all(b"disk image").unwrap();
+        tee.finish().unwrap();
+
+        assert_eq!(sink.0, b"disk image");
+        assert!(mirror.persist("image.iso").is_err());
+    }
+
+    #[test]
+    fn stalled_mirror_is_given_up_on() {
+        // Nothing's taking chunks off the other end.
+        let (chunks, _received) = mpsc::sync_channel(MIRROR_BACKLOG);
+        let mut mirror = Mirror { chunks: Some(chunks), done: None, writer: None, directory: String::from("/mnt/nas"), error: None };
+
+        let mut sink = MemorySink(Vec::new());
+        let mut tee = Tee::new(&mut sink, Some(&mut mirror));
+        for _ in 0..=MIRROR_BACKLOG {
+            tee.write_all(b"block").unwrap();
+        }
+
+        assert_eq!(sink.0.len(), (MIRROR_BACKLOG + 1) * 5);
+        assert!(mirror.chunks.is_none());
+        assert!(mirror.persist("image.iso").is_err());
+    }
+
+    #[test]
+    fn mirror_paths_stay_inside() {
+        assert_eq!(get_mirror_path("/mnt/nas", "/home/me/image.iso"), PathBuf::from("/mnt/nas/home/me/image.iso"));
+        assert_eq!(get_mirror_path("/mnt/nas", "../image.iso"), PathBuf::from("/mnt/nas/image.iso"));
+    }
+
+    #[test]
+    fn missing_files_fail_the_copy() {
+        let directory = get_test_directory("copy");
+        let result = copy_to_mirror(&directory, &[String::from("/nonexistent/auto_archive/image.json")]);
+        let _ = fs::remove_dir_all(&directory);
+
+        assert!(result.is_err());
+    }
+}
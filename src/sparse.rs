@@ -0,0 +1,171 @@
+
+// Writes images as sparse files. Runs of zeros are skipped over instead of written, so a mostly empty
+// 64GB stick or 700MB disk only takes up as much room as the data actually on it. Disks are read a sector at a time,
+// which is smaller than a hole is worth making for, so writes are gathered up into whole blocks before they're looked
+// at. Blocks line up with the start of the file, so the file has to be empty to begin with.
+
+use std::fs;
+use std::io;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::mem;
+use std::ops::DerefMut;
+
+// Smallest run of zeros worth making a hole for. Filesystems allocate in blocks about this size anyway.
+const HOLE_GRANULARITY: usize = 4096;
+
+// Works on anything that gets to a file, so it can own its file or just borrow one.
+pub struct SparseWriter<F: DerefMut<Target = fs::File>> {
+    file: F,
+    enabled: bool,     // When false, everything is written through as is.
+    pending_hole: u64, // Zeros we've skipped but haven't seeked past yet.
+    block: Vec<u8>,    // The start of the next block, until there's a whole one to look at.
+}
+
+impl<F: DerefMut<Target = fs::File>> SparseWriter<F> {
+    pub fn new(file: F, enabled: bool) -> SparseWriter<F> {
+        SparseWriter {
+            file,
+            enabled,
+            pending_hole: 0,
+            block: Vec::new(),
+        }
+    }
+
+    fn write_block(&mut self, block: &[u8]) -> io::Result<()> {
+        if block.iter().all(|byte| *byte == 0) {
+            self.pending_hole += block.len() as u64;
+            Ok(())
+        } else {
+            self.flush_hole()?;
+            self.file.write_all(block)
+        }
+    }
+
+    fn flush_hole(&mut self) -> io::Result<()> {
+        if self.pending_hole > 0 {
+            self.file.seek(SeekFrom::Current(self.pending_hole as i64))?;
+            self.pending_hole = 0;
+        }
+
+        Ok(())
+    }
+
+    // Must be called once everything is written, or an image that ends in zeros will come out short.
+    pub fn finish(&mut self) -> io::Result<()> {
+        // The end of the file doesn't have to be a whole block.
+        if !self.block.is_empty() {
+            let block = mem::take(&mut self.block);
+            self.write_block(&block)?;
+        }
+
+        if self.pending_hole > 0 {
+            let end = self.file.stream_position()? + self.pending_hole;
+            self.file.set_len(end)?;
+            self.file.seek(SeekFrom::Start(end))?;
+            self.pending_hole = 0;
+        }
+
+        self.file.flush()
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
+    pub fn get_ref(&self) -> &fs::File {
+        &self.file
+    }
+}
+
+impl<F: DerefMut<Target = fs::File>> Write for SparseWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.enabled {
+            return self.file.write(buf);
+        }
+
+        let mut rest = buf;
+
+        // Fill in the block that was started by the last write first.
+        if !self.block.is_empty() {
+            let (start, end) = rest.split_at((HOLE_GRANULARITY - self.block.len()).min(rest.len()));
+            self.block.extend_from_slice(start);
+            rest = end;
+
+            if self.block.len() < HOLE_GRANULARITY {
+                return Ok(buf.len());
+            }
+
+            let mut block = mem::take(&mut self.block);
+            self.write_block(&block)?;
+
+            block.clear();
+            self.block = block;
+        }
+
+        let mut blocks = rest.chunks_exact(HOLE_GRANULARITY);
+        for block in &mut blocks {
+            self.write_block(block)?;
+        }
+        self.block.extend_from_slice(blocks.remainder());
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Read;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn zeros_written_a_sector_at_a_time_make_holes() {
+        let path = std::env::temp_dir().join(format!("auto_archive-sparse-test-{}", std::process::id()));
+        let size = 1024 * 1024;
+
+        let mut writer = SparseWriter::new(Box::new(fs::File::create(&path).unwrap()), true);
+        for _ in 0..(size / 2048) {
+            writer.write_all(&[0u8; 2048]).unwrap();
+        }
+        writer.write_all(b"end").unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let metadata = fs::metadata(&path).unwrap();
+        let mut contents = Vec::new();
+        fs::File::open(&path).unwrap().read_to_end(&mut contents).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(metadata.len(), size as u64 + 3);
+        assert!(contents[..size].iter().all(|byte| *byte == 0));
+        assert_eq!(&contents[size..], b"end");
+
+        // Only the last block should be allocated, but file systems are allowed some slack.
+        assert!(metadata.blocks() * 512 < (size as u64) / 4, "{} bytes allocated", metadata.blocks() * 512);
+    }
+
+    #[test]
+    fn data_split_across_writes_comes_out_whole() {
+        let path = std::env::temp_dir().join(format!("auto_archive-sparse-split-test-{}", std::process::id()));
+        let data: Vec<u8> = (0..10000u32).map(|index| (index % 251) as u8).collect();
+
+        let mut writer = SparseWriter::new(Box::new(fs::File::create(&path).unwrap()), true);
+        for piece in data.chunks(777) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+
+        let contents = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(contents, data);
+    }
+}
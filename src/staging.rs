@@ -0,0 +1,76 @@
+
+// Room for images in the working directory while they're ripped. Every drive going at once can run it out of space
+// even when each disk would fit on its own, so each rip sets aside room for its whole image before it starts, and
+// has to fit on top of what every other rip still has left to write.
+
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+const STAGING_DIRECTORY: &str = "./";
+
+pub struct Shortfall {
+    pub needed: u64, // For this image, and everything the other rips still have to write.
+    pub free: u64,
+    pub others: usize, // Rips still going. With none, waiting won't make any more room.
+}
+
+// What each rip going right now still has left to write.
+static RESERVED: Mutex<Vec<Arc<AtomicU64>>> = Mutex::new(Vec::new());
+
+// Room set aside for one image. Given back as the image is written, and all of it once it's dropped.
+pub struct Reservation {
+    remaining: Arc<AtomicU64>,
+}
+
+impl Reservation {
+    pub fn wrote(&self, bytes: u64) {
+        let _ = self.remaining.fetch_update(Relaxed, Relaxed, |remaining| Some(remaining.saturating_sub(bytes)));
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        RESERVED.lock().unwrap().retain(|remaining| !Arc::ptr_eq(remaining, &self.remaining));
+    }
+}
+
+fn get_free_space(directory: &str) -> io::Result<u64> {
+    let path = CString::new(directory).map_err(|e| { io::Error::new(io::ErrorKind::InvalidInput, e) })?;
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let stats = unsafe { stats.assume_init() };
+
+    #[allow(clippy::unnecessary_cast)] // They're only u64 on some platforms.
+    Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+// Sets aside room for an image of `size` bytes, if there is any. If there's no telling how much room there is, it
+// goes ahead anyway, same as before there was a check.
+pub fn reserve(size: u64) -> Result<Reservation, Shortfall> {
+    let mut reserved = RESERVED.lock().unwrap();
+    let others: u64 = reserved.iter().map(|remaining| remaining.load(Relaxed)).sum();
+
+    if let Ok(free) = get_free_space(STAGING_DIRECTORY) {
+        if size + others > free {
+            return Err(Shortfall {
+                needed: size + others,
+                free,
+                others: reserved.len(),
+            });
+        }
+    }
+
+    let remaining = Arc::new(AtomicU64::new(size));
+    reserved.push(remaining.clone());
+
+    Ok(Reservation { remaining })
+}
@@ -0,0 +1,147 @@
+
+// For running more than one rip station as one. Each station lists the others' API addresses in its config, and
+// then it asks them before ripping a disk they already have, shows what their drives are doing next to its own, and
+// can pull their catalogs into its own with `auto_archive sync-stations`. The other stations need api_address set,
+// and the same api_token as this one if they have one. Addresses starting with https:// are checked against this
+// station's tls_certificate, so stations sharing one self-signed certificate trust each other.
+
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::catalog::CatalogEntry;
+use crate::chat_bot::quote;
+use crate::config::Config;
+use crate::export;
+use crate::format_size;
+
+// A station that's down shouldn't hold up a rip for long.
+const REQUEST_TIMEOUT_SECONDS: u64 = 5;
+
+// How often to check on the other stations' drives.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// The token goes in on stdin, so it doesn't show up in the process list.
+fn fetch(config: &Config, address: &str, path: &str) -> Result<Value, String> {
+    let url = if address.contains("://") { format!("{}{}", address, path) } else { format!("http://{}{}", address, path) };
+    let mut options = format!("url = {}\n", quote(&url));
+
+    if let Some(token) = &config.api_token {
+        options += &format!("header = {}\n", quote(&format!("Authorization: Bearer {}", token)));
+    }
+    if let Some(certificate) = &config.tls_certificate {
+        options += &format!("cacert = {}\n", quote(certificate));
+    }
+
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--max-time", &REQUEST_TIMEOUT_SECONDS.to_string(), "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| { String::from("Failed to launch curl. Is it installed?") })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(options.as_bytes());
+    }
+
+    let output = child.wait_with_output().map_err(|_| { String::from("Failed to launch curl. Is it installed?") })?;
+
+    if !output.status.success() {
+        return Err(String::from(String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let value: Value = serde_json::from_slice(&output.stdout).map_err(|e| { format!("{} sent back nonsense: {}", address, e) })?;
+
+    // Errors come back as JSON too, like for a wrong token.
+    match value["error"].as_str() {
+        Some(message) => Err(String::from(message)),
+        None => Ok(value),
+    }
+}
+
+// Which station already has a disk with this fingerprint, and what it's called there. Stations that can't be reached
+// are skipped.
+pub fn find_duplicate(config: &Config, fingerprint: &str) -> Option<String> {
+    config.stations.iter().find_map(|address| {
+        let entry = fetch(config, address, &format!("/catalog/fingerprint/{}", fingerprint)).ok()?;
+        entry["image"].as_str().map(|image| format!("{} on {}", image, address))
+    })
+}
+
+pub fn fetch_catalog(config: &Config, address: &str) -> Result<Vec<CatalogEntry>, String> {
+    export::import_json(&fetch(config, address, "/catalog")?.to_string())
+}
+
+pub struct Station {
+    pub address: String,
+    pub status_message: Mutex<String>,
+    pub drives: Mutex<usize>,
+    pub busy_drives: Mutex<usize>,
+    pub disks_archived: Mutex<u64>,
+}
+
+impl Station {
+    pub fn new(address: &str) -> Station {
+        Station {
+            address: String::from(address),
+            status_message: Mutex::new(String::from("----")),
+            drives: Mutex::new(0),
+            busy_drives: Mutex::new(0),
+            disks_archived: Mutex::new(0),
+        }
+    }
+
+    fn check(&self, config: &Config) {
+        let result = fetch(config, &self.address, "/session/stats")
+            .and_then(|stats| fetch(config, &self.address, "/drives").map(|drives| (stats, drives)));
+
+        let (stats, drives) = match result {
+            Ok(result) => result,
+            Err(message) => {
+                *self.drives.lock().unwrap() = 0;
+                *self.busy_drives.lock().unwrap() = 0;
+                *self.status_message.lock().unwrap() = format!("Can't reach it: {}", message);
+                return;
+            },
+        };
+
+        let count = |name: &str| stats[name].as_u64().unwrap_or(0);
+        *self.drives.lock().unwrap() = count("drives") as usize;
+        *self.busy_drives.lock().unwrap() = count("busy_drives") as usize;
+        *self.disks_archived.lock().unwrap() = count("disks_archived");
+
+        let mut message = format!("{} of {} drives busy. {} disks, {} archived since it started.",
+            count("busy_drives"), count("drives"), count("disks_archived"), format_size(count("bytes_archived")));
+
+        for drive in drives.as_array().cloned().unwrap_or_default().iter() {
+            message += &format!("\n  {}: {}", drive["id"].as_str().unwrap_or_default(), drive["status"].as_str().unwrap_or_default());
+
+            if drive["busy"].as_bool().unwrap_or(false) {
+                message += &format!(" ({}%)", drive["progress"]["percent"].as_f64().unwrap_or(0.0) as u64);
+            }
+        }
+
+        *self.status_message.lock().unwrap() = message;
+    }
+}
+
+pub fn spawn_station_poller(config: &Config, stations: &Arc<Vec<Station>>) {
+    let (config, stations) = (config.clone(), stations.clone());
+
+    thread::spawn(move || {
+        loop {
+            for station in stations.iter() {
+                station.check(&config);
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
@@ -0,0 +1,175 @@
+
+// Spools finished images out to tape. Images pile up until there's a batch worth writing, then they all go onto the
+// end of the tape as one tar archive. Each tape carries a label as its first archive, so the catalog can say which
+// tape, and which file on it, every disk ended up on.
+//
+// This needs the non-rewinding tape device (/dev/nst0, not /dev/st0) so the tape stays put between commands.
+
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::catalog;
+use crate::catalog::Catalog;
+
+// Name of the file in the label archive at the start of every tape.
+const LABEL_FILE: &str = "AUTO_ARCHIVE_TAPE_LABEL";
+
+// Marks catalog entries still waiting to go to tape.
+pub const PENDING: &str = "pending";
+
+const SPOOL_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+pub enum TapeError {
+    LaunchFail(&'static str), // Failed to launch mt or tar.
+    Command(String),          // mt or tar ran but failed. Holds what it had to say about it.
+    NoLabel,                  // The tape doesn't start with a label, so it's blank or not one of ours.
+    Parse,                    // Couldn't find the file number in mt's status.
+    Catalog(String),          // The tape got written, but the catalog couldn't be saved.
+}
+
+pub struct TapeSpooler {
+    device: String,
+    batch_size: u64,  // Bytes to wait for before writing.
+    keep_images: bool,
+    pub status_message: Mutex<String>,
+}
+
+fn run(program: &'static str, args: &[&str]) -> Result<Vec<u8>, TapeError> {
+    let output = Command::new(program).args(args).output().map_err(|_| { TapeError::LaunchFail(program) })?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("failed.");
+        Err(TapeError::Command(format!("{}: {}", program, reason.trim())))
+    }
+}
+
+pub fn read_label(device: &str) -> Result<String, TapeError> {
+    run("mt", &["-f", device, "rewind"])?;
+
+    let label = run("tar", &["-xOf", device, LABEL_FILE]).map_err(|_| { TapeError::NoLabel })?;
+    let label = String::from_utf8_lossy(&label).trim().to_string();
+
+    if label.is_empty() {
+        return Err(TapeError::NoLabel);
+    }
+
+    Ok(label)
+}
+
+// Writes a label at the start of the tape. Anything already on the tape is lost.
+pub fn write_label(device: &str, label: &str) -> Result<(), TapeError> {
+    // tar wants a file to put in the archive, so make one somewhere out of the way.
+    let staging = format!("./.tape-label-{}", std::process::id());
+    fs::create_dir_all(&staging).map_err(|e| { TapeError::Command(format!("{}", e)) })?;
+    let result = fs::write(format!("{}/{}", staging, LABEL_FILE), format!("{}\n", label))
+        .map_err(|e| { TapeError::Command(format!("{}", e)) })
+        .and_then(|_| run("mt", &["-f", device, "rewind"]))
+        .and_then(|_| run("tar", &["-cf", device, "-C", &staging, LABEL_FILE]));
+
+    let _ = fs::remove_dir_all(&staging);
+
+    result.map(|_| ())
+}
+
+// Which file the tape is at, counting the label as file 0.
+fn get_file_number(device: &str) -> Result<u64, TapeError> {
+    let status = run("mt", &["-f", device, "status"])?;
+    let status = String::from_utf8_lossy(&status);
+
+    status.split(['\n', ','])
+        .filter_map(|part| part.trim().strip_prefix("File number="))
+        .find_map(|number| number.trim().parse().ok())
+        .ok_or(TapeError::Parse)
+}
+
+impl TapeSpooler {
+    pub fn new(device: &str, batch_size: u64, keep_images: bool) -> TapeSpooler {
+        TapeSpooler {
+            device: String::from(device),
+            batch_size,
+            keep_images,
+            status_message: Mutex::new(String::from("Waiting for images.")),
+        }
+    }
+
+    fn set_status(&self, message: String) {
+        *self.status_message.lock().unwrap() = message;
+    }
+
+    // Writes a batch if there's enough waiting. Returns how many disks were written.
+    fn spool(&self, catalog: &Mutex<Catalog>) -> Result<usize, TapeError> {
+        let pending: Vec<(u64, String, u64)> = catalog.lock().unwrap().entries().iter()
+            .filter(|entry| entry.tape.as_deref() == Some(PENDING))
+            .map(|entry| (entry.id, entry.image.clone(), entry.size))
+            .collect();
+
+        let total: u64 = pending.iter().map(|(_, _, size)| size).sum();
+        self.set_status(format!("{} disks waiting, {} of {} MB.", pending.len(), total / 1_000_000, self.batch_size / 1_000_000));
+
+        if pending.is_empty() || total < self.batch_size {
+            return Ok(0);
+        }
+
+        self.set_status(String::from("Reading tape label..."));
+        let label = read_label(&self.device)?;
+
+        self.set_status(format!("Writing {} disks to tape {}...", pending.len(), label));
+        run("mt", &["-f", &self.device, "eod"])?;
+        let file_number = get_file_number(&self.device)?;
+
+        let mut args = vec!["-cf", self.device.as_str()];
+        args.extend(pending.iter().map(|(_, image, _)| image.as_str()));
+        run("tar", &args)?;
+
+        let mut catalog = catalog.lock().unwrap();
+        for (id, _, _) in pending.iter() {
+            if let Some(entry) = catalog.get_mut(*id) {
+                entry.tape = Some(format!("{} file {}", label, file_number));
+            }
+        }
+        catalog.save().map_err(|e| { TapeError::Catalog(catalog::get_catalog_error_message(&e)) })?;
+
+        if !self.keep_images {
+            for (_, image, _) in pending.iter() {
+                let _ = fs::remove_file(image);
+            }
+        }
+
+        self.set_status(format!("Wrote {} disks to tape {} as file {}.", pending.len(), label, file_number));
+
+        Ok(pending.len())
+    }
+}
+
+pub fn spawn_spooler_thread(spooler: &Arc<TapeSpooler>, catalog: &Arc<Mutex<Catalog>>) {
+    let spooler = spooler.clone();
+    let catalog = catalog.clone();
+
+    thread::spawn(move || {
+        loop {
+            // Failures get retried next time around. Usually it's just the wrong tape or no tape in the drive.
+            if let Err(error) = spooler.spool(&catalog) {
+                spooler.set_status(get_tape_error_message(&error));
+            }
+
+            thread::sleep(SPOOL_POLL_INTERVAL);
+        }
+    });
+}
+
+pub fn get_tape_error_message(error: &TapeError) -> String {
+    match error {
+        TapeError::LaunchFail(program) => format!("Failed to launch {}. Is it installed?", program),
+        TapeError::Command(message) => message.clone(),
+        TapeError::NoLabel => String::from("Tape has no label. If it's blank, label it with `auto_archive tape-label <name>`."),
+        TapeError::Parse => String::from("Couldn't find the file number in the tape drive's status."),
+        TapeError::Catalog(message) => format!("Wrote the tape, but couldn't update the catalog. {}", message),
+    }
+}
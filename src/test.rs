@@ -1,4 +1,156 @@
 
 mod disk_list {
 
-}
\ No newline at end of file
+}
+
+mod verify {
+    use crate::*;
+
+    fn write_temp(name: &str, data: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("auto_archive-verify-{}-{}", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        String::from(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn matching_disk_passes() {
+        let data: Vec<u8> = (0..10000).map(|i| i as u8).collect();
+        let image = write_temp("match-image", &data);
+        let disk = write_temp("match-disk", &data);
+
+        let mut read = 0;
+        let result = verify_disk_against_image(&disk, &image, 4096, |len| read += len);
+
+        let _ = fs::remove_file(&image);
+        let _ = fs::remove_file(&disk);
+
+        assert!(result.is_ok());
+        assert_eq!(read, data.len());
+    }
+
+    #[test]
+    fn differences_are_found() {
+        let data = vec![7; 10000];
+        let mut changed = data.clone();
+        changed[5000] = 8;
+
+        let image = write_temp("differ-image", &data);
+        let disk = write_temp("differ-disk", &changed);
+        let short = write_temp("differ-short", &data[..6000]);
+
+        let mismatch = verify_disk_against_image(&disk, &image, 4096, |_| {});
+        let shortened = verify_disk_against_image(&short, &image, 4096, |_| {});
+
+        let _ = fs::remove_file(&image);
+        let _ = fs::remove_file(&disk);
+        let _ = fs::remove_file(&short);
+
+        assert!(matches!(mismatch, Err(VerifyError::Mismatch(5000))));
+        assert!(matches!(shortened, Err(VerifyError::ShortDisk(6000))));
+    }
+}
+
+mod naming {
+    use crate::*;
+
+    // A drive that's read its disk and wants a name.
+    fn waiting_drive() -> DiskDrive {
+        let drive = new_disk_drive(String::from("/dev/sr9"), DriveKind::Optical);
+
+        for status in [DriveStatus::Fingerprinting, DriveStatus::Copying, DriveStatus::WaitingForName] {
+            assert!(drive.state.transition(status).is_ok());
+        }
+
+        drive
+    }
+
+    #[test]
+    fn remote_names_stay_in_the_working_directory() {
+        let drive = waiting_drive();
+
+        for name in ["", ".", "..", "../escape.iso", "/tmp/escape.iso", "dir/escape.iso"] {
+            assert!(name_drive(&drive, name).is_err(), "{:?} was allowed", name);
+        }
+
+        assert!(drive.state.get() == DriveStatus::WaitingForName);
+        assert!(name_drive(&drive, "auto_archive-test-name.iso").is_ok());
+        assert!(drive.state.get() == DriveStatus::Saving(String::from("auto_archive-test-name.iso")));
+    }
+
+    #[test]
+    fn only_drives_waiting_for_a_name_are_named() {
+        let drive = new_disk_drive(String::from("/dev/sr9"), DriveKind::Optical);
+
+        assert_eq!(name_drive(&drive, "auto_archive-test-name.iso").err().as_deref(), Some("/dev/sr9 isn't waiting for a name."));
+        assert!(drive.state.get() == DriveStatus::Setup);
+    }
+
+    #[test]
+    fn only_volume_names_get_hashed() {
+        let drive = new_disk_drive(String::from("/dev/sr9"), DriveKind::Optical);
+        let config = Config { barcode_names: true, ..Config::default() };
+
+        let (name, from_volume, _) = get_default_base_name(&drive, &config, "DATA", "iso");
+        assert_eq!((name.as_str(), from_volume), ("DATA", true));
+
+        *drive.barcode.lock().unwrap() = Some(String::from("AB123"));
+        let (name, from_volume, _) = get_default_base_name(&drive, &config, "DATA", "iso");
+        assert_eq!((name.as_str(), from_volume), ("AB123", false));
+    }
+
+    #[test]
+    fn scanned_names_stay_file_names() {
+        assert_eq!(get_safe_file_name("AB/123"), "AB_123");
+        assert_eq!(get_safe_file_name("."), "_");
+        assert_eq!(get_safe_file_name(".."), "__");
+        assert_eq!(get_safe_file_name("..AB"), "..AB");
+    }
+
+    #[test]
+    fn names_can_only_be_claimed_once() {
+        let name = std::env::temp_dir().join(format!("auto_archive-claim-{}.iso", std::process::id()));
+        let name = name.to_string_lossy();
+        let first = new_disk_drive(String::from("/dev/sr8"), DriveKind::Optical);
+        let second = new_disk_drive(String::from("/dev/sr9"), DriveKind::Optical);
+
+        let claimed = claim_name(&first, &name).unwrap();
+        let taken = claim_name(&second, &name).unwrap();
+
+        // Unless it's being overwritten on purpose.
+        second.overwrite.store(true, Relaxed);
+        let overwritten = claim_name(&second, &name).unwrap();
+
+        let _ = fs::remove_file(name.as_ref());
+
+        assert!(claimed);
+        assert!(!taken);
+        assert!(overwritten);
+    }
+}
+
+mod hot_plug {
+    use crate::*;
+
+    fn drive_with_key(key: &str) -> DiskDrive {
+        let mut drive = new_disk_drive(String::from("/dev/sr9"), DriveKind::Optical);
+        drive.settings_key = Some(String::from(key));
+        drive
+    }
+
+    #[test]
+    fn only_the_same_drive_comes_back() {
+        let drives = drive_list::DriveList::new(vec![Arc::new(drive_with_key("first"))]);
+        assert!(drives.retire("/dev/sr9").is_some());
+
+        // Something else plugged in where it was.
+        assert!(drives.bring_back(&drive_with_key("second")).is_none());
+        assert!(drives.find_plugged("/dev/sr9").is_none());
+
+        // Pulled out drives can still be found, for whatever heard about it going.
+        assert!(drives.find("/dev/sr9").is_some());
+
+        let returned = drives.bring_back(&drive_with_key("first"));
+        assert!(returned.and_then(|drive| drive.settings_key.clone()).as_deref() == Some("first"));
+        assert!(drives.find_plugged("/dev/sr9").is_some());
+    }
+}
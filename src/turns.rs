@@ -0,0 +1,110 @@
+
+// Lets drives take turns, for setups that can't handle them all going at once, like drives sharing a flaky USB hub or
+// an output disk that can't keep up. Drives get their turn in the order their disks went in, unless the operator
+// moves one up the line.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+#[derive(Default)]
+pub struct TurnQueue {
+    queue: Mutex<VecDeque<String>>, // Drives waiting, by device file. The first one has the turn.
+}
+
+// A place in line. Leaves the line when dropped, whether it got its turn or gave up waiting.
+pub struct Turn<'a> {
+    queue: &'a TurnQueue,
+    drive: String,
+}
+
+impl TurnQueue {
+    pub fn join(&self, drive: &str) -> Turn<'_> {
+        self.queue.lock().unwrap().push_back(String::from(drive));
+
+        Turn {
+            queue: self,
+            drive: String::from(drive),
+        }
+    }
+
+    // Drives waiting for their turn, next first. Doesn't include the one that has it.
+    pub fn waiting(&self) -> Vec<String> {
+        self.queue.lock().unwrap().iter().skip(1).cloned().collect()
+    }
+
+    // Moves a waiting drive `places` closer to the front, or further back if it's negative. Never ahead of the drive
+    // that has the turn, since that one's already going.
+    pub fn move_by(&self, drive: &str, places: isize) {
+        let mut queue = self.queue.lock().unwrap();
+
+        let index = match queue.iter().position(|existing| existing == drive) {
+            Some(index) if index > 0 => index,
+            _ => return,
+        };
+
+        let new_index = index.saturating_add_signed(places.saturating_neg()).clamp(1, queue.len() - 1);
+        if let Some(drive) = queue.remove(index) {
+            queue.insert(new_index, drive);
+        }
+    }
+
+    // Puts a waiting drive next in line.
+    pub fn rush(&self, drive: &str) {
+        self.move_by(drive, isize::MAX);
+    }
+}
+
+impl Turn<'_> {
+    // How many drives are ahead of this one. Zero means it's this one's turn.
+    pub fn position(&self) -> usize {
+        self.queue.queue.lock().unwrap().iter().position(|drive| *drive == self.drive).unwrap_or(0)
+    }
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        let mut queue = self.queue.queue.lock().unwrap();
+
+        if let Some(index) = queue.iter().position(|drive| *drive == self.drive) {
+            queue.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_stay_behind_the_turn() {
+        let queue = TurnQueue::default();
+        let _turns: Vec<Turn> = ["a", "b", "c", "d"].iter().map(|drive| queue.join(drive)).collect();
+
+        queue.move_by("d", 1);
+        assert_eq!(queue.waiting(), vec!["b", "d", "c"]);
+
+        queue.rush("c");
+        assert_eq!(queue.waiting(), vec!["c", "b", "d"]);
+
+        queue.move_by("c", -5);
+        assert_eq!(queue.waiting(), vec!["b", "d", "c"]);
+
+        queue.move_by("c", isize::MIN);
+        assert_eq!(queue.waiting(), vec!["b", "d", "c"]);
+
+        // The one with the turn stays put.
+        queue.move_by("a", -2);
+        assert_eq!(queue.waiting(), vec!["b", "d", "c"]);
+    }
+
+    #[test]
+    fn places_go_when_dropped() {
+        let queue = TurnQueue::default();
+        let first = queue.join("a");
+        let second = queue.join("b");
+
+        assert_eq!(second.position(), 1);
+        drop(first);
+        assert_eq!(second.position(), 0);
+    }
+}
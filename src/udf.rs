@@ -0,0 +1,350 @@
+
+// Just enough UDF to find and read small files on a disk, for the ones that don't have them anywhere else. Blu-rays
+// only have to have UDF, so plenty have no ISO 9660 side for isoinfo to read, or one with nothing on it. Only reads
+// whole files, and only what DVDs and Blu-rays use, which includes the metadata partition UDF 2.50 added.
+// The layout is in ECMA-167, with the UDF spec on top of it.
+
+use std::fs;
+use std::os::unix::fs::FileExt;
+
+const SECTOR_SIZE: u64 = 2048;
+
+// The anchor is always here, and says where everything else is.
+const ANCHOR_SECTOR: u64 = 256;
+
+// What each descriptor says it is in its first two bytes.
+const TAG_ANCHOR: u16 = 2;
+const TAG_PARTITION: u16 = 5;
+const TAG_LOGICAL_VOLUME: u16 = 6;
+const TAG_TERMINATOR: u16 = 8;
+const TAG_FILE_SET: u16 = 256;
+const TAG_FILE_IDENTIFIER: u16 = 257;
+const TAG_FILE_ENTRY: u16 = 261;
+const TAG_EXTENDED_FILE_ENTRY: u16 = 266;
+
+// Nothing we read is anywhere near this big, so anything bigger is a broken image.
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+// Also more than any real disk has, so a broken image can't send us around in circles.
+const MAX_DESCRIPTORS: u64 = 64;
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(read_u32(data, offset)? as u64 | (read_u32(data, offset + 4)? as u64) << 32)
+}
+
+// A run of blocks in one of the volume's partitions. The top two bits of the length say whether it's really there.
+#[derive(Clone, Copy)]
+struct Extent {
+    length: u32,
+    block: u32,
+    partition: u16,
+    recorded: bool, // Extents that aren't are read as zeros.
+}
+
+fn read_short_extent(data: &[u8], offset: usize, partition: u16) -> Option<Extent> {
+    let length = read_u32(data, offset)?;
+    Some(Extent { length: length & 0x3fff_ffff, block: read_u32(data, offset + 4)?, partition, recorded: length >> 30 == 0 })
+}
+
+fn read_long_extent(data: &[u8], offset: usize) -> Option<Extent> {
+    let length = read_u32(data, offset)?;
+    Some(Extent { length: length & 0x3fff_ffff, block: read_u32(data, offset + 4)?, partition: read_u16(data, offset + 8)?, recorded: length >> 30 == 0 })
+}
+
+// How a partition number in an extent turns into somewhere on the disk.
+enum PartitionMap {
+    Physical,
+    Metadata(Vec<Extent>), // Blocks are counted through the metadata file, which is in the physical partition.
+}
+
+pub struct UdfImage {
+    file: fs::File,
+    start: u64, // The physical partition's first sector.
+    maps: Vec<PartitionMap>,
+    root: Extent, // The root directory's file entry.
+}
+
+impl UdfImage {
+    // None if it doesn't have a UDF file system we can read.
+    pub fn open(image: &str) -> Option<UdfImage> {
+        let file = fs::File::open(image).ok()?;
+
+        let anchor = read_descriptor(&file, ANCHOR_SECTOR, TAG_ANCHOR)?;
+        let sequence_length = read_u32(&anchor, 16)? as u64 / SECTOR_SIZE;
+        let sequence_start = read_u32(&anchor, 20)? as u64;
+
+        let mut start = None;
+        let mut volume = None;
+
+        for sector in sequence_start..sequence_start + sequence_length.min(MAX_DESCRIPTORS) {
+            let descriptor = read_sector(&file, sector)?;
+
+            match read_u16(&descriptor, 0)? {
+                TAG_PARTITION => start = Some(read_u32(&descriptor, 188)? as u64),
+                TAG_LOGICAL_VOLUME => volume = Some(descriptor),
+                TAG_TERMINATOR => break,
+                _ => {},
+            }
+        }
+
+        let (start, volume) = (start?, volume?);
+        if read_u32(&volume, 212)? as u64 != SECTOR_SIZE {
+            return None;
+        }
+
+        let mut udf = UdfImage { file, start, maps: Vec::new(), root: read_long_extent(&volume, 248)? };
+
+        // The file set is where the root directory is, so `root` is it until that's read.
+        let mut offset = 440;
+        for _ in 0..read_u32(&volume, 268)? {
+            let (kind, length) = (*volume.get(offset)?, *volume.get(offset + 1)? as usize);
+
+            udf.maps.push(match kind {
+                // Sparable partitions are physical ones with spares for bad blocks, which pressed disks don't have.
+                2 if volume.get(offset + 5..offset + 28)? == b"*UDF Metadata Partition" => {
+                    let entry = read_file_entry(&udf.file, start + read_u32(&volume, offset + 40)? as u64)?;
+                    PartitionMap::Metadata(read_extents(&entry, 0)?.0)
+                },
+                1 | 2 => PartitionMap::Physical,
+                _ => return None,
+            });
+
+            if length == 0 {
+                return None;
+            }
+            offset += length;
+        }
+
+        let file_set = read_descriptor(&udf.file, udf.get_sector(&udf.root)?, TAG_FILE_SET)?;
+        udf.root = read_long_extent(&file_set, 400)?;
+
+        Some(udf)
+    }
+
+    // Where a block in one of the partitions is on the disk.
+    fn get_sector(&self, extent: &Extent) -> Option<u64> {
+        match self.maps.get(extent.partition as usize)? {
+            PartitionMap::Physical => Some(self.start + extent.block as u64),
+            PartitionMap::Metadata(extents) => {
+                let mut offset = extent.block as u64 * SECTOR_SIZE;
+
+                for metadata in extents.iter() {
+                    if offset < metadata.length as u64 {
+                        return Some(self.start + metadata.block as u64 + offset / SECTOR_SIZE);
+                    }
+                    offset -= metadata.length as u64;
+                }
+
+                None
+            },
+        }
+    }
+
+    // Everything in the file its entry is at.
+    fn read_entry(&self, icb: &Extent) -> Option<Vec<u8>> {
+        let entry = read_file_entry(&self.file, self.get_sector(icb)?)?;
+
+        let size = read_u64(&entry, 56)?;
+        if size > MAX_FILE_SIZE {
+            return None;
+        }
+
+        let (extents, embedded) = read_extents(&entry, icb.partition)?;
+        if let Some(data) = embedded {
+            return data.get(..size as usize).map(|data| data.to_vec());
+        }
+
+        let mut data = Vec::new();
+        for extent in extents.iter() {
+            // Only as much as the file says it has, whatever the extent says.
+            let mut piece = vec![0u8; (extent.length as u64).min(size - data.len() as u64) as usize];
+            if extent.recorded && !piece.is_empty() {
+                self.file.read_exact_at(&mut piece, self.get_sector(extent)? * SECTOR_SIZE).ok()?;
+            }
+            data.extend(piece);
+        }
+
+        Some(data)
+    }
+
+    // What's in a directory, by name, along with where each one's file entry is.
+    fn read_directory(&self, icb: &Extent) -> Option<Vec<(String, Extent)>> {
+        let data = self.read_entry(icb)?;
+        let mut files = Vec::new();
+        let mut offset = 0;
+
+        while offset + 38 <= data.len() {
+            if read_u16(&data, offset)? != TAG_FILE_IDENTIFIER {
+                return None;
+            }
+
+            let characteristics = *data.get(offset + 18)?;
+            let name_length = *data.get(offset + 19)? as usize;
+            let name_start = offset + 38 + read_u16(&data, offset + 36)? as usize;
+
+            // Deleted files and the entry for the parent directory don't count.
+            if characteristics & 0x0c == 0 {
+                files.push((decode_name(data.get(name_start..name_start + name_length)?)?, read_long_extent(&data, offset + 20)?));
+            }
+
+            offset = (name_start + name_length + 3) & !3;
+        }
+
+        Some(files)
+    }
+
+    // Finds a file by its path from the root. Case doesn't matter, since disks aren't consistent about it.
+    fn find(&self, path: &str) -> Option<Extent> {
+        let mut icb = self.root;
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            icb = self.read_directory(&icb)?.into_iter().find(|(file, _)| file.eq_ignore_ascii_case(name))?.1;
+        }
+
+        Some(icb)
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.find(path).is_some()
+    }
+
+    // The names of everything in a directory.
+    pub fn list(&self, path: &str) -> Option<Vec<String>> {
+        Some(self.read_directory(&self.find(path)?)?.into_iter().map(|(name, _)| name).collect())
+    }
+
+    pub fn read(&self, path: &str) -> Option<Vec<u8>> {
+        self.read_entry(&self.find(path)?)
+    }
+}
+
+fn read_sector(file: &fs::File, sector: u64) -> Option<Vec<u8>> {
+    let mut data = vec![0u8; SECTOR_SIZE as usize];
+    file.read_exact_at(&mut data, sector * SECTOR_SIZE).ok()?;
+    Some(data)
+}
+
+fn read_descriptor(file: &fs::File, sector: u64, tag: u16) -> Option<Vec<u8>> {
+    read_sector(file, sector).filter(|data| read_u16(data, 0) == Some(tag))
+}
+
+// Plain and extended file entries only differ in where things are past the start.
+fn read_file_entry(file: &fs::File, sector: u64) -> Option<Vec<u8>> {
+    read_sector(file, sector).filter(|data| matches!(read_u16(data, 0), Some(TAG_FILE_ENTRY) | Some(TAG_EXTENDED_FILE_ENTRY)))
+}
+
+// Where a file entry's data is. Small files are kept in the entry itself instead.
+fn read_extents(entry: &[u8], partition: u16) -> Option<(Vec<Extent>, Option<Vec<u8>>)> {
+    let (extended_length, extents_length, start) = match read_u16(entry, 0)? {
+        TAG_EXTENDED_FILE_ENTRY => (read_u32(entry, 208)?, read_u32(entry, 212)?, 216),
+        _ => (read_u32(entry, 168)?, read_u32(entry, 172)?, 176),
+    };
+
+    let start = start + extended_length as usize;
+    let descriptors = entry.get(start..start + extents_length as usize)?;
+
+    match read_u16(entry, 34)? & 7 {
+        0 => Some(((0..descriptors.len() / 8).map(|n| read_short_extent(descriptors, n * 8, partition)).collect::<Option<_>>()?, None)),
+        1 => Some(((0..descriptors.len() / 16).map(|n| read_long_extent(descriptors, n * 16)).collect::<Option<_>>()?, None)),
+        3 => Some((Vec::new(), Some(descriptors.to_vec()))),
+        _ => None,
+    }
+}
+
+// Names start with a byte that says whether the rest is one byte a letter or two.
+fn decode_name(name: &[u8]) -> Option<String> {
+    match name.split_first()? {
+        (8, rest) => Some(rest.iter().map(|byte| *byte as char).collect()),
+        (16, rest) => String::from_utf16(&rest.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect::<Vec<u16>>()).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: usize = 270;
+
+    fn put(image: &mut [u8], sector: usize, offset: usize, bytes: &[u8]) {
+        let at = sector * SECTOR_SIZE as usize + offset;
+        image[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn long_extent(length: u32, block: u32) -> Vec<u8> {
+        [&length.to_le_bytes()[..], &block.to_le_bytes(), &[0u8; 8]].concat()
+    }
+
+    fn identifier(name: &str, block: u32, directory: bool) -> Vec<u8> {
+        let mut record = vec![0u8; 38];
+        record[0..2].copy_from_slice(&TAG_FILE_IDENTIFIER.to_le_bytes());
+        record[18] = if directory { 2 } else { 0 };
+        record[19] = name.len() as u8 + 1;
+        record[20..36].copy_from_slice(&long_extent(SECTOR_SIZE as u32, block));
+        record.push(8);
+        record.extend(name.bytes());
+        record.resize((record.len() + 3) & !3, 0);
+        record
+    }
+
+    // A file entry in the partition. Its data goes in the entry if it's small, otherwise in the next block.
+    fn entry(image: &mut [u8], block: usize, data: &[u8], embedded: bool) {
+        let sector = START + block;
+        put(image, sector, 0, &TAG_FILE_ENTRY.to_le_bytes());
+        put(image, sector, 34, &(if embedded { 3u16 } else { 0 }).to_le_bytes());
+        put(image, sector, 56, &(data.len() as u64).to_le_bytes());
+
+        if embedded {
+            put(image, sector, 172, &(data.len() as u32).to_le_bytes());
+            put(image, sector, 176, data);
+        } else {
+            put(image, sector, 172, &8u32.to_le_bytes());
+            put(image, sector, 176, &[&(data.len() as u32).to_le_bytes()[..], &(block as u32 + 1).to_le_bytes()].concat());
+            put(image, sector + 1, 0, data);
+        }
+    }
+
+    #[test]
+    fn files_are_found_without_iso9660() {
+        let mut image = vec![0u8; (START + 16) * SECTOR_SIZE as usize];
+
+        put(&mut image, ANCHOR_SECTOR as usize, 0, &TAG_ANCHOR.to_le_bytes());
+        put(&mut image, ANCHOR_SECTOR as usize, 16, &[&(3 * SECTOR_SIZE as u32).to_le_bytes()[..], &257u32.to_le_bytes()].concat());
+        put(&mut image, 257, 0, &TAG_PARTITION.to_le_bytes());
+        put(&mut image, 257, 188, &(START as u32).to_le_bytes());
+        put(&mut image, 258, 0, &TAG_LOGICAL_VOLUME.to_le_bytes());
+        put(&mut image, 258, 212, &(SECTOR_SIZE as u32).to_le_bytes());
+        put(&mut image, 258, 248, &long_extent(SECTOR_SIZE as u32, 0));
+        put(&mut image, 258, 268, &1u32.to_le_bytes());
+        put(&mut image, 258, 440, &[1, 6, 1, 0, 0, 0]);
+        put(&mut image, 259, 0, &TAG_TERMINATOR.to_le_bytes());
+
+        put(&mut image, START, 0, &TAG_FILE_SET.to_le_bytes());
+        put(&mut image, START, 400, &long_extent(SECTOR_SIZE as u32, 1));
+
+        entry(&mut image, 1, &identifier("BDMV", 3, true), false);
+        entry(&mut image, 3, &[identifier("index.bdmv", 4, false), identifier("PLAYLIST", 5, true)].concat(), true);
+        entry(&mut image, 4, b"INDX0200", true);
+        entry(&mut image, 5, &identifier("00000.mpls", 7, false), false);
+        entry(&mut image, 7, b"MPLS0200", false);
+
+        let path = std::env::temp_dir().join(format!("auto_archive-udf-test-{}", std::process::id()));
+        fs::write(&path, &image).unwrap();
+        let udf = UdfImage::open(path.to_str().unwrap());
+        let _ = fs::remove_file(&path);
+        let udf = udf.unwrap();
+
+        assert!(udf.exists("/BDMV/INDEX.BDMV"));
+        assert!(!udf.exists("/VIDEO_TS"));
+        assert_eq!(udf.list("/BDMV/PLAYLIST"), Some(vec![String::from("00000.mpls")]));
+        assert_eq!(udf.read("/BDMV/PLAYLIST/00000.mpls").as_deref(), Some(&b"MPLS0200"[..]));
+    }
+}
@@ -0,0 +1,36 @@
+
+// Photos of disk labels, for handwritten CD-Rs whose volume ids are gibberish. A webcam pointed at the disk takes a
+// picture when it's detected, and the picture gets kept next to the image. fswebcam does the capturing, since it
+// knows every webcam V4L2 does.
+
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+pub enum WebcamError {
+    LaunchFail,      // Failed to launch fswebcam.
+    Capture(String), // fswebcam couldn't get a picture. Holds what it had to say about it.
+}
+
+// Skips the first few frames, since a lot of webcams take a moment to sort out their exposure.
+pub fn capture(device: &str, path: &str) -> Result<(), WebcamError> {
+    let output = Command::new("fswebcam")
+        .args(["--quiet", "--no-banner", "--skip", "10", "--resolution", "1280x720", "--device", device, "--jpeg", "90", path])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|_| { WebcamError::LaunchFail })?;
+
+    // It doesn't always fail when it didn't get anything.
+    if !output.status.success() || !Path::new(path).exists() {
+        return Err(WebcamError::Capture(String::from(String::from_utf8_lossy(&output.stderr).trim())));
+    }
+
+    Ok(())
+}
+
+pub fn get_webcam_error_message(error: &WebcamError) -> String {
+    match error {
+        WebcamError::LaunchFail => String::from("Failed to launch fswebcam. Is it installed?"),
+        WebcamError::Capture(message) => format!("fswebcam failed: {}", message),
+    }
+}